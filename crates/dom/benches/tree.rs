@@ -0,0 +1,59 @@
+fn main() {
+    divan::main();
+}
+
+/// Time per node stays constant while the tree grows, i.e. construction is linear.
+#[divan::bench_group(sample_count = 10)]
+mod construction {
+    use divan::{counter::ItemsCount, Bencher};
+
+    use rexml_dom::Document;
+
+    const SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+
+    /// All elements are children of the root element.
+    #[divan::bench(args = SIZES)]
+    fn wide(bencher: Bencher, size: usize) {
+        bencher.counter(ItemsCount::new(size)).bench(|| {
+            let mut doc = Document::default();
+
+            let root = doc.create_element("root").unwrap();
+
+            doc.append_child(None, root).unwrap();
+
+            for _ in 0..size {
+                let el = doc.create_element("item").unwrap();
+                let attr = doc.create_attr("id", "value").unwrap();
+                let text = doc.create_text("text").unwrap();
+
+                doc.append_child(Some(&el), attr).unwrap();
+                doc.append_child(Some(&el), text).unwrap();
+                doc.append_child(Some(&root), el).unwrap();
+            }
+
+            doc
+        });
+    }
+
+    /// Every element is the child of the previous one.
+    #[divan::bench(args = SIZES)]
+    fn deep(bencher: Bencher, size: usize) {
+        bencher.counter(ItemsCount::new(size)).bench(|| {
+            let mut doc = Document::default();
+
+            let mut parent = doc.create_element("root").unwrap();
+
+            doc.append_child(None, parent).unwrap();
+
+            for _ in 0..size {
+                let el = doc.create_element("item").unwrap();
+
+                doc.append_child(Some(&parent), el).unwrap();
+
+                parent = el;
+            }
+
+            doc
+        });
+    }
+}
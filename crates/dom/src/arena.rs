@@ -1,35 +1,38 @@
 //! This mod provide a DOM implementation with  memory managerment.
 
-use std::{borrow::Cow, slice::Iter};
-
-use crate::{DOMObject, Error, ExceptionCode, NodeType, QName, Result};
+use std::{
+    borrow::Cow,
+    slice::Iter,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::{
+    elements::IdIndex,
+    events::{AttrChange, EventListeners},
+    namespace::check_namespace,
+    range::Ranges,
+    slab::Slab,
+    traversal::NodeIterators,
+    DOMObject, Error, ExceptionCode, NodeType, QName, Result,
+};
 
 /// Use by gc process.
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Default)]
 enum GcState {
+    #[default]
     Unmark,
     Marked,
 }
 
-impl Default for GcState {
-    fn default() -> Self {
-        Self::Unmark
-    }
-}
-
 /// A DOM node is allocated by and belongs to one [`DOM`]
 #[derive(Default)]
 struct Node {
     gc_state: GcState,
-    #[allow(unused)]
     parent: Option<DOMObject>,
     children: Vec<DOMObject>,
 }
 
 impl Node {
-    fn append_child(&mut self, child: DOMObject) {
-        self.children.push(child);
-    }
     fn remove_child(&mut self, child: &DOMObject) {
         if let Some(index) =
             self.children.iter().enumerate().find_map(
@@ -46,10 +49,10 @@ impl Node {
         }
     }
 
-    fn first_child_of(&self, node_type: &NodeType) -> Option<&DOMObject> {
+    fn children_of(&self, node_type: NodeType) -> impl Iterator<Item = &DOMObject> {
         self.children
             .iter()
-            .find(|obj| obj.node_type() == *node_type)
+            .filter(move |obj| obj.node_type() == node_type)
     }
 
     fn gc_mark(&mut self) {
@@ -105,7 +108,9 @@ pub struct Attr<'a> {
     object: DOMObject,
     node: Node,
     name: QName<'a>,
+    namespace_uri: Option<Cow<'a, str>>,
     value: Cow<'a, str>,
+    specified: bool,
 }
 
 impl<'a> Attr<'a> {
@@ -114,35 +119,40 @@ impl<'a> Attr<'a> {
             node: Default::default(),
             object,
             name,
+            namespace_uri: None,
             value,
+            specified: true,
         }
     }
 
+    /// Returns true if this attribute has the namespace URI and local name.
+    fn matches_ns(&self, namespace_uri: Option<&str>, local_name: &str) -> bool {
+        self.namespace_uri.as_deref() == namespace_uri && self.name.local_part == local_name
+    }
+
     /// Returns the name of this attribute.
     pub fn name(&self) -> &QName<'a> {
         &self.name
     }
 
+    /// Returns the namespace URI of this attribute, `None` if it has no namespace.
+    pub fn namespace_uri(&self) -> Option<&str> {
+        self.namespace_uri.as_deref()
+    }
+
     /// Returns the this attribute as string.
     pub fn value(&self) -> &str {
         &self.value
     }
 
-    fn append_child(&mut self, child: DOMObject) -> Result<()> {
-        self.remove_child(&child)?;
-        self.node.append_child(child);
-
-        Ok(())
+    /// Returns false if this attribute was not given a value in the document,
+    /// but was defaulted from the DTD.
+    pub fn specified(&self) -> bool {
+        self.specified
     }
 
-    fn remove_child(&mut self, child: &DOMObject) -> Result<()> {
-        match child.node_type() {
-            NodeType::Text | NodeType::EntityReference => {
-                self.node.remove_child(child);
-                Ok(())
-            }
-            _ => return Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR)),
-        }
+    pub(crate) fn set_specified(&mut self, specified: bool) {
+        self.specified = specified;
     }
 }
 
@@ -151,6 +161,8 @@ pub struct Element<'a> {
     object: DOMObject,
     node: Node,
     tag: QName<'a>,
+    namespace_uri: Option<Cow<'a, str>>,
+    attributes: NamedNodeMap,
 }
 
 impl<'a> AsRef<DOMObject> for Element<'a> {
@@ -164,38 +176,66 @@ impl<'a> Element<'a> {
         Self {
             object,
             tag,
+            namespace_uri: None,
             node: Default::default(),
+            attributes: Default::default(),
         }
     }
 
-    fn append_child(&mut self, child: DOMObject) -> Result<()> {
-        self.remove_child(&child)?;
-        self.node.append_child(child);
+    /// Returns the element's tag name.
+    pub fn tag(&self) -> &QName<'a> {
+        &self.tag
+    }
+
+    /// Returns the namespace URI of this element, `None` if it has no namespace.
+    pub fn namespace_uri(&self) -> Option<&str> {
+        self.namespace_uri.as_deref()
+    }
 
-        Ok(())
+    /// Returns the attributes of this element in document order.
+    pub fn attributes(&self) -> &NamedNodeMap {
+        &self.attributes
     }
+}
 
-    fn remove_child(&mut self, child: &DOMObject) -> Result<()> {
-        match child.node_type() {
-            NodeType::Element
-            | NodeType::Text
-            | NodeType::Comment
-            | NodeType::CData
-            | NodeType::ProcessingInstruction
-            | NodeType::EntityReference
-            | NodeType::Attribute
-            | NodeType::Namespace
-            | NodeType::DocumentType => {
-                self.node.remove_child(child);
-                Ok(())
-            }
-            _ => return Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR)),
-        }
+/// The attributes of one element, in the order they were set.
+///
+/// Use [`Document::attr`] to resolve the items, or the attribute methods of `Document`
+/// to access them by name.
+#[derive(Debug, Default, Clone)]
+pub struct NamedNodeMap {
+    items: Vec<DOMObject>,
+}
+
+impl NamedNodeMap {
+    /// Returns the number of attributes in this map.
+    pub fn len(&self) -> usize {
+        self.items.len()
     }
 
-    /// Returns the element's tag name.
-    pub fn tag(&self) -> &QName<'a> {
-        &self.tag
+    /// Returns true if this map has no attributes.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the attribute at `index`.
+    pub fn item(&self, index: usize) -> Option<&DOMObject> {
+        self.items.get(index)
+    }
+
+    /// Returns an iterator over attributes in order.
+    pub fn iter(&self) -> Iter<'_, DOMObject> {
+        self.items.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a NamedNodeMap {
+    type Item = &'a DOMObject;
+
+    type IntoIter = Iter<'a, DOMObject>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
     }
 }
 
@@ -325,6 +365,7 @@ impl<'a> Text<'a> {
 pub struct Notation<'a> {
     object: DOMObject,
     node: Node,
+    name: Cow<'a, str>,
     public_id: Cow<'a, str>,
     system_id: Cow<'a, str>,
 }
@@ -336,15 +377,26 @@ impl<'a> AsRef<DOMObject> for Notation<'a> {
 }
 
 impl<'a> Notation<'a> {
-    fn new(object: DOMObject, target: Cow<'a, str>, data: Cow<'a, str>) -> Self {
+    fn new(
+        object: DOMObject,
+        name: Cow<'a, str>,
+        public_id: Cow<'a, str>,
+        system_id: Cow<'a, str>,
+    ) -> Self {
         Self {
             object,
             node: Default::default(),
-            public_id: target,
-            system_id: data,
+            name,
+            public_id,
+            system_id,
         }
     }
 
+    /// Returns the name of this notation.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     /// Returns the target as str
     pub fn public_id(&self) -> &str {
         &self.public_id
@@ -360,6 +412,7 @@ impl<'a> Notation<'a> {
 pub struct Entity<'a> {
     object: DOMObject,
     node: Node,
+    name: Cow<'a, str>,
     notation_name: Option<Cow<'a, str>>,
     public_id: Cow<'a, str>,
     system_id: Cow<'a, str>,
@@ -374,6 +427,7 @@ impl<'a> AsRef<DOMObject> for Entity<'a> {
 impl<'a> Entity<'a> {
     fn new(
         object: DOMObject,
+        name: Cow<'a, str>,
         public_id: Cow<'a, str>,
         system_id: Cow<'a, str>,
         notation_name: Option<Cow<'a, str>>,
@@ -381,6 +435,7 @@ impl<'a> Entity<'a> {
         Self {
             object,
             node: Default::default(),
+            name,
             public_id,
             system_id,
             notation_name,
@@ -397,16 +452,52 @@ impl<'a> Entity<'a> {
         &self.system_id
     }
 
+    /// Returns the name of this entity.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     /// For unparsed entities, the name of the notation for the entity. For parsed entities, this is [`None`].
     pub fn notation_name(&self) -> Option<&str> {
         self.notation_name.as_deref()
     }
 }
 
+/// `EntityReference` allocated by one `Document`.
+///
+/// The children of an entity reference are the nodes of the replacement text.
+pub struct EntityReference<'a> {
+    object: DOMObject,
+    node: Node,
+    name: Cow<'a, str>,
+}
+
+impl<'a> AsRef<DOMObject> for EntityReference<'a> {
+    fn as_ref(&self) -> &DOMObject {
+        &self.object
+    }
+}
+
+impl<'a> EntityReference<'a> {
+    fn new(object: DOMObject, name: Cow<'a, str>) -> Self {
+        Self {
+            object,
+            node: Default::default(),
+            name,
+        }
+    }
+
+    /// Returns the name of the referenced entity.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
 /// `DocumentType` allocated by one `Document`.
 pub struct DocumentType<'a> {
     object: DOMObject,
     node: Node,
+    name: Cow<'a, str>,
     internal_subset: Cow<'a, str>,
     public_id: Cow<'a, str>,
     system_id: Cow<'a, str>,
@@ -421,6 +512,7 @@ impl<'a> AsRef<DOMObject> for DocumentType<'a> {
 impl<'a> DocumentType<'a> {
     fn new(
         object: DOMObject,
+        name: Cow<'a, str>,
         public_id: Cow<'a, str>,
         system_id: Cow<'a, str>,
         internal_subset: Cow<'a, str>,
@@ -428,6 +520,7 @@ impl<'a> DocumentType<'a> {
         Self {
             object,
             node: Default::default(),
+            name,
             public_id,
             system_id,
             internal_subset,
@@ -444,179 +537,290 @@ impl<'a> DocumentType<'a> {
         &self.system_id
     }
 
-    /// For unparsed entities, the name of the notation for the entity. For parsed entities, this is [`None`].
+    /// Returns the name of the document element.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the source text of the internal subset, empty if there is none.
     pub fn internal_subset(&self) -> &str {
         &self.internal_subset
     }
+
+    /// Returns the entities declared in this document type.
+    pub fn entities(&self) -> impl Iterator<Item = &DOMObject> {
+        self.node.children_of(NodeType::Entity)
+    }
+
+    /// Returns the notations declared in this document type.
+    pub fn notations(&self) -> impl Iterator<Item = &DOMObject> {
+        self.node.children_of(NodeType::Notation)
+    }
 }
 
 /// A DOM `Document` implementation with  memory managerment.
-#[derive(Default)]
 pub struct Document<'a> {
+    /// The unique id of this document, carried by every [`DOMObject`] it allocates.
+    id: u32,
     this_node: Node,
-    doc_types: Vec<DocumentType<'a>>,
-    els: Vec<Element<'a>>,
-    attrs: Vec<Attr<'a>>,
-    nss: Vec<Namespace<'a>>,
-    pis: Vec<ProcessingInstruction<'a>>,
-    cms: Vec<Comment<'a>>,
-    texts: Vec<Text<'a>>,
-    notations: Vec<Notation<'a>>,
-    entities: Vec<Entity<'a>>,
-    cdatas: Vec<CData<'a>>,
+    doc_types: Slab<DocumentType<'a>>,
+    els: Slab<Element<'a>>,
+    attrs: Slab<Attr<'a>>,
+    nss: Slab<Namespace<'a>>,
+    pis: Slab<ProcessingInstruction<'a>>,
+    cms: Slab<Comment<'a>>,
+    texts: Slab<Text<'a>>,
+    notations: Slab<Notation<'a>>,
+    entities: Slab<Entity<'a>>,
+    entity_refs: Slab<EntityReference<'a>>,
+    cdatas: Slab<CData<'a>>,
+    /// The ID attributes by value, see [`get_element_by_id`](Self::get_element_by_id).
+    pub(crate) ids: IdIndex,
+    /// The positions of the live node iterators, see [`create_node_iterator`](Self::create_node_iterator).
+    pub(crate) node_iterators: NodeIterators,
+    /// The boundary points of the live ranges, see [`create_range`](Self::create_range).
+    pub(crate) ranges: Ranges,
+    /// The event listeners by node, see [`add_event_listener`](Self::add_event_listener).
+    pub(crate) event_listeners: EventListeners,
+}
+
+impl<'a> Default for Document<'a> {
+    fn default() -> Self {
+        static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+
+        Self {
+            id,
+            this_node: Default::default(),
+            doc_types: Slab::new(id),
+            els: Slab::new(id),
+            attrs: Slab::new(id),
+            nss: Slab::new(id),
+            pis: Slab::new(id),
+            cms: Slab::new(id),
+            texts: Slab::new(id),
+            notations: Slab::new(id),
+            entities: Slab::new(id),
+            entity_refs: Slab::new(id),
+            cdatas: Slab::new(id),
+            ids: Default::default(),
+            node_iterators: Default::default(),
+            ranges: Default::default(),
+            event_listeners: Default::default(),
+        }
+    }
 }
 
 impl<'a> Document<'a> {
+    /// Returns the unique id of this document.
+    pub(crate) fn id(&self) -> u32 {
+        self.id
+    }
+
     fn gc_mark(&mut self, object: &DOMObject) {
+        if let Some(node) = self.node_mut(object) {
+            node.gc_mark();
+        }
+    }
+
+    /// Returns true if `object` is a node of this document which is not freed.
+    pub(crate) fn is_allocated(&self, object: &DOMObject) -> bool {
+        object.node_type() == NodeType::Document
+            || (object.document() == self.id && self.node(object).is_some())
+    }
+
+    /// Returns the [`Node`] part of one allocated node.
+    fn node(&self, object: &DOMObject) -> Option<&Node> {
         match object.node_type() {
-            NodeType::Element => {
-                self.element_mut(object).unwrap().node.gc_mark();
-            }
-            _ => unimplemented!(),
+            NodeType::Element => self.els.get(object).map(|n| &n.node),
+            NodeType::Attribute => self.attrs.get(object).map(|n| &n.node),
+            NodeType::Text => self.texts.get(object).map(|n| &n.node),
+            NodeType::CData => self.cdatas.get(object).map(|n| &n.node),
+            NodeType::Entity => self.entities.get(object).map(|n| &n.node),
+            NodeType::ProcessingInstruction => self.pis.get(object).map(|n| &n.node),
+            NodeType::Comment => self.cms.get(object).map(|n| &n.node),
+            NodeType::DocumentType => self.doc_types.get(object).map(|n| &n.node),
+            NodeType::Notation => self.notations.get(object).map(|n| &n.node),
+            NodeType::Namespace => self.nss.get(object).map(|n| &n.node),
+            NodeType::EntityReference => self.entity_refs.get(object).map(|n| &n.node),
+            NodeType::Document => Some(&self.this_node),
+            NodeType::DocumentFragment => None,
         }
     }
 
-    /// Append a new child to the `Document` node.
-    fn append_to_document(&mut self, child: DOMObject) -> Result<()> {
-        match child.node_type() {
-            NodeType::Element | NodeType::DocumentType => {
-                if self.this_node.first_child_of(&child.node_type()).is_some() {
-                    return Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR));
-                }
+    /// Returns the mutable [`Node`] part of one allocated node.
+    fn node_mut(&mut self, object: &DOMObject) -> Option<&mut Node> {
+        match object.node_type() {
+            NodeType::Element => self.els.get_mut(object).map(|n| &mut n.node),
+            NodeType::Attribute => self.attrs.get_mut(object).map(|n| &mut n.node),
+            NodeType::Text => self.texts.get_mut(object).map(|n| &mut n.node),
+            NodeType::CData => self.cdatas.get_mut(object).map(|n| &mut n.node),
+            NodeType::Entity => self.entities.get_mut(object).map(|n| &mut n.node),
+            NodeType::ProcessingInstruction => self.pis.get_mut(object).map(|n| &mut n.node),
+            NodeType::Comment => self.cms.get_mut(object).map(|n| &mut n.node),
+            NodeType::DocumentType => self.doc_types.get_mut(object).map(|n| &mut n.node),
+            NodeType::Notation => self.notations.get_mut(object).map(|n| &mut n.node),
+            NodeType::Namespace => self.nss.get_mut(object).map(|n| &mut n.node),
+            NodeType::EntityReference => self.entity_refs.get_mut(object).map(|n| &mut n.node),
+            NodeType::Document => Some(&mut self.this_node),
+            NodeType::DocumentFragment => None,
+        }
+    }
 
-                self.this_node.remove_child(&child);
-                self.this_node.append_child(child);
+    fn append_child_check(&mut self, child: &DOMObject) -> Result<()> {
+        if self
+            .node(child)
+            .ok_or(Error::DOMException(ExceptionCode::NOT_FOUND_ERR))?
+            .parent
+            .is_some()
+        {
+            return Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR));
+        }
 
-                Ok(())
-            }
-            NodeType::ProcessingInstruction | NodeType::Comment => {
-                self.this_node.remove_child(&child);
-                self.this_node.append_child(child);
+        Ok(())
+    }
 
-                Ok(())
-            }
-            _ => {
+    fn new_attr(&mut self, name: QName<'a>, value: Cow<'a, str>) -> DOMObject {
+        self.attrs
+            .insert_with(NodeType::Attribute, |object| Attr::new(object, name, value))
+            .object
+    }
+
+    /// Check if `child` can be inserted into `parent`, optionally in place of `replacing`.
+    pub(crate) fn insert_check(
+        &self,
+        parent: &DOMObject,
+        child: &DOMObject,
+        replacing: Option<&DOMObject>,
+    ) -> Result<()> {
+        if child.node_type() == NodeType::Document {
+            return Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR));
+        }
+
+        if child.document() != self.id {
+            return Err(Error::DOMException(ExceptionCode::WRONG_DOCUMENT_ERR));
+        }
+
+        if self.node(child).is_none() || self.node(parent).is_none() {
+            return Err(Error::DOMException(ExceptionCode::NOT_FOUND_ERR));
+        }
+
+        if !is_allowed_child(parent.node_type(), child.node_type()) {
+            return Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR));
+        }
+
+        // the document has at most one element and one document type.
+        if parent.node_type() == NodeType::Document
+            && matches!(
+                child.node_type(),
+                NodeType::Element | NodeType::DocumentType
+            )
+            && self
+                .this_node
+                .children_of(child.node_type())
+                .any(|obj| Some(obj) != replacing)
+        {
+            return Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR));
+        }
+
+        // a node can't be inserted into itself or its descendants,
+        // only nodes with children need the walk up the ancestors.
+        let has_children = self
+            .node(child)
+            .is_some_and(|node| !node.children.is_empty());
+
+        let mut ancestor = Some(*parent).filter(|parent| has_children || parent == child);
+
+        while let Some(object) = ancestor {
+            if object == *child {
                 return Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR));
             }
+
+            ancestor = self.node(&object).and_then(|node| node.parent);
         }
+
+        Ok(())
     }
 
-    fn append_child_check(&mut self, child: &DOMObject) -> Result<()> {
-        match child.node_type() {
-            NodeType::Element => {
-                if self
-                    .element(child)
-                    .ok_or(Error::DOMException(ExceptionCode::NOT_FOUND_ERR))?
-                    .node
-                    .parent
-                    .is_some()
-                {
-                    return Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR));
-                }
-            }
-            NodeType::Attribute => {
-                if self
-                    .attr(child)
-                    .ok_or(Error::DOMException(ExceptionCode::NOT_FOUND_ERR))?
-                    .node
-                    .parent
-                    .is_some()
-                {
-                    return Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR));
-                }
-            }
-            NodeType::Namespace => {
-                if self
-                    .ns(child)
-                    .ok_or(Error::DOMException(ExceptionCode::NOT_FOUND_ERR))?
-                    .node
-                    .parent
-                    .is_some()
-                {
-                    return Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR));
-                }
-            }
-            NodeType::Comment => {
-                if self
-                    .comment(child)
-                    .ok_or(Error::DOMException(ExceptionCode::NOT_FOUND_ERR))?
-                    .node
-                    .parent
-                    .is_some()
-                {
-                    return Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR));
-                }
-            }
-            NodeType::ProcessingInstruction => {
-                if self
-                    .pi(child)
-                    .ok_or(Error::DOMException(ExceptionCode::NOT_FOUND_ERR))?
-                    .node
-                    .parent
-                    .is_some()
-                {
-                    return Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR));
-                }
-            }
-            NodeType::Text => {
-                if self
-                    .text(child)
-                    .ok_or(Error::DOMException(ExceptionCode::NOT_FOUND_ERR))?
-                    .node
-                    .parent
-                    .is_some()
-                {
-                    return Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR));
-                }
-            }
-            NodeType::DocumentType => {
-                if self
-                    .doc_type(child)
-                    .ok_or(Error::DOMException(ExceptionCode::NOT_FOUND_ERR))?
-                    .node
-                    .parent
-                    .is_some()
-                {
-                    return Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR));
-                }
+    /// Returns the index of `child` in the children list of `parent`.
+    fn child_index(&self, parent: &DOMObject, child: &DOMObject) -> Result<usize> {
+        if is_child_node(child) {
+            if let Some(index) = self
+                .node(parent)
+                .and_then(|node| node.children.iter().position(|obj| obj == child))
+            {
+                return Ok(index);
             }
-            _ => unimplemented!(),
         }
 
-        Ok(())
+        Err(Error::DOMException(ExceptionCode::NOT_FOUND_ERR))
     }
 
-    /// Attach a new child to one element node.
-    fn append_element(&mut self, parent: &DOMObject, child: DOMObject) -> Result<()> {
-        assert_eq!(parent.node_type(), NodeType::Element);
+    /// Remove `child` from the children list of its parent.
+    fn detach(&mut self, child: &DOMObject) {
+        if self.node(child).is_none_or(|node| node.parent.is_none()) {
+            return;
+        }
 
-        self.els
-            .iter_mut()
-            .find(|el| el.object == *parent)
-            .ok_or(Error::DOMException(ExceptionCode::NOT_FOUND_ERR))?
-            .append_child(child)?;
+        if child.node_type() == NodeType::Attribute {
+            self.unindex_id(child);
+        } else if is_child_node(child) {
+            self.node_removed(child);
+            self.remove_from_iterators(child);
+            self.remove_from_ranges(child);
+        }
 
-        Ok(())
+        let Some(parent) = self.node_mut(child).and_then(|node| node.parent.take()) else {
+            return;
+        };
+
+        if child.node_type() == NodeType::Attribute {
+            if let Some(el) = self.els.get_mut(&parent) {
+                el.attributes.items.retain(|obj| obj != child);
+            }
+
+            self.attr_modified(&parent, child, AttrChange::Removal, None);
+        } else if let Some(node) = self.node_mut(&parent) {
+            node.remove_child(child);
+
+            if is_child_node(child) {
+                self.subtree_modified(&parent);
+            }
+        }
     }
 
-    /// Attach a new child to one element node.
-    fn append_attr(&mut self, parent: &DOMObject, child: DOMObject) -> Result<()> {
-        assert_eq!(parent.node_type(), NodeType::Attribute);
+    /// Insert `child` into the children list of `parent` at `index`.
+    fn attach(&mut self, parent: &DOMObject, index: usize, child: DOMObject) {
+        if let Some(node) = self.node_mut(parent) {
+            node.children.insert(index, child);
+        }
 
-        self.attrs
-            .iter_mut()
-            .find(|el| el.object == *parent)
-            .ok_or(Error::DOMException(ExceptionCode::NOT_FOUND_ERR))?
-            .append_child(child)?;
+        if let Some(node) = self.node_mut(&child) {
+            node.parent = Some(*parent);
+        }
 
-        Ok(())
+        if is_child_node(&child) {
+            self.insert_into_ranges(&child);
+            self.node_inserted(&child);
+        }
     }
 }
 
 impl<'a> Document<'a> {
     /// Returns nodes allocated by this `Document` includes unused ones.
     pub fn allocated(&self) -> usize {
-        self.els.len() + self.nss.len() + self.attrs.len()
+        self.doc_types.len()
+            + self.els.len()
+            + self.attrs.len()
+            + self.nss.len()
+            + self.pis.len()
+            + self.cms.len()
+            + self.texts.len()
+            + self.notations.len()
+            + self.entities.len()
+            + self.entity_refs.len()
+            + self.cdatas.len()
     }
 
     /// Free unused allocated nodes.
@@ -629,120 +833,54 @@ impl<'a> Document<'a> {
         while let Some(top) = stack.pop() {
             self.gc_mark(&top);
 
-            let mut children = self.children(Some(&top)).cloned().collect::<Vec<_>>();
-
-            stack.append(&mut children);
-        }
-
-        // check elements.
-        let mut els = vec![];
+            stack.extend(self.children(Some(&top)));
 
-        for mut el in self.els.drain(..) {
-            if el.node.check_gc_state() {
-                els.push(el);
+            if top.node_type() == NodeType::Element {
+                if let Some(el) = self.els.get(&top) {
+                    stack.extend(&el.attributes);
+                }
             }
         }
 
-        self.els = els;
+        // check elements.
+        self.els.retain(|el| el.node.check_gc_state());
 
         // check attrs.
-        let mut attrs = vec![];
-
-        for mut attr in self.attrs.drain(..) {
-            if attr.node.check_gc_state() {
-                attrs.push(attr);
-            }
-        }
-
-        self.attrs = attrs;
+        self.attrs.retain(|attr| attr.node.check_gc_state());
 
         // check namespaces.
-        let mut nss = vec![];
-
-        for mut ns in self.nss.drain(..) {
-            if ns.node.check_gc_state() {
-                nss.push(ns);
-            }
-        }
-
-        self.nss = nss;
+        self.nss.retain(|ns| ns.node.check_gc_state());
 
         // check ProcessingInstructions.
-        let mut pis = vec![];
-
-        for mut pi in self.pis.drain(..) {
-            if pi.node.check_gc_state() {
-                pis.push(pi);
-            }
-        }
-
-        self.pis = pis;
+        self.pis.retain(|pi| pi.node.check_gc_state());
 
         // check Comment list.
-        let mut cms = vec![];
-
-        for mut cm in self.cms.drain(..) {
-            if cm.node.check_gc_state() {
-                cms.push(cm);
-            }
-        }
-
-        self.cms = cms;
+        self.cms.retain(|cm| cm.node.check_gc_state());
 
         // check Text list.
-        let mut texts = vec![];
-
-        for mut text in self.texts.drain(..) {
-            if text.node.check_gc_state() {
-                texts.push(text);
-            }
-        }
-
-        self.texts = texts;
+        self.texts.retain(|text| text.node.check_gc_state());
 
         // check Notation list.
-        let mut notations = vec![];
-
-        for mut notation in self.notations.drain(..) {
-            if notation.node.check_gc_state() {
-                notations.push(notation);
-            }
-        }
-
-        self.notations = notations;
+        self.notations
+            .retain(|notation| notation.node.check_gc_state());
 
         // check Entity list.
-        let mut entities = vec![];
+        self.entities.retain(|entity| entity.node.check_gc_state());
 
-        for mut entity in self.entities.drain(..) {
-            if entity.node.check_gc_state() {
-                entities.push(entity);
-            }
-        }
-
-        self.entities = entities;
+        // check EntityReference list.
+        self.entity_refs
+            .retain(|entity_ref| entity_ref.node.check_gc_state());
 
         // check DocumentType list.
-        let mut doc_types = vec![];
-
-        for mut doc_type in self.doc_types.drain(..) {
-            if doc_type.node.check_gc_state() {
-                doc_types.push(doc_type);
-            }
-        }
-
-        self.doc_types = doc_types;
+        self.doc_types
+            .retain(|doc_type| doc_type.node.check_gc_state());
 
         // check CData list.
-        let mut cdatas = vec![];
-
-        for mut cdata in self.cdatas.drain(..) {
-            if cdata.node.check_gc_state() {
-                cdatas.push(cdata);
-            }
-        }
+        self.cdatas.retain(|cdata| cdata.node.check_gc_state());
 
-        self.cdatas = cdatas;
+        self.prune_ids();
+        self.prune_ranges();
+        self.prune_event_listeners();
     }
 
     /// Create a new `Element` node.
@@ -751,13 +889,40 @@ impl<'a> Document<'a> {
         T: TryInto<QName<'a>>,
         Error: From<T::Error>,
     {
-        let object = DOMObject::new(self.els.len(), NodeType::Element);
+        let tag = tag.try_into()?;
+
+        let el = self
+            .els
+            .insert_with(NodeType::Element, |object| Element::new(object, tag));
+
+        Ok(el.object)
+    }
+
+    /// Create a new `Element` node in the namespace `namespace_uri`.
+    ///
+    /// Raises `NAMESPACE_ERR` if the prefix of `qualified_name` doesn't fit the namespace URI.
+    pub fn create_element_ns<N, T>(
+        &mut self,
+        namespace_uri: Option<N>,
+        qualified_name: T,
+    ) -> Result<DOMObject>
+    where
+        N: Into<Cow<'a, str>>,
+        T: TryInto<QName<'a>>,
+        Error: From<T::Error>,
+    {
+        let tag = qualified_name.try_into()?;
+        let namespace_uri = namespace_uri.map(Into::into);
 
-        let el = Element::new(object.clone(), tag.try_into()?);
+        check_namespace(namespace_uri.as_deref(), &tag, false)?;
 
-        self.els.push(el);
+        let el = self.els.insert_with(NodeType::Element, |object| {
+            let mut el = Element::new(object, tag);
+            el.namespace_uri = namespace_uri;
+            el
+        });
 
-        Ok(object)
+        Ok(el.object)
     }
 
     /// Create a new `Attr` node.
@@ -767,13 +932,37 @@ impl<'a> Document<'a> {
         Error: From<T::Error>,
         V: Into<Cow<'a, str>>,
     {
-        let object = DOMObject::new(self.els.len(), NodeType::Attribute);
+        Ok(self.new_attr(tag.try_into()?, value.into()))
+    }
+
+    /// Create a new `Attr` node in the namespace `namespace_uri`.
+    ///
+    /// Raises `NAMESPACE_ERR` if the prefix of `qualified_name` doesn't fit the namespace URI.
+    pub fn create_attr_ns<N, T, V>(
+        &mut self,
+        namespace_uri: Option<N>,
+        qualified_name: T,
+        value: V,
+    ) -> Result<DOMObject>
+    where
+        N: Into<Cow<'a, str>>,
+        T: TryInto<QName<'a>>,
+        Error: From<T::Error>,
+        V: Into<Cow<'a, str>>,
+    {
+        let name = qualified_name.try_into()?;
+        let namespace_uri = namespace_uri.map(Into::into);
+
+        check_namespace(namespace_uri.as_deref(), &name, true)?;
 
-        let attr = Attr::new(object.clone(), tag.try_into()?, value.into());
+        let attr = self.new_attr(name, value.into());
 
-        self.attrs.push(attr);
+        self.attrs
+            .get_mut(&attr)
+            .expect("new attribute")
+            .namespace_uri = namespace_uri;
 
-        Ok(object)
+        Ok(attr)
     }
 
     /// Create a new `Namespace` node.
@@ -782,13 +971,11 @@ impl<'a> Document<'a> {
         P: Into<Cow<'a, str>>,
         H: Into<Cow<'a, str>>,
     {
-        let object = DOMObject::new(self.els.len(), NodeType::Namespace);
-
-        let ns = Namespace::new(object.clone(), prefix.into(), href.into());
+        let ns = self.nss.insert_with(NodeType::Namespace, |object| {
+            Namespace::new(object, prefix.into(), href.into())
+        });
 
-        self.nss.push(ns);
-
-        Ok(object)
+        Ok(ns.object)
     }
 
     /// Create a new `ProcessingInstruction` node.
@@ -797,31 +984,44 @@ impl<'a> Document<'a> {
         T: Into<Cow<'a, str>>,
         D: Into<Cow<'a, str>>,
     {
-        let object = DOMObject::new(self.els.len(), NodeType::ProcessingInstruction);
-
-        let pi = ProcessingInstruction::new(object.clone(), target.into(), data.into());
+        let pi = self
+            .pis
+            .insert_with(NodeType::ProcessingInstruction, |object| {
+                ProcessingInstruction::new(object, target.into(), data.into())
+            });
 
-        self.pis.push(pi);
-
-        Ok(object)
+        Ok(pi.object)
     }
 
-    /// Create a new `Notation` node.
+    /// Create a new `Notation` node without a name.
     pub fn create_notation<P, S>(&mut self, public_id: P, system_id: S) -> Result<DOMObject>
     where
         P: Into<Cow<'a, str>>,
         S: Into<Cow<'a, str>>,
     {
-        let object = DOMObject::new(self.els.len(), NodeType::Notation);
-
-        let notation = Notation::new(object.clone(), public_id.into(), system_id.into());
+        self.create_notation_named("", public_id, system_id)
+    }
 
-        self.notations.push(notation);
+    /// Create a new `Notation` node named `name`.
+    pub fn create_notation_named<N, P, S>(
+        &mut self,
+        name: N,
+        public_id: P,
+        system_id: S,
+    ) -> Result<DOMObject>
+    where
+        N: Into<Cow<'a, str>>,
+        P: Into<Cow<'a, str>>,
+        S: Into<Cow<'a, str>>,
+    {
+        let notation = self.notations.insert_with(NodeType::Notation, |object| {
+            Notation::new(object, name.into(), public_id.into(), system_id.into())
+        });
 
-        Ok(object)
+        Ok(notation.object)
     }
 
-    /// Create a new `Entity` node.
+    /// Create a new `Entity` node without a name.
     pub fn create_entity<P, S>(
         &mut self,
         public_id: P,
@@ -832,46 +1032,71 @@ impl<'a> Document<'a> {
         P: Into<Cow<'a, str>>,
         S: Into<Cow<'a, str>>,
     {
-        let object = DOMObject::new(self.els.len(), NodeType::Entity);
-
-        let entity = Entity::new(
-            object.clone(),
-            public_id.into(),
-            system_id.into(),
-            notation_name,
-        );
-
-        self.entities.push(entity);
-
-        Ok(object)
+        self.create_entity_named("", public_id, system_id, notation_name)
     }
 
-    /// Create a new `Comment` node.
-    pub fn create_comment<D>(&mut self, data: D) -> Result<DOMObject>
-    where
-        D: Into<Cow<'a, str>>,
+    /// Create a new `Entity` node named `name`.
+    pub fn create_entity_named<N, P, S>(
+        &mut self,
+        name: N,
+        public_id: P,
+        system_id: S,
+        notation_name: Option<Cow<'a, str>>,
+    ) -> Result<DOMObject>
+    where
+        N: Into<Cow<'a, str>>,
+        P: Into<Cow<'a, str>>,
+        S: Into<Cow<'a, str>>,
     {
-        let object = DOMObject::new(self.els.len(), NodeType::Comment);
+        let entity = self.entities.insert_with(NodeType::Entity, |object| {
+            Entity::new(
+                object,
+                name.into(),
+                public_id.into(),
+                system_id.into(),
+                notation_name,
+            )
+        });
 
-        let cm = Comment::new(object.clone(), data.into());
+        Ok(entity.object)
+    }
 
-        self.cms.push(cm);
+    /// Create a new `EntityReference` node.
+    pub fn create_entity_reference<N>(&mut self, name: N) -> Result<DOMObject>
+    where
+        N: Into<Cow<'a, str>>,
+    {
+        let entity_ref = self
+            .entity_refs
+            .insert_with(NodeType::EntityReference, |object| {
+                EntityReference::new(object, name.into())
+            });
 
-        Ok(object)
+        Ok(entity_ref.object)
     }
 
-    /// Create a new `CData` node.
-    pub fn create_cdata<D>(&mut self, data: D) -> Result<DOMObject>
+    /// Create a new `Comment` node.
+    pub fn create_comment<D>(&mut self, data: D) -> Result<DOMObject>
     where
         D: Into<Cow<'a, str>>,
     {
-        let object = DOMObject::new(self.els.len(), NodeType::CData);
+        let cm = self.cms.insert_with(NodeType::Comment, |object| {
+            Comment::new(object, data.into())
+        });
 
-        let cdata = CData::new(object.clone(), data.into());
+        Ok(cm.object)
+    }
 
-        self.cdatas.push(cdata);
+    /// Create a new `CData` node.
+    pub fn create_cdata<D>(&mut self, data: D) -> Result<DOMObject>
+    where
+        D: Into<Cow<'a, str>>,
+    {
+        let cdata = self
+            .cdatas
+            .insert_with(NodeType::CData, |object| CData::new(object, data.into()));
 
-        Ok(object)
+        Ok(cdata.object)
     }
 
     /// Create a new `Text` node.
@@ -879,16 +1104,14 @@ impl<'a> Document<'a> {
     where
         D: Into<Cow<'a, str>>,
     {
-        let object = DOMObject::new(self.els.len(), NodeType::Text);
-
-        let text = Text::new(object.clone(), data.into());
+        let text = self
+            .texts
+            .insert_with(NodeType::Text, |object| Text::new(object, data.into()));
 
-        self.texts.push(text);
-
-        Ok(object)
+        Ok(text.object)
     }
 
-    /// Create a new `DocumentType` node.
+    /// Create a new `DocumentType` node without a name.
     pub fn create_document_type<P, S, I>(
         &mut self,
         public_id: P,
@@ -900,203 +1123,1026 @@ impl<'a> Document<'a> {
         S: Into<Cow<'a, str>>,
         I: Into<Cow<'a, str>>,
     {
-        let object = DOMObject::new(self.els.len(), NodeType::DocumentType);
-
-        let doc_type = DocumentType::new(
-            object.clone(),
-            public_id.into(),
-            system_id.into(),
-            internal_subset.into(),
-        );
+        self.create_document_type_named("", public_id, system_id, internal_subset)
+    }
 
-        self.doc_types.push(doc_type);
+    /// Create a new `DocumentType` node named `name`, the name of the root element.
+    pub fn create_document_type_named<N, P, S, I>(
+        &mut self,
+        name: N,
+        public_id: P,
+        system_id: S,
+        internal_subset: I,
+    ) -> Result<DOMObject>
+    where
+        N: Into<Cow<'a, str>>,
+        P: Into<Cow<'a, str>>,
+        S: Into<Cow<'a, str>>,
+        I: Into<Cow<'a, str>>,
+    {
+        let doc_type = self
+            .doc_types
+            .insert_with(NodeType::DocumentType, |object| {
+                DocumentType::new(
+                    object,
+                    name.into(),
+                    public_id.into(),
+                    system_id.into(),
+                    internal_subset.into(),
+                )
+            });
 
-        Ok(object)
+        Ok(doc_type.object)
     }
 
     /// Attach a new child to the parent node.
     ///
     /// If the parent is none, the new child will be attached to root node.
+    ///
+    /// If the child is already in the tree, it is first removed. Attributes are added to
+    /// the element as [`set_attribute_node`](Self::set_attribute_node) does. Namespaces,
+    /// entities and notations are attached to their owner and must not be attached elsewhere.
     pub fn append_child(&mut self, parent: Option<&DOMObject>, child: DOMObject) -> Result<()> {
+        if is_child_node(&child) {
+            return self.insert_before(parent, child, None).map(|_| ());
+        }
+
+        if let Some(parent) = parent.filter(|parent| parent.node_type() == NodeType::Element) {
+            if child.node_type() == NodeType::Attribute {
+                return self.set_attribute_node(parent, child).map(|_| ());
+            }
+        }
+
+        let parent = parent.copied().unwrap_or_default();
+
+        self.insert_check(&parent, &child, None)?;
         self.append_child_check(&child)?;
 
-        if parent.is_none() {
-            return self.append_to_document(child);
+        let index = self.node(&parent).map(|node| node.children.len());
+
+        self.attach(&parent, index.unwrap_or_default(), child);
+
+        Ok(())
+    }
+
+    /// Insert `new_child` before `ref_child`, or at the end of the children list if `ref_child` is none.
+    ///
+    /// If `new_child` is already in the tree, it is first removed. Returns the inserted node.
+    pub fn insert_before(
+        &mut self,
+        parent: Option<&DOMObject>,
+        new_child: DOMObject,
+        ref_child: Option<&DOMObject>,
+    ) -> Result<DOMObject> {
+        let parent = parent.copied().unwrap_or_default();
+
+        self.insert_check(&parent, &new_child, None)?;
+
+        if !is_child_node(&new_child) {
+            return Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR));
         }
 
-        let parent = parent.unwrap();
+        if let Some(ref_child) = ref_child {
+            self.child_index(&parent, ref_child)?;
 
-        match parent.node_type() {
-            NodeType::Element => {
-                return self.append_element(parent, child);
-            }
-            NodeType::Attribute => {
-                return self.append_attr(parent, child);
+            if *ref_child == new_child {
+                return Ok(new_child);
             }
-            _ => unimplemented!(),
         }
+
+        self.detach(&new_child);
+
+        let index = match ref_child {
+            Some(ref_child) => self.child_index(&parent, ref_child)?,
+            None => self
+                .node(&parent)
+                .map(|node| node.children.len())
+                .unwrap_or_default(),
+        };
+
+        self.attach(&parent, index, new_child);
+
+        Ok(new_child)
     }
 
-    /// Returns one node's children list.
-    pub fn children(&self, parent: Option<&DOMObject>) -> NodeIterator<'_> {
-        if let Some(parent) = parent {
-            match parent.node_type() {
-                NodeType::Element => {
-                    if let Some(el) = self.element(parent) {
-                        NodeIterator::Iter(el.node.children.iter())
-                    } else {
-                        NodeIterator::Empty
+    /// Replace `old_child` with `new_child` in the children list, returns the replaced node.
+    ///
+    /// If `new_child` is already in the tree, it is first removed.
+    pub fn replace_child(
+        &mut self,
+        parent: Option<&DOMObject>,
+        new_child: DOMObject,
+        old_child: &DOMObject,
+    ) -> Result<DOMObject> {
+        let parent = parent.copied().unwrap_or_default();
+
+        self.insert_check(&parent, &new_child, Some(old_child))?;
+
+        if !is_child_node(&new_child) {
+            return Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR));
+        }
+
+        self.child_index(&parent, old_child)?;
+
+        if *old_child == new_child {
+            return Ok(new_child);
+        }
+
+        self.detach(&new_child);
+
+        let index = self.child_index(&parent, old_child)?;
+
+        self.detach(old_child);
+        self.attach(&parent, index, new_child);
+
+        Ok(*old_child)
+    }
+
+    /// Remove `old_child` from the children list, returns the removed node.
+    pub fn remove_child(
+        &mut self,
+        parent: Option<&DOMObject>,
+        old_child: &DOMObject,
+    ) -> Result<DOMObject> {
+        let parent = parent.copied().unwrap_or_default();
+
+        self.child_index(&parent, old_child)?;
+        self.detach(old_child);
+
+        Ok(*old_child)
+    }
+
+    /// Returns true if this node, or the `Document` node if `parent` is none, has any children.
+    pub fn has_child_nodes(&self, parent: Option<&DOMObject>) -> bool {
+        self.first_child(parent).is_some()
+    }
+
+    /// Merge adjacent `Text` nodes and remove empty ones in the full depth of the subtree,
+    /// including attribute nodes.
+    pub fn normalize(&mut self, object: Option<&DOMObject>) {
+        let mut stack = vec![object.copied().unwrap_or_default()];
+
+        while let Some(parent) = stack.pop() {
+            let children = match self.node(&parent) {
+                Some(node) => node.children.clone(),
+                None => continue,
+            };
+
+            if parent.node_type() == NodeType::Element {
+                if let Some(el) = self.els.get(&parent) {
+                    stack.extend(&el.attributes);
+                }
+            }
+
+            let mut last_text: Option<DOMObject> = None;
+
+            for child in children {
+                match child.node_type() {
+                    NodeType::Text => {
+                        let data = match self.texts.get(&child) {
+                            Some(text) => text.data.to_string(),
+                            None => continue,
+                        };
+
+                        if let Some(last) = last_text.filter(|_| !data.is_empty()) {
+                            let listening = self.has_event_listeners();
+
+                            if let Some(text) = self.texts.get_mut(&last) {
+                                let length = text.data.chars().count();
+                                let prev_value = listening.then(|| text.data.to_string());
+
+                                text.data.to_mut().push_str(&data);
+
+                                self.merge_text_in_ranges(&last, length, &child);
+
+                                if let Some(prev_value) = prev_value {
+                                    self.character_data_modified(&last, prev_value);
+                                }
+                            }
+
+                            self.detach(&child);
+                        } else if data.is_empty() {
+                            self.detach(&child);
+                        } else {
+                            last_text = Some(child);
+                        }
+                    }
+                    // namespaces are not in the children list of the DOM.
+                    NodeType::Namespace => {}
+                    _ => {
+                        last_text = None;
+                        stack.push(child);
                     }
                 }
-                _ => unimplemented!(),
             }
-        } else {
-            NodeIterator::Iter(self.this_node.children.iter())
         }
     }
 
+    /// Returns one node's children list.
+    ///
+    /// The children of a `DocumentType` are its entities and notations.
+    pub fn children(&self, parent: Option<&DOMObject>) -> NodeIterator<'_> {
+        let node = match parent {
+            Some(parent) => self.node(parent),
+            None => Some(&self.this_node),
+        };
+
+        match node {
+            Some(node) => NodeIterator::Iter(node.children.iter()),
+            None => NodeIterator::Empty,
+        }
+    }
+
+    /// Returns the parent of this node.
+    ///
+    /// The top-level nodes have the `Document` node as parent, which is [`DOMObject::default`].
+    /// Attributes, namespaces, entities and notations have no parent, see [`owner_element`](Self::owner_element).
+    pub fn parent_node(&self, object: &DOMObject) -> Option<DOMObject> {
+        if !is_child_node(object) {
+            return None;
+        }
+
+        self.node(object)?.parent
+    }
+
+    /// Returns the element to which this attribute or namespace is attached.
+    pub fn owner_element(&self, object: &DOMObject) -> Option<DOMObject> {
+        match object.node_type() {
+            NodeType::Attribute | NodeType::Namespace => self.node(object)?.parent,
+            _ => None,
+        }
+    }
+
+    /// Returns the first child of this node, or of the `Document` node if `parent` is none.
+    pub fn first_child(&self, parent: Option<&DOMObject>) -> Option<DOMObject> {
+        self.children(parent)
+            .find(|obj| is_child_node(obj))
+            .copied()
+    }
+
+    /// Returns the last child of this node, or of the `Document` node if `parent` is none.
+    pub fn last_child(&self, parent: Option<&DOMObject>) -> Option<DOMObject> {
+        self.children(parent)
+            .rev()
+            .find(|obj| is_child_node(obj))
+            .copied()
+    }
+
+    /// Returns the node immediately preceding this node.
+    pub fn previous_sibling(&self, object: &DOMObject) -> Option<DOMObject> {
+        let siblings = self.siblings(object)?;
+
+        let index = siblings.iter().position(|obj| obj == object)?;
+
+        siblings[..index]
+            .iter()
+            .rev()
+            .find(|obj| is_child_node(obj))
+            .copied()
+    }
+
+    /// Returns the node immediately following this node.
+    pub fn next_sibling(&self, object: &DOMObject) -> Option<DOMObject> {
+        let siblings = self.siblings(object)?;
+
+        let index = siblings.iter().position(|obj| obj == object)?;
+
+        siblings[index + 1..]
+            .iter()
+            .find(|obj| is_child_node(obj))
+            .copied()
+    }
+
+    /// Returns the children list of the parent of this node.
+    fn siblings(&self, object: &DOMObject) -> Option<&[DOMObject]> {
+        let parent = self.parent_node(object)?;
+
+        Some(&self.node(&parent)?.children)
+    }
+
     /// Returns a immutable reference to [`Element`]
     pub fn element(&self, object: &DOMObject) -> Option<&Element<'a>> {
         assert_eq!(object.node_type(), NodeType::Element);
 
-        self.els.iter().find(|el| el.object == *object)
+        self.els.get(object)
     }
 
     /// Returns a mutable reference to [`Element`]
     pub fn element_mut(&mut self, object: &DOMObject) -> Option<&mut Element<'a>> {
         assert_eq!(object.node_type(), NodeType::Element);
 
-        self.els.iter_mut().find(|el| el.object == *object)
+        self.els.get_mut(object)
     }
 
     /// Returns a immutable reference to [`Attr`]
     pub fn attr(&self, object: &DOMObject) -> Option<&Attr<'a>> {
         assert_eq!(object.node_type(), NodeType::Attribute);
 
-        self.attrs.iter().find(|el| el.object == *object)
+        self.attrs.get(object)
     }
 
     /// Returns a mutable reference to [`Attr`]
     pub fn attr_mut(&mut self, object: &DOMObject) -> Option<&mut Attr<'a>> {
         assert_eq!(object.node_type(), NodeType::Attribute);
 
-        self.attrs.iter_mut().find(|el| el.object == *object)
+        self.attrs.get_mut(object)
     }
 
     /// Returns a immutable reference to [`Namespace`]
     pub fn ns(&self, object: &DOMObject) -> Option<&Namespace<'a>> {
         assert_eq!(object.node_type(), NodeType::Namespace);
 
-        self.nss.iter().find(|el| el.object == *object)
+        self.nss.get(object)
     }
 
     /// Returns a mutable reference to [`Namespace`]
     pub fn ns_mut(&mut self, object: &DOMObject) -> Option<&mut Namespace<'a>> {
         assert_eq!(object.node_type(), NodeType::Namespace);
 
-        self.nss.iter_mut().find(|el| el.object == *object)
+        self.nss.get_mut(object)
     }
 
     /// Returns a immutable reference to [`ProcessingInstruction`]
     pub fn pi(&self, object: &DOMObject) -> Option<&ProcessingInstruction<'a>> {
         assert_eq!(object.node_type(), NodeType::ProcessingInstruction);
 
-        self.pis.iter().find(|el| el.object == *object)
+        self.pis.get(object)
     }
 
     /// Returns a mutable reference to [`ProcessingInstruction`]
     pub fn pi_mut(&mut self, object: &DOMObject) -> Option<&mut ProcessingInstruction<'a>> {
         assert_eq!(object.node_type(), NodeType::ProcessingInstruction);
 
-        self.pis.iter_mut().find(|el| el.object == *object)
+        self.pis.get_mut(object)
     }
 
     /// Returns a immutable reference to [`Comment`]
     pub fn comment(&self, object: &DOMObject) -> Option<&Comment<'a>> {
         assert_eq!(object.node_type(), NodeType::Comment);
 
-        self.cms.iter().find(|el| el.object == *object)
+        self.cms.get(object)
     }
 
     /// Returns a mutable reference to [`Comment`]
     pub fn comment_mut(&mut self, object: &DOMObject) -> Option<&mut Comment<'a>> {
         assert_eq!(object.node_type(), NodeType::Comment);
 
-        self.cms.iter_mut().find(|el| el.object == *object)
+        self.cms.get_mut(object)
     }
 
     /// Returns a immutable reference to [`Text`]
     pub fn text(&self, object: &DOMObject) -> Option<&Text<'a>> {
         assert_eq!(object.node_type(), NodeType::Text);
 
-        self.texts.iter().find(|el| el.object == *object)
+        self.texts.get(object)
     }
 
     /// Returns a mutable reference to [`Text`]
     pub fn text_mut(&mut self, object: &DOMObject) -> Option<&mut Text<'a>> {
         assert_eq!(object.node_type(), NodeType::Text);
 
-        self.texts.iter_mut().find(|el| el.object == *object)
+        self.texts.get_mut(object)
     }
 
     /// Returns a immutable reference to [`Notation`]
     pub fn notation(&self, object: &DOMObject) -> Option<&Notation<'a>> {
         assert_eq!(object.node_type(), NodeType::Notation);
 
-        self.notations.iter().find(|el| el.object == *object)
+        self.notations.get(object)
     }
 
     /// Returns a mutable reference to [`Text`]
     pub fn notation_mut(&mut self, object: &DOMObject) -> Option<&mut Notation<'a>> {
         assert_eq!(object.node_type(), NodeType::Notation);
 
-        self.notations.iter_mut().find(|el| el.object == *object)
+        self.notations.get_mut(object)
     }
 
     /// Returns a immutable reference to [`Entity`]
     pub fn entity(&self, object: &DOMObject) -> Option<&Entity<'a>> {
         assert_eq!(object.node_type(), NodeType::Entity);
 
-        self.entities.iter().find(|el| el.object == *object)
+        self.entities.get(object)
     }
 
     /// Returns a mutable reference to [`Entity`]
     pub fn entity_mut(&mut self, object: &DOMObject) -> Option<&mut Entity<'a>> {
         assert_eq!(object.node_type(), NodeType::Entity);
 
-        self.entities.iter_mut().find(|el| el.object == *object)
+        self.entities.get_mut(object)
+    }
+
+    /// Returns a immutable reference to [`EntityReference`]
+    pub fn entity_reference(&self, object: &DOMObject) -> Option<&EntityReference<'a>> {
+        assert_eq!(object.node_type(), NodeType::EntityReference);
+
+        self.entity_refs.get(object)
     }
 
     /// Returns a immutable reference to [`DocumentType`]
     pub fn doc_type(&self, object: &DOMObject) -> Option<&DocumentType<'a>> {
         assert_eq!(object.node_type(), NodeType::DocumentType);
 
-        self.doc_types.iter().find(|el| el.object == *object)
+        self.doc_types.get(object)
     }
 
     /// Returns a mutable reference to [`Entity`]
     pub fn doc_type_mut(&mut self, object: &DOMObject) -> Option<&mut DocumentType<'a>> {
         assert_eq!(object.node_type(), NodeType::DocumentType);
 
-        self.doc_types.iter_mut().find(|el| el.object == *object)
+        self.doc_types.get_mut(object)
     }
 
     /// Returns a immutable reference to [`CData`]
     pub fn cdata(&self, object: &DOMObject) -> Option<&CData<'a>> {
-        assert_eq!(object.node_type(), NodeType::DocumentType);
+        assert_eq!(object.node_type(), NodeType::CData);
 
-        self.cdatas.iter().find(|el| el.object == *object)
+        self.cdatas.get(object)
     }
 
     /// Returns a mutable reference to [`CData`]
     pub fn cdata_mut(&mut self, object: &DOMObject) -> Option<&mut CData<'a>> {
-        assert_eq!(object.node_type(), NodeType::DocumentType);
+        assert_eq!(object.node_type(), NodeType::CData);
+
+        self.cdatas.get_mut(object)
+    }
+}
+
+/// Character data and cloning.
+impl<'a> Document<'a> {
+    /// Returns the data of a `Text`, `CDATASection`, `Comment` or `ProcessingInstruction` node.
+    pub fn character_data(&self, object: &DOMObject) -> Option<&str> {
+        match object.node_type() {
+            NodeType::Text => self.texts.get(object).map(|n| n.data.as_ref()),
+            NodeType::CData => self.cdatas.get(object).map(|n| n.data.as_ref()),
+            NodeType::Comment => self.cms.get(object).map(|n| n.data.as_ref()),
+            NodeType::ProcessingInstruction => self.pis.get(object).map(|n| n.data.as_ref()),
+            _ => None,
+        }
+    }
+
+    fn character_data_mut(&mut self, object: &DOMObject) -> Result<&mut Cow<'a, str>> {
+        let data = match object.node_type() {
+            NodeType::Text => self.texts.get_mut(object).map(|n| &mut n.data),
+            NodeType::CData => self.cdatas.get_mut(object).map(|n| &mut n.data),
+            NodeType::Comment => self.cms.get_mut(object).map(|n| &mut n.data),
+            NodeType::ProcessingInstruction => self.pis.get_mut(object).map(|n| &mut n.data),
+            _ => return Err(Error::DOMException(ExceptionCode::NOT_SUPPORTED_ERR)),
+        };
+
+        data.ok_or(Error::DOMException(ExceptionCode::NOT_FOUND_ERR))
+    }
+
+    /// Returns `count` characters of the data of `object` from `offset`, or the ones
+    /// to the end of the data.
+    ///
+    /// The offsets of character data are counted in `char`s.
+    pub fn substring_data(&self, object: &DOMObject, offset: usize, count: usize) -> Result<&str> {
+        let data = self
+            .character_data(object)
+            .ok_or(Error::DOMException(ExceptionCode::NOT_FOUND_ERR))?;
+
+        let (start, end) = char_range(data, offset, count)?;
+
+        Ok(&data[start..end])
+    }
+
+    /// Replace `count` characters of the data of `object` from `offset` with `data`.
+    ///
+    /// Raises `INDEX_SIZE_ERR` if `offset` is greater than the number of characters.
+    pub fn replace_data(
+        &mut self,
+        object: &DOMObject,
+        offset: usize,
+        count: usize,
+        data: &str,
+    ) -> Result<()> {
+        let listening = self.has_event_listeners();
+        let current = self.character_data_mut(object)?;
+
+        let (start, end) = char_range(current, offset, count)?;
+        let count = current[start..end].chars().count();
+        let prev_value = listening.then(|| current.to_string());
+
+        current.to_mut().replace_range(start..end, data);
+
+        self.replace_data_in_ranges(object, offset, count, data.chars().count());
+
+        if let Some(prev_value) = prev_value {
+            self.character_data_modified(object, prev_value);
+        }
+
+        Ok(())
+    }
+
+    /// Insert `data` into the data of `object` at `offset`.
+    pub fn insert_data(&mut self, object: &DOMObject, offset: usize, data: &str) -> Result<()> {
+        self.replace_data(object, offset, 0, data)
+    }
+
+    /// Remove `count` characters of the data of `object` from `offset`.
+    pub fn delete_data(&mut self, object: &DOMObject, offset: usize, count: usize) -> Result<()> {
+        self.replace_data(object, offset, count, "")
+    }
+
+    /// Append `data` to the data of `object`.
+    pub fn append_data(&mut self, object: &DOMObject, data: &str) -> Result<()> {
+        let offset = self
+            .character_data(object)
+            .map(|data| data.chars().count())
+            .unwrap_or_default();
+
+        self.replace_data(object, offset, 0, data)
+    }
+
+    /// Split a `Text` or `CDATASection` node at `offset`, the data after `offset` is moved
+    /// to a new node of the same type, inserted after `object` if it has a parent.
+    /// Returns the new node.
+    pub fn split_text(&mut self, object: &DOMObject, offset: usize) -> Result<DOMObject> {
+        let tail = match object.node_type() {
+            NodeType::Text | NodeType::CData => {
+                self.substring_data(object, offset, usize::MAX)?.to_owned()
+            }
+            _ => return Err(Error::DOMException(ExceptionCode::NOT_SUPPORTED_ERR)),
+        };
+
+        let count = tail.chars().count();
+
+        let new_node = match object.node_type() {
+            NodeType::Text => self.create_text(tail)?,
+            _ => self.create_cdata(tail)?,
+        };
+
+        if let Some(parent) = self.parent_node(object) {
+            let next = self.next_sibling(object);
+
+            self.insert_before(Some(&parent), new_node, next.as_ref())?;
+            self.split_text_in_ranges(object, offset, &new_node);
+        }
+
+        self.replace_data(object, offset, count, "")?;
+
+        Ok(new_node)
+    }
+
+    /// Returns a copy of `object` without parent, with the attributes and namespace
+    /// declarations of an element, and with copies of its children if `deep` is true.
+    ///
+    /// The copies of the attributes of an element keep their [`specified`](Attr::specified)
+    /// flag, a copy of an attribute alone is specified.
+    ///
+    /// Raises `NOT_SUPPORTED_ERR` for `Document`, `DocumentType`, `Entity` and `Notation` nodes.
+    pub fn clone_node(&mut self, object: &DOMObject, deep: bool) -> Result<DOMObject> {
+        if self.node(object).is_none() {
+            return Err(Error::DOMException(ExceptionCode::NOT_FOUND_ERR));
+        }
+
+        let clone = match object.node_type() {
+            NodeType::Element => {
+                let el = self.els.get(object).expect("checked");
+                let (tag, namespace_uri) = (el.tag.clone(), el.namespace_uri.clone());
+                let attrs = el.attributes.items.clone();
+
+                let clone = self
+                    .els
+                    .insert_with(NodeType::Element, |object| Element::new(object, tag));
+
+                clone.namespace_uri = namespace_uri;
+
+                let clone = clone.object;
+
+                for attr in attrs {
+                    let specified = self.attrs.get(&attr).map(|attr| attr.specified);
+                    let attr = self.clone_node(&attr, false)?;
+
+                    // the attributes defaulted from the DTD stay defaulted.
+                    if let Some(specified) = specified {
+                        self.attr_mut(&attr)
+                            .expect("new attribute")
+                            .set_specified(specified);
+                    }
+
+                    self.set_attribute_node(&clone, attr)?;
+                }
+
+                clone
+            }
+            NodeType::Attribute => {
+                let attr = self.attrs.get(object).expect("checked");
+                let (name, value) = (attr.name.clone(), attr.value.clone());
+                let namespace_uri = attr.namespace_uri.clone();
+
+                let clone = self.new_attr(name, value);
+
+                self.attrs
+                    .get_mut(&clone)
+                    .expect("new attribute")
+                    .namespace_uri = namespace_uri;
+
+                clone
+            }
+            NodeType::Namespace => {
+                let ns = self.nss.get(object).expect("checked");
+                let (prefix, href) = (ns.prefix.clone(), ns.href.clone());
+
+                self.create_ns(prefix, href)?
+            }
+            NodeType::Text => {
+                let data = self.texts.get(object).expect("checked").data.clone();
+
+                self.create_text(data)?
+            }
+            NodeType::CData => {
+                let data = self.cdatas.get(object).expect("checked").data.clone();
+
+                self.create_cdata(data)?
+            }
+            NodeType::Comment => {
+                let data = self.cms.get(object).expect("checked").data.clone();
+
+                self.create_comment(data)?
+            }
+            NodeType::ProcessingInstruction => {
+                let pi = self.pis.get(object).expect("checked");
+                let (target, data) = (pi.target.clone(), pi.data.clone());
+
+                self.create_pi(target, data)?
+            }
+            NodeType::EntityReference => {
+                let name = self.entity_refs.get(object).expect("checked").name.clone();
+
+                self.create_entity_reference(name)?
+            }
+            _ => return Err(Error::DOMException(ExceptionCode::NOT_SUPPORTED_ERR)),
+        };
+
+        let children = self.children(Some(object)).copied().collect::<Vec<_>>();
+
+        for child in children {
+            if deep || child.node_type() == NodeType::Namespace {
+                let child = self.clone_node(&child, deep)?;
+
+                self.append_child(Some(&clone), child)?;
+            }
+        }
+
+        Ok(clone)
+    }
+}
+
+/// Attributes of elements.
+impl<'a> Document<'a> {
+    /// Returns the attribute node of `element` by qualified name.
+    pub fn get_attribute_node(&self, element: &DOMObject, name: &str) -> Option<DOMObject> {
+        self.element(element)?
+            .attributes
+            .iter()
+            .find(|obj| {
+                self.attrs
+                    .get(obj)
+                    .is_some_and(|attr| attr.name.matches(name))
+            })
+            .copied()
+    }
+
+    /// Returns the attribute node of `element` by namespace URI and local name.
+    pub fn get_attribute_node_ns(
+        &self,
+        element: &DOMObject,
+        namespace_uri: Option<&str>,
+        local_name: &str,
+    ) -> Option<DOMObject> {
+        self.element(element)?
+            .attributes
+            .iter()
+            .find(|obj| {
+                self.attrs
+                    .get(obj)
+                    .is_some_and(|attr| attr.matches_ns(namespace_uri, local_name))
+            })
+            .copied()
+    }
+
+    /// Returns the value of one attribute of `element` by qualified name.
+    pub fn get_attribute(&self, element: &DOMObject, name: &str) -> Option<&str> {
+        let attr = self.get_attribute_node(element, name)?;
+
+        self.attrs.get(&attr).map(|attr| attr.value())
+    }
+
+    /// Returns the value of one attribute of `element` by namespace URI and local name.
+    pub fn get_attribute_ns(
+        &self,
+        element: &DOMObject,
+        namespace_uri: Option<&str>,
+        local_name: &str,
+    ) -> Option<&str> {
+        let attr = self.get_attribute_node_ns(element, namespace_uri, local_name)?;
+
+        self.attrs.get(&attr).map(|attr| attr.value())
+    }
+
+    /// Returns true if `element` has an attribute with the qualified name.
+    pub fn has_attribute(&self, element: &DOMObject, name: &str) -> bool {
+        self.get_attribute_node(element, name).is_some()
+    }
+
+    /// Returns true if `element` has an attribute with the namespace URI and local name.
+    pub fn has_attribute_ns(
+        &self,
+        element: &DOMObject,
+        namespace_uri: Option<&str>,
+        local_name: &str,
+    ) -> bool {
+        self.get_attribute_node_ns(element, namespace_uri, local_name)
+            .is_some()
+    }
+
+    /// Set the value of one attribute, a new attribute is added if none has the qualified name.
+    pub fn set_attribute<T, V>(&mut self, element: &DOMObject, name: T, value: V) -> Result<()>
+    where
+        T: TryInto<QName<'a>>,
+        Error: From<T::Error>,
+        V: Into<Cow<'a, str>>,
+    {
+        let name = name.try_into()?;
+
+        if self.element(element).is_none() {
+            return Err(Error::DOMException(ExceptionCode::NOT_FOUND_ERR));
+        }
+
+        if let Some(object) = self.get_attribute_node(element, &name.to_string()) {
+            self.unindex_id(&object);
+
+            let listening = self.has_event_listeners();
+            let attr = self.attrs.get_mut(&object).expect("attribute of element");
+            let prev_value = listening.then(|| attr.value.to_string());
+
+            attr.value = value.into();
+            attr.specified = true;
+
+            self.index_id(&object);
+            self.attr_modified(element, &object, AttrChange::Modification, prev_value);
+
+            return Ok(());
+        }
+
+        let attr = self.new_attr(name, value.into());
+
+        self.set_attribute_node(element, attr)?;
+
+        Ok(())
+    }
+
+    /// Set the value of one attribute, a new attribute is added if none has
+    /// the namespace URI and the local part of `qualified_name`.
+    ///
+    /// Raises `NAMESPACE_ERR` if the prefix of `qualified_name` doesn't fit the namespace URI.
+    pub fn set_attribute_ns<N, T, V>(
+        &mut self,
+        element: &DOMObject,
+        namespace_uri: Option<N>,
+        qualified_name: T,
+        value: V,
+    ) -> Result<()>
+    where
+        N: Into<Cow<'a, str>>,
+        T: TryInto<QName<'a>>,
+        Error: From<T::Error>,
+        V: Into<Cow<'a, str>>,
+    {
+        let name = qualified_name.try_into()?;
+        let namespace_uri = namespace_uri.map(Into::into);
+
+        check_namespace(namespace_uri.as_deref(), &name, true)?;
+
+        if self.element(element).is_none() {
+            return Err(Error::DOMException(ExceptionCode::NOT_FOUND_ERR));
+        }
+
+        if let Some(object) =
+            self.get_attribute_node_ns(element, namespace_uri.as_deref(), &name.local_part)
+        {
+            self.unindex_id(&object);
+
+            let listening = self.has_event_listeners();
+            let attr = self.attrs.get_mut(&object).expect("attribute of element");
+            let prev_value = listening.then(|| attr.value.to_string());
+
+            attr.name = name;
+            attr.value = value.into();
+            attr.specified = true;
+
+            self.index_id(&object);
+            self.attr_modified(element, &object, AttrChange::Modification, prev_value);
+
+            return Ok(());
+        }
+
+        let attr = self.new_attr(name, value.into());
+
+        self.attrs
+            .get_mut(&attr)
+            .expect("new attribute")
+            .namespace_uri = namespace_uri;
+
+        self.set_attribute_node_ns(element, attr)?;
+
+        Ok(())
+    }
 
-        self.cdatas.iter_mut().find(|el| el.object == *object)
+    /// Add an attribute node to `element`, returns the replaced attribute with the same qualified name.
+    ///
+    /// Raises `INUSE_ATTRIBUTE_ERR` if `attr` is already an attribute of another element.
+    pub fn set_attribute_node(
+        &mut self,
+        element: &DOMObject,
+        attr: DOMObject,
+    ) -> Result<Option<DOMObject>> {
+        self.put_attribute_node(element, attr, false)
+    }
+
+    /// Add an attribute node to `element`, returns the replaced attribute with
+    /// the same namespace URI and local name.
+    ///
+    /// Raises `INUSE_ATTRIBUTE_ERR` if `attr` is already an attribute of another element.
+    pub fn set_attribute_node_ns(
+        &mut self,
+        element: &DOMObject,
+        attr: DOMObject,
+    ) -> Result<Option<DOMObject>> {
+        self.put_attribute_node(element, attr, true)
+    }
+
+    /// Add an attribute node, replacing the one with the same qualified name,
+    /// or the same namespace URI and local name if `ns` is true.
+    fn put_attribute_node(
+        &mut self,
+        element: &DOMObject,
+        attr: DOMObject,
+        ns: bool,
+    ) -> Result<Option<DOMObject>> {
+        self.insert_check(element, &attr, None)?;
+
+        if attr.node_type() != NodeType::Attribute {
+            return Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR));
+        }
+
+        match self.owner_element(&attr) {
+            Some(owner) if owner == *element => return Ok(None),
+            Some(_) => return Err(Error::DOMException(ExceptionCode::INUSE_ATTRIBUTE_ERR)),
+            None => {}
+        }
+
+        let node = self.attrs.get(&attr).expect("checked");
+
+        let replaced = if ns {
+            let namespace_uri = node.namespace_uri.clone();
+            let local_name = node.name.local_part.clone();
+
+            self.get_attribute_node_ns(element, namespace_uri.as_deref(), &local_name)
+        } else {
+            let name = node.name.to_string();
+
+            self.get_attribute_node(element, &name)
+        };
+
+        if let Some(replaced) = replaced {
+            self.unindex_id(&replaced);
+        }
+
+        let el = self.els.get_mut(element).expect("checked");
+
+        match replaced
+            .and_then(|replaced| el.attributes.items.iter().position(|obj| *obj == replaced))
+        {
+            Some(index) => el.attributes.items[index] = attr,
+            None => el.attributes.items.push(attr),
+        }
+
+        if let Some(replaced) = replaced {
+            self.attrs.get_mut(&replaced).expect("checked").node.parent = None;
+        }
+
+        self.attrs.get_mut(&attr).expect("checked").node.parent = Some(*element);
+
+        self.index_id(&attr);
+
+        if let Some(replaced) = replaced {
+            self.attr_modified(element, &replaced, AttrChange::Removal, None);
+        }
+
+        self.attr_modified(element, &attr, AttrChange::Addition, None);
+
+        Ok(replaced)
+    }
+
+    /// Remove one attribute node from `element`, returns the removed node.
+    pub fn remove_attribute_node(
+        &mut self,
+        element: &DOMObject,
+        attr: &DOMObject,
+    ) -> Result<DOMObject> {
+        if attr.node_type() != NodeType::Attribute || self.owner_element(attr) != Some(*element) {
+            return Err(Error::DOMException(ExceptionCode::NOT_FOUND_ERR));
+        }
+
+        self.detach(attr);
+
+        Ok(*attr)
+    }
+
+    /// Remove one attribute of `element` by qualified name, returns the removed node if any.
+    pub fn remove_attribute(&mut self, element: &DOMObject, name: &str) -> Option<DOMObject> {
+        let attr = self.get_attribute_node(element, name)?;
+
+        self.detach(&attr);
+
+        Some(attr)
+    }
+
+    /// Remove one attribute of `element` by namespace URI and local name,
+    /// returns the removed node if any.
+    pub fn remove_attribute_ns(
+        &mut self,
+        element: &DOMObject,
+        namespace_uri: Option<&str>,
+        local_name: &str,
+    ) -> Option<DOMObject> {
+        let attr = self.get_attribute_node_ns(element, namespace_uri, local_name)?;
+
+        self.detach(&attr);
+
+        Some(attr)
+    }
+}
+
+/// Returns true if a node of type `child` can be a child of a node of type `parent`.
+fn is_allowed_child(parent: NodeType, child: NodeType) -> bool {
+    match parent {
+        NodeType::Document => matches!(
+            child,
+            NodeType::Element
+                | NodeType::ProcessingInstruction
+                | NodeType::Comment
+                | NodeType::DocumentType
+        ),
+        NodeType::Element => matches!(
+            child,
+            NodeType::Element
+                | NodeType::Text
+                | NodeType::Comment
+                | NodeType::CData
+                | NodeType::ProcessingInstruction
+                | NodeType::EntityReference
+                | NodeType::Attribute
+                | NodeType::Namespace
+        ),
+        NodeType::Entity | NodeType::EntityReference => matches!(
+            child,
+            NodeType::Element
+                | NodeType::Text
+                | NodeType::Comment
+                | NodeType::CData
+                | NodeType::ProcessingInstruction
+                | NodeType::EntityReference
+        ),
+        NodeType::Attribute => matches!(child, NodeType::Text | NodeType::EntityReference),
+        NodeType::DocumentType => matches!(child, NodeType::Entity | NodeType::Notation),
+        _ => false,
     }
 }
 
+/// Returns the byte range of `count` characters of `data` from `offset`, to the end of
+/// `data` if there are less characters.
+fn char_range(data: &str, offset: usize, count: usize) -> Result<(usize, usize)> {
+    let mut boundaries = data
+        .char_indices()
+        .map(|(index, _)| index)
+        .chain([data.len()]);
+
+    let start = boundaries
+        .nth(offset)
+        .ok_or(Error::DOMException(ExceptionCode::INDEX_SIZE_ERR))?;
+
+    let end = match count {
+        0 => start,
+        count => boundaries.nth(count - 1).unwrap_or(data.len()),
+    };
+
+    Ok((start, end))
+}
+
+/// Returns false for nodes which are stored by their owner, but not part of its child list.
+pub(crate) fn is_child_node(object: &DOMObject) -> bool {
+    !matches!(
+        object.node_type(),
+        NodeType::Attribute | NodeType::Namespace | NodeType::Entity | NodeType::Notation
+    )
+}
+
 /// An Iterator over one node's children.
+#[derive(Clone)]
 pub enum NodeIterator<'a> {
     Iter(Iter<'a, DOMObject>),
 
@@ -1112,11 +2158,28 @@ impl<'a> Iterator for NodeIterator<'a> {
             NodeIterator::Empty => None,
         }
     }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        match self {
+            NodeIterator::Iter(iter) => iter.nth(n),
+            NodeIterator::Empty => None,
+        }
+    }
+}
+
+impl<'a> DoubleEndedIterator for NodeIterator<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match self {
+            NodeIterator::Iter(iter) => iter.next_back(),
+            NodeIterator::Empty => None,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::Document;
+    use crate::{DOMObject, Error, ExceptionCode, NodeType};
 
     #[test]
     fn test_gc() {
@@ -1139,6 +2202,485 @@ mod tests {
         assert_eq!(doc.allocated(), 1);
     }
 
+    #[test]
+    fn test_gc_handles() {
+        let mut doc = Document::default();
+
+        let unused = doc.create_element("unused").unwrap();
+        let root = doc.create_element("root").unwrap();
+        let child = doc.create_element("child").unwrap();
+
+        doc.append_child(Some(&root), child).unwrap();
+        doc.append_child(None, root).unwrap();
+
+        doc.gc();
+
+        assert_eq!(doc.element(&root).unwrap().tag().local_part, "root");
+        assert_eq!(doc.element(&child).unwrap().tag().local_part, "child");
+        assert!(doc.element(&unused).is_none());
+
+        // the freed slot is reused by a new node with another generation.
+        let reused = doc.create_element("reused").unwrap();
+
+        assert_ne!(reused, unused);
+        assert!(doc.element(&unused).is_none());
+
+        assert!(matches!(
+            doc.append_child(Some(&root), unused),
+            Err(Error::DOMException(ExceptionCode::NOT_FOUND_ERR))
+        ));
+    }
+
+    #[test]
+    fn test_gc_node_types() {
+        let mut doc = Document::default();
+
+        let doc_type = doc.create_document_type("", "doc.dtd", "").unwrap();
+        let entity = doc.create_entity("", "entity.xml", None).unwrap();
+        let entity_text = doc.create_text("replacement").unwrap();
+        let notation = doc.create_notation("", "image/png").unwrap();
+        let root = doc.create_element("root").unwrap();
+        let attr = doc.create_attr("id", "").unwrap();
+        let attr_text = doc.create_text("1").unwrap();
+        let ns = doc.create_ns("x", "urn:x").unwrap();
+        let pi = doc.create_pi("target", "data").unwrap();
+        let comment = doc.create_comment("comment").unwrap();
+        let text = doc.create_text("text").unwrap();
+        let cdata = doc.create_cdata("cdata").unwrap();
+        let entity_ref = doc.create_entity_reference("entity").unwrap();
+        let entity_ref_text = doc.create_text("replacement").unwrap();
+
+        doc.append_child(Some(&entity), entity_text).unwrap();
+        doc.append_child(Some(&entity_ref), entity_ref_text)
+            .unwrap();
+        doc.append_child(Some(&doc_type), entity).unwrap();
+        doc.append_child(Some(&doc_type), notation).unwrap();
+        doc.append_child(None, doc_type).unwrap();
+        doc.append_child(Some(&attr), attr_text).unwrap();
+
+        for child in [attr, ns, pi, comment, text, cdata, entity_ref] {
+            doc.append_child(Some(&root), child).unwrap();
+        }
+
+        doc.append_child(None, root).unwrap();
+
+        let reachable = doc.allocated();
+
+        // one unreachable node of every type.
+        let unused_doc_type = doc.create_document_type("", "", "").unwrap();
+        let unused_entity = doc.create_entity("", "", None).unwrap();
+        let unused_notation = doc.create_notation("", "").unwrap();
+        let unused_attr = doc.create_attr("id", "").unwrap();
+        let unused_ns = doc.create_ns("y", "urn:y").unwrap();
+        let unused_pi = doc.create_pi("target", "").unwrap();
+        let unused_comment = doc.create_comment("").unwrap();
+        let unused_text = doc.create_text("").unwrap();
+        let unused_cdata = doc.create_cdata("").unwrap();
+        let unused_entity_ref = doc.create_entity_reference("entity").unwrap();
+
+        doc.gc();
+
+        assert_eq!(doc.allocated(), reachable);
+
+        assert!(doc.doc_type(&doc_type).is_some());
+        assert_eq!(doc.doc_type(&doc_type).unwrap().entities().count(), 1);
+        assert_eq!(doc.doc_type(&doc_type).unwrap().notations().count(), 1);
+        assert!(doc.entity(&entity).is_some());
+        assert_eq!(doc.text(&entity_text).unwrap().data(), "replacement");
+        assert!(doc.notation(&notation).is_some());
+        assert!(doc.attr(&attr).is_some());
+        assert_eq!(doc.text(&attr_text).unwrap().data(), "1");
+        assert!(doc.ns(&ns).is_some());
+        assert!(doc.pi(&pi).is_some());
+        assert!(doc.comment(&comment).is_some());
+        assert!(doc.text(&text).is_some());
+        assert!(doc.cdata(&cdata).is_some());
+        assert_eq!(doc.text(&entity_ref_text).unwrap().data(), "replacement");
+
+        assert!(doc.doc_type(&unused_doc_type).is_none());
+        assert!(doc.entity(&unused_entity).is_none());
+        assert!(doc.notation(&unused_notation).is_none());
+        assert!(doc.attr(&unused_attr).is_none());
+        assert!(doc.ns(&unused_ns).is_none());
+        assert!(doc.pi(&unused_pi).is_none());
+        assert!(doc.comment(&unused_comment).is_none());
+        assert!(doc.text(&unused_text).is_none());
+        assert!(doc.cdata(&unused_cdata).is_none());
+        assert!(doc.entity_reference(&unused_entity_ref).is_none());
+
+        // children of every node type can be listed.
+        assert_eq!(doc.children(Some(&attr)).count(), 1);
+        assert_eq!(doc.children(Some(&text)).count(), 0);
+        assert_eq!(doc.children(Some(&unused_text)).count(), 0);
+    }
+
+    #[test]
+    fn test_navigation() {
+        let mut doc = Document::default();
+
+        let root = doc.create_element("root").unwrap();
+        let attr = doc.create_attr("id", "1").unwrap();
+        let a = doc.create_element("a").unwrap();
+        let text = doc.create_text("text").unwrap();
+        let b = doc.create_element("b").unwrap();
+
+        doc.append_child(Some(&root), attr).unwrap();
+        doc.append_child(Some(&root), a).unwrap();
+        doc.append_child(Some(&root), text).unwrap();
+        doc.append_child(Some(&root), b).unwrap();
+        doc.append_child(None, root).unwrap();
+
+        assert_eq!(doc.parent_node(&root), Some(DOMObject::default()));
+        assert_eq!(doc.parent_node(&text), Some(root));
+        assert_eq!(doc.parent_node(&attr), None);
+        assert_eq!(doc.owner_element(&attr), Some(root));
+        assert_eq!(doc.owner_element(&a), None);
+
+        assert_eq!(doc.first_child(None), Some(root));
+        assert_eq!(doc.first_child(Some(&root)), Some(a));
+        assert_eq!(doc.last_child(Some(&root)), Some(b));
+        assert_eq!(doc.first_child(Some(&a)), None);
+
+        assert_eq!(doc.previous_sibling(&a), None);
+        assert_eq!(doc.next_sibling(&a), Some(text));
+        assert_eq!(doc.previous_sibling(&b), Some(text));
+        assert_eq!(doc.next_sibling(&b), None);
+        assert_eq!(doc.next_sibling(&attr), None);
+        assert_eq!(doc.next_sibling(&root), None);
+    }
+
+    #[test]
+    fn append_attached() {
+        let mut doc = Document::default();
+
+        let a = doc.create_element("a").unwrap();
+        let b = doc.create_element("b").unwrap();
+        let text = doc.create_text("text").unwrap();
+        let attr = doc.create_attr("id", "1").unwrap();
+
+        doc.append_child(Some(&a), text).unwrap();
+        doc.append_child(Some(&a), attr).unwrap();
+
+        // child nodes are moved.
+        doc.append_child(Some(&b), text).unwrap();
+
+        assert!(!doc.has_child_nodes(Some(&a)));
+        assert_eq!(doc.parent_node(&text), Some(b));
+
+        assert!(matches!(
+            doc.append_child(Some(&b), attr),
+            Err(Error::DOMException(ExceptionCode::INUSE_ATTRIBUTE_ERR))
+        ));
+    }
+
+    #[test]
+    fn test_attributes() {
+        let mut doc = Document::default();
+
+        let el = doc.create_element("el").unwrap();
+        let other = doc.create_element("other").unwrap();
+
+        doc.set_attribute(&el, "b", "1").unwrap();
+        doc.set_attribute(&el, "a", "2").unwrap();
+        doc.set_attribute_ns(&el, Some("urn:x"), "x:c", "3")
+            .unwrap();
+        doc.set_attribute(&el, "b", "4").unwrap();
+
+        assert_eq!(doc.get_attribute(&el, "b"), Some("4"));
+        assert_eq!(doc.get_attribute(&el, "x:c"), Some("3"));
+        assert_eq!(doc.get_attribute_ns(&el, Some("urn:x"), "c"), Some("3"));
+        assert_eq!(doc.get_attribute_ns(&el, None, "c"), None);
+        assert!(doc.has_attribute(&el, "a"));
+        assert!(!doc.has_attribute(&el, "c"));
+
+        // attributes are not children.
+        assert!(!doc.has_child_nodes(Some(&el)));
+
+        let names = |doc: &Document, el: &DOMObject| {
+            doc.element(el)
+                .unwrap()
+                .attributes()
+                .iter()
+                .map(|attr| doc.attr(attr).unwrap().name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(&doc, &el), ["b", "a", "x:c"]);
+
+        // replace keeps the position.
+        let a = doc.create_attr("a", "5").unwrap();
+        let replaced = doc.set_attribute_node(&el, a).unwrap().unwrap();
+
+        assert_eq!(doc.attr(&replaced).unwrap().value(), "2");
+        assert_eq!(doc.owner_element(&replaced), None);
+        assert_eq!(doc.owner_element(&a), Some(el));
+        assert_eq!(names(&doc, &el), ["b", "a", "x:c"]);
+        assert_eq!(doc.set_attribute_node(&el, a).unwrap(), None);
+
+        assert!(matches!(
+            doc.set_attribute_node(&other, a),
+            Err(Error::DOMException(ExceptionCode::INUSE_ATTRIBUTE_ERR))
+        ));
+
+        doc.set_attribute_node(&other, replaced).unwrap();
+
+        // the namespace version matches the namespace URI and local name, not the prefix.
+        let c = doc.create_attr_ns(Some("urn:x"), "y:c", "6").unwrap();
+        let replaced = doc.set_attribute_node_ns(&el, c).unwrap().unwrap();
+
+        assert_eq!(doc.attr(&replaced).unwrap().value(), "3");
+        assert_eq!(names(&doc, &el), ["b", "a", "y:c"]);
+
+        let c = doc.create_attr_ns(Some("urn:z"), "y:c", "7").unwrap();
+
+        assert_eq!(doc.set_attribute_node_ns(&el, c).unwrap(), None);
+        assert_eq!(names(&doc, &el), ["b", "a", "y:c", "y:c"]);
+        assert!(doc.remove_attribute_ns(&el, Some("urn:z"), "c").is_some());
+
+        assert!(doc.remove_attribute(&el, "b").is_some());
+        assert!(doc.remove_attribute(&el, "b").is_none());
+        assert!(doc.remove_attribute_ns(&el, Some("urn:x"), "c").is_some());
+        assert_eq!(names(&doc, &el), ["a"]);
+
+        assert!(matches!(
+            doc.remove_attribute_node(&el, &replaced),
+            Err(Error::DOMException(ExceptionCode::NOT_FOUND_ERR))
+        ));
+        assert_eq!(doc.remove_attribute_node(&el, &a).unwrap(), a);
+        assert!(doc.element(&el).unwrap().attributes().is_empty());
+    }
+
+    fn err(result: crate::Result<DOMObject>) -> ExceptionCode {
+        match result {
+            Err(Error::DOMException(code)) => code,
+            _ => panic!("expect DOMException"),
+        }
+    }
+
+    #[test]
+    fn test_mutation() {
+        let mut doc = Document::default();
+
+        let root = doc.create_element("root").unwrap();
+        let a = doc.create_element("a").unwrap();
+        let b = doc.create_element("b").unwrap();
+        let c = doc.create_element("c").unwrap();
+
+        doc.append_child(None, root).unwrap();
+        doc.append_child(Some(&root), b).unwrap();
+
+        assert_eq!(doc.insert_before(Some(&root), a, Some(&b)).unwrap(), a);
+        assert_eq!(
+            doc.children(Some(&root)).copied().collect::<Vec<_>>(),
+            [a, b]
+        );
+
+        assert_eq!(doc.replace_child(Some(&root), c, &a).unwrap(), a);
+        assert_eq!(
+            doc.children(Some(&root)).copied().collect::<Vec<_>>(),
+            [c, b]
+        );
+        assert_eq!(doc.parent_node(&a), None);
+
+        // move b before c.
+        doc.insert_before(Some(&root), b, Some(&c)).unwrap();
+        assert_eq!(
+            doc.children(Some(&root)).copied().collect::<Vec<_>>(),
+            [b, c]
+        );
+
+        assert_eq!(doc.remove_child(Some(&root), &b).unwrap(), b);
+        assert_eq!(doc.parent_node(&b), None);
+        assert!(doc.has_child_nodes(Some(&root)));
+        assert!(!doc.has_child_nodes(Some(&b)));
+
+        assert_eq!(
+            err(doc.remove_child(Some(&root), &a)),
+            ExceptionCode::NOT_FOUND_ERR
+        );
+        assert_eq!(
+            err(doc.insert_before(Some(&root), a, Some(&b))),
+            ExceptionCode::NOT_FOUND_ERR
+        );
+
+        // cycles.
+        doc.append_child(Some(&c), a).unwrap();
+
+        assert_eq!(
+            err(doc.insert_before(Some(&a), root, None)),
+            ExceptionCode::HIERARCHY_REQUEST_ERR
+        );
+        assert_eq!(
+            err(doc.insert_before(Some(&a), a, None)),
+            ExceptionCode::HIERARCHY_REQUEST_ERR
+        );
+
+        // node types.
+        let text = doc.create_text("text").unwrap();
+
+        assert_eq!(
+            err(doc.insert_before(None, text, None)),
+            ExceptionCode::HIERARCHY_REQUEST_ERR
+        );
+        assert_eq!(
+            err(doc.insert_before(Some(&text), b, None)),
+            ExceptionCode::HIERARCHY_REQUEST_ERR
+        );
+        assert_eq!(
+            err(doc.insert_before(None, b, None)),
+            ExceptionCode::HIERARCHY_REQUEST_ERR
+        );
+
+        // the document element can be replaced.
+        assert_eq!(doc.replace_child(None, b, &root).unwrap(), root);
+        assert_eq!(doc.first_child(None), Some(b));
+
+        let mut other = Document::default();
+
+        let foreign = other.create_element("foreign").unwrap();
+
+        assert_eq!(
+            err(doc.insert_before(Some(&b), foreign, None)),
+            ExceptionCode::WRONG_DOCUMENT_ERR
+        );
+    }
+
+    #[test]
+    fn test_normalize() {
+        let mut doc = Document::default();
+
+        let root = doc.create_element("root").unwrap();
+        let attr = doc.create_attr("id", "").unwrap();
+        let child = doc.create_element("child").unwrap();
+
+        for data in ["", "a"] {
+            let text = doc.create_text(data).unwrap();
+            doc.append_child(Some(&attr), text).unwrap();
+        }
+
+        doc.append_child(Some(&root), attr).unwrap();
+
+        for data in ["a", "", "b"] {
+            let text = doc.create_text(data).unwrap();
+            doc.append_child(Some(&root), text).unwrap();
+        }
+
+        doc.append_child(Some(&root), child).unwrap();
+
+        for data in ["c", "d"] {
+            let text = doc.create_text(data).unwrap();
+            doc.append_child(Some(&child), text).unwrap();
+        }
+
+        let text = doc.create_text("e").unwrap();
+        doc.append_child(Some(&root), text).unwrap();
+
+        doc.append_child(None, root).unwrap();
+
+        doc.normalize(None);
+
+        let data = |doc: &Document, parent: &DOMObject| {
+            doc.children(Some(parent))
+                .filter(|obj| obj.node_type() == NodeType::Text)
+                .map(|obj| doc.text(obj).unwrap().data().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(data(&doc, &root), ["ab", "e"]);
+        assert_eq!(data(&doc, &child), ["cd"]);
+        assert_eq!(data(&doc, &attr), ["a"]);
+    }
+
+    #[test]
+    fn test_character_data() {
+        let mut doc = Document::default();
+
+        let root = doc.create_element("root").unwrap();
+        let text = doc.create_text("héllo").unwrap();
+
+        doc.append_child(Some(&root), text).unwrap();
+
+        doc.replace_data(&text, 1, 4, "ey").unwrap();
+        assert_eq!(doc.character_data(&text), Some("hey"));
+
+        doc.insert_data(&text, 3, " wörld").unwrap();
+        doc.append_data(&text, "!").unwrap();
+        assert_eq!(doc.substring_data(&text, 4, 5).unwrap(), "wörld");
+
+        doc.delete_data(&text, 3, 100).unwrap();
+        assert_eq!(doc.character_data(&text), Some("hey"));
+
+        assert!(matches!(
+            doc.replace_data(&text, 4, 0, ""),
+            Err(Error::DOMException(ExceptionCode::INDEX_SIZE_ERR))
+        ));
+
+        let tail = doc.split_text(&text, 1).unwrap();
+
+        assert_eq!(doc.character_data(&text), Some("h"));
+        assert_eq!(doc.character_data(&tail), Some("ey"));
+        assert_eq!(doc.next_sibling(&text), Some(tail));
+
+        assert!(matches!(
+            doc.replace_data(&root, 0, 0, ""),
+            Err(Error::DOMException(ExceptionCode::NOT_SUPPORTED_ERR))
+        ));
+    }
+
+    #[test]
+    fn test_clone_node() {
+        let mut doc =
+            Document::parse(r#"<a xmlns:x="urn:x" x:k="v"><b>text</b><!--c--></a>"#).unwrap();
+
+        let a = doc.first_child(None).unwrap();
+
+        let shallow = doc.clone_node(&a, false).unwrap();
+
+        assert_eq!(doc.parent_node(&shallow), None);
+        assert_eq!(doc.first_child(Some(&shallow)), None);
+        assert_eq!(
+            doc.get_attribute_ns(&shallow, Some("urn:x"), "k"),
+            Some("v")
+        );
+        assert_eq!(doc.lookup_namespace_uri(&shallow, Some("x")), Some("urn:x"));
+
+        let deep = doc.clone_node(&a, true).unwrap();
+        let b = doc.first_child(Some(&deep)).unwrap();
+
+        assert_ne!(Some(b), doc.first_child(Some(&a)));
+        assert_eq!(doc.element(&b).unwrap().tag().to_string(), "b");
+        assert_eq!(
+            doc.children(Some(&deep))
+                .filter(|obj| obj.node_type() == NodeType::Comment)
+                .count(),
+            1
+        );
+
+        assert!(matches!(
+            doc.clone_node(&DOMObject::default(), true),
+            Err(Error::DOMException(ExceptionCode::NOT_SUPPORTED_ERR))
+        ));
+
+        let mut doc =
+            Document::parse(r#"<!DOCTYPE a [<!ATTLIST a d CDATA "default">]><a s="v"/>"#).unwrap();
+
+        let a = doc.children(None).nth(1).copied().unwrap();
+        let clone = doc.clone_node(&a, false).unwrap();
+
+        let specified = |doc: &Document<'_>, element: &DOMObject, name: &str| {
+            let attr = doc.get_attribute_node(element, name).unwrap();
+            doc.attr(&attr).unwrap().specified()
+        };
+
+        assert!(specified(&doc, &clone, "s"));
+        assert!(!specified(&doc, &clone, "d"));
+
+        let d = doc.get_attribute_node(&a, "d").unwrap();
+        let d = doc.clone_node(&d, false).unwrap();
+
+        assert!(doc.attr(&d).unwrap().specified());
+    }
+
     #[test]
     fn doc_maximum_of_one() {
         let mut doc = Document::default();
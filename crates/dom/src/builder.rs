@@ -0,0 +1,536 @@
+//! Build a [`Document`] from the events of the stax [`Reader`].
+
+use std::borrow::Cow;
+
+use futures::executor::block_on;
+use rexml_stax::parser::{
+    is_whitespace, Attribute, AttributeType, BorrowInput, DocType, Dtd, Event, NamespaceStack,
+    Reader,
+};
+
+use crate::{DOMObject, Document, Result, XMLNS_NAMESPACE};
+
+/// Options of the construction of a [`Document`] from reader events.
+///
+/// Strings of the events are moved into the document, so a document parsed from
+/// an in-memory `&'a str` borrows its text instead of copying it.
+#[derive(Debug, Clone)]
+pub struct DocumentBuilder {
+    whitespace: bool,
+    comments: bool,
+    processing_instructions: bool,
+    coalesce_cdata: bool,
+    entity_references: bool,
+}
+
+impl Default for DocumentBuilder {
+    fn default() -> Self {
+        Self {
+            whitespace: true,
+            comments: true,
+            processing_instructions: true,
+            coalesce_cdata: false,
+            entity_references: false,
+        }
+    }
+}
+
+impl DocumentBuilder {
+    /// Create a new `DocumentBuilder` with the default options.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Keep text nodes consisting of white spaces only, default is true.
+    pub fn whitespace(mut self, keep: bool) -> Self {
+        self.whitespace = keep;
+        self
+    }
+
+    /// Keep comments, default is true.
+    pub fn comments(mut self, keep: bool) -> Self {
+        self.comments = keep;
+        self
+    }
+
+    /// Keep processing instructions, default is true.
+    pub fn processing_instructions(mut self, keep: bool) -> Self {
+        self.processing_instructions = keep;
+        self
+    }
+
+    /// Convert CDATA sections into text merged with the adjacent text, default is false.
+    pub fn coalesce_cdata(mut self, coalesce: bool) -> Self {
+        self.coalesce_cdata = coalesce;
+        self
+    }
+
+    /// Create `EntityReference` nodes holding the replacement text of general entities,
+    /// instead of inserting the replacement text in place, default is false.
+    ///
+    /// This only applies to [`build`](Self::build), which enables the entity events of the reader.
+    pub fn entity_references(mut self, create: bool) -> Self {
+        self.entity_references = create;
+        self
+    }
+
+    /// Read all events of `reader` into a new `Document`.
+    ///
+    /// The entities and notations declared in the internal subset are added to the document type.
+    pub async fn build<'a, I>(&self, reader: &mut Reader<I>) -> Result<Document<'a>>
+    where
+        I: BorrowInput<'a>,
+    {
+        reader.set_entity_events(self.entity_references);
+
+        let mut tree = TreeBuilder::new(self);
+
+        while let Some(event) = reader.next().await? {
+            let doc_type = matches!(event, Event::DocType(_));
+
+            tree.event(event)?;
+
+            if doc_type {
+                tree.declarations(reader.dtd())?;
+            }
+        }
+
+        Ok(tree.document)
+    }
+
+    /// Build a new `Document` from a sequence of events, such as the ones
+    /// returned by [`FeedParser`](rexml_stax::parser::FeedParser).
+    ///
+    /// The declarations are parsed again from the internal subset of the doctype event,
+    /// and added as [`build`](Self::build) does.
+    pub fn build_from_events<'a, E>(&self, events: E) -> Result<Document<'a>>
+    where
+        E: IntoIterator<Item = Event<'a>>,
+    {
+        let mut tree = TreeBuilder::new(self);
+
+        for event in events {
+            let dtd = match &event {
+                Event::DocType(doc_type) => Some(Dtd::parse(
+                    doc_type.internal_subset.as_deref().unwrap_or_default(),
+                )?),
+                _ => None,
+            };
+
+            tree.event(event)?;
+
+            if let Some(dtd) = dtd {
+                tree.declarations(&dtd)?;
+            }
+        }
+
+        Ok(tree.document)
+    }
+}
+
+impl<'a> Document<'a> {
+    /// Parse a document from `input` with the default options of [`DocumentBuilder`].
+    pub fn parse(input: &'a str) -> Result<Self> {
+        block_on(DocumentBuilder::new().build(&mut Reader::new(input)))
+    }
+}
+
+/// The state of one construction.
+struct TreeBuilder<'b, 'a> {
+    options: &'b DocumentBuilder,
+    document: Document<'a>,
+    /// The open elements and entity references, the document is the parent if empty.
+    parents: Vec<DOMObject>,
+    namespaces: NamespaceStack<'a>,
+    /// The document type node, if any.
+    doc_type: Option<DOMObject>,
+    /// Character data not yet added, adjacent events are merged into one text node.
+    text: Option<Cow<'a, str>>,
+}
+
+impl<'b, 'a> TreeBuilder<'b, 'a> {
+    fn new(options: &'b DocumentBuilder) -> Self {
+        Self {
+            options,
+            document: Default::default(),
+            parents: vec![],
+            namespaces: Default::default(),
+            doc_type: None,
+            text: None,
+        }
+    }
+
+    fn event(&mut self, event: Event<'a>) -> Result<()> {
+        match event {
+            Event::Characters(text) => self.characters(text),
+            Event::CData(text) if self.options.coalesce_cdata => self.characters(text),
+            Event::CData(text) => {
+                self.flush()?;
+
+                let cdata = self.document.create_cdata(text)?;
+                self.append(cdata)?;
+            }
+            Event::StartElement { name, attributes } => {
+                self.flush()?;
+                self.start_element(name, attributes)?;
+            }
+            Event::EndElement { .. } => {
+                self.flush()?;
+                self.parents.pop();
+                self.namespaces.pop_scope();
+            }
+            Event::Comment(text) => {
+                self.flush()?;
+
+                if self.options.comments {
+                    let comment = self.document.create_comment(text)?;
+                    self.append(comment)?;
+                }
+            }
+            Event::ProcessingInstruction { target, data } => {
+                self.flush()?;
+
+                if self.options.processing_instructions {
+                    let pi = self
+                        .document
+                        .create_pi(target, data.unwrap_or(Cow::Borrowed("")))?;
+                    self.append(pi)?;
+                }
+            }
+            Event::DocType(doc_type) => self.doc_type(doc_type)?,
+            Event::StartEntity(name) => {
+                self.flush()?;
+
+                if self.options.entity_references {
+                    let entity_ref = self.document.create_entity_reference(name)?;
+                    self.append(entity_ref)?;
+                    self.parents.push(entity_ref);
+                }
+            }
+            Event::EndEntity(_) => {
+                self.flush()?;
+
+                if self.options.entity_references {
+                    self.parents.pop();
+                }
+            }
+            Event::StartDocument { .. } => {}
+            Event::EndDocument => self.flush()?,
+        }
+
+        Ok(())
+    }
+
+    /// Append `child` to the current parent node.
+    fn append(&mut self, child: DOMObject) -> Result<()> {
+        self.document.append_child(self.parents.last(), child)
+    }
+
+    fn characters(&mut self, text: Cow<'a, str>) {
+        match &mut self.text {
+            Some(pending) => pending.to_mut().push_str(&text),
+            None => self.text = Some(text),
+        }
+    }
+
+    /// Add the pending character data as one text node.
+    fn flush(&mut self) -> Result<()> {
+        let Some(text) = self.text.take() else {
+            return Ok(());
+        };
+
+        if !self.options.whitespace && text.chars().all(is_whitespace) {
+            return Ok(());
+        }
+
+        let text = self.document.create_text(text)?;
+
+        self.append(text)
+    }
+
+    fn start_element(&mut self, name: Cow<'a, str>, attributes: Vec<Attribute<'a>>) -> Result<()> {
+        self.namespaces.push_scope(&attributes)?;
+
+        let (namespace_uri, _) = self.namespaces.resolve_name_cow(&name, false)?;
+
+        let el = self.document.create_element_ns(namespace_uri, name)?;
+
+        for attr in attributes {
+            let namespace_uri = if attr.name == "xmlns" || attr.name.starts_with("xmlns:") {
+                Some(Cow::Borrowed(XMLNS_NAMESPACE))
+            } else {
+                self.namespaces.resolve_name_cow(&attr.name, true)?.0
+            };
+
+            let node = self
+                .document
+                .create_attr_ns(namespace_uri, attr.name, attr.value)?;
+
+            if !attr.specified {
+                self.document
+                    .attr_mut(&node)
+                    .expect("new attribute")
+                    .set_specified(false);
+            }
+
+            self.document.set_attribute_node(&el, node)?;
+        }
+
+        self.append(el)?;
+        self.parents.push(el);
+
+        Ok(())
+    }
+
+    fn doc_type(&mut self, doc_type: DocType<'a>) -> Result<()> {
+        let node = self.document.create_document_type_named(
+            doc_type.name,
+            doc_type.public_id.unwrap_or_default(),
+            doc_type.system_id.unwrap_or_default(),
+            doc_type.internal_subset.unwrap_or_default(),
+        )?;
+
+        self.append(node)?;
+        self.doc_type = Some(node);
+
+        Ok(())
+    }
+
+    /// Add the entities and notations declared by `dtd` to the document type,
+    /// and record the attributes declared of type ID.
+    fn declarations(&mut self, dtd: &Dtd) -> Result<()> {
+        let Some(doc_type) = self.doc_type else {
+            return Ok(());
+        };
+
+        for (element, decls) in dtd.attlists() {
+            for decl in decls
+                .iter()
+                .filter(|decl| decl.attr_type == AttributeType::Id)
+            {
+                self.document.declare_id_attribute(element, &decl.name);
+            }
+        }
+
+        for decl in dtd.entities() {
+            let entity = self.document.create_entity_named(
+                decl.name.clone(),
+                decl.public_id.clone().unwrap_or_default(),
+                decl.system_id.clone().unwrap_or_default(),
+                decl.notation_name.clone().map(Cow::Owned),
+            )?;
+
+            self.document.append_child(Some(&doc_type), entity)?;
+        }
+
+        for decl in dtd.notations() {
+            let notation = self.document.create_notation_named(
+                decl.name.clone(),
+                decl.public_id.clone().unwrap_or_default(),
+                decl.system_id.clone().unwrap_or_default(),
+            )?;
+
+            self.document.append_child(Some(&doc_type), notation)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NodeType;
+
+    const DOCUMENT: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE doc [
+    <!ATTLIST item kind CDATA "a">
+    <!ENTITY hello "Hello <b>world</b>">
+    <!NOTATION png SYSTEM "image/png">
+]>
+<doc xmlns="urn:doc" xmlns:x="urn:x">
+    <!-- comment -->
+    <item x:id="1">&hello;!</item>
+    <item kind="b">a<![CDATA[<b>]]>c</item>
+    <?pi data?>
+</doc>
+"#;
+
+    /// Returns the children of `parent` of types other than `Text`.
+    fn nodes<'d>(doc: &'d Document<'_>, parent: &DOMObject) -> Vec<&'d DOMObject> {
+        doc.children(Some(parent))
+            .filter(|obj| obj.node_type() != NodeType::Text)
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let doc = Document::parse(DOCUMENT).unwrap();
+
+        let children = doc.children(None).copied().collect::<Vec<_>>();
+
+        assert_eq!(children.len(), 2);
+
+        let doc_type = doc.doc_type(&children[0]).unwrap();
+
+        assert_eq!(doc_type.name(), "doc");
+        assert_eq!(doc_type.entities().count(), 1);
+        assert_eq!(doc_type.notations().count(), 1);
+
+        let root = children[1];
+        let el = doc.element(&root).unwrap();
+
+        assert_eq!(el.tag().to_string(), "doc");
+        assert_eq!(el.namespace_uri(), Some("urn:doc"));
+        assert_eq!(doc.lookup_namespace_uri(&root, Some("x")), Some("urn:x"));
+
+        let root_children = nodes(&doc, &root);
+
+        assert_eq!(
+            root_children
+                .iter()
+                .map(|obj| obj.node_type())
+                .collect::<Vec<_>>(),
+            [
+                NodeType::Comment,
+                NodeType::Element,
+                NodeType::Element,
+                NodeType::ProcessingInstruction
+            ]
+        );
+
+        let item = *root_children[1];
+
+        assert_eq!(doc.get_attribute_ns(&item, Some("urn:x"), "id"), Some("1"));
+
+        let kind = doc.get_attribute_node(&item, "kind").unwrap();
+
+        assert_eq!(doc.attr(&kind).unwrap().value(), "a");
+        assert!(!doc.attr(&kind).unwrap().specified());
+
+        let text = doc.first_child(Some(&item)).unwrap();
+
+        assert_eq!(doc.text(&text).unwrap().data(), "Hello ");
+
+        let item = *root_children[2];
+
+        assert_eq!(
+            doc.children(Some(&item))
+                .map(|obj| obj.node_type())
+                .collect::<Vec<_>>(),
+            [NodeType::Text, NodeType::CData, NodeType::Text]
+        );
+
+        let pi = doc.pi(root_children[3]).unwrap();
+
+        assert_eq!((pi.target(), pi.data()), ("pi", "data"));
+    }
+
+    #[test]
+    fn test_borrowed() {
+        let input = "<a b='1' xmlns='urn:a' xmlns:p='urn:p'>text<c p:d='2'/></a>";
+
+        let doc = Document::parse(input).unwrap();
+
+        let root = doc.first_child(None).unwrap();
+        let text = doc.first_child(Some(&root)).unwrap();
+
+        let data = doc.text(&text).unwrap().data();
+
+        assert!(input.as_bytes().as_ptr_range().contains(&data.as_ptr()));
+
+        // namespace names are borrowed from the declarations.
+        let uri = doc.element(&root).unwrap().namespace_uri().unwrap();
+
+        assert!(input.as_bytes().as_ptr_range().contains(&uri.as_ptr()));
+
+        let c = doc.last_child(Some(&root)).unwrap();
+        let d = doc.get_attribute_node_ns(&c, Some("urn:p"), "d").unwrap();
+        let uri = doc.attr(&d).unwrap().namespace_uri().unwrap();
+
+        assert!(input.as_bytes().as_ptr_range().contains(&uri.as_ptr()));
+    }
+
+    #[test]
+    fn test_options() {
+        let builder = DocumentBuilder::new()
+            .whitespace(false)
+            .comments(false)
+            .processing_instructions(false)
+            .coalesce_cdata(true)
+            .entity_references(true);
+
+        let doc = block_on(builder.build(&mut Reader::new(DOCUMENT))).unwrap();
+
+        let root = doc.last_child(None).unwrap();
+
+        let items = doc.children(Some(&root)).copied().collect::<Vec<_>>();
+
+        assert_eq!(items.len(), 2);
+
+        let children = doc.children(Some(&items[0])).copied().collect::<Vec<_>>();
+
+        assert_eq!(children[0].node_type(), NodeType::EntityReference);
+        assert_eq!(doc.entity_reference(&children[0]).unwrap().name(), "hello");
+        assert_eq!(doc.children(Some(&children[0])).count(), 2);
+        assert_eq!(doc.text(&children[1]).unwrap().data(), "!");
+
+        let text = doc.first_child(Some(&items[1])).unwrap();
+
+        assert_eq!(doc.children(Some(&items[1])).count(), 1);
+        assert_eq!(doc.text(&text).unwrap().data(), "a<b>c");
+    }
+
+    fn parse_events(input: &str) -> Vec<Event<'_>> {
+        let mut reader = Reader::new(input);
+        let mut events = vec![];
+
+        while let Some(event) = block_on(reader.next()).unwrap() {
+            events.push(event);
+        }
+
+        events
+    }
+
+    #[test]
+    fn test_from_events() {
+        let mut parser = rexml_stax::parser::FeedParser::new();
+
+        let mut events = parser.feed(b"<a>x<b/>y</a>").unwrap();
+        events.append(&mut parser.finish().unwrap());
+
+        let doc = DocumentBuilder::new().build_from_events(events).unwrap();
+
+        let root = doc.first_child(None).unwrap();
+
+        assert_eq!(doc.children(Some(&root)).count(), 3);
+
+        // the declarations are the same as the ones of `Document::parse`.
+        let mut parser = rexml_stax::parser::FeedParser::new();
+
+        let mut events = parser.feed(DOCUMENT.as_bytes()).unwrap();
+        events.append(&mut parser.finish().unwrap());
+
+        let doc = DocumentBuilder::new().build_from_events(events).unwrap();
+
+        let doc_type = doc.first_child(None).unwrap();
+        let doc_type = doc.doc_type(&doc_type).unwrap();
+
+        assert_eq!(doc_type.entities().count(), 1);
+        assert_eq!(doc_type.notations().count(), 1);
+
+        let doc = DocumentBuilder::new()
+            .build_from_events(parse_events(
+                "<!DOCTYPE a [<!ATTLIST b id ID #IMPLIED>]><a><b id='x'/></a>",
+            ))
+            .unwrap();
+
+        assert!(doc.get_element_by_id("x").is_some());
+
+        assert!(Document::parse("<a><b></a>").is_err(), "mismatched tag");
+        assert!(
+            Document::parse("<a p:b='1'/>").is_err(),
+            "undeclared prefix"
+        );
+    }
+}
@@ -0,0 +1,803 @@
+//! Canonical XML 1.0/1.1 and Exclusive XML Canonicalization.
+
+use std::fmt;
+
+use rexml_stax::{
+    parser::{BorrowInput, Event, Reader},
+    writer::escape,
+};
+
+use crate::{
+    DOMObject, Document, Error, ExceptionCode, NodeType, Result, XMLNS_NAMESPACE, XML_NAMESPACE,
+};
+
+/// The canonicalization algorithms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum C14nMethod {
+    /// [Canonical XML 1.0](https://www.w3.org/TR/xml-c14n)
+    Canonical10,
+    /// [Canonical XML 1.1](https://www.w3.org/TR/xml-c14n11)
+    Canonical11,
+    /// [Exclusive XML Canonicalization 1.0](https://www.w3.org/TR/xml-exc-c14n)
+    Exclusive,
+}
+
+/// Options of the canonicalization of a [`Document`] or of reader events.
+///
+/// The canonical form has no XML declaration nor document type declaration,
+/// character data is written with the minimal escaping, CDATA sections as text,
+/// elements with start and end tags, namespace declarations and attributes sorted,
+/// and superfluous namespace declarations removed.
+#[derive(Debug, Clone)]
+pub struct Canonicalizer {
+    method: C14nMethod,
+    comments: bool,
+    inclusive_namespaces: Vec<String>,
+}
+
+impl Canonicalizer {
+    /// Create a new `Canonicalizer` of `method`, without comments.
+    pub fn new(method: C14nMethod) -> Self {
+        Self {
+            method,
+            comments: false,
+            inclusive_namespaces: vec![],
+        }
+    }
+
+    /// Create the `Canonicalizer` identified by the algorithm URI `uri`, as used by XML Signature.
+    pub fn from_algorithm(uri: &str) -> Option<Self> {
+        let (method, comments) = match uri {
+            "http://www.w3.org/TR/2001/REC-xml-c14n-20010315" => (C14nMethod::Canonical10, false),
+            "http://www.w3.org/TR/2001/REC-xml-c14n-20010315#WithComments" => {
+                (C14nMethod::Canonical10, true)
+            }
+            "http://www.w3.org/2006/12/xml-c14n11" => (C14nMethod::Canonical11, false),
+            "http://www.w3.org/2006/12/xml-c14n11#WithComments" => (C14nMethod::Canonical11, true),
+            "http://www.w3.org/2001/10/xml-exc-c14n#" => (C14nMethod::Exclusive, false),
+            "http://www.w3.org/2001/10/xml-exc-c14n#WithComments" => (C14nMethod::Exclusive, true),
+            _ => return None,
+        };
+
+        Some(Self::new(method).with_comments(comments))
+    }
+
+    /// Returns the algorithm URI of this `Canonicalizer`.
+    pub fn algorithm(&self) -> &'static str {
+        match (self.method, self.comments) {
+            (C14nMethod::Canonical10, false) => "http://www.w3.org/TR/2001/REC-xml-c14n-20010315",
+            (C14nMethod::Canonical10, true) => {
+                "http://www.w3.org/TR/2001/REC-xml-c14n-20010315#WithComments"
+            }
+            (C14nMethod::Canonical11, false) => "http://www.w3.org/2006/12/xml-c14n11",
+            (C14nMethod::Canonical11, true) => "http://www.w3.org/2006/12/xml-c14n11#WithComments",
+            (C14nMethod::Exclusive, false) => "http://www.w3.org/2001/10/xml-exc-c14n#",
+            (C14nMethod::Exclusive, true) => "http://www.w3.org/2001/10/xml-exc-c14n#WithComments",
+        }
+    }
+
+    /// Returns the canonicalization algorithm.
+    pub fn method(&self) -> C14nMethod {
+        self.method
+    }
+
+    /// Returns the InclusiveNamespaces PrefixList.
+    pub fn prefix_list(&self) -> &[String] {
+        &self.inclusive_namespaces
+    }
+
+    /// Keep comments, default is false.
+    pub fn with_comments(mut self, comments: bool) -> Self {
+        self.comments = comments;
+        self
+    }
+
+    /// The InclusiveNamespaces PrefixList of exclusive canonicalization, `#default` is the default namespace.
+    ///
+    /// The namespaces bound to these prefixes are rendered as by inclusive canonicalization,
+    /// the list is ignored by the other methods.
+    pub fn inclusive_namespaces<I, S>(mut self, prefixes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.inclusive_namespaces = prefixes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Write the canonical form of the subtree of `node` to `writer`,
+    /// or of the whole document if `node` is `None`.
+    ///
+    /// Namespace declarations and, for the inclusive methods, the `xml:*` attributes in
+    /// the scope of a subtree are written on its top element. Canonical XML 1.1 only
+    /// inherits `xml:lang` and `xml:space`, `xml:base` is not fixed up.
+    pub fn write<W>(
+        &self,
+        document: &Document<'_>,
+        node: Option<&DOMObject>,
+        writer: &mut W,
+    ) -> Result<()>
+    where
+        W: fmt::Write + ?Sized,
+    {
+        self.write_filtered(document, node, &|_| true, writer)
+    }
+
+    /// Write the canonical form of the subtree of `node` without the subtrees
+    /// of the nodes for which `filter` returns false.
+    pub(crate) fn write_filtered<W>(
+        &self,
+        document: &Document<'_>,
+        node: Option<&DOMObject>,
+        filter: &dyn Fn(&DOMObject) -> bool,
+        writer: &mut W,
+    ) -> Result<()>
+    where
+        W: fmt::Write + ?Sized,
+    {
+        let mut output = Output::new(self, writer);
+
+        let node = node.filter(|node| node.node_type() != NodeType::Document);
+
+        let mut stack = match node {
+            Some(node) => {
+                output.prolog = None;
+
+                // the namespaces in scope of the subtree.
+                let mut ancestors = vec![];
+                let mut parent = document.parent_node(node);
+
+                while let Some(element) = parent.filter(|obj| obj.node_type() == NodeType::Element)
+                {
+                    ancestors.push(element);
+                    parent = document.parent_node(&element);
+                }
+
+                for ancestor in ancestors.iter().rev() {
+                    output.push_scope();
+                    declare_namespaces(document, ancestor, &mut output)?;
+                }
+
+                if filter(node) {
+                    vec![Item::Node(*node)]
+                } else {
+                    vec![]
+                }
+            }
+            None => document
+                .children(None)
+                .rev()
+                .filter(|obj| filter(obj))
+                .map(|obj| Item::Node(*obj))
+                .collect(),
+        };
+
+        let top = node.copied();
+
+        while let Some(item) = stack.pop() {
+            let node = match item {
+                Item::Node(node) => node,
+                Item::End(name) => {
+                    output.end_element(&name)?;
+                    continue;
+                }
+            };
+
+            let not_found = || Error::DOMException(ExceptionCode::NOT_FOUND_ERR);
+
+            match node.node_type() {
+                NodeType::Element => {
+                    let el = document.element(&node).ok_or_else(not_found)?;
+
+                    output.push_scope();
+                    declare_namespaces(document, &node, &mut output)?;
+
+                    let mut attrs = el
+                        .attributes()
+                        .into_iter()
+                        .filter_map(|attr| document.attr(attr))
+                        .filter(|attr| attr.namespace_uri() != Some(XMLNS_NAMESPACE))
+                        .map(|attr| (attr.name().to_string(), attr.value()))
+                        .collect::<Vec<_>>();
+
+                    if top == Some(node) {
+                        self.inherit_xml_attributes(document, &node, &mut attrs);
+                    }
+
+                    let name = el.tag().to_string();
+
+                    output.start_element(&name, attrs.iter().map(|(n, v)| (n.as_str(), *v)))?;
+
+                    stack.push(Item::End(name));
+                    stack.extend(
+                        document
+                            .children(Some(&node))
+                            .rev()
+                            .filter(|obj| filter(obj))
+                            .map(|obj| Item::Node(*obj)),
+                    );
+                }
+                NodeType::Text => {
+                    output.text(document.text(&node).ok_or_else(not_found)?.data())?
+                }
+                NodeType::CData => {
+                    output.text(document.cdata(&node).ok_or_else(not_found)?.data())?
+                }
+                NodeType::Comment => {
+                    output.comment(document.comment(&node).ok_or_else(not_found)?.data())?
+                }
+                NodeType::ProcessingInstruction => {
+                    let pi = document.pi(&node).ok_or_else(not_found)?;
+                    output.processing_instruction(pi.target(), pi.data())?;
+                }
+                // the replacement text of entity references is written in place.
+                NodeType::EntityReference => stack.extend(
+                    document
+                        .children(Some(&node))
+                        .rev()
+                        .filter(|obj| filter(obj))
+                        .map(|obj| Item::Node(*obj)),
+                ),
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add the `xml:*` attributes of the ancestors of `element`, which are not
+    /// specified by `element`, to `attrs`.
+    fn inherit_xml_attributes<'d>(
+        &self,
+        document: &'d Document<'_>,
+        element: &DOMObject,
+        attrs: &mut Vec<(String, &'d str)>,
+    ) {
+        let inherited: &[&str] = match self.method {
+            C14nMethod::Canonical10 => &["xml:lang", "xml:space", "xml:base", "xml:id"],
+            C14nMethod::Canonical11 => &["xml:lang", "xml:space"],
+            C14nMethod::Exclusive => return,
+        };
+
+        let mut parent = document.parent_node(element);
+
+        while let Some(ancestor) = parent.filter(|obj| obj.node_type() == NodeType::Element) {
+            for name in inherited {
+                if attrs.iter().any(|(attr, _)| attr == name) {
+                    continue;
+                }
+
+                if let Some(value) = document.get_attribute(&ancestor, name) {
+                    attrs.push((name.to_string(), value));
+                }
+            }
+
+            parent = document.parent_node(&ancestor);
+        }
+    }
+
+    /// Write the canonical form of the document read from `events` to `writer`.
+    pub fn write_events<'a, E, W>(&self, events: E, writer: &mut W) -> Result<()>
+    where
+        E: IntoIterator<Item = Event<'a>>,
+        W: fmt::Write + ?Sized,
+    {
+        let mut output = Output::new(self, writer);
+
+        for event in events {
+            output.event(event)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the canonical form of the document read by `reader` to `writer`.
+    pub async fn write_reader<'a, I, W>(&self, reader: &mut Reader<I>, writer: &mut W) -> Result<()>
+    where
+        I: BorrowInput<'a>,
+        W: fmt::Write + ?Sized,
+    {
+        let mut output = Output::new(self, writer);
+
+        while let Some(event) = reader.next().await? {
+            output.event(event)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the canonical form of the subtree of `node`, or of the whole document if `node` is `None`.
+    pub fn to_string(&self, document: &Document<'_>, node: Option<&DOMObject>) -> Result<String> {
+        let mut output = String::new();
+
+        self.write(document, node, &mut output)?;
+
+        Ok(output)
+    }
+}
+
+/// A step of the traversal of the tree.
+enum Item {
+    Node(DOMObject),
+    /// The end tag of one element.
+    End(String),
+}
+
+/// Declare the namespaces used or declared by `element` in the current scope of `output`.
+fn declare_namespaces<W>(
+    document: &Document<'_>,
+    element: &DOMObject,
+    output: &mut Output<'_, W>,
+) -> Result<()>
+where
+    W: fmt::Write + ?Sized,
+{
+    let el = document
+        .element(element)
+        .ok_or(Error::DOMException(ExceptionCode::NOT_FOUND_ERR))?;
+
+    let attrs = el
+        .attributes()
+        .into_iter()
+        .filter_map(|attr| document.attr(attr));
+
+    for attr in attrs.clone() {
+        match (
+            attr.name().prefix.as_deref(),
+            attr.name().local_part.as_ref(),
+        ) {
+            (Some("xmlns"), prefix) => output.declare(prefix, attr.value()),
+            (None, "xmlns") => output.declare("", attr.value()),
+            _ => {}
+        }
+    }
+
+    for ns in document
+        .children(Some(element))
+        .filter(|obj| obj.node_type() == NodeType::Namespace)
+        .filter_map(|obj| document.ns(obj))
+    {
+        output.declare(ns.prefix(), ns.href());
+    }
+
+    // namespaces of the element and attributes without declaration.
+    if el.namespace_uri().is_some() || el.tag().prefix.is_none() {
+        let prefix = el.tag().prefix.as_deref().unwrap_or("");
+        let uri = el.namespace_uri().unwrap_or("");
+
+        if output.resolve(prefix) != uri {
+            output.declare(prefix, uri);
+        }
+    }
+
+    for attr in attrs {
+        if let (Some(prefix), Some(uri)) = (attr.name().prefix.as_deref(), attr.namespace_uri()) {
+            if uri != XMLNS_NAMESPACE && output.resolve(prefix) != uri {
+                output.declare(prefix, uri);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The state of one canonicalization.
+struct Output<'w, W: ?Sized> {
+    options: &'w Canonicalizer,
+    writer: &'w mut W,
+    /// In-scope `(prefix, namespace URI)` bindings, the default namespace has an empty prefix.
+    bindings: Vec<(String, String)>,
+    /// The length of `bindings` before each element.
+    scopes: Vec<usize>,
+    /// The namespace declarations rendered by the open elements.
+    rendered: Vec<(String, String)>,
+    /// The length of `rendered` before each open element.
+    rendered_scopes: Vec<usize>,
+    /// The number of open elements.
+    depth: usize,
+    /// Whether the root element has been written, `None` if the top nodes are not children of the document.
+    prolog: Option<bool>,
+}
+
+impl<'w, W: fmt::Write + ?Sized> Output<'w, W> {
+    fn new(options: &'w Canonicalizer, writer: &'w mut W) -> Self {
+        Self {
+            options,
+            writer,
+            bindings: vec![],
+            scopes: vec![],
+            rendered: vec![],
+            rendered_scopes: vec![],
+            depth: 0,
+            prolog: Some(true),
+        }
+    }
+
+    fn event(&mut self, event: Event<'_>) -> Result<()> {
+        match event {
+            Event::StartElement { name, attributes } => {
+                self.push_scope();
+
+                for attr in &attributes {
+                    if attr.name == "xmlns" {
+                        self.declare("", &attr.value);
+                    } else if let Some(prefix) = attr.name.strip_prefix("xmlns:") {
+                        self.declare(prefix, &attr.value);
+                    }
+                }
+
+                self.start_element(
+                    &name,
+                    attributes
+                        .iter()
+                        .filter(|attr| attr.name != "xmlns" && !attr.name.starts_with("xmlns:"))
+                        .map(|attr| (attr.name.as_ref(), attr.value.as_ref())),
+                )?;
+            }
+            Event::EndElement { name } => self.end_element(&name)?,
+            Event::Characters(text) | Event::CData(text) => self.text(&text)?,
+            Event::Comment(text) => self.comment(&text)?,
+            Event::ProcessingInstruction { target, data } => {
+                self.processing_instruction(&target, data.as_deref().unwrap_or(""))?
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Open the scope of the namespace declarations of a new element.
+    fn push_scope(&mut self) {
+        self.scopes.push(self.bindings.len());
+    }
+
+    fn declare(&mut self, prefix: &str, uri: &str) {
+        self.bindings.push((prefix.to_owned(), uri.to_owned()));
+    }
+
+    /// Returns the namespace URI bound to `prefix`, empty if it is not bound.
+    fn resolve(&self, prefix: &str) -> &str {
+        if prefix == "xml" {
+            return XML_NAMESPACE;
+        }
+
+        self.bindings
+            .iter()
+            .rev()
+            .find(|(bound, _)| bound == prefix)
+            .map_or("", |(_, uri)| uri)
+    }
+
+    /// Returns the namespace URI bound to `prefix` by the rendered declarations, empty if none.
+    fn rendered(&self, prefix: &str) -> &str {
+        self.rendered
+            .iter()
+            .rev()
+            .find(|(bound, _)| bound == prefix)
+            .map_or("", |(_, uri)| uri)
+    }
+
+    /// Returns the prefixes visibly utilized by an element and its attributes,
+    /// the rendered namespaces of exclusive canonicalization.
+    fn visible_prefixes<'a, I>(name: &'a str, attrs: I) -> Vec<&'a str>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        let prefix = |qname: &'a str| qname.split_once(':').map(|(prefix, _)| prefix);
+
+        Some(prefix(name).unwrap_or(""))
+            .into_iter()
+            .chain(attrs.filter_map(prefix))
+            .filter(|prefix| *prefix != "xml")
+            .collect()
+    }
+
+    fn start_element<'a, I>(&mut self, name: &str, attrs: I) -> Result<()>
+    where
+        I: Iterator<Item = (&'a str, &'a str)>,
+    {
+        let attrs = attrs.collect::<Vec<_>>();
+
+        // `(prefix, namespace URI)` of the declarations to render.
+        let mut candidates: Vec<(&str, &str)> = vec![];
+
+        if self.options.method == C14nMethod::Exclusive {
+            let prefixes = Self::visible_prefixes(name, attrs.iter().map(|(name, _)| *name));
+
+            let inclusive = self
+                .options
+                .inclusive_namespaces
+                .iter()
+                .map(|prefix| match prefix.as_str() {
+                    "#default" => "",
+                    prefix => prefix,
+                })
+                .filter(|prefix| {
+                    self.bindings
+                        .iter()
+                        .any(|(bound, _)| bound == prefix && *prefix != "xml")
+                });
+
+            for prefix in prefixes.into_iter().chain(inclusive) {
+                candidates.push((prefix, self.resolve(prefix)));
+            }
+        } else {
+            for (prefix, uri) in self.bindings.iter().rev() {
+                if prefix != "xml" || uri != XML_NAMESPACE {
+                    candidates.push((prefix, uri));
+                }
+            }
+        }
+
+        // the innermost binding of each prefix, unless an output ancestor renders the same one.
+        let mut namespaces: Vec<(&str, &str)> = vec![];
+
+        for (prefix, uri) in candidates {
+            if namespaces.iter().any(|(bound, _)| *bound == prefix) {
+                continue;
+            }
+
+            namespaces.push((prefix, uri));
+        }
+
+        namespaces.retain(|(prefix, uri)| self.rendered(prefix) != *uri);
+        namespaces.sort();
+
+        let namespaces = namespaces
+            .into_iter()
+            .map(|(prefix, uri)| (prefix.to_owned(), uri.to_owned()))
+            .collect::<Vec<_>>();
+
+        // attributes sorted by namespace URI and local name.
+        let mut sorted = attrs
+            .iter()
+            .map(|(name, value)| {
+                let (uri, local_part) = match name.split_once(':') {
+                    Some((prefix, local_part)) => (self.resolve(prefix), local_part),
+                    None => ("", *name),
+                };
+
+                ((uri, local_part), *name, *value)
+            })
+            .collect::<Vec<_>>();
+
+        sorted.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let sorted = sorted
+            .into_iter()
+            .map(|(_, name, value)| (name, value))
+            .collect::<Vec<_>>();
+
+        self.writer.write_char('<')?;
+        self.writer.write_str(name)?;
+
+        for (prefix, uri) in &namespaces {
+            if prefix.is_empty() {
+                self.writer.write_str(" xmlns=\"")?;
+            } else {
+                write!(self.writer, " xmlns:{}=\"", prefix)?;
+            }
+
+            self.attr_value(uri)?;
+            self.writer.write_char('"')?;
+        }
+
+        for (name, value) in sorted {
+            write!(self.writer, " {}=\"", name)?;
+            self.attr_value(value)?;
+            self.writer.write_char('"')?;
+        }
+
+        self.writer.write_char('>')?;
+
+        self.rendered_scopes.push(self.rendered.len());
+        self.rendered.extend(namespaces);
+        self.depth += 1;
+
+        Ok(())
+    }
+
+    fn end_element(&mut self, name: &str) -> Result<()> {
+        // the events of `write_events` may be unbalanced.
+        if self.depth == 0 {
+            return Err(rexml_stax::Error::WriterState("no open element").into());
+        }
+
+        write!(self.writer, "</{}>", name)?;
+
+        if let Some(len) = self.rendered_scopes.pop() {
+            self.rendered.truncate(len);
+        }
+
+        if let Some(len) = self.scopes.pop() {
+            self.bindings.truncate(len);
+        }
+
+        self.depth -= 1;
+
+        if self.depth == 0 {
+            self.prolog = self.prolog.map(|_| false);
+        }
+
+        Ok(())
+    }
+
+    fn attr_value(&mut self, value: &str) -> fmt::Result {
+        escape(self.writer, value, false, |c| match c {
+            '&' => Some("&amp;"),
+            '<' => Some("&lt;"),
+            '"' => Some("&quot;"),
+            '\t' => Some("&#x9;"),
+            '\n' => Some("&#xA;"),
+            '\r' => Some("&#xD;"),
+            _ => None,
+        })
+    }
+
+    fn text(&mut self, text: &str) -> Result<()> {
+        // white spaces outside of the root element are not part of the canonical form.
+        if self.depth == 0 && self.prolog.is_some() {
+            return Ok(());
+        }
+
+        escape(self.writer, text, false, |c| match c {
+            '&' => Some("&amp;"),
+            '<' => Some("&lt;"),
+            '>' => Some("&gt;"),
+            '\r' => Some("&#xD;"),
+            _ => None,
+        })?;
+
+        Ok(())
+    }
+
+    /// Write a comment or processing instruction with the line breaks separating
+    /// the children of the document.
+    fn top_level<F>(&mut self, write: F) -> Result<()>
+    where
+        F: FnOnce(&mut W) -> fmt::Result,
+    {
+        let prolog = if self.depth == 0 { self.prolog } else { None };
+
+        if prolog == Some(false) {
+            self.writer.write_char('\n')?;
+        }
+
+        write(self.writer)?;
+
+        if prolog == Some(true) {
+            self.writer.write_char('\n')?;
+        }
+
+        Ok(())
+    }
+
+    fn comment(&mut self, text: &str) -> Result<()> {
+        if !self.options.comments {
+            return Ok(());
+        }
+
+        self.top_level(|writer| write!(writer, "<!--{}-->", text))
+    }
+
+    fn processing_instruction(&mut self, target: &str, data: &str) -> Result<()> {
+        self.top_level(|writer| match data {
+            "" => write!(writer, "<?{}?>", target),
+            data => write!(writer, "<?{} {}?>", target, data),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+
+    fn c14n(method: C14nMethod, input: &str) -> String {
+        let doc = Document::parse(input).unwrap();
+
+        Canonicalizer::new(method).to_string(&doc, None).unwrap()
+    }
+
+    #[test]
+    fn test_document() {
+        let input = "<?xml version=\"1.0\"?>\r\n<?xml-stylesheet href=\"doc.xsl\"\r\n   type=\"text/xsl\"   ?>\r\n<!DOCTYPE doc [<!ATTLIST e9 attr CDATA \"default\">]>\r\n<!-- comment -->\r\n<doc>\r\n   <e1   />\r\n   <e2   ></e2>\r\n   <e3   name = \"elem3\"   id=\"elem3\"   />\r\n   <e5 a:attr=\"out\" b:attr=\"sorted\" attr2=\"all\" attr=\"I'm\"\r\n      xmlns:b=\"http://www.ietf.org\"\r\n      xmlns:a=\"http://www.w3.org\"\r\n      xmlns=\"http://example.org\"/>\r\n   <e9 xmlns=\"\" xmlns:a=\"http://www.w3.org\"/>\r\n   <e6><![CDATA[ x < y & \"z\" ]]>&#xD;</e6>\r\n</doc>\r\n<?pi ?>";
+
+        let expected = "<?xml-stylesheet href=\"doc.xsl\"\n   type=\"text/xsl\"   ?>\n<doc>\n   <e1></e1>\n   <e2></e2>\n   <e3 id=\"elem3\" name=\"elem3\"></e3>\n   <e5 xmlns=\"http://example.org\" xmlns:a=\"http://www.w3.org\" xmlns:b=\"http://www.ietf.org\" attr=\"I'm\" attr2=\"all\" b:attr=\"sorted\" a:attr=\"out\"></e5>\n   <e9 xmlns:a=\"http://www.w3.org\" attr=\"default\"></e9>\n   <e6> x &lt; y &amp; \"z\" &#xD;</e6>\n</doc>\n<?pi?>";
+
+        assert_eq!(c14n(C14nMethod::Canonical10, input), expected);
+
+        let with_comments = Canonicalizer::new(C14nMethod::Canonical10)
+            .with_comments(true)
+            .to_string(&Document::parse(input).unwrap(), None)
+            .unwrap();
+
+        assert!(with_comments.contains("?>\n<!-- comment -->\n<doc>"));
+
+        // the same output from the reader events.
+        let mut output = String::new();
+
+        block_on(
+            Canonicalizer::new(C14nMethod::Canonical10)
+                .write_reader(&mut Reader::new(input), &mut output),
+        )
+        .unwrap();
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_subtree() {
+        let input = r#"<a:root xmlns:a="urn:a" xmlns:b="urn:b" xmlns="urn:d" xml:lang="en"><a:child b:attr="1"><c:leaf xmlns:c="urn:c"/><plain xmlns=""/></a:child></a:root>"#;
+
+        let doc = Document::parse(input).unwrap();
+        let root = doc.first_child(None).unwrap();
+        let child = doc.first_child(Some(&root)).unwrap();
+
+        assert_eq!(
+            Canonicalizer::new(C14nMethod::Canonical10)
+                .to_string(&doc, Some(&child))
+                .unwrap(),
+            r#"<a:child xmlns="urn:d" xmlns:a="urn:a" xmlns:b="urn:b" xml:lang="en" b:attr="1"><c:leaf xmlns:c="urn:c"></c:leaf><plain xmlns=""></plain></a:child>"#
+        );
+
+        assert_eq!(
+            Canonicalizer::new(C14nMethod::Exclusive)
+                .to_string(&doc, Some(&child))
+                .unwrap(),
+            r#"<a:child xmlns:a="urn:a" xmlns:b="urn:b" b:attr="1"><c:leaf xmlns:c="urn:c"></c:leaf><plain></plain></a:child>"#
+        );
+
+        assert_eq!(
+            Canonicalizer::new(C14nMethod::Exclusive)
+                .inclusive_namespaces(["#default"])
+                .to_string(&doc, Some(&child))
+                .unwrap(),
+            r#"<a:child xmlns="urn:d" xmlns:a="urn:a" xmlns:b="urn:b" b:attr="1"><c:leaf xmlns:c="urn:c"></c:leaf><plain xmlns=""></plain></a:child>"#
+        );
+
+        // namespace declarations already rendered by an ancestor are removed.
+        assert_eq!(
+            c14n(
+                C14nMethod::Exclusive,
+                r#"<a:root xmlns:a="urn:a" xmlns:b="urn:b"><a:child xmlns:a="urn:a"><b:x/></a:child></a:root>"#
+            ),
+            r#"<a:root xmlns:a="urn:a"><a:child><b:x xmlns:b="urn:b"></b:x></a:child></a:root>"#
+        );
+    }
+
+    #[test]
+    fn test_algorithm() {
+        for uri in [
+            "http://www.w3.org/TR/2001/REC-xml-c14n-20010315",
+            "http://www.w3.org/2006/12/xml-c14n11#WithComments",
+            "http://www.w3.org/2001/10/xml-exc-c14n#",
+        ] {
+            assert_eq!(Canonicalizer::from_algorithm(uri).unwrap().algorithm(), uri);
+        }
+
+        assert!(Canonicalizer::from_algorithm("urn:unknown").is_none());
+    }
+
+    #[test]
+    fn test_unbalanced_events() {
+        let canonicalizer = Canonicalizer::new(C14nMethod::Canonical10);
+        let mut output = String::new();
+
+        let events = [
+            Event::StartElement {
+                name: "a".into(),
+                attributes: vec![],
+            },
+            Event::EndElement { name: "a".into() },
+            Event::EndElement { name: "a".into() },
+        ];
+
+        assert!(matches!(
+            canonicalizer.write_events(events, &mut output),
+            Err(Error::Parse(rexml_stax::Error::WriterState(_)))
+        ));
+        assert_eq!(output, "<a></a>");
+    }
+}
@@ -0,0 +1,1078 @@
+//! XML Signature generation and verification.
+//!
+//! Same-document references (`URI=""` and `URI="#id"`) are supported, with the
+//! enveloped-signature and canonicalization transforms.
+//! RSA signing is exposed to a timing side channel, see [`SigningKey::Rsa`].
+
+use std::borrow::Cow;
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use hmac::{digest::KeyInit, Hmac, Mac};
+use rsa::Pkcs1v15Sign;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+pub use p256::ecdsa::{SigningKey as P256SigningKey, VerifyingKey as P256VerifyingKey};
+pub use p384::ecdsa::{SigningKey as P384SigningKey, VerifyingKey as P384VerifyingKey};
+pub use rsa::{RsaPrivateKey, RsaPublicKey};
+
+use crate::{
+    C14nMethod, Canonicalizer, DOMObject, Document, Error, ExceptionCode, NodeType, Result,
+    XMLNS_NAMESPACE,
+};
+
+/// The namespace of XML Signature elements.
+pub const DSIG_NAMESPACE: &str = "http://www.w3.org/2000/09/xmldsig#";
+
+/// The algorithm URI of the enveloped-signature transform.
+const ENVELOPED_SIGNATURE: &str = "http://www.w3.org/2000/09/xmldsig#enveloped-signature";
+
+/// The namespace of the `InclusiveNamespaces` element of exclusive canonicalization.
+const EXC_C14N_NAMESPACE: &str = "http://www.w3.org/2001/10/xml-exc-c14n#";
+
+/// The digest algorithms of references.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestMethod {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl DigestMethod {
+    /// Returns the digest algorithm identified by `uri`.
+    pub fn from_algorithm(uri: &str) -> Option<Self> {
+        match uri {
+            "http://www.w3.org/2001/04/xmlenc#sha256" => Some(Self::Sha256),
+            "http://www.w3.org/2001/04/xmldsig-more#sha384" => Some(Self::Sha384),
+            "http://www.w3.org/2001/04/xmlenc#sha512" => Some(Self::Sha512),
+            _ => None,
+        }
+    }
+
+    /// Returns the algorithm URI.
+    pub fn algorithm(&self) -> &'static str {
+        match self {
+            Self::Sha256 => "http://www.w3.org/2001/04/xmlenc#sha256",
+            Self::Sha384 => "http://www.w3.org/2001/04/xmldsig-more#sha384",
+            Self::Sha512 => "http://www.w3.org/2001/04/xmlenc#sha512",
+        }
+    }
+
+    /// Returns the digest of `data`.
+    pub fn digest(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Sha256 => Sha256::digest(data).to_vec(),
+            Self::Sha384 => Sha384::digest(data).to_vec(),
+            Self::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+}
+
+/// The signature algorithms of `SignedInfo`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureMethod {
+    RsaSha256,
+    RsaSha384,
+    RsaSha512,
+    EcdsaSha256,
+    EcdsaSha384,
+    EcdsaSha512,
+    HmacSha256,
+    HmacSha384,
+    HmacSha512,
+}
+
+impl SignatureMethod {
+    /// Returns the signature algorithm identified by `uri`.
+    pub fn from_algorithm(uri: &str) -> Option<Self> {
+        let method = match uri.strip_prefix("http://www.w3.org/2001/04/xmldsig-more#")? {
+            "rsa-sha256" => Self::RsaSha256,
+            "rsa-sha384" => Self::RsaSha384,
+            "rsa-sha512" => Self::RsaSha512,
+            "ecdsa-sha256" => Self::EcdsaSha256,
+            "ecdsa-sha384" => Self::EcdsaSha384,
+            "ecdsa-sha512" => Self::EcdsaSha512,
+            "hmac-sha256" => Self::HmacSha256,
+            "hmac-sha384" => Self::HmacSha384,
+            "hmac-sha512" => Self::HmacSha512,
+            _ => return None,
+        };
+
+        Some(method)
+    }
+
+    /// Returns the algorithm URI.
+    pub fn algorithm(&self) -> &'static str {
+        match self {
+            Self::RsaSha256 => "http://www.w3.org/2001/04/xmldsig-more#rsa-sha256",
+            Self::RsaSha384 => "http://www.w3.org/2001/04/xmldsig-more#rsa-sha384",
+            Self::RsaSha512 => "http://www.w3.org/2001/04/xmldsig-more#rsa-sha512",
+            Self::EcdsaSha256 => "http://www.w3.org/2001/04/xmldsig-more#ecdsa-sha256",
+            Self::EcdsaSha384 => "http://www.w3.org/2001/04/xmldsig-more#ecdsa-sha384",
+            Self::EcdsaSha512 => "http://www.w3.org/2001/04/xmldsig-more#ecdsa-sha512",
+            Self::HmacSha256 => "http://www.w3.org/2001/04/xmldsig-more#hmac-sha256",
+            Self::HmacSha384 => "http://www.w3.org/2001/04/xmldsig-more#hmac-sha384",
+            Self::HmacSha512 => "http://www.w3.org/2001/04/xmldsig-more#hmac-sha512",
+        }
+    }
+
+    /// Returns the digest algorithm of the signed data.
+    pub fn digest_method(&self) -> DigestMethod {
+        match self {
+            Self::RsaSha256 | Self::EcdsaSha256 | Self::HmacSha256 => DigestMethod::Sha256,
+            Self::RsaSha384 | Self::EcdsaSha384 | Self::HmacSha384 => DigestMethod::Sha384,
+            Self::RsaSha512 | Self::EcdsaSha512 | Self::HmacSha512 => DigestMethod::Sha512,
+        }
+    }
+}
+
+/// The transforms of a reference.
+#[derive(Debug, Clone)]
+pub enum Transform {
+    /// Removes the `Signature` element from the referenced data.
+    EnvelopedSignature,
+    /// Canonicalize the referenced data.
+    Canonicalize(Canonicalizer),
+}
+
+impl Transform {
+    /// Returns the algorithm URI.
+    pub fn algorithm(&self) -> &'static str {
+        match self {
+            Self::EnvelopedSignature => ENVELOPED_SIGNATURE,
+            Self::Canonicalize(canonicalizer) => canonicalizer.algorithm(),
+        }
+    }
+}
+
+/// The key signing `SignedInfo`.
+///
+/// An ECDSA key only fits the signature method of the digest of its curve size,
+/// `EcdsaSha256` for P-256 and `EcdsaSha384` for P-384.
+#[derive(Clone)]
+pub enum SigningKey {
+    /// The private key operations of the `rsa` crate are affected by the Marvin timing
+    /// side channel ([RUSTSEC-2023-0071](https://rustsec.org/advisories/RUSTSEC-2023-0071)),
+    /// an attacker observing the timing of many signatures may recover the key.
+    /// Don't sign with RSA where the timing is observable over a network, prefer ECDSA or HMAC.
+    /// Verification only uses the public key and is not affected.
+    Rsa(RsaPrivateKey),
+    P256(P256SigningKey),
+    P384(P384SigningKey),
+    Hmac(Vec<u8>),
+}
+
+impl SigningKey {
+    /// Returns the `SignatureValue` of `data`.
+    fn sign(&self, method: SignatureMethod, data: &[u8]) -> Result<Vec<u8>> {
+        use p256::ecdsa::signature::hazmat::PrehashSigner;
+
+        let digest_method = method.digest_method();
+
+        match (method, self) {
+            (
+                SignatureMethod::RsaSha256
+                | SignatureMethod::RsaSha384
+                | SignatureMethod::RsaSha512,
+                Self::Rsa(key),
+            ) => key
+                .sign(pkcs1v15(digest_method), &digest_method.digest(data))
+                .map_err(|err| Error::Key(err.to_string())),
+            (SignatureMethod::EcdsaSha256, Self::P256(key)) => {
+                let signature: p256::ecdsa::Signature = key
+                    .sign_prehash(&digest_method.digest(data))
+                    .map_err(|err| Error::Key(err.to_string()))?;
+
+                Ok(signature.to_bytes().to_vec())
+            }
+            (SignatureMethod::EcdsaSha384, Self::P384(key)) => {
+                let signature: p384::ecdsa::Signature = key
+                    .sign_prehash(&digest_method.digest(data))
+                    .map_err(|err| Error::Key(err.to_string()))?;
+
+                Ok(signature.to_bytes().to_vec())
+            }
+            (
+                SignatureMethod::HmacSha256
+                | SignatureMethod::HmacSha384
+                | SignatureMethod::HmacSha512,
+                Self::Hmac(key),
+            ) => match digest_method {
+                DigestMethod::Sha256 => Ok(hmac::<Hmac<Sha256>>(key, data)?
+                    .finalize()
+                    .into_bytes()
+                    .to_vec()),
+                DigestMethod::Sha384 => Ok(hmac::<Hmac<Sha384>>(key, data)?
+                    .finalize()
+                    .into_bytes()
+                    .to_vec()),
+                DigestMethod::Sha512 => Ok(hmac::<Hmac<Sha512>>(key, data)?
+                    .finalize()
+                    .into_bytes()
+                    .to_vec()),
+            },
+            _ => Err(Error::Key(format!(
+                "key doesn't fit {}",
+                method.algorithm()
+            ))),
+        }
+    }
+}
+
+/// The key verifying `SignedInfo`, ECDSA keys fit the methods as [`SigningKey`] ones.
+#[derive(Clone)]
+pub enum VerifyingKey {
+    Rsa(RsaPublicKey),
+    P256(P256VerifyingKey),
+    P384(P384VerifyingKey),
+    Hmac(Vec<u8>),
+}
+
+impl VerifyingKey {
+    /// Check `signature`, the `SignatureValue` of `data`.
+    fn verify(&self, method: SignatureMethod, data: &[u8], signature: &[u8]) -> Result<()> {
+        use p256::ecdsa::signature::hazmat::PrehashVerifier;
+
+        let digest_method = method.digest_method();
+
+        let verified = match (method, self) {
+            (
+                SignatureMethod::RsaSha256
+                | SignatureMethod::RsaSha384
+                | SignatureMethod::RsaSha512,
+                Self::Rsa(key),
+            ) => key
+                .verify(
+                    pkcs1v15(digest_method),
+                    &digest_method.digest(data),
+                    signature,
+                )
+                .is_ok(),
+            (SignatureMethod::EcdsaSha256, Self::P256(key)) => {
+                p256::ecdsa::Signature::from_slice(signature).is_ok_and(|signature| {
+                    key.verify_prehash(&digest_method.digest(data), &signature)
+                        .is_ok()
+                })
+            }
+            (SignatureMethod::EcdsaSha384, Self::P384(key)) => {
+                p384::ecdsa::Signature::from_slice(signature).is_ok_and(|signature| {
+                    key.verify_prehash(&digest_method.digest(data), &signature)
+                        .is_ok()
+                })
+            }
+            (
+                SignatureMethod::HmacSha256
+                | SignatureMethod::HmacSha384
+                | SignatureMethod::HmacSha512,
+                Self::Hmac(key),
+            ) => match digest_method {
+                DigestMethod::Sha256 => hmac::<Hmac<Sha256>>(key, data)?
+                    .verify_slice(signature)
+                    .is_ok(),
+                DigestMethod::Sha384 => hmac::<Hmac<Sha384>>(key, data)?
+                    .verify_slice(signature)
+                    .is_ok(),
+                DigestMethod::Sha512 => hmac::<Hmac<Sha512>>(key, data)?
+                    .verify_slice(signature)
+                    .is_ok(),
+            },
+            _ => {
+                return Err(Error::Key(format!(
+                    "key doesn't fit {}",
+                    method.algorithm()
+                )))
+            }
+        };
+
+        if verified {
+            Ok(())
+        } else {
+            Err(Error::SignatureMismatch)
+        }
+    }
+}
+
+fn pkcs1v15(digest_method: DigestMethod) -> Pkcs1v15Sign {
+    match digest_method {
+        DigestMethod::Sha256 => Pkcs1v15Sign::new::<Sha256>(),
+        DigestMethod::Sha384 => Pkcs1v15Sign::new::<Sha384>(),
+        DigestMethod::Sha512 => Pkcs1v15Sign::new::<Sha512>(),
+    }
+}
+
+/// Returns the MAC `M` of `data`, not finalized yet.
+fn hmac<M>(key: &[u8], data: &[u8]) -> Result<M>
+where
+    M: Mac + KeyInit,
+{
+    let mut mac = <M as KeyInit>::new_from_slice(key).map_err(|err| Error::Key(err.to_string()))?;
+
+    mac.update(data);
+
+    Ok(mac)
+}
+
+/// One `Reference` of `SignedInfo`.
+#[derive(Debug, Clone)]
+pub struct Reference {
+    uri: String,
+    transforms: Vec<Transform>,
+    digest_method: DigestMethod,
+    digest_value: Vec<u8>,
+}
+
+impl Reference {
+    /// Create a new reference to `uri`, `""` for the whole document or `#id` for one element.
+    pub fn new<U>(uri: U, digest_method: DigestMethod) -> Self
+    where
+        U: Into<String>,
+    {
+        Self {
+            uri: uri.into(),
+            transforms: vec![],
+            digest_method,
+            digest_value: vec![],
+        }
+    }
+
+    /// Append one transform.
+    pub fn transform(mut self, transform: Transform) -> Self {
+        self.transforms.push(transform);
+        self
+    }
+
+    /// Returns the URI of the referenced data.
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// Returns the transforms applied to the referenced data.
+    pub fn transforms(&self) -> &[Transform] {
+        &self.transforms
+    }
+
+    /// Returns the digest algorithm.
+    pub fn digest_method(&self) -> DigestMethod {
+        self.digest_method
+    }
+
+    /// Returns the `DigestValue`, empty if not computed yet.
+    pub fn digest_value(&self) -> &[u8] {
+        &self.digest_value
+    }
+
+    /// Returns the referenced node, the document node for `URI=""`.
+    fn resolve(&self, document: &Document<'_>) -> Result<DOMObject> {
+        match self.uri.as_str() {
+            "" => Ok(DOMObject::default()),
+            uri if uri.starts_with('#') && !uri.starts_with("#xpointer(") => {
+                find_id(document, &uri[1..])
+            }
+            uri => Err(Error::MalformedSignature(format!(
+                "unsupported reference URI {}",
+                uri
+            ))),
+        }
+    }
+
+    /// Returns the digest of the referenced `node` of the document of `signature`.
+    fn digest(
+        &self,
+        document: &Document<'_>,
+        node: &DOMObject,
+        signature: &DOMObject,
+    ) -> Result<Vec<u8>> {
+        let node = Some(node).filter(|node| **node != DOMObject::default());
+
+        // the data of a reference without canonicalization transform is canonicalized by C14N 1.0.
+        let mut canonicalizer = Canonicalizer::new(C14nMethod::Canonical10);
+        let mut enveloped = false;
+
+        for transform in &self.transforms {
+            match transform {
+                Transform::EnvelopedSignature => enveloped = true,
+                Transform::Canonicalize(transform) => canonicalizer = transform.clone(),
+            }
+        }
+
+        // same-document references remove comments.
+        let canonicalizer = canonicalizer.with_comments(false);
+
+        let mut data = String::new();
+
+        canonicalizer.write_filtered(
+            document,
+            node,
+            &|obj| !enveloped || obj != signature,
+            &mut data,
+        )?;
+
+        Ok(self.digest_method.digest(data.as_bytes()))
+    }
+}
+
+/// The names of the attributes identifying signed elements, besides the ones of type ID.
+const ID_ATTRIBUTES: [&str; 4] = ["Id", "ID", "id", "xml:id"];
+
+/// Returns the element with an attribute of type ID or one of [`ID_ATTRIBUTES`] of value `id`.
+///
+/// An ID of several elements is an error, otherwise the signed element could be moved
+/// and replaced by another one with the same ID (signature wrapping).
+fn find_id(document: &Document<'_>, id: &str) -> Result<DOMObject> {
+    let identified = |el: &DOMObject| {
+        ID_ATTRIBUTES
+            .iter()
+            .any(|name| document.get_attribute(el, name) == Some(id))
+            || document.element(el).is_some_and(|element| {
+                element.attributes().iter().any(|attr| {
+                    document.is_id(attr) && document.attr(attr).is_some_and(|a| a.value() == id)
+                })
+            })
+    };
+
+    let mut elements = document
+        .get_elements_by_tag_name(&DOMObject::default(), "*")
+        .filter(identified);
+
+    match (elements.next(), elements.next()) {
+        (Some(element), None) => Ok(element),
+        (None, _) => Err(Error::MalformedSignature(format!(
+            "no element with ID {}",
+            id
+        ))),
+        (Some(_), Some(_)) => Err(Error::MalformedSignature(format!(
+            "more than one element with ID {}",
+            id
+        ))),
+    }
+}
+
+/// A parsed `ds:Signature` element.
+#[derive(Debug, Clone)]
+pub struct Signature {
+    element: DOMObject,
+    signed_info: DOMObject,
+    canonicalization: Canonicalizer,
+    signature_method: SignatureMethod,
+    references: Vec<Reference>,
+    signature_value: Vec<u8>,
+}
+
+impl Signature {
+    /// Parse the `ds:Signature` element `element`.
+    pub fn parse(document: &Document<'_>, element: &DOMObject) -> Result<Self> {
+        if !is_dsig(document, element, "Signature") {
+            return Err(Error::MalformedSignature("not a Signature element".into()));
+        }
+
+        let signed_info = child(document, element, "SignedInfo")?;
+
+        let canonicalization = canonicalizer(
+            document,
+            &child(document, &signed_info, "CanonicalizationMethod")?,
+        )?;
+
+        let signature_method =
+            algorithm(document, &child(document, &signed_info, "SignatureMethod")?)?;
+
+        let signature_method =
+            SignatureMethod::from_algorithm(signature_method).ok_or_else(|| {
+                Error::MalformedSignature(format!(
+                    "unsupported signature method {}",
+                    signature_method
+                ))
+            })?;
+
+        let mut references = vec![];
+
+        for reference in children(document, &signed_info, "Reference") {
+            let uri = document.get_attribute(&reference, "URI").unwrap_or("");
+
+            let digest_method = algorithm(document, &child(document, &reference, "DigestMethod")?)?;
+
+            let digest_method = DigestMethod::from_algorithm(digest_method).ok_or_else(|| {
+                Error::MalformedSignature(format!("unsupported digest method {}", digest_method))
+            })?;
+
+            let mut transforms = vec![];
+
+            if let Some(list) = children(document, &reference, "Transforms").next() {
+                for transform in children(document, &list, "Transform") {
+                    let transform = match algorithm(document, &transform)? {
+                        ENVELOPED_SIGNATURE => Transform::EnvelopedSignature,
+                        _ => Transform::Canonicalize(canonicalizer(document, &transform)?),
+                    };
+
+                    transforms.push(transform);
+                }
+            }
+
+            references.push(Reference {
+                uri: uri.to_owned(),
+                transforms,
+                digest_method,
+                digest_value: base64(document, &child(document, &reference, "DigestValue")?)?,
+            });
+        }
+
+        if references.is_empty() {
+            return Err(Error::MalformedSignature("missing Reference".into()));
+        }
+
+        let signature_value = base64(document, &child(document, element, "SignatureValue")?)?;
+
+        Ok(Self {
+            element: *element,
+            signed_info,
+            canonicalization,
+            signature_method,
+            references,
+            signature_value,
+        })
+    }
+
+    /// Returns the `ds:Signature` element.
+    pub fn element(&self) -> &DOMObject {
+        &self.element
+    }
+
+    /// Returns the canonicalization of `SignedInfo`.
+    pub fn canonicalization(&self) -> &Canonicalizer {
+        &self.canonicalization
+    }
+
+    /// Returns the signature algorithm.
+    pub fn signature_method(&self) -> SignatureMethod {
+        self.signature_method
+    }
+
+    /// Returns the references of `SignedInfo`.
+    pub fn references(&self) -> &[Reference] {
+        &self.references
+    }
+
+    /// Returns the decoded `SignatureValue`.
+    pub fn signature_value(&self) -> &[u8] {
+        &self.signature_value
+    }
+
+    /// Check the digests of all references and the `SignatureValue` with `key`,
+    /// returns the referenced nodes in the order of the references,
+    /// the document node for `URI=""`.
+    ///
+    /// Only the returned nodes are signed, use them instead of looking up the signed data again.
+    ///
+    /// Raises `WRONG_DOCUMENT_ERR` if `document` is not the one this signature was parsed
+    /// from, and `NOT_FOUND_ERR` if the signature elements have been freed.
+    pub fn verify(&self, document: &Document<'_>, key: &VerifyingKey) -> Result<Vec<DOMObject>> {
+        if self.element.document() != document.id() {
+            return Err(Error::DOMException(ExceptionCode::WRONG_DOCUMENT_ERR));
+        }
+
+        if !document.is_allocated(&self.element) || !document.is_allocated(&self.signed_info) {
+            return Err(Error::DOMException(ExceptionCode::NOT_FOUND_ERR));
+        }
+
+        let mut nodes = vec![];
+
+        for reference in &self.references {
+            let node = reference.resolve(document)?;
+
+            if reference.digest(document, &node, &self.element)? != reference.digest_value {
+                return Err(Error::DigestMismatch(reference.uri.clone()));
+            }
+
+            nodes.push(node);
+        }
+
+        let signed_info = self
+            .canonicalization
+            .to_string(document, Some(&self.signed_info))?;
+
+        key.verify(
+            self.signature_method,
+            signed_info.as_bytes(),
+            &self.signature_value,
+        )?;
+
+        Ok(nodes)
+    }
+}
+
+/// Returns true if `node` is the XML Signature element `local_name`.
+fn is_dsig(document: &Document<'_>, node: &DOMObject, local_name: &str) -> bool {
+    node.node_type() == NodeType::Element
+        && document.element(node).is_some_and(|el| {
+            el.namespace_uri() == Some(DSIG_NAMESPACE) && el.tag().local_part == local_name
+        })
+}
+
+/// Returns the XML Signature child elements `local_name` of `parent`.
+fn children<'d>(
+    document: &'d Document<'_>,
+    parent: &DOMObject,
+    local_name: &'d str,
+) -> impl Iterator<Item = DOMObject> + 'd {
+    document
+        .children(Some(parent))
+        .filter(move |obj| is_dsig(document, obj, local_name))
+        .copied()
+}
+
+fn child(document: &Document<'_>, parent: &DOMObject, local_name: &str) -> Result<DOMObject> {
+    children(document, parent, local_name)
+        .next()
+        .ok_or_else(|| Error::MalformedSignature(format!("missing {}", local_name)))
+}
+
+fn algorithm<'d>(document: &'d Document<'_>, element: &DOMObject) -> Result<&'d str> {
+    document
+        .get_attribute(element, "Algorithm")
+        .ok_or_else(|| Error::MalformedSignature("missing Algorithm".into()))
+}
+
+/// Returns the canonicalization of a `CanonicalizationMethod` or `Transform` element.
+fn canonicalizer(document: &Document<'_>, element: &DOMObject) -> Result<Canonicalizer> {
+    let uri = algorithm(document, element)?;
+
+    let canonicalizer = Canonicalizer::from_algorithm(uri).ok_or_else(|| {
+        Error::MalformedSignature(format!("unsupported canonicalization {}", uri))
+    })?;
+
+    let prefixes = document
+        .children(Some(element))
+        .filter(|obj| obj.node_type() == NodeType::Element)
+        .filter(|obj| {
+            document.element(obj).is_some_and(|el| {
+                el.namespace_uri() == Some(EXC_C14N_NAMESPACE)
+                    && el.tag().local_part == "InclusiveNamespaces"
+            })
+        })
+        .filter_map(|obj| document.get_attribute(obj, "PrefixList"))
+        .flat_map(|list| list.split_ascii_whitespace())
+        .collect::<Vec<_>>();
+
+    Ok(canonicalizer.inclusive_namespaces(prefixes))
+}
+
+/// Decode the base64 text content of `element`.
+fn base64(document: &Document<'_>, element: &DOMObject) -> Result<Vec<u8>> {
+    let text = document
+        .children(Some(element))
+        .filter(|obj| obj.node_type() == NodeType::Text)
+        .filter_map(|obj| document.text(obj))
+        .flat_map(|text| text.data().chars())
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect::<String>();
+
+    STANDARD
+        .decode(text)
+        .map_err(|err| Error::MalformedSignature(err.to_string()))
+}
+
+/// Options of the generation of an enveloped `ds:Signature`.
+#[derive(Debug, Clone)]
+pub struct Signer {
+    canonicalization: Canonicalizer,
+    signature_method: SignatureMethod,
+    references: Vec<Reference>,
+    prefix: String,
+}
+
+impl Signer {
+    /// Create a new `Signer` of `signature_method`, `SignedInfo` is canonicalized by exclusive C14N.
+    pub fn new(signature_method: SignatureMethod) -> Self {
+        Self {
+            canonicalization: Canonicalizer::new(C14nMethod::Exclusive),
+            signature_method,
+            references: vec![],
+            prefix: "ds".to_owned(),
+        }
+    }
+
+    /// The canonicalization of `SignedInfo`.
+    pub fn canonicalization(mut self, canonicalization: Canonicalizer) -> Self {
+        self.canonicalization = canonicalization;
+        self
+    }
+
+    /// Add one reference, of which the digest is computed by [`sign`](Self::sign).
+    pub fn reference(mut self, reference: Reference) -> Self {
+        self.references.push(reference);
+        self
+    }
+
+    /// The prefix of the XML Signature elements, default is `ds`, empty for the default namespace.
+    pub fn prefix<P>(mut self, prefix: P) -> Self
+    where
+        P: Into<String>,
+    {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Append a new `ds:Signature` element to `parent`, signed with `key`.
+    pub fn sign(
+        &self,
+        document: &mut Document<'_>,
+        parent: &DOMObject,
+        key: &SigningKey,
+    ) -> Result<Signature> {
+        if self.references.is_empty() {
+            return Err(Error::MalformedSignature("missing Reference".into()));
+        }
+
+        let signature = self.element(document, None, "Signature")?;
+
+        let xmlns = match self.prefix.as_str() {
+            "" => "xmlns".to_owned(),
+            prefix => format!("xmlns:{}", prefix),
+        };
+
+        document.set_attribute_ns(
+            &signature,
+            Some(XMLNS_NAMESPACE),
+            Cow::Owned(xmlns),
+            DSIG_NAMESPACE,
+        )?;
+
+        let signed_info = self.element(document, Some(&signature), "SignedInfo")?;
+
+        self.algorithm_element(
+            document,
+            &signed_info,
+            "CanonicalizationMethod",
+            &Transform::Canonicalize(self.canonicalization.clone()),
+        )?;
+
+        let method = self.element(document, Some(&signed_info), "SignatureMethod")?;
+        document.set_attribute(&method, "Algorithm", self.signature_method.algorithm())?;
+
+        let mut digest_values = vec![];
+
+        for reference in &self.references {
+            let element = self.element(document, Some(&signed_info), "Reference")?;
+            document.set_attribute(&element, "URI", reference.uri.clone())?;
+
+            if !reference.transforms.is_empty() {
+                let transforms = self.element(document, Some(&element), "Transforms")?;
+
+                for transform in &reference.transforms {
+                    self.algorithm_element(document, &transforms, "Transform", transform)?;
+                }
+            }
+
+            let method = self.element(document, Some(&element), "DigestMethod")?;
+            document.set_attribute(&method, "Algorithm", reference.digest_method.algorithm())?;
+
+            digest_values.push(self.element(document, Some(&element), "DigestValue")?);
+        }
+
+        let signature_value = self.element(document, Some(&signature), "SignatureValue")?;
+
+        document.append_child(Some(parent), signature)?;
+
+        for (reference, element) in self.references.iter().zip(digest_values) {
+            let node = reference.resolve(document)?;
+            let digest = reference.digest(document, &node, &signature)?;
+
+            let text = document.create_text(STANDARD.encode(digest))?;
+            document.append_child(Some(&element), text)?;
+        }
+
+        let data = self
+            .canonicalization
+            .to_string(document, Some(&signed_info))?;
+
+        let value = key.sign(self.signature_method, data.as_bytes())?;
+
+        let text = document.create_text(STANDARD.encode(value))?;
+        document.append_child(Some(&signature_value), text)?;
+
+        Signature::parse(document, &signature)
+    }
+
+    /// Create the XML Signature element `local_name`, appended to `parent` if any.
+    fn element(
+        &self,
+        document: &mut Document<'_>,
+        parent: Option<&DOMObject>,
+        local_name: &str,
+    ) -> Result<DOMObject> {
+        let name = match self.prefix.as_str() {
+            "" => local_name.to_owned(),
+            prefix => format!("{}:{}", prefix, local_name),
+        };
+
+        let element = document.create_element_ns(Some(DSIG_NAMESPACE), Cow::Owned(name))?;
+
+        if let Some(parent) = parent {
+            document.append_child(Some(parent), element)?;
+        }
+
+        Ok(element)
+    }
+
+    /// Create the element `local_name` with the `Algorithm` of `transform`.
+    fn algorithm_element(
+        &self,
+        document: &mut Document<'_>,
+        parent: &DOMObject,
+        local_name: &str,
+        transform: &Transform,
+    ) -> Result<()> {
+        let element = self.element(document, Some(parent), local_name)?;
+        document.set_attribute(&element, "Algorithm", transform.algorithm())?;
+
+        match transform {
+            Transform::Canonicalize(canonicalizer)
+                if canonicalizer.method() == C14nMethod::Exclusive
+                    && !canonicalizer.prefix_list().is_empty() =>
+            {
+                let inclusive = document
+                    .create_element_ns(Some(EXC_C14N_NAMESPACE), "ec:InclusiveNamespaces")?;
+
+                document.set_attribute_ns(
+                    &inclusive,
+                    Some(XMLNS_NAMESPACE),
+                    "xmlns:ec",
+                    EXC_C14N_NAMESPACE,
+                )?;
+                document.set_attribute(
+                    &inclusive,
+                    "PrefixList",
+                    canonicalizer.prefix_list().join(" "),
+                )?;
+
+                document.append_child(Some(&element), inclusive)?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = r#"<app:Response xmlns:app="urn:app" ID="r1"><!-- comment --><app:Item>1 &amp; 2</app:Item></app:Response>"#;
+
+    fn round_trip(signer: &Signer, signing: &SigningKey, verifying: &VerifyingKey) {
+        let mut doc = Document::parse(DOCUMENT).unwrap();
+        let root = doc.first_child(None).unwrap();
+
+        signer.sign(&mut doc, &root, signing).unwrap();
+
+        let output = doc.to_string();
+        let doc = Document::parse(&output).unwrap();
+        let root = doc.first_child(None).unwrap();
+
+        let signature = doc
+            .children(Some(&root))
+            .copied()
+            .find(|obj| is_dsig(&doc, obj, "Signature"))
+            .unwrap();
+
+        let signature = Signature::parse(&doc, &signature).unwrap();
+
+        let nodes = signature.verify(&doc, verifying).unwrap();
+
+        assert_eq!(nodes.len(), signature.references().len());
+
+        // modified data.
+        let tampered = output.replace("1 &amp; 2", "1 &amp; 3");
+        assert_ne!(tampered, output);
+
+        let doc = Document::parse(&tampered).unwrap();
+
+        assert!(matches!(
+            signature.verify(&doc, verifying),
+            Err(Error::DOMException(ExceptionCode::WRONG_DOCUMENT_ERR))
+        ));
+
+        let root = doc.first_child(None).unwrap();
+        let element = doc
+            .children(Some(&root))
+            .copied()
+            .find(|obj| is_dsig(&doc, obj, "Signature"))
+            .unwrap();
+
+        assert!(matches!(
+            Signature::parse(&doc, &element)
+                .unwrap()
+                .verify(&doc, verifying),
+            Err(Error::DigestMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_hmac() {
+        let signer = Signer::new(SignatureMethod::HmacSha256).reference(
+            Reference::new("#r1", DigestMethod::Sha256)
+                .transform(Transform::EnvelopedSignature)
+                .transform(Transform::Canonicalize(
+                    Canonicalizer::new(C14nMethod::Exclusive).inclusive_namespaces(["app"]),
+                )),
+        );
+
+        let key = b"secret".to_vec();
+
+        round_trip(
+            &signer,
+            &SigningKey::Hmac(key.clone()),
+            &VerifyingKey::Hmac(key),
+        );
+
+        // wrong key.
+        let mut doc = Document::parse(DOCUMENT).unwrap();
+        let root = doc.first_child(None).unwrap();
+
+        let signature = signer
+            .sign(&mut doc, &root, &SigningKey::Hmac(b"secret".to_vec()))
+            .unwrap();
+
+        assert!(matches!(
+            signature.verify(&doc, &VerifyingKey::Hmac(b"other".to_vec())),
+            Err(Error::SignatureMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_ecdsa() {
+        let key = P384SigningKey::from_slice(&[7; 48]).unwrap();
+
+        let signer = Signer::new(SignatureMethod::EcdsaSha384)
+            .canonicalization(Canonicalizer::new(C14nMethod::Canonical10))
+            .reference(
+                Reference::new("", DigestMethod::Sha512).transform(Transform::EnvelopedSignature),
+            );
+
+        round_trip(
+            &signer,
+            &SigningKey::P384(key.clone()),
+            &VerifyingKey::P384(*key.verifying_key()),
+        );
+
+        let key = P256SigningKey::from_slice(&[7; 32]).unwrap();
+
+        round_trip(
+            &Signer::new(SignatureMethod::EcdsaSha256).reference(
+                Reference::new("#r1", DigestMethod::Sha256)
+                    .transform(Transform::EnvelopedSignature),
+            ),
+            &SigningKey::P256(key.clone()),
+            &VerifyingKey::P256(*key.verifying_key()),
+        );
+
+        // the curve doesn't fit the digest of the signature method.
+        let mut doc = Document::parse(DOCUMENT).unwrap();
+        let root = doc.first_child(None).unwrap();
+
+        assert!(matches!(
+            signer.sign(&mut doc, &root, &SigningKey::P256(key.clone())),
+            Err(Error::Key(_))
+        ));
+
+        let signature = Signer::new(SignatureMethod::EcdsaSha256)
+            .reference(
+                Reference::new("", DigestMethod::Sha256).transform(Transform::EnvelopedSignature),
+            )
+            .sign(&mut doc, &root, &SigningKey::P256(key))
+            .unwrap();
+
+        let key = P384SigningKey::from_slice(&[7; 48]).unwrap();
+
+        assert!(matches!(
+            signature.verify(&doc, &VerifyingKey::P384(*key.verifying_key())),
+            Err(Error::Key(_))
+        ));
+    }
+
+    #[test]
+    fn test_wrapping() {
+        let signer = Signer::new(SignatureMethod::HmacSha256).reference(
+            Reference::new("#r1", DigestMethod::Sha256).transform(Transform::EnvelopedSignature),
+        );
+
+        let key = b"secret".to_vec();
+
+        let mut doc = Document::parse(DOCUMENT).unwrap();
+        let root = doc.first_child(None).unwrap();
+
+        let signature = signer
+            .sign(&mut doc, &root, &SigningKey::Hmac(key.clone()))
+            .unwrap();
+
+        assert_eq!(
+            signature
+                .verify(&doc, &VerifyingKey::Hmac(key.clone()))
+                .unwrap(),
+            [root]
+        );
+
+        // the signed element is wrapped in a new root with the same ID.
+        let output = crate::Serializer::new()
+            .xml_declaration(false)
+            .to_string(&doc, None)
+            .unwrap();
+        let wrapped = format!(
+            r#"<app:Response xmlns:app="urn:app" ID="r1">{}</app:Response>"#,
+            output
+        );
+        let doc = Document::parse(&wrapped).unwrap();
+
+        let element = doc
+            .get_elements_by_tag_name_ns(&DOMObject::default(), Some(DSIG_NAMESPACE), "Signature")
+            .next()
+            .unwrap();
+        let signature = Signature::parse(&doc, &element).unwrap();
+
+        assert!(matches!(
+            signature.verify(&doc, &VerifyingKey::Hmac(key)),
+            Err(Error::MalformedSignature(reason)) if reason.contains("more than one")
+        ));
+    }
+
+    #[test]
+    fn test_rsa() {
+        let key = RsaPrivateKey::new(&mut rand_core::OsRng, 1024).unwrap();
+
+        let signer = Signer::new(SignatureMethod::RsaSha256)
+            .prefix("")
+            .reference(
+                Reference::new("", DigestMethod::Sha256).transform(Transform::EnvelopedSignature),
+            );
+
+        round_trip(
+            &signer,
+            &SigningKey::Rsa(key.clone()),
+            &VerifyingKey::Rsa(key.to_public_key()),
+        );
+    }
+
+    #[test]
+    fn test_digest() {
+        // SHA-256 of the canonical form of `<a></a>`.
+        let doc = Document::parse("<a/>").unwrap();
+        let reference = Reference::new("", DigestMethod::Sha256);
+
+        assert_eq!(
+            STANDARD.encode(
+                reference
+                    .digest(&doc, &DOMObject::default(), &DOMObject::default())
+                    .unwrap()
+            ),
+            STANDARD.encode(Sha256::digest(b"<a></a>"))
+        );
+
+        assert_eq!(
+            DigestMethod::from_algorithm(DigestMethod::Sha384.algorithm()),
+            Some(DigestMethod::Sha384)
+        );
+        assert_eq!(
+            SignatureMethod::from_algorithm(SignatureMethod::EcdsaSha512.algorithm()),
+            Some(SignatureMethod::EcdsaSha512)
+        );
+    }
+}
@@ -0,0 +1,340 @@
+//! Lookup of elements by tag name and by ID.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{DOMObject, Document, NodeType, XML_NAMESPACE};
+
+/// The attributes of type ID of one document.
+#[derive(Debug, Default)]
+pub(crate) struct IdIndex {
+    /// The `(element, attribute)` qualified names declared of type ID by the DTD.
+    declared: BTreeSet<(String, String)>,
+    /// The ID attributes attached to an element, by value.
+    values: BTreeMap<String, Vec<DOMObject>>,
+}
+
+impl<'a> Document<'a> {
+    /// Returns the elements of the subtree of `root` with the qualified name `name`,
+    /// in document order, `root` itself excluded. `"*"` matches all elements.
+    ///
+    /// Use [`DOMObject::default`] as `root` to search the whole document.
+    pub fn get_elements_by_tag_name<'d>(
+        &'d self,
+        root: &DOMObject,
+        name: &str,
+    ) -> Elements<'d, 'a> {
+        let test = match name {
+            "*" => TagTest::Any,
+            name => TagTest::Name(name.to_owned()),
+        };
+
+        Elements::new(self, root, test)
+    }
+
+    /// Returns the elements of the subtree of `root` with the namespace URI and local name,
+    /// in document order, `root` itself excluded. `"*"` matches all namespaces, or all local names.
+    pub fn get_elements_by_tag_name_ns<'d>(
+        &'d self,
+        root: &DOMObject,
+        namespace_uri: Option<&str>,
+        local_name: &str,
+    ) -> Elements<'d, 'a> {
+        let test = TagTest::NameNS {
+            namespace_uri: match namespace_uri {
+                Some("*") => None,
+                namespace_uri => Some(namespace_uri.map(str::to_owned)),
+            },
+            local_name: Some(local_name.to_owned()).filter(|name| name != "*"),
+        };
+
+        Elements::new(self, root, test)
+    }
+
+    /// Returns the element of the document tree with an ID attribute of value `id`.
+    ///
+    /// The ID attributes are the ones declared of type ID by the DTD and `xml:id`.
+    /// If several elements have the same ID, the first one in document order is returned.
+    pub fn get_element_by_id(&self, id: &str) -> Option<DOMObject> {
+        let owners = self
+            .ids
+            .values
+            .get(id)?
+            .iter()
+            .filter_map(|attr| self.owner_element(attr))
+            .filter(|el| self.is_in_tree(el))
+            .collect::<Vec<_>>();
+
+        match owners.as_slice() {
+            [] => None,
+            [owner] => Some(*owner),
+            _ => self
+                .get_elements_by_tag_name(&DOMObject::default(), "*")
+                .find(|el| owners.contains(el)),
+        }
+    }
+
+    /// Returns true if this attribute is of type ID, because the DTD declares it
+    /// for the name of its owner element or because it is `xml:id`.
+    pub fn is_id(&self, attr: &DOMObject) -> bool {
+        if attr.node_type() != NodeType::Attribute {
+            return false;
+        }
+
+        let Some(node) = self.attr(attr) else {
+            return false;
+        };
+
+        if node.namespace_uri() == Some(XML_NAMESPACE) && node.name().local_part == "id" {
+            return true;
+        }
+
+        let Some(el) = self
+            .owner_element(attr)
+            .and_then(|owner| self.element(&owner))
+        else {
+            return false;
+        };
+
+        !self.ids.declared.is_empty()
+            && self
+                .ids
+                .declared
+                .contains(&(el.tag().to_string(), node.name().to_string()))
+    }
+
+    /// Declare the attributes `attr` of the elements `element` of type ID,
+    /// this only applies to attributes attached afterwards.
+    pub(crate) fn declare_id_attribute(&mut self, element: &str, attr: &str) {
+        self.ids
+            .declared
+            .insert((element.to_owned(), attr.to_owned()));
+    }
+
+    /// Add `attr` to the ID index if it is an ID attribute, call after it is attached
+    /// or its value is changed.
+    pub(crate) fn index_id(&mut self, attr: &DOMObject) {
+        if !self.is_id(attr) {
+            return;
+        }
+
+        if let Some(value) = self.attr(attr).map(|attr| attr.value().to_owned()) {
+            self.ids.values.entry(value).or_default().push(*attr);
+        }
+    }
+
+    /// Remove `attr` from the ID index, call before it is detached or its value is changed.
+    pub(crate) fn unindex_id(&mut self, attr: &DOMObject) {
+        let Some(value) = self.attr(attr).map(|attr| attr.value().to_owned()) else {
+            return;
+        };
+
+        if let Some(attrs) = self.ids.values.get_mut(&value) {
+            attrs.retain(|obj| obj != attr);
+
+            if attrs.is_empty() {
+                self.ids.values.remove(&value);
+            }
+        }
+    }
+
+    /// Remove the attributes released by [`gc`](Self::gc) from the ID index.
+    pub(crate) fn prune_ids(&mut self) {
+        let mut values = std::mem::take(&mut self.ids.values);
+
+        values.retain(|_, attrs| {
+            attrs.retain(|attr| self.attr(attr).is_some());
+            !attrs.is_empty()
+        });
+
+        self.ids.values = values;
+    }
+
+    /// Returns true if `object` is a descendant of the `Document` node.
+    fn is_in_tree(&self, object: &DOMObject) -> bool {
+        let mut current = *object;
+
+        while let Some(parent) = self.parent_node(&current) {
+            current = parent;
+        }
+
+        current.node_type() == NodeType::Document
+    }
+}
+
+/// The names matched by [`Elements`].
+#[derive(Debug, Clone)]
+enum TagTest {
+    Any,
+    Name(String),
+    /// `None` matches any namespace, or any local name.
+    NameNS {
+        namespace_uri: Option<Option<String>>,
+        local_name: Option<String>,
+    },
+}
+
+/// An iterator over the elements of a subtree in document order, see
+/// [`get_elements_by_tag_name`](Document::get_elements_by_tag_name).
+pub struct Elements<'d, 'a> {
+    document: &'d Document<'a>,
+    test: TagTest,
+    /// The nodes to visit, the next one is the last.
+    stack: Vec<DOMObject>,
+}
+
+impl<'d, 'a> Elements<'d, 'a> {
+    fn new(document: &'d Document<'a>, root: &DOMObject, test: TagTest) -> Self {
+        let stack = document.children(Some(root)).rev().copied().collect();
+
+        Self {
+            document,
+            test,
+            stack,
+        }
+    }
+
+    fn matches(&self, element: &DOMObject) -> bool {
+        let Some(el) = self.document.element(element) else {
+            return false;
+        };
+
+        match &self.test {
+            TagTest::Any => true,
+            TagTest::Name(name) => el.tag().matches(name),
+            TagTest::NameNS {
+                namespace_uri,
+                local_name,
+            } => {
+                namespace_uri
+                    .as_ref()
+                    .is_none_or(|uri| el.namespace_uri() == uri.as_deref())
+                    && local_name
+                        .as_ref()
+                        .is_none_or(|name| el.tag().local_part == name.as_str())
+            }
+        }
+    }
+}
+
+impl Iterator for Elements<'_, '_> {
+    type Item = DOMObject;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            if !matches!(
+                node.node_type(),
+                NodeType::Element | NodeType::EntityReference
+            ) {
+                continue;
+            }
+
+            let children = self.document.children(Some(&node)).rev();
+            self.stack.extend(children.copied());
+
+            if node.node_type() == NodeType::Element && self.matches(&node) {
+                return Some(node);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DOMObject, Document, XML_NAMESPACE};
+
+    const DOCUMENT: &str = r#"<!DOCTYPE doc [
+    <!ATTLIST item key ID #IMPLIED>
+]>
+<doc xmlns:x="urn:x">
+    <item key="a"><x:item key="b"/><item xml:id="c"/></item>
+    <x:other key="d"><item key="e"/></x:other>
+</doc>"#;
+
+    #[test]
+    fn test_tag_name() {
+        let doc = Document::parse(DOCUMENT).unwrap();
+        let root = DOMObject::default();
+
+        let keys = |elements: &mut dyn Iterator<Item = DOMObject>| {
+            elements
+                .map(|el| {
+                    doc.get_attribute(&el, "key")
+                        .or_else(|| doc.get_attribute(&el, "xml:id"))
+                        .unwrap_or_default()
+                        .to_owned()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            keys(&mut doc.get_elements_by_tag_name(&root, "item")),
+            ["a", "c", "e"]
+        );
+        assert_eq!(keys(&mut doc.get_elements_by_tag_name(&root, "*")).len(), 6);
+        assert_eq!(
+            keys(&mut doc.get_elements_by_tag_name_ns(&root, Some("urn:x"), "*")),
+            ["b", "d"]
+        );
+        assert_eq!(
+            keys(&mut doc.get_elements_by_tag_name_ns(&root, Some("*"), "item")),
+            ["a", "b", "c", "e"]
+        );
+        assert_eq!(
+            keys(&mut doc.get_elements_by_tag_name_ns(&root, None, "item")),
+            ["a", "c", "e"]
+        );
+
+        let first = doc.get_elements_by_tag_name(&root, "item").next().unwrap();
+
+        assert_eq!(
+            keys(&mut doc.get_elements_by_tag_name(&first, "*")),
+            ["b", "c"]
+        );
+    }
+
+    #[test]
+    fn test_id() {
+        let mut doc = Document::parse(DOCUMENT).unwrap();
+
+        let by_id = |doc: &Document<'_>, id: &str| {
+            doc.get_element_by_id(id)
+                .map(|el| doc.element(&el).unwrap().tag().to_string())
+        };
+
+        assert_eq!(by_id(&doc, "a").as_deref(), Some("item"));
+        assert_eq!(by_id(&doc, "c").as_deref(), Some("item"));
+        assert_eq!(by_id(&doc, "e").as_deref(), Some("item"));
+        // not declared for `x:item` and `x:other`.
+        assert_eq!(by_id(&doc, "b"), None);
+        assert_eq!(by_id(&doc, "d"), None);
+
+        let a = doc.get_element_by_id("a").unwrap();
+
+        doc.set_attribute(&a, "key", "renamed").unwrap();
+        assert_eq!(by_id(&doc, "a"), None);
+        assert_eq!(doc.get_element_by_id("renamed"), Some(a));
+
+        let e = doc.get_element_by_id("e").unwrap();
+
+        doc.remove_attribute(&e, "key");
+        assert_eq!(by_id(&doc, "e"), None);
+
+        doc.set_attribute_ns(&e, Some(XML_NAMESPACE), "xml:id", "renamed")
+            .unwrap();
+
+        // the first in document order.
+        assert_eq!(doc.get_element_by_id("renamed"), Some(a));
+
+        let parent = doc.parent_node(&a).unwrap();
+        doc.remove_child(Some(&parent), &a).unwrap();
+
+        assert_eq!(doc.get_element_by_id("renamed"), Some(e));
+        assert_eq!(by_id(&doc, "c"), None);
+
+        doc.gc();
+
+        assert_eq!(doc.get_element_by_id("renamed"), Some(e));
+    }
+}
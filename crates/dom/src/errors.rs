@@ -19,6 +19,14 @@ pub enum ExceptionCode {
     INVALID_ACCESS_ERR = 15,
 }
 
+/// Error code corresponds to DOM Level 2 Range `RangeExceptionCode`.
+#[allow(non_camel_case_types)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum RangeExceptionCode {
+    BAD_BOUNDARYPOINTS_ERR = 1,
+    INVALID_NODE_TYPE_ERR = 2,
+}
+
 /// Error type returns by this mod.
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -27,6 +35,36 @@ pub enum Error {
 
     #[error("DOMException: {0:?}")]
     DOMException(ExceptionCode),
+
+    #[error("RangeException: {0:?}")]
+    RangeException(RangeExceptionCode),
+
+    #[error(transparent)]
+    Parse(#[from] rexml_stax::Error),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Fmt(#[from] std::fmt::Error),
+
+    #[error("XPath: {0}")]
+    XPath(String),
+
+    #[error("Selector: {0}")]
+    Selector(String),
+
+    #[error("Malformed signature: {0}")]
+    MalformedSignature(String),
+
+    #[error("Digest of reference {0:?} doesn't match")]
+    DigestMismatch(String),
+
+    #[error("Signature value doesn't match")]
+    SignatureMismatch,
+
+    #[error("Invalid key: {0}")]
+    Key(String),
 }
 
 /// Result type returns by this mod.
@@ -0,0 +1,601 @@
+//! DOM Level 2 Events: [`EventListener`]s on nodes and the mutation events.
+//!
+//! Listeners get the document read-only. The mutation events are dispatched by the
+//! mutation methods of [`Document`] once the change is done, except `DOMNodeRemoved`
+//! which is dispatched while the node is still in the tree.
+
+use std::collections::BTreeMap;
+
+use crate::{DOMObject, Document};
+
+/// The phase of the propagation of a [`DOMEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventPhase {
+    /// The event goes down from the root to the parent of the target.
+    Capturing = 1,
+    /// The event is at its target.
+    AtTarget = 2,
+    /// The event goes up from the parent of the target to the root.
+    Bubbling = 3,
+}
+
+/// How an attribute changed, see [`DOMEvent::attr_change`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttrChange {
+    /// The value of the attribute changed, both the previous and the new value are set.
+    Modification = 1,
+    /// The attribute was added, only the new value is set.
+    Addition = 2,
+    /// The attribute was removed, only the previous value is set.
+    Removal = 3,
+}
+
+/// An event dispatched to a node, with the context of the mutation events.
+#[derive(Debug, Clone)]
+pub struct DOMEvent {
+    event_type: String,
+    bubbles: bool,
+    cancelable: bool,
+    target: DOMObject,
+    current_target: DOMObject,
+    event_phase: EventPhase,
+    propagation_stopped: bool,
+    default_prevented: bool,
+    related_node: Option<DOMObject>,
+    prev_value: Option<String>,
+    new_value: Option<String>,
+    attr_name: Option<String>,
+    attr_change: Option<AttrChange>,
+}
+
+impl DOMEvent {
+    /// Dispatched to the lowest common ancestor of the changes of a mutation,
+    /// after the other mutation events.
+    pub const SUBTREE_MODIFIED: &'static str = "DOMSubtreeModified";
+    /// Dispatched to a node inserted as a child, the related node is the parent.
+    pub const NODE_INSERTED: &'static str = "DOMNodeInserted";
+    /// Dispatched to a child node before it is removed, the related node is the parent.
+    pub const NODE_REMOVED: &'static str = "DOMNodeRemoved";
+    /// Dispatched to an element when one of its attributes is added, changed or
+    /// removed, the related node is the attribute.
+    pub const ATTR_MODIFIED: &'static str = "DOMAttrModified";
+    /// Dispatched to a character data node when its data is changed.
+    pub const CHARACTER_DATA_MODIFIED: &'static str = "DOMCharacterDataModified";
+
+    /// Create an event to dispatch with [`Document::dispatch_event`].
+    pub fn new<T>(event_type: T, bubbles: bool, cancelable: bool) -> Self
+    where
+        T: Into<String>,
+    {
+        Self {
+            event_type: event_type.into(),
+            bubbles,
+            cancelable,
+            target: DOMObject::default(),
+            current_target: DOMObject::default(),
+            event_phase: EventPhase::AtTarget,
+            propagation_stopped: false,
+            default_prevented: false,
+            related_node: None,
+            prev_value: None,
+            new_value: None,
+            attr_name: None,
+            attr_change: None,
+        }
+    }
+
+    /// Returns the name of the event, as `DOMNodeInserted`.
+    pub fn event_type(&self) -> &str {
+        &self.event_type
+    }
+
+    /// Returns true if the event goes through the bubbling phase.
+    pub fn bubbles(&self) -> bool {
+        self.bubbles
+    }
+
+    /// Returns true if the default action can be prevented.
+    pub fn cancelable(&self) -> bool {
+        self.cancelable
+    }
+
+    /// Returns the node to which the event was dispatched.
+    pub fn target(&self) -> DOMObject {
+        self.target
+    }
+
+    /// Returns the node whose listeners are called.
+    pub fn current_target(&self) -> DOMObject {
+        self.current_target
+    }
+
+    /// Returns the phase of the propagation.
+    pub fn event_phase(&self) -> EventPhase {
+        self.event_phase
+    }
+
+    /// Stop the propagation once the listeners of the current target are called.
+    pub fn stop_propagation(&mut self) {
+        self.propagation_stopped = true;
+    }
+
+    /// Cancel the default action, if the event is cancelable.
+    pub fn prevent_default(&mut self) {
+        if self.cancelable {
+            self.default_prevented = true;
+        }
+    }
+
+    /// Returns true if [`prevent_default`](Self::prevent_default) canceled the default action.
+    pub fn default_prevented(&self) -> bool {
+        self.default_prevented
+    }
+
+    /// Returns the parent of an inserted or removed node, or the changed attribute.
+    pub fn related_node(&self) -> Option<DOMObject> {
+        self.related_node
+    }
+
+    /// Returns the value of the attribute or the data before the change.
+    pub fn prev_value(&self) -> Option<&str> {
+        self.prev_value.as_deref()
+    }
+
+    /// Returns the value of the attribute or the data after the change.
+    pub fn new_value(&self) -> Option<&str> {
+        self.new_value.as_deref()
+    }
+
+    /// Returns the qualified name of the changed attribute.
+    pub fn attr_name(&self) -> Option<&str> {
+        self.attr_name.as_deref()
+    }
+
+    /// Returns how the attribute changed.
+    pub fn attr_change(&self) -> Option<AttrChange> {
+        self.attr_change
+    }
+
+    /// Create a mutation event, which bubbles and is not cancelable.
+    fn mutation(event_type: &str, related_node: Option<DOMObject>) -> Self {
+        Self {
+            related_node,
+            ..Self::new(event_type, true, false)
+        }
+    }
+}
+
+/// A listener of the events dispatched to a node.
+pub trait EventListener {
+    /// Called for each event of the type the listener is registered for.
+    fn handle_event(&self, document: &Document<'_>, event: &mut DOMEvent);
+}
+
+impl<F> EventListener for F
+where
+    F: Fn(&Document<'_>, &mut DOMEvent),
+{
+    fn handle_event(&self, document: &Document<'_>, event: &mut DOMEvent) {
+        self(document, event)
+    }
+}
+
+/// The handle of a registered [`EventListener`], to remove it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EventListenerId(u64);
+
+/// One listener registered on a node.
+struct Registration {
+    id: EventListenerId,
+    event_type: String,
+    use_capture: bool,
+    listener: Box<dyn EventListener + Send + Sync>,
+}
+
+/// The event listeners of one document, by node.
+#[derive(Default)]
+pub(crate) struct EventListeners {
+    next_id: u64,
+    registrations: BTreeMap<DOMObject, Vec<Registration>>,
+}
+
+impl<'a> Document<'a> {
+    /// Register `listener` for the events of type `event_type` dispatched to `target`
+    /// or, if `use_capture` is true, to its descendants in the capturing phase.
+    ///
+    /// Use [`DOMObject::default`] as `target` to listen to the `Document` node.
+    pub fn add_event_listener<L>(
+        &mut self,
+        target: &DOMObject,
+        event_type: &str,
+        listener: L,
+        use_capture: bool,
+    ) -> EventListenerId
+    where
+        L: EventListener + Send + Sync + 'static,
+    {
+        let listeners = &mut self.event_listeners;
+
+        let id = EventListenerId(listeners.next_id);
+
+        listeners.next_id += 1;
+
+        listeners
+            .registrations
+            .entry(*target)
+            .or_default()
+            .push(Registration {
+                id,
+                event_type: event_type.to_owned(),
+                use_capture,
+                listener: Box::new(listener),
+            });
+
+        id
+    }
+
+    /// Remove a listener registered on `target` with the same event type and `use_capture`.
+    pub fn remove_event_listener(
+        &mut self,
+        target: &DOMObject,
+        event_type: &str,
+        listener: EventListenerId,
+        use_capture: bool,
+    ) {
+        let registrations = &mut self.event_listeners.registrations;
+
+        if let Some(listeners) = registrations.get_mut(target) {
+            listeners.retain(|registration| {
+                registration.id != listener
+                    || registration.event_type != event_type
+                    || registration.use_capture != use_capture
+            });
+
+            if listeners.is_empty() {
+                registrations.remove(target);
+            }
+        }
+    }
+
+    /// Dispatch `event` to `target`: the capturing listeners of its ancestors are called
+    /// from the root, then the listeners of `target`, then the other listeners of its
+    /// ancestors from the parent if the event bubbles.
+    ///
+    /// Returns false if a listener called [`prevent_default`](DOMEvent::prevent_default).
+    pub fn dispatch_event(&self, target: &DOMObject, mut event: DOMEvent) -> bool {
+        event.target = *target;
+        event.propagation_stopped = false;
+        event.default_prevented = false;
+
+        let mut ancestors = vec![];
+        let mut node = self.parent_node(target);
+
+        while let Some(parent) = node {
+            ancestors.push(parent);
+            node = self.parent_node(&parent);
+        }
+
+        let phases = ancestors
+            .iter()
+            .rev()
+            .map(|node| (*node, EventPhase::Capturing))
+            .chain([(*target, EventPhase::AtTarget)])
+            .chain(
+                ancestors
+                    .iter()
+                    .filter(|_| event.bubbles)
+                    .map(|node| (*node, EventPhase::Bubbling)),
+            )
+            .collect::<Vec<_>>();
+
+        for (node, phase) in phases {
+            let Some(listeners) = self.event_listeners.registrations.get(&node) else {
+                continue;
+            };
+
+            event.current_target = node;
+            event.event_phase = phase;
+
+            for registration in listeners {
+                if registration.event_type == event.event_type
+                    && registration.use_capture == (phase == EventPhase::Capturing)
+                {
+                    registration.listener.handle_event(self, &mut event);
+                }
+            }
+
+            if event.propagation_stopped {
+                break;
+            }
+        }
+
+        !event.default_prevented
+    }
+
+    /// Returns true if any listener is registered, the mutation events are only
+    /// created if so.
+    pub(crate) fn has_event_listeners(&self) -> bool {
+        !self.event_listeners.registrations.is_empty()
+    }
+
+    /// Dispatch `DOMSubtreeModified` to `node`.
+    pub(crate) fn subtree_modified(&self, node: &DOMObject) {
+        if self.has_event_listeners() {
+            self.dispatch_event(node, DOMEvent::mutation(DOMEvent::SUBTREE_MODIFIED, None));
+        }
+    }
+
+    /// Dispatch the mutation events of `child`, call after it is inserted.
+    pub(crate) fn node_inserted(&self, child: &DOMObject) {
+        if !self.has_event_listeners() {
+            return;
+        }
+
+        let parent = self.parent_node(child);
+
+        self.dispatch_event(child, DOMEvent::mutation(DOMEvent::NODE_INSERTED, parent));
+
+        if let Some(parent) = parent {
+            self.subtree_modified(&parent);
+        }
+    }
+
+    /// Dispatch `DOMNodeRemoved` to `child`, call before it is removed.
+    pub(crate) fn node_removed(&self, child: &DOMObject) {
+        if self.has_event_listeners() {
+            let parent = self.parent_node(child);
+
+            self.dispatch_event(child, DOMEvent::mutation(DOMEvent::NODE_REMOVED, parent));
+        }
+    }
+
+    /// Dispatch the mutation events of `attr` of `element`, call after the change.
+    ///
+    /// `prev_value` is the value before a [`AttrChange::Modification`].
+    pub(crate) fn attr_modified(
+        &self,
+        element: &DOMObject,
+        attr: &DOMObject,
+        change: AttrChange,
+        prev_value: Option<String>,
+    ) {
+        if !self.has_event_listeners() {
+            return;
+        }
+
+        let Some(node) = self.attr(attr) else {
+            return;
+        };
+
+        let value = Some(node.value().to_owned());
+
+        let (prev_value, new_value) = match change {
+            AttrChange::Modification => (prev_value, value),
+            AttrChange::Addition => (None, value),
+            AttrChange::Removal => (value, None),
+        };
+
+        let event = DOMEvent {
+            prev_value,
+            new_value,
+            attr_name: Some(node.name().to_string()),
+            attr_change: Some(change),
+            ..DOMEvent::mutation(DOMEvent::ATTR_MODIFIED, Some(*attr))
+        };
+
+        self.dispatch_event(element, event);
+        self.subtree_modified(element);
+    }
+
+    /// Dispatch the mutation events of the character data `node`, call after
+    /// its data is changed from `prev_value`.
+    pub(crate) fn character_data_modified(&self, node: &DOMObject, prev_value: String) {
+        let event = DOMEvent {
+            prev_value: Some(prev_value),
+            new_value: self.character_data(node).map(str::to_owned),
+            ..DOMEvent::mutation(DOMEvent::CHARACTER_DATA_MODIFIED, None)
+        };
+
+        self.dispatch_event(node, event);
+        self.subtree_modified(node);
+    }
+
+    /// Remove the listeners of the nodes released by [`gc`](Self::gc).
+    pub(crate) fn prune_event_listeners(&mut self) {
+        let mut registrations = std::mem::take(&mut self.event_listeners.registrations);
+
+        registrations.retain(|node, _| self.is_allocated(node));
+
+        self.event_listeners.registrations = registrations;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::NodeType;
+
+    fn name(document: &Document<'_>, node: &DOMObject) -> String {
+        match node.node_type() {
+            NodeType::Element => document.element(node).unwrap().tag().to_string(),
+            NodeType::Text => document.text(node).unwrap().data().to_owned(),
+            NodeType::Attribute => document.attr(node).unwrap().name().to_string(),
+            node_type => format!("{:?}", node_type),
+        }
+    }
+
+    /// Returns a listener pushing a description of the events to the log.
+    fn logger(
+        log: &Arc<Mutex<Vec<String>>>,
+        label: &'static str,
+    ) -> impl Fn(&Document<'_>, &mut DOMEvent) + Send + Sync + 'static {
+        let log = log.clone();
+
+        move |document, event| {
+            let mut entry = format!(
+                "{} {} {}",
+                label,
+                event.event_type(),
+                name(document, &event.target())
+            );
+
+            if let Some(related) = event.related_node() {
+                entry.push_str(&format!(" ({})", name(document, &related)));
+            }
+
+            if let Some(change) = event.attr_change() {
+                entry.push_str(&format!(" {:?}", change));
+            }
+
+            for value in [event.prev_value(), event.new_value()]
+                .into_iter()
+                .flatten()
+            {
+                entry.push_str(&format!(" {:?}", value));
+            }
+
+            log.lock().unwrap().push(entry);
+        }
+    }
+
+    fn take(log: &Arc<Mutex<Vec<String>>>) -> Vec<String> {
+        std::mem::take(&mut *log.lock().unwrap())
+    }
+
+    #[test]
+    fn test_send() {
+        fn assert_send<T: Send + Sync>() {}
+
+        assert_send::<Document<'_>>();
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let mut doc = Document::parse("<a><b><c/></b></a>").unwrap();
+        let root = DOMObject::default();
+        let a = doc.first_child(None).unwrap();
+        let b = doc.first_child(Some(&a)).unwrap();
+        let c = doc.first_child(Some(&b)).unwrap();
+
+        let log = Arc::new(Mutex::new(vec![]));
+
+        doc.add_event_listener(&root, "x", logger(&log, "capture"), true);
+        doc.add_event_listener(&a, "x", logger(&log, "capture"), true);
+        // capturing listeners of the target are not called.
+        doc.add_event_listener(&c, "x", logger(&log, "capture"), true);
+        doc.add_event_listener(&c, "x", logger(&log, "target"), false);
+        doc.add_event_listener(&b, "x", logger(&log, "bubble"), false);
+        doc.add_event_listener(&b, "y", logger(&log, "bubble"), false);
+
+        let bubble = doc.add_event_listener(&root, "x", logger(&log, "bubble"), false);
+
+        assert!(doc.dispatch_event(&c, DOMEvent::new("x", true, false)));
+        assert_eq!(
+            take(&log),
+            [
+                "capture x c",
+                "capture x c",
+                "target x c",
+                "bubble x c",
+                "bubble x c"
+            ]
+        );
+
+        doc.dispatch_event(&c, DOMEvent::new("x", false, false));
+        assert_eq!(take(&log), ["capture x c", "capture x c", "target x c"]);
+
+        doc.remove_event_listener(&root, "x", bubble, true);
+        doc.remove_event_listener(&root, "x", bubble, false);
+
+        let stop = doc.add_event_listener(
+            &b,
+            "x",
+            |_: &Document<'_>, event: &mut DOMEvent| {
+                event.stop_propagation();
+                event.prevent_default();
+            },
+            false,
+        );
+
+        assert!(!doc.dispatch_event(&c, DOMEvent::new("x", true, true)));
+        assert_eq!(
+            take(&log),
+            ["capture x c", "capture x c", "target x c", "bubble x c"]
+        );
+
+        doc.remove_event_listener(&b, "x", stop, false);
+
+        assert!(doc.dispatch_event(&c, DOMEvent::new("x", true, true)));
+        assert_eq!(take(&log).len(), 4);
+    }
+
+    #[test]
+    fn test_mutation_events() {
+        let mut doc = Document::parse("<a><b>text</b></a>").unwrap();
+        let a = doc.first_child(None).unwrap();
+        let b = doc.first_child(Some(&a)).unwrap();
+        let text = doc.first_child(Some(&b)).unwrap();
+
+        let log = Arc::new(Mutex::new(vec![]));
+
+        for event_type in [
+            DOMEvent::NODE_INSERTED,
+            DOMEvent::NODE_REMOVED,
+            DOMEvent::ATTR_MODIFIED,
+            DOMEvent::CHARACTER_DATA_MODIFIED,
+        ] {
+            doc.add_event_listener(&a, event_type, logger(&log, "a"), false);
+        }
+
+        doc.add_event_listener(&b, DOMEvent::SUBTREE_MODIFIED, logger(&log, "b"), false);
+
+        let c = doc.create_element("c").unwrap();
+
+        doc.append_child(Some(&b), c).unwrap();
+        assert_eq!(
+            take(&log),
+            ["a DOMNodeInserted c (b)", "b DOMSubtreeModified b"]
+        );
+
+        doc.set_attribute(&b, "k", "1").unwrap();
+        doc.set_attribute(&b, "k", "2").unwrap();
+        doc.remove_attribute(&b, "k");
+        assert_eq!(
+            take(&log),
+            [
+                "a DOMAttrModified b (k) Addition \"1\"",
+                "b DOMSubtreeModified b",
+                "a DOMAttrModified b (k) Modification \"1\" \"2\"",
+                "b DOMSubtreeModified b",
+                "a DOMAttrModified b (k) Removal \"2\"",
+                "b DOMSubtreeModified b",
+            ]
+        );
+
+        doc.replace_data(&text, 0, 1, "n").unwrap();
+        assert_eq!(
+            take(&log),
+            [
+                "a DOMCharacterDataModified next \"text\" \"next\"",
+                "b DOMSubtreeModified next",
+            ]
+        );
+
+        doc.remove_child(Some(&b), &c).unwrap();
+        assert_eq!(
+            take(&log),
+            ["a DOMNodeRemoved c (b)", "b DOMSubtreeModified b"]
+        );
+
+        // removed nodes are out of the propagation path.
+        doc.set_attribute(&c, "k", "1").unwrap();
+        assert!(take(&log).is_empty());
+
+        doc.remove_child(Some(&a), &b).unwrap();
+        doc.gc();
+
+        assert_eq!(doc.event_listeners.registrations.len(), 1);
+    }
+}
@@ -16,5 +16,42 @@ pub use qname::*;
 mod object;
 pub use object::*;
 
+mod slab;
+
 mod arena;
 pub use arena::*;
+
+mod namespace;
+pub use namespace::*;
+
+mod elements;
+pub use elements::*;
+
+mod traversal;
+pub use traversal::*;
+
+mod range;
+pub use range::*;
+
+mod events;
+pub use events::*;
+
+mod builder;
+pub use builder::*;
+
+mod serializer;
+pub use serializer::*;
+
+mod c14n;
+pub use c14n::*;
+
+mod xpath;
+pub use xpath::*;
+
+mod selector;
+pub use selector::*;
+
+#[cfg(feature = "dsig")]
+mod dsig;
+#[cfg(feature = "dsig")]
+pub use dsig::*;
@@ -0,0 +1,256 @@
+//! Namespace validation and in-scope namespace lookup.
+
+use crate::{DOMObject, Document, Error, ExceptionCode, NodeType, QName, Result};
+
+pub use rexml_stax::parser::{XMLNS_NAMESPACE, XML_NAMESPACE};
+
+/// Check the namespace URI of a new element or attribute against its qualified name,
+/// raises `NAMESPACE_ERR` for malformed combinations.
+pub(crate) fn check_namespace(
+    namespace_uri: Option<&str>,
+    name: &QName<'_>,
+    attribute: bool,
+) -> Result<()> {
+    let prefix = name.prefix.as_deref();
+
+    let is_xmlns = prefix == Some("xmlns") || (prefix.is_none() && name.local_part == "xmlns");
+
+    let valid = match (prefix, namespace_uri) {
+        (_, Some("")) => false,
+        (Some(_), None) => false,
+        (Some("xml"), Some(uri)) => uri == XML_NAMESPACE,
+        _ if is_xmlns => attribute && namespace_uri == Some(XMLNS_NAMESPACE),
+        (_, Some(XMLNS_NAMESPACE)) => false,
+        _ => true,
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::DOMException(ExceptionCode::NAMESPACE_ERR))
+    }
+}
+
+impl<'a> Document<'a> {
+    /// Returns the namespace URI bound to `prefix` in the scope of `object`,
+    /// use `None` for the default namespace.
+    pub fn lookup_namespace_uri(&self, object: &DOMObject, prefix: Option<&str>) -> Option<&str> {
+        match prefix {
+            Some("xml") => return Some(XML_NAMESPACE),
+            Some("xmlns") => return Some(XMLNS_NAMESPACE),
+            _ => {}
+        }
+
+        let mut element = self.scope_element(object);
+
+        while let Some(object) = element {
+            let el = self.element(&object)?;
+
+            if el.tag().prefix.as_deref() == prefix {
+                if let Some(uri) = el.namespace_uri() {
+                    return Some(uri);
+                }
+            }
+
+            if let Some(uri) = self
+                .namespace_declarations(&object)
+                .find(|(declared, _)| *declared == prefix)
+                .map(|(_, uri)| uri)
+            {
+                return Some(uri).filter(|uri| !uri.is_empty());
+            }
+
+            element = self.parent_element(&object);
+        }
+
+        None
+    }
+
+    /// Returns a prefix bound to `namespace_uri` in the scope of `object`.
+    ///
+    /// The default namespace is not considered.
+    pub fn lookup_prefix(&self, object: &DOMObject, namespace_uri: &str) -> Option<&str> {
+        let mut element = self.scope_element(object);
+
+        while let Some(current) = element {
+            let el = self.element(&current)?;
+
+            let candidates = el
+                .tag()
+                .prefix
+                .as_deref()
+                .filter(|_| el.namespace_uri() == Some(namespace_uri))
+                .into_iter()
+                .chain(
+                    self.namespace_declarations(&current)
+                        .filter(|(_, uri)| *uri == namespace_uri)
+                        .filter_map(|(prefix, _)| prefix),
+                );
+
+            for prefix in candidates {
+                // the prefix may be bound to another namespace by a descendant.
+                if self.lookup_namespace_uri(object, Some(prefix)) == Some(namespace_uri) {
+                    return Some(prefix);
+                }
+            }
+
+            element = self.parent_element(&current);
+        }
+
+        None
+    }
+
+    /// Returns true if `namespace_uri` is the default namespace in the scope of `object`.
+    pub fn is_default_namespace(&self, object: &DOMObject, namespace_uri: Option<&str>) -> bool {
+        self.lookup_namespace_uri(object, None) == namespace_uri.filter(|uri| !uri.is_empty())
+    }
+
+    /// Returns the element from which the namespace lookup of `object` starts.
+    fn scope_element(&self, object: &DOMObject) -> Option<DOMObject> {
+        match object.node_type() {
+            NodeType::Element => Some(*object),
+            NodeType::Document => self
+                .children(None)
+                .find(|obj| obj.node_type() == NodeType::Element)
+                .copied(),
+            NodeType::Attribute | NodeType::Namespace => self.owner_element(object),
+            NodeType::DocumentType | NodeType::Entity | NodeType::Notation => None,
+            _ => self.parent_element(object),
+        }
+    }
+
+    fn parent_element(&self, object: &DOMObject) -> Option<DOMObject> {
+        self.parent_node(object)
+            .filter(|parent| parent.node_type() == NodeType::Element)
+    }
+
+    /// Returns the `(prefix, namespace URI)` pairs declared on one element by `xmlns`
+    /// attributes and namespace nodes, `None` is the default namespace.
+    pub(crate) fn namespace_declarations<'s>(
+        &'s self,
+        element: &DOMObject,
+    ) -> impl Iterator<Item = (Option<&'s str>, &'s str)> + 's {
+        let attrs = self
+            .element(element)
+            .into_iter()
+            .flat_map(|el| el.attributes())
+            .filter_map(|attr| self.attr(attr))
+            .filter_map(
+                |attr| match (attr.name().prefix.as_deref(), &attr.name().local_part) {
+                    (Some("xmlns"), prefix) => Some((Some(prefix.as_ref()), attr.value())),
+                    (None, local_part) if local_part == "xmlns" => Some((None, attr.value())),
+                    _ => None,
+                },
+            );
+
+        let nss = self
+            .children(Some(element))
+            .filter(|obj| obj.node_type() == NodeType::Namespace)
+            .filter_map(|obj| self.ns(obj))
+            .map(|ns| {
+                (
+                    Some(ns.prefix()).filter(|prefix| !prefix.is_empty()),
+                    ns.href(),
+                )
+            });
+
+        attrs.chain(nss)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_namespace() {
+        let check = |uri, name: &str, attribute| {
+            check_namespace(uri, &QName::try_from(name).unwrap(), attribute).is_ok()
+        };
+
+        assert!(check(None, "a", false));
+        assert!(check(Some("urn:a"), "p:a", false));
+        assert!(!check(None, "p:a", false));
+        assert!(!check(Some(""), "a", false));
+        assert!(check(Some(XML_NAMESPACE), "xml:lang", true));
+        assert!(!check(Some("urn:a"), "xml:lang", true));
+        assert!(check(Some(XMLNS_NAMESPACE), "xmlns:p", true));
+        assert!(check(Some(XMLNS_NAMESPACE), "xmlns", true));
+        assert!(!check(Some("urn:a"), "xmlns", true));
+        assert!(!check(Some(XMLNS_NAMESPACE), "xmlns:p", false));
+        assert!(!check(Some(XMLNS_NAMESPACE), "p:a", true));
+    }
+
+    #[test]
+    fn test_lookup() {
+        let mut doc = Document::default();
+
+        let root = doc.create_element_ns(Some("urn:a"), "a:root").unwrap();
+        let child = doc.create_element_ns(Some("urn:b"), "child").unwrap();
+        let leaf = doc.create_element("leaf").unwrap();
+        let text = doc.create_text("text").unwrap();
+
+        doc.set_attribute_ns(&root, Some(XMLNS_NAMESPACE), "xmlns:a", "urn:a")
+            .unwrap();
+        doc.set_attribute(&root, "xmlns:c", "urn:c").unwrap();
+        doc.set_attribute(&child, "xmlns", "urn:b").unwrap();
+        doc.set_attribute(&child, "xmlns:c", "urn:other").unwrap();
+
+        let ns = doc.create_ns("d", "urn:d").unwrap();
+        doc.append_child(Some(&child), ns).unwrap();
+
+        doc.append_child(Some(&leaf), text).unwrap();
+        doc.append_child(Some(&child), leaf).unwrap();
+        doc.append_child(Some(&root), child).unwrap();
+        doc.append_child(None, root).unwrap();
+
+        assert_eq!(doc.lookup_namespace_uri(&text, Some("a")), Some("urn:a"));
+        assert_eq!(doc.lookup_namespace_uri(&text, None), Some("urn:b"));
+        assert_eq!(
+            doc.lookup_namespace_uri(&text, Some("c")),
+            Some("urn:other")
+        );
+        assert_eq!(doc.lookup_namespace_uri(&text, Some("d")), Some("urn:d"));
+        assert_eq!(doc.lookup_namespace_uri(&root, Some("c")), Some("urn:c"));
+        assert_eq!(doc.lookup_namespace_uri(&root, None), None);
+        assert_eq!(
+            doc.lookup_namespace_uri(&DOMObject::default(), Some("a")),
+            Some("urn:a")
+        );
+        assert_eq!(doc.lookup_namespace_uri(&leaf, Some("x")), None);
+
+        assert_eq!(doc.lookup_prefix(&leaf, "urn:a"), Some("a"));
+        assert_eq!(doc.lookup_prefix(&leaf, "urn:c"), None);
+        assert_eq!(doc.lookup_prefix(&root, "urn:c"), Some("c"));
+        assert_eq!(doc.lookup_prefix(&leaf, "urn:b"), None);
+
+        assert!(doc.is_default_namespace(&leaf, Some("urn:b")));
+        assert!(doc.is_default_namespace(&root, None));
+        assert!(!doc.is_default_namespace(&root, Some("urn:a")));
+
+        assert_eq!(doc.element(&child).unwrap().namespace_uri(), Some("urn:b"));
+        assert_eq!(doc.element(&leaf).unwrap().namespace_uri(), None);
+
+        assert!(matches!(
+            doc.create_element_ns(None::<&str>, "p:a"),
+            Err(Error::DOMException(ExceptionCode::NAMESPACE_ERR))
+        ));
+        assert!(matches!(
+            doc.create_attr_ns(Some("urn:a"), "xmlns:p", ""),
+            Err(Error::DOMException(ExceptionCode::NAMESPACE_ERR))
+        ));
+        assert!(matches!(
+            doc.set_attribute_ns(&root, Some("urn:a"), "xml:lang", "en"),
+            Err(Error::DOMException(ExceptionCode::NAMESPACE_ERR))
+        ));
+
+        let attr = doc
+            .create_attr_ns(Some(XML_NAMESPACE), "xml:lang", "en")
+            .unwrap();
+
+        assert_eq!(
+            doc.attr(&attr).unwrap().namespace_uri(),
+            Some(XML_NAMESPACE)
+        );
+    }
+}
@@ -44,8 +44,12 @@ impl NodeType {
 /// A reference to a node of one `Document`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct DOMObject {
+    /// The id of the document to which this node belongs.
+    document: u32,
     /// The reference id of the memory manager of the document to which this node belongs.
     id: u32,
+    /// The generation of the slot `id`, a freed node never matches a new one in the same slot.
+    generation: u32,
     /// The [`node_type`](NodeType) of this node.
     node_type: NodeType,
 }
@@ -53,21 +57,36 @@ pub struct DOMObject {
 impl Default for DOMObject {
     fn default() -> Self {
         Self {
+            document: 0,
             id: 0,
+            generation: 0,
             node_type: NodeType::Document,
         }
     }
 }
 
 impl DOMObject {
-    #[allow(unused)]
-    pub(crate) fn new(id: usize, node_type: NodeType) -> Self {
+    pub(crate) fn new(document: u32, id: usize, generation: u32, node_type: NodeType) -> Self {
         Self {
+            document,
             id: id as u32,
+            generation,
             node_type,
         }
     }
 
+    pub(crate) fn document(&self) -> u32 {
+        self.document
+    }
+
+    pub(crate) fn id(&self) -> usize {
+        self.id as usize
+    }
+
+    pub(crate) fn generation(&self) -> u32 {
+        self.generation
+    }
+
     /// Returns the [`NodeType`] of the node referenced by this object.
     pub fn node_type(&self) -> NodeType {
         self.node_type
@@ -76,7 +95,7 @@ impl DOMObject {
 
 impl Display for DOMObject {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}({})", self.node_type, self.id,)
+        write!(f, "{:?}({}v{})", self.node_type, self.id, self.generation)
     }
 }
 
@@ -87,6 +106,6 @@ mod tests {
 
     #[test]
     fn test_node() {
-        println!("{}", DOMObject::new(1, NodeType::Attribute));
+        println!("{}", DOMObject::new(1, 1, 0, NodeType::Attribute));
     }
 }
@@ -8,9 +8,9 @@ use nom::*;
 fn nc_name(value: &str) -> IResult<&str, &str> {
     let (input, _) = satisfy(is_name_start_char)(value)?;
 
-    let (input, body) = take_while(is_name_char)(input)?;
+    let (input, _) = take_while(is_name_char)(input)?;
 
-    let (name, _) = value.split_at(1 + body.len());
+    let (name, _) = value.split_at(value.len() - input.len());
 
     Ok((input, name))
 }
@@ -54,19 +54,19 @@ fn parse_qname(input: &str) -> IResult<&str, QName<'_>> {
 
     let (input, local_part) = nc_name(input)?;
 
-    return Ok((
+    Ok((
         input,
         QName {
             prefix: Some(prefix_or_local_part.into()),
             local_part: local_part.into(),
         },
-    ));
+    ))
 }
 
 /// A [`QName`], or qualified name, is the fully qualified name of an element, attribute, or identifier in an XML document.
 ///
 /// [`QName`]: https://www.wikiwand.com/en/articles/QName
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct QName<'a> {
     /// The prefix name of qualified name.
     pub prefix: Option<Cow<'a, str>>,
@@ -88,6 +88,16 @@ impl<'a> TryFrom<&'a str> for QName<'a> {
     }
 }
 
+impl<'a> TryFrom<Cow<'a, str>> for QName<'a> {
+    type Error = crate::Error;
+    fn try_from(value: Cow<'a, str>) -> std::result::Result<Self, Self::Error> {
+        match value {
+            Cow::Borrowed(value) => value.try_into(),
+            Cow::Owned(value) => QName::try_from(value.as_str()).map(QName::into_owned),
+        }
+    }
+}
+
 impl<'a> Display for QName<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if let Some(prefix) = &self.prefix {
@@ -99,6 +109,17 @@ impl<'a> Display for QName<'a> {
 }
 
 impl<'a> QName<'a> {
+    /// Returns true if the qualified name is `name`, without formatting it.
+    pub(crate) fn matches(&self, name: &str) -> bool {
+        match (&self.prefix, name.split_once(':')) {
+            (Some(prefix), Some((name_prefix, local_part))) => {
+                prefix == name_prefix && self.local_part == local_part
+            }
+            (None, None) => self.local_part == name,
+            _ => false,
+        }
+    }
+
     /// Returns a new owning QName from the given existing one.
     pub fn into_owned(self) -> QName<'static> {
         QName::<'static> {
@@ -0,0 +1,1318 @@
+//! DOM Level 2 Range: [`Range`].
+//!
+//! Like the position of a [`DOMNodeIterator`](crate::DOMNodeIterator), the boundary
+//! points of a range are stored by the document, which moves them as nodes are inserted
+//! and removed and as character data is replaced, split or merged.
+
+use std::{cmp::Ordering, sync::Arc};
+
+use crate::{
+    arena::is_child_node, slab::Handles, traversal::is_inclusive_ancestor, DOMObject, Document,
+    Error, ExceptionCode, NodeType, RangeExceptionCode, Result,
+};
+
+/// A boundary point of a range.
+///
+/// The offset is an index in the child nodes of `node`, or in the characters of its
+/// data for the character data nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Boundary {
+    node: DOMObject,
+    offset: usize,
+}
+
+/// The boundary points of one [`Range`], stored by the document.
+#[derive(Debug, Clone, Copy)]
+struct RangeState {
+    start: Boundary,
+    end: Boundary,
+}
+
+/// The boundary points of the live [`Range`]s of one document.
+#[derive(Debug, Default)]
+pub(crate) struct Ranges {
+    slots: Handles<RangeState>,
+}
+
+/// The boundary points compared by [`Range::compare_boundary_points`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareHow {
+    /// The start of this range to the start of the source range.
+    StartToStart,
+    /// The end of this range to the start of the source range.
+    StartToEnd,
+    /// The end of this range to the end of the source range.
+    EndToEnd,
+    /// The start of this range to the end of the source range.
+    EndToStart,
+}
+
+/// A contiguous part of the tree between two boundary points, as a selection of an editor.
+///
+/// The boundary points are kept by the document, and moved when the tree is mutated
+/// so the range keeps selecting the same content. They are released by
+/// [`detach`](Self::detach) or when the range is dropped.
+pub struct Range {
+    document: u32,
+    slot: usize,
+    /// Frees the slot of the boundary points when dropped.
+    _handle: Arc<()>,
+}
+
+impl Range {
+    /// Returns the node of the start boundary point.
+    pub fn start_container(&self, document: &Document<'_>) -> Result<DOMObject> {
+        Ok(self.state(document)?.start.node)
+    }
+
+    /// Returns the offset of the start boundary point.
+    pub fn start_offset(&self, document: &Document<'_>) -> Result<usize> {
+        Ok(self.state(document)?.start.offset)
+    }
+
+    /// Returns the node of the end boundary point.
+    pub fn end_container(&self, document: &Document<'_>) -> Result<DOMObject> {
+        Ok(self.state(document)?.end.node)
+    }
+
+    /// Returns the offset of the end boundary point.
+    pub fn end_offset(&self, document: &Document<'_>) -> Result<usize> {
+        Ok(self.state(document)?.end.offset)
+    }
+
+    /// Returns true if the start and end boundary points are the same.
+    pub fn collapsed(&self, document: &Document<'_>) -> Result<bool> {
+        let state = self.state(document)?;
+
+        Ok(state.start == state.end)
+    }
+
+    /// Returns the deepest node which contains both boundary points.
+    pub fn common_ancestor_container(&self, document: &Document<'_>) -> Result<DOMObject> {
+        let state = self.state(document)?;
+
+        Ok(common_ancestor(
+            document,
+            &state.start.node,
+            &state.end.node,
+        ))
+    }
+
+    /// Set the start boundary point, the end is moved to it if it was before or in another tree.
+    ///
+    /// Raises `INVALID_NODE_TYPE_ERR` if `node` or one of its ancestors is not a child node
+    /// or is a `DocumentType`, and `INDEX_SIZE_ERR` if `offset` is greater than its length.
+    pub fn set_start(
+        &self,
+        document: &mut Document<'_>,
+        node: &DOMObject,
+        offset: usize,
+    ) -> Result<()> {
+        let boundary = boundary(document, node, offset)?;
+        let mut state = self.state(document)?;
+
+        if !matches!(
+            compare(document, &boundary, &state.end),
+            Some(Ordering::Less | Ordering::Equal)
+        ) {
+            state.end = boundary;
+        }
+
+        state.start = boundary;
+
+        self.set_state(document, state);
+
+        Ok(())
+    }
+
+    /// Set the end boundary point, the start is moved to it if it was after or in another tree.
+    pub fn set_end(
+        &self,
+        document: &mut Document<'_>,
+        node: &DOMObject,
+        offset: usize,
+    ) -> Result<()> {
+        let boundary = boundary(document, node, offset)?;
+        let mut state = self.state(document)?;
+
+        if !matches!(
+            compare(document, &boundary, &state.start),
+            Some(Ordering::Greater | Ordering::Equal)
+        ) {
+            state.start = boundary;
+        }
+
+        state.end = boundary;
+
+        self.set_state(document, state);
+
+        Ok(())
+    }
+
+    /// Set the start boundary point before `node`.
+    pub fn set_start_before(&self, document: &mut Document<'_>, node: &DOMObject) -> Result<()> {
+        let parent = parent(document, node)?;
+
+        self.set_start(document, &parent, index(document, node))
+    }
+
+    /// Set the start boundary point after `node`.
+    pub fn set_start_after(&self, document: &mut Document<'_>, node: &DOMObject) -> Result<()> {
+        let parent = parent(document, node)?;
+
+        self.set_start(document, &parent, index(document, node) + 1)
+    }
+
+    /// Set the end boundary point before `node`.
+    pub fn set_end_before(&self, document: &mut Document<'_>, node: &DOMObject) -> Result<()> {
+        let parent = parent(document, node)?;
+
+        self.set_end(document, &parent, index(document, node))
+    }
+
+    /// Set the end boundary point after `node`.
+    pub fn set_end_after(&self, document: &mut Document<'_>, node: &DOMObject) -> Result<()> {
+        let parent = parent(document, node)?;
+
+        self.set_end(document, &parent, index(document, node) + 1)
+    }
+
+    /// Move one boundary point to the other, to the start if `to_start` is true.
+    pub fn collapse(&self, document: &mut Document<'_>, to_start: bool) -> Result<()> {
+        let mut state = self.state(document)?;
+
+        if to_start {
+            state.end = state.start;
+        } else {
+            state.start = state.end;
+        }
+
+        self.set_state(document, state);
+
+        Ok(())
+    }
+
+    /// Select `node` and its content, the boundary points are set in its parent.
+    pub fn select_node(&self, document: &mut Document<'_>, node: &DOMObject) -> Result<()> {
+        self.state(document)?;
+
+        let parent = parent(document, node)?;
+        let index = index(document, node);
+
+        let start = boundary(document, &parent, index)?;
+        let end = boundary(document, &parent, index + 1)?;
+
+        self.set_state(document, RangeState { start, end });
+
+        Ok(())
+    }
+
+    /// Select the content of `node`.
+    pub fn select_node_contents(
+        &self,
+        document: &mut Document<'_>,
+        node: &DOMObject,
+    ) -> Result<()> {
+        self.state(document)?;
+
+        let start = boundary(document, node, 0)?;
+        let end = boundary(document, node, length(document, node))?;
+
+        self.set_state(document, RangeState { start, end });
+
+        Ok(())
+    }
+
+    /// Returns the position of one boundary point of this range relative to one
+    /// of `source`, see [`CompareHow`].
+    ///
+    /// Raises `WRONG_DOCUMENT_ERR` if the ranges are not in the same tree.
+    pub fn compare_boundary_points(
+        &self,
+        document: &Document<'_>,
+        how: CompareHow,
+        source: &Range,
+    ) -> Result<Ordering> {
+        let this = self.state(document)?;
+        let source = source.state(document)?;
+
+        let (a, b) = match how {
+            CompareHow::StartToStart => (this.start, source.start),
+            CompareHow::StartToEnd => (this.end, source.start),
+            CompareHow::EndToEnd => (this.end, source.end),
+            CompareHow::EndToStart => (this.start, source.end),
+        };
+
+        compare(document, &a, &b).ok_or(Error::DOMException(ExceptionCode::WRONG_DOCUMENT_ERR))
+    }
+
+    /// Remove the content of this range from the tree, the range is collapsed where it was.
+    ///
+    /// The partially selected character data nodes are cut, the partially selected
+    /// elements are kept with their remaining content.
+    pub fn delete_contents(&self, document: &mut Document<'_>) -> Result<()> {
+        let state = self.state(document)?;
+
+        if state.start == state.end {
+            return Ok(());
+        }
+
+        let point = collapse_point(document, &state);
+
+        delete(document, &state)?;
+
+        self.set_state(
+            document,
+            RangeState {
+                start: point,
+                end: point,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Move the content of this range out of the tree, the range is collapsed where it was.
+    ///
+    /// Returns the moved top-level nodes in document order, without parent; this crate has no
+    /// `DocumentFragment` node. The partially selected nodes are copied with the selected part
+    /// of their content.
+    pub fn extract_contents(&self, document: &mut Document<'_>) -> Result<Vec<DOMObject>> {
+        let state = self.state(document)?;
+
+        let point = collapse_point(document, &state);
+
+        let fragment = contents(document, &state, true)?;
+
+        self.set_state(
+            document,
+            RangeState {
+                start: point,
+                end: point,
+            },
+        );
+
+        Ok(fragment)
+    }
+
+    /// Returns copies of the top-level nodes of the content of this range in document order,
+    /// without parent, as [`extract_contents`](Self::extract_contents) returns them.
+    pub fn clone_contents(&self, document: &mut Document<'_>) -> Result<Vec<DOMObject>> {
+        let state = self.state(document)?;
+
+        contents(document, &state, false)
+    }
+
+    /// Insert `node` at the start of this range, a character data start container is split.
+    ///
+    /// If this range is collapsed, it is extended to include `node`.
+    pub fn insert_node(&self, document: &mut Document<'_>, node: DOMObject) -> Result<()> {
+        let start = self.state(document)?.start;
+        let is_text = matches!(start.node.node_type(), NodeType::Text | NodeType::CData);
+
+        if matches!(
+            start.node.node_type(),
+            NodeType::ProcessingInstruction | NodeType::Comment
+        ) || (is_text && document.parent_node(&start.node).is_none())
+            || start.node == node
+        {
+            return Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR));
+        }
+
+        let mut reference = match is_text {
+            true => Some(start.node),
+            false => child_nodes(document, &start.node)
+                .get(start.offset)
+                .copied(),
+        };
+
+        let parent = match reference {
+            Some(reference) => parent(document, &reference)?,
+            None => start.node,
+        };
+
+        document.insert_check(&parent, &node, None)?;
+
+        if !is_child_node(&node) {
+            return Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR));
+        }
+
+        if is_text {
+            reference = Some(document.split_text(&start.node, start.offset)?);
+        }
+
+        if reference == Some(node) {
+            reference = document.next_sibling(&node);
+        }
+
+        if let Some(old_parent) = document.parent_node(&node) {
+            document.remove_child(Some(&old_parent), &node)?;
+        }
+
+        let offset = match reference {
+            Some(reference) => index(document, &reference),
+            None => length(document, &parent),
+        } + 1;
+
+        document.insert_before(Some(&parent), node, reference.as_ref())?;
+
+        let mut state = self.state(document)?;
+
+        if state.start == state.end {
+            state.end = Boundary {
+                node: parent,
+                offset,
+            };
+
+            self.set_state(document, state);
+        }
+
+        Ok(())
+    }
+
+    /// Move the content of this range into `new_parent`, which replaces it in the tree
+    /// and is selected. The former children of `new_parent` are removed.
+    ///
+    /// Raises `BAD_BOUNDARYPOINTS_ERR` if this range partially selects a node which
+    /// is not a `Text` or `CDATASection`.
+    pub fn surround_contents(
+        &self,
+        document: &mut Document<'_>,
+        new_parent: DOMObject,
+    ) -> Result<()> {
+        let state = self.state(document)?;
+
+        for (node, other) in [
+            (state.start.node, state.end.node),
+            (state.end.node, state.start.node),
+        ] {
+            let mut ancestor = Some(node);
+
+            while let Some(current) = ancestor {
+                if is_inclusive_ancestor(document, &current, &other) {
+                    break;
+                }
+
+                if !matches!(current.node_type(), NodeType::Text | NodeType::CData) {
+                    return Err(Error::RangeException(
+                        RangeExceptionCode::BAD_BOUNDARYPOINTS_ERR,
+                    ));
+                }
+
+                ancestor = document.parent_node(&current);
+            }
+        }
+
+        if !is_child_node(&new_parent)
+            || matches!(
+                new_parent.node_type(),
+                NodeType::Document | NodeType::DocumentType | NodeType::DocumentFragment
+            )
+        {
+            return Err(Error::RangeException(
+                RangeExceptionCode::INVALID_NODE_TYPE_ERR,
+            ));
+        }
+
+        let fragment = self.extract_contents(document)?;
+
+        for child in child_nodes(document, &new_parent) {
+            document.remove_child(Some(&new_parent), &child)?;
+        }
+
+        self.insert_node(document, new_parent)?;
+
+        for node in fragment {
+            document.append_child(Some(&new_parent), node)?;
+        }
+
+        self.select_node(document, &new_parent)
+    }
+
+    /// Returns a new range with the same boundary points.
+    pub fn clone_range(&self, document: &mut Document<'_>) -> Result<Range> {
+        let state = self.state(document)?;
+
+        Ok(document.new_range(state))
+    }
+
+    /// Release the boundary points kept by `document`.
+    pub fn detach(self, document: &mut Document<'_>) {
+        if self.document == document.id() {
+            document.ranges.slots.remove(self.slot);
+        }
+    }
+
+    fn state(&self, document: &Document<'_>) -> Result<RangeState> {
+        if self.document != document.id() {
+            return Err(Error::DOMException(ExceptionCode::INVALID_STATE_ERR));
+        }
+
+        document
+            .ranges
+            .slots
+            .get(self.slot)
+            .copied()
+            .ok_or(Error::DOMException(ExceptionCode::INVALID_STATE_ERR))
+    }
+
+    fn set_state(&self, document: &mut Document<'_>, state: RangeState) {
+        if let Some(slot) = document.ranges.slots.get_mut(self.slot) {
+            *slot = state;
+        }
+    }
+}
+
+impl<'a> Document<'a> {
+    /// Create a [`Range`] collapsed at the start of the `Document` node.
+    pub fn create_range(&mut self) -> Range {
+        let start = Boundary {
+            node: DOMObject::default(),
+            offset: 0,
+        };
+
+        self.new_range(RangeState { start, end: start })
+    }
+
+    fn new_range(&mut self, state: RangeState) -> Range {
+        let (slot, handle) = self.ranges.slots.insert(state);
+
+        Range {
+            document: self.id(),
+            slot,
+            _handle: handle,
+        }
+    }
+
+    /// Apply `f` to the boundary points of the live ranges.
+    fn update_ranges<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&Self, &mut Boundary),
+    {
+        if self.ranges.slots.is_empty() {
+            return;
+        }
+
+        let mut slots = std::mem::take(&mut self.ranges.slots);
+
+        for state in slots.iter_mut() {
+            f(self, &mut state.start);
+            f(self, &mut state.end);
+        }
+
+        self.ranges.slots = slots;
+    }
+
+    /// Move the boundary points after `child` in its parent, call after `child` is inserted.
+    pub(crate) fn insert_into_ranges(&mut self, child: &DOMObject) {
+        let Some(parent) = self.parent_node(child) else {
+            return;
+        };
+
+        let mut position = None;
+
+        self.update_ranges(|document, boundary| {
+            let index = *position.get_or_insert_with(|| index(document, child));
+
+            if boundary.node == parent && boundary.offset > index {
+                boundary.offset += 1;
+            }
+        });
+    }
+
+    /// Move the boundary points in the subtree of `child` or after it in its parent,
+    /// call before `child` is removed.
+    pub(crate) fn remove_from_ranges(&mut self, child: &DOMObject) {
+        let Some(parent) = self.parent_node(child) else {
+            return;
+        };
+
+        let mut position = None;
+
+        self.update_ranges(|document, boundary| {
+            let index = *position.get_or_insert_with(|| index(document, child));
+
+            if is_inclusive_ancestor(document, child, &boundary.node) {
+                *boundary = Boundary {
+                    node: parent,
+                    offset: index,
+                };
+            } else if boundary.node == parent && boundary.offset > index {
+                boundary.offset -= 1;
+            }
+        });
+    }
+
+    /// Move the boundary points in the data of `node`, call after `count` characters
+    /// from `offset` are replaced with `length` characters.
+    pub(crate) fn replace_data_in_ranges(
+        &mut self,
+        node: &DOMObject,
+        offset: usize,
+        count: usize,
+        length: usize,
+    ) {
+        self.update_ranges(|_, boundary| {
+            if boundary.node != *node || boundary.offset <= offset {
+                return;
+            }
+
+            if boundary.offset <= offset + count {
+                boundary.offset = offset;
+            } else {
+                boundary.offset = boundary.offset + length - count;
+            }
+        });
+    }
+
+    /// Move the boundary points after `offset` in `node` to `new_node`, call after
+    /// `new_node` is inserted after `node` by [`split_text`](Self::split_text).
+    pub(crate) fn split_text_in_ranges(
+        &mut self,
+        node: &DOMObject,
+        offset: usize,
+        new_node: &DOMObject,
+    ) {
+        let parent = self.parent_node(node);
+        let index = index(self, node);
+
+        self.update_ranges(|_, boundary| {
+            if boundary.node == *node && boundary.offset > offset {
+                *boundary = Boundary {
+                    node: *new_node,
+                    offset: boundary.offset - offset,
+                };
+            } else if Some(boundary.node) == parent && boundary.offset == index + 1 {
+                boundary.offset += 1;
+            }
+        });
+    }
+
+    /// Move the boundary points in and before `merged` to `node`, call after the data
+    /// of `merged` is appended to the `length` characters of `node` by
+    /// [`normalize`](Self::normalize), before `merged` is removed.
+    pub(crate) fn merge_text_in_ranges(
+        &mut self,
+        node: &DOMObject,
+        length: usize,
+        merged: &DOMObject,
+    ) {
+        let parent = self.parent_node(merged);
+        let mut position = None;
+
+        self.update_ranges(|document, boundary| {
+            let index = *position.get_or_insert_with(|| index(document, merged));
+
+            if boundary.node == *merged {
+                *boundary = Boundary {
+                    node: *node,
+                    offset: boundary.offset + length,
+                };
+            } else if Some(boundary.node) == parent && boundary.offset == index {
+                *boundary = Boundary {
+                    node: *node,
+                    offset: length,
+                };
+            }
+        });
+    }
+
+    /// Collapse the ranges with a boundary point in a node released by [`gc`](Self::gc)
+    /// at the start of the `Document` node.
+    pub(crate) fn prune_ranges(&mut self) {
+        let mut slots = std::mem::take(&mut self.ranges.slots);
+
+        for state in slots.iter_mut() {
+            if !self.is_allocated(&state.start.node) || !self.is_allocated(&state.end.node) {
+                let start = Boundary {
+                    node: DOMObject::default(),
+                    offset: 0,
+                };
+
+                *state = RangeState { start, end: start };
+            }
+        }
+
+        self.ranges.slots = slots;
+    }
+}
+
+/// Returns a boundary point after checking `node` can contain one at `offset`.
+fn boundary(document: &Document<'_>, node: &DOMObject, offset: usize) -> Result<Boundary> {
+    if !document.is_allocated(node) {
+        return Err(Error::DOMException(if node.document() == document.id() {
+            ExceptionCode::NOT_FOUND_ERR
+        } else {
+            ExceptionCode::WRONG_DOCUMENT_ERR
+        }));
+    }
+
+    let mut ancestor = Some(*node);
+
+    while let Some(current) = ancestor {
+        if !is_child_node(&current) || current.node_type() == NodeType::DocumentType {
+            return Err(Error::RangeException(
+                RangeExceptionCode::INVALID_NODE_TYPE_ERR,
+            ));
+        }
+
+        ancestor = document.parent_node(&current);
+    }
+
+    if offset > length(document, node) {
+        return Err(Error::DOMException(ExceptionCode::INDEX_SIZE_ERR));
+    }
+
+    Ok(Boundary {
+        node: *node,
+        offset,
+    })
+}
+
+/// Returns the parent of `node`, a boundary point before or after it is in the parent.
+fn parent(document: &Document<'_>, node: &DOMObject) -> Result<DOMObject> {
+    document.parent_node(node).ok_or(Error::RangeException(
+        RangeExceptionCode::INVALID_NODE_TYPE_ERR,
+    ))
+}
+
+/// Returns true if `node` has character data, its offsets are in the data.
+fn is_character_data(node: &DOMObject) -> bool {
+    matches!(
+        node.node_type(),
+        NodeType::Text | NodeType::CData | NodeType::Comment | NodeType::ProcessingInstruction
+    )
+}
+
+/// Returns the child nodes of `node`, without its namespace declarations.
+fn child_nodes(document: &Document<'_>, node: &DOMObject) -> Vec<DOMObject> {
+    document
+        .children(Some(node))
+        .filter(|obj| is_child_node(obj))
+        .copied()
+        .collect()
+}
+
+/// Returns the index of `node` in the child nodes of its parent.
+fn index(document: &Document<'_>, node: &DOMObject) -> usize {
+    document
+        .parent_node(node)
+        .and_then(|parent| {
+            document
+                .children(Some(&parent))
+                .filter(|obj| is_child_node(obj))
+                .position(|obj| obj == node)
+        })
+        .unwrap_or_default()
+}
+
+/// Returns the greatest offset of a boundary point in `node`.
+fn length(document: &Document<'_>, node: &DOMObject) -> usize {
+    match document.character_data(node) {
+        Some(data) => data.chars().count(),
+        None => document
+            .children(Some(node))
+            .filter(|obj| is_child_node(obj))
+            .count(),
+    }
+}
+
+/// Returns the root of `node` and the indices of its inclusive ancestors, from the root.
+fn path(document: &Document<'_>, node: &DOMObject) -> (DOMObject, Vec<usize>) {
+    let mut path = vec![];
+    let mut node = *node;
+
+    while let Some(parent) = document.parent_node(&node) {
+        path.push(index(document, &node));
+        node = parent;
+    }
+
+    path.reverse();
+
+    (node, path)
+}
+
+/// Returns the position of `a` relative to `b` in document order, `None` if they
+/// are in different trees.
+///
+/// A boundary point is at the path of its node followed by its offset, so the
+/// points compare as their paths.
+fn compare(document: &Document<'_>, a: &Boundary, b: &Boundary) -> Option<Ordering> {
+    if a.node == b.node {
+        return Some(a.offset.cmp(&b.offset));
+    }
+
+    let (root_a, mut path_a) = path(document, &a.node);
+    let (root_b, mut path_b) = path(document, &b.node);
+
+    path_a.push(a.offset);
+    path_b.push(b.offset);
+
+    (root_a == root_b).then(|| path_a.cmp(&path_b))
+}
+
+/// Returns the deepest inclusive ancestor of `a` which is an inclusive ancestor of `b`.
+fn common_ancestor(document: &Document<'_>, a: &DOMObject, b: &DOMObject) -> DOMObject {
+    let mut node = *a;
+
+    while !is_inclusive_ancestor(document, &node, b) {
+        match document.parent_node(&node) {
+            Some(parent) => node = parent,
+            None => break,
+        }
+    }
+
+    node
+}
+
+/// Returns true if `node` and its whole content are in the range.
+fn contains(document: &Document<'_>, state: &RangeState, node: &DOMObject) -> bool {
+    let start = Boundary {
+        node: *node,
+        offset: 0,
+    };
+
+    let end = Boundary {
+        node: *node,
+        offset: length(document, node),
+    };
+
+    compare(document, &state.start, &start) == Some(Ordering::Less)
+        && compare(document, &end, &state.end) == Some(Ordering::Less)
+}
+
+/// Returns the boundary point where the range is collapsed once its content is removed.
+fn collapse_point(document: &Document<'_>, state: &RangeState) -> Boundary {
+    if is_inclusive_ancestor(document, &state.start.node, &state.end.node) {
+        return state.start;
+    }
+
+    let mut reference = state.start.node;
+
+    while let Some(parent) = document.parent_node(&reference) {
+        if is_inclusive_ancestor(document, &parent, &state.end.node) {
+            return Boundary {
+                node: parent,
+                offset: index(document, &reference) + 1,
+            };
+        }
+
+        reference = parent;
+    }
+
+    state.start
+}
+
+/// Remove the content of the range.
+fn delete(document: &mut Document<'_>, state: &RangeState) -> Result<()> {
+    let RangeState { start, end } = *state;
+
+    if start.node == end.node && is_character_data(&start.node) {
+        return document.replace_data(&start.node, start.offset, end.offset - start.offset, "");
+    }
+
+    // the contained nodes whose parent is not contained.
+    let mut removed = vec![];
+    let mut stack = vec![common_ancestor(document, &start.node, &end.node)];
+
+    while let Some(node) = stack.pop() {
+        for child in child_nodes(document, &node) {
+            if contains(document, state, &child) {
+                removed.push(child);
+            } else if is_inclusive_ancestor(document, &child, &start.node)
+                || is_inclusive_ancestor(document, &child, &end.node)
+            {
+                stack.push(child);
+            }
+        }
+    }
+
+    if is_character_data(&start.node) {
+        document.replace_data(&start.node, start.offset, usize::MAX, "")?;
+    }
+
+    for node in removed {
+        if let Some(parent) = document.parent_node(&node) {
+            document.remove_child(Some(&parent), &node)?;
+        }
+    }
+
+    if is_character_data(&end.node) {
+        document.replace_data(&end.node, 0, end.offset, "")?;
+    }
+
+    Ok(())
+}
+
+/// Returns the content of the range, moved out of the tree if `extract` is true
+/// or copied otherwise.
+fn contents(
+    document: &mut Document<'_>,
+    state: &RangeState,
+    extract: bool,
+) -> Result<Vec<DOMObject>> {
+    let RangeState { start, end } = *state;
+
+    let mut fragment = vec![];
+
+    if start == end {
+        return Ok(fragment);
+    }
+
+    if start.node == end.node && is_character_data(&start.node) {
+        fragment.push(copy_data(
+            document,
+            &start.node,
+            start.offset,
+            end.offset,
+            extract,
+        )?);
+
+        return Ok(fragment);
+    }
+
+    let common = common_ancestor(document, &start.node, &end.node);
+    let children = child_nodes(document, &common);
+
+    let partially_contained = |node: &DOMObject, other: &DOMObject| {
+        if is_inclusive_ancestor(document, node, other) {
+            return None;
+        }
+
+        children
+            .iter()
+            .find(|child| is_inclusive_ancestor(document, child, node))
+            .copied()
+    };
+
+    let first = partially_contained(&start.node, &end.node);
+    let last = partially_contained(&end.node, &start.node);
+
+    let contained = children
+        .iter()
+        .filter(|child| contains(document, state, child))
+        .copied()
+        .collect::<Vec<_>>();
+
+    if contained
+        .iter()
+        .any(|child| child.node_type() == NodeType::DocumentType)
+    {
+        return Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR));
+    }
+
+    if let Some(first) = first {
+        if is_character_data(&first) {
+            let length = length(document, &first);
+
+            fragment.push(copy_data(document, &first, start.offset, length, extract)?);
+        } else {
+            let end = Boundary {
+                node: first,
+                offset: length(document, &first),
+            };
+
+            fragment.push(copy_partially_contained(
+                document,
+                &first,
+                &RangeState { start, end },
+                extract,
+            )?);
+        }
+    }
+
+    for child in contained {
+        if extract {
+            document.remove_child(Some(&common), &child)?;
+            fragment.push(child);
+        } else {
+            fragment.push(document.clone_node(&child, true)?);
+        }
+    }
+
+    if let Some(last) = last {
+        if is_character_data(&last) {
+            fragment.push(copy_data(document, &last, 0, end.offset, extract)?);
+        } else {
+            let start = Boundary {
+                node: last,
+                offset: 0,
+            };
+
+            fragment.push(copy_partially_contained(
+                document,
+                &last,
+                &RangeState { start, end },
+                extract,
+            )?);
+        }
+    }
+
+    Ok(fragment)
+}
+
+/// Returns a shallow copy of a partially contained `node`, with the content of
+/// `state`, the range of its selected part.
+fn copy_partially_contained(
+    document: &mut Document<'_>,
+    node: &DOMObject,
+    state: &RangeState,
+    extract: bool,
+) -> Result<DOMObject> {
+    let clone = document.clone_node(node, false)?;
+
+    for child in contents(document, state, extract)? {
+        document.append_child(Some(&clone), child)?;
+    }
+
+    Ok(clone)
+}
+
+/// Returns a copy of the character data `node` with the characters from `start`
+/// to `end`, which are removed from `node` if `extract` is true.
+fn copy_data(
+    document: &mut Document<'_>,
+    node: &DOMObject,
+    start: usize,
+    end: usize,
+    extract: bool,
+) -> Result<DOMObject> {
+    let count = end.saturating_sub(start);
+    let data = document.substring_data(node, start, count)?.to_owned();
+
+    let clone = document.clone_node(node, false)?;
+
+    document.replace_data(&clone, 0, usize::MAX, &data)?;
+
+    if extract {
+        document.replace_data(node, start, count, "")?;
+    }
+
+    Ok(clone)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Serializer;
+
+    const DOCUMENT: &str = "<p>Hello <b>bold</b> world<i>it</i>!</p>";
+
+    fn xml(document: &Document<'_>, node: &DOMObject) -> String {
+        Serializer::new()
+            .xml_declaration(false)
+            .to_string(document, Some(node))
+            .unwrap()
+    }
+
+    fn fragment(document: &Document<'_>, nodes: &[DOMObject]) -> Vec<String> {
+        nodes.iter().map(|node| xml(document, node)).collect()
+    }
+
+    /// Returns the `p` element and its child nodes.
+    fn nodes(document: &Document<'_>) -> (DOMObject, Vec<DOMObject>) {
+        let p = document.first_child(None).unwrap();
+
+        (p, child_nodes(document, &p))
+    }
+
+    #[test]
+    fn test_boundaries() {
+        let mut doc = Document::parse(DOCUMENT).unwrap();
+        let (p, children) = nodes(&doc);
+        let (hello, b, world) = (children[0], children[1], children[2]);
+
+        let range = doc.create_range();
+
+        assert!(range.collapsed(&doc).unwrap());
+        assert_eq!(range.start_container(&doc).unwrap(), DOMObject::default());
+
+        range.set_start(&mut doc, &hello, 2).unwrap();
+        range.set_end(&mut doc, &world, 3).unwrap();
+
+        assert!(!range.collapsed(&doc).unwrap());
+        assert_eq!(range.common_ancestor_container(&doc).unwrap(), p);
+
+        // the end is moved to a start after it.
+        let other = range.clone_range(&mut doc).unwrap();
+
+        other.set_start(&mut doc, &world, 4).unwrap();
+        assert_eq!(other.end_container(&doc).unwrap(), world);
+        assert_eq!(other.end_offset(&doc).unwrap(), 4);
+
+        assert_eq!(
+            range
+                .compare_boundary_points(&doc, CompareHow::StartToStart, &other)
+                .unwrap(),
+            Ordering::Less
+        );
+        assert_eq!(
+            range
+                .compare_boundary_points(&doc, CompareHow::EndToStart, &other)
+                .unwrap(),
+            Ordering::Less
+        );
+        assert_eq!(
+            range
+                .compare_boundary_points(&doc, CompareHow::StartToEnd, &other)
+                .unwrap(),
+            Ordering::Less
+        );
+
+        other.set_start_before(&mut doc, &b).unwrap();
+        assert_eq!(
+            range
+                .compare_boundary_points(&doc, CompareHow::StartToStart, &other)
+                .unwrap(),
+            Ordering::Less
+        );
+
+        other.select_node(&mut doc, &b).unwrap();
+        assert_eq!(other.start_container(&doc).unwrap(), p);
+        assert_eq!(other.start_offset(&doc).unwrap(), 1);
+        assert_eq!(other.end_offset(&doc).unwrap(), 2);
+
+        other.select_node_contents(&mut doc, &b).unwrap();
+        assert_eq!(other.start_container(&doc).unwrap(), b);
+        assert_eq!(other.end_offset(&doc).unwrap(), 1);
+        assert_eq!(
+            range
+                .compare_boundary_points(&doc, CompareHow::EndToEnd, &other)
+                .unwrap(),
+            Ordering::Greater
+        );
+
+        other.collapse(&mut doc, false).unwrap();
+        assert_eq!(other.start_container(&doc).unwrap(), b);
+        assert_eq!(other.start_offset(&doc).unwrap(), 1);
+
+        assert!(matches!(
+            range.set_start(&mut doc, &hello, 7),
+            Err(Error::DOMException(ExceptionCode::INDEX_SIZE_ERR))
+        ));
+
+        let attr = doc.create_attr("a", "b").unwrap();
+
+        assert!(matches!(
+            range.set_start(&mut doc, &attr, 0),
+            Err(Error::RangeException(
+                RangeExceptionCode::INVALID_NODE_TYPE_ERR
+            ))
+        ));
+
+        other.detach(&mut doc);
+    }
+
+    #[test]
+    fn test_contents() {
+        let mut doc = Document::parse(DOCUMENT).unwrap();
+        let (p, children) = nodes(&doc);
+        let (hello, world) = (children[0], children[2]);
+
+        let range = doc.create_range();
+
+        range.set_start(&mut doc, &hello, 2).unwrap();
+        range.set_end(&mut doc, &world, 3).unwrap();
+
+        let cloned = range.clone_contents(&mut doc).unwrap();
+
+        assert_eq!(fragment(&doc, &cloned), ["llo ", "<b>bold</b>", " wo"]);
+        assert_eq!(xml(&doc, &p), DOCUMENT);
+
+        let extracted = range.extract_contents(&mut doc).unwrap();
+
+        assert_eq!(fragment(&doc, &extracted), ["llo ", "<b>bold</b>", " wo"]);
+        assert_eq!(xml(&doc, &p), "<p>Herld<i>it</i>!</p>");
+        assert_eq!(range.start_container(&doc).unwrap(), p);
+        assert_eq!(range.start_offset(&doc).unwrap(), 1);
+        assert!(range.collapsed(&doc).unwrap());
+
+        // partially selected elements keep the unselected part.
+        let mut doc = Document::parse(DOCUMENT).unwrap();
+        let (p, children) = nodes(&doc);
+        let (b, i) = (children[1], children[3]);
+        let (bold, it) = (
+            doc.first_child(Some(&b)).unwrap(),
+            doc.first_child(Some(&i)).unwrap(),
+        );
+
+        let range = doc.create_range();
+
+        range.set_start(&mut doc, &bold, 1).unwrap();
+        range.set_end(&mut doc, &it, 1).unwrap();
+
+        let cloned = range.clone_contents(&mut doc).unwrap();
+
+        assert_eq!(
+            fragment(&doc, &cloned),
+            ["<b>old</b>", " world", "<i>i</i>"]
+        );
+
+        range.delete_contents(&mut doc).unwrap();
+
+        assert_eq!(xml(&doc, &p), "<p>Hello <b>b</b><i>t</i>!</p>");
+        assert_eq!(range.start_container(&doc).unwrap(), p);
+        assert_eq!(range.start_offset(&doc).unwrap(), 2);
+
+        // in one text node.
+        let text = doc.first_child(Some(&p)).unwrap();
+
+        range.set_start(&mut doc, &text, 1).unwrap();
+        range.set_end(&mut doc, &text, 4).unwrap();
+
+        let extracted = range.extract_contents(&mut doc).unwrap();
+
+        assert_eq!(fragment(&doc, &extracted), ["ell"]);
+        assert_eq!(xml(&doc, &p), "<p>Ho <b>b</b><i>t</i>!</p>");
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut doc = Document::parse(DOCUMENT).unwrap();
+        let (p, children) = nodes(&doc);
+        let (hello, b, world, i) = (children[0], children[1], children[2], children[3]);
+
+        let range = doc.create_range();
+
+        range.set_start(&mut doc, &hello, 5).unwrap();
+
+        let x = doc.create_element("x").unwrap();
+
+        range.insert_node(&mut doc, x).unwrap();
+
+        assert_eq!(
+            xml(&doc, &p),
+            "<p>Hello<x/> <b>bold</b> world<i>it</i>!</p>"
+        );
+        assert_eq!(range.start_container(&doc).unwrap(), hello);
+        assert_eq!(range.end_container(&doc).unwrap(), p);
+        assert_eq!(range.end_offset(&doc).unwrap(), 2);
+
+        // the selected nodes are moved into the new parent.
+        range.set_start(&mut doc, &p, 2).unwrap();
+        range.set_end(&mut doc, &world, 6).unwrap();
+
+        let s = doc.create_element("s").unwrap();
+
+        range.surround_contents(&mut doc, s).unwrap();
+
+        assert_eq!(
+            xml(&doc, &p),
+            "<p>Hello<x/><s> <b>bold</b> world</s><i>it</i>!</p>"
+        );
+        assert_eq!(range.start_container(&doc).unwrap(), p);
+        assert_eq!(range.start_offset(&doc).unwrap(), 2);
+        assert_eq!(range.end_offset(&doc).unwrap(), 3);
+
+        let bold = doc.first_child(Some(&b)).unwrap();
+
+        range.set_start(&mut doc, &bold, 1).unwrap();
+        range.set_end_after(&mut doc, &i).unwrap();
+
+        let u = doc.create_element("u").unwrap();
+
+        assert!(matches!(
+            range.surround_contents(&mut doc, u),
+            Err(Error::RangeException(
+                RangeExceptionCode::BAD_BOUNDARYPOINTS_ERR
+            ))
+        ));
+
+        let comment = doc.create_comment("c").unwrap();
+
+        range.collapse(&mut doc, true).unwrap();
+        range.insert_node(&mut doc, comment).unwrap();
+        range.set_start(&mut doc, &comment, 0).unwrap();
+
+        assert!(matches!(
+            range.insert_node(&mut doc, u),
+            Err(Error::DOMException(ExceptionCode::HIERARCHY_REQUEST_ERR))
+        ));
+    }
+
+    #[test]
+    fn test_mutation() {
+        let mut doc = Document::parse(DOCUMENT).unwrap();
+        let (p, children) = nodes(&doc);
+        let (hello, b, world) = (children[0], children[1], children[2]);
+        let bold = doc.first_child(Some(&b)).unwrap();
+
+        let offsets = |doc: &Document<'_>, range: &Range| {
+            (
+                range.start_offset(doc).unwrap(),
+                range.end_offset(doc).unwrap(),
+            )
+        };
+
+        let range = doc.create_range();
+
+        range.set_start(&mut doc, &p, 1).unwrap();
+        range.set_end(&mut doc, &p, 3).unwrap();
+
+        let comment = doc.create_comment("c").unwrap();
+
+        doc.insert_before(Some(&p), comment, Some(&hello)).unwrap();
+        assert_eq!(offsets(&doc, &range), (2, 4));
+
+        doc.remove_child(Some(&p), &hello).unwrap();
+        assert_eq!(offsets(&doc, &range), (1, 3));
+
+        // boundary points in a removed subtree are moved to its parent.
+        let inner = doc.create_range();
+
+        inner.set_start(&mut doc, &bold, 2).unwrap();
+        inner.set_end(&mut doc, &world, 3).unwrap();
+
+        doc.remove_child(Some(&p), &b).unwrap();
+
+        assert_eq!(inner.start_container(&doc).unwrap(), p);
+        assert_eq!(offsets(&doc, &inner), (1, 3));
+        assert_eq!(offsets(&doc, &range), (1, 2));
+
+        // character data changes.
+        doc.delete_data(&world, 0, 2).unwrap();
+        assert_eq!(offsets(&doc, &inner), (1, 1));
+
+        doc.insert_data(&world, 0, "xx").unwrap();
+        assert_eq!(offsets(&doc, &inner), (1, 3));
+
+        let tail = doc.split_text(&world, 2).unwrap();
+
+        assert_eq!(inner.end_container(&doc).unwrap(), tail);
+        assert_eq!(offsets(&doc, &inner), (1, 1));
+        assert_eq!(offsets(&doc, &range), (1, 3));
+
+        doc.normalize(Some(&p));
+
+        assert_eq!(inner.end_container(&doc).unwrap(), world);
+        assert_eq!(offsets(&doc, &inner), (1, 3));
+        assert_eq!(offsets(&doc, &range), (1, 2));
+        assert_eq!(xml(&doc, &p), "<p><!--c-->xxorld<i>it</i>!</p>");
+
+        doc.gc();
+
+        assert_eq!(inner.start_container(&doc).unwrap(), p);
+    }
+
+    #[test]
+    fn test_drop() {
+        let mut doc = Document::parse(DOCUMENT).unwrap();
+        let (p, children) = nodes(&doc);
+
+        let range = doc.create_range();
+        range.select_node_contents(&mut doc, &p).unwrap();
+
+        // the boundary points of a dropped range are not updated any more.
+        for _ in 0..3 {
+            let _ = range.clone_range(&mut doc).unwrap();
+        }
+
+        assert_eq!(doc.ranges.slots.iter_mut().count(), 1);
+
+        doc.remove_child(Some(&p), &children[0]).unwrap();
+        assert_eq!(range.end_offset(&doc).unwrap(), children.len() - 1);
+
+        drop(range);
+        assert!(doc.ranges.slots.is_empty());
+    }
+}
@@ -0,0 +1,1095 @@
+//! CSS selectors matching the elements of a [`Document`].
+//!
+//! The supported syntax is the one of Selectors Level 3 without the pseudo-elements,
+//! the id and class selectors and the pseudo-classes of user interaction, which have
+//! no meaning for XML documents.
+
+use std::collections::BTreeMap;
+
+use crate::{DOMObject, Document, Error, NodeType, Result, XMLNS_NAMESPACE};
+
+fn error<T>(reason: String) -> Result<T> {
+    Err(Error::Selector(reason))
+}
+
+/// The namespace part of a type or attribute selector.
+#[derive(Debug, Clone, PartialEq)]
+enum NamespaceTest {
+    /// No prefix: the default namespace if one is bound, otherwise any namespace,
+    /// attributes without prefix have no namespace.
+    Default,
+    /// `*|name`
+    Any,
+    /// `|name`
+    None,
+    /// `prefix|name`, replaced by [`Uri`](Self::Uri) before matching.
+    Prefix(String),
+    Uri(String),
+}
+
+/// A qualified name test, `None` local name is `*`.
+#[derive(Debug, Clone, PartialEq)]
+struct NameTest {
+    namespace: NamespaceTest,
+    local_name: Option<String>,
+}
+
+impl NameTest {
+    fn resolve(
+        &mut self,
+        bindings: &mut dyn FnMut(&str) -> Result<String>,
+        attribute: bool,
+    ) -> Result<()> {
+        self.namespace = match &self.namespace {
+            NamespaceTest::Default if attribute => NamespaceTest::None,
+            NamespaceTest::Default => match bindings("") {
+                Ok(uri) => NamespaceTest::Uri(uri),
+                Err(_) => NamespaceTest::Any,
+            },
+            NamespaceTest::Prefix(prefix) => NamespaceTest::Uri(bindings(prefix)?),
+            namespace => namespace.clone(),
+        };
+
+        Ok(())
+    }
+
+    fn matches(&self, namespace_uri: Option<&str>, local_name: &str) -> bool {
+        let namespace = match &self.namespace {
+            NamespaceTest::Any | NamespaceTest::Default => true,
+            NamespaceTest::None => namespace_uri.is_none(),
+            NamespaceTest::Uri(uri) => namespace_uri == Some(uri.as_str()),
+            NamespaceTest::Prefix(_) => false,
+        };
+
+        namespace
+            && self
+                .local_name
+                .as_deref()
+                .is_none_or(|name| name == local_name)
+    }
+}
+
+/// The operators of attribute selectors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AttributeOp {
+    /// `[name]`
+    Exists,
+    /// `[name=value]`
+    Equals,
+    /// `[name~=value]`
+    Includes,
+    /// `[name|=value]`
+    DashMatch,
+    /// `[name^=value]`
+    Prefix,
+    /// `[name$=value]`
+    Suffix,
+    /// `[name*=value]`
+    Substring,
+}
+
+impl AttributeOp {
+    fn matches(self, attr: &str, value: &str) -> bool {
+        match self {
+            Self::Exists => true,
+            Self::Equals => attr == value,
+            Self::Includes => {
+                !value.is_empty()
+                    && !value.contains(is_whitespace)
+                    && attr.split(is_whitespace).any(|word| word == value)
+            }
+            Self::DashMatch => {
+                attr == value
+                    || attr
+                        .strip_prefix(value)
+                        .is_some_and(|rest| rest.starts_with('-'))
+            }
+            Self::Prefix => !value.is_empty() && attr.starts_with(value),
+            Self::Suffix => !value.is_empty() && attr.ends_with(value),
+            Self::Substring => !value.is_empty() && attr.contains(value),
+        }
+    }
+}
+
+/// One simple selector of a compound selector.
+#[derive(Debug, Clone, PartialEq)]
+enum Simple {
+    Type(NameTest),
+    Attribute {
+        name: NameTest,
+        op: AttributeOp,
+        value: String,
+    },
+    Root,
+    Empty,
+    /// The `:nth-*` family, the position is one of `a * n + b` for some `n >= 0`.
+    Nth {
+        a: i64,
+        b: i64,
+        /// Count the siblings of the same expanded name only.
+        of_type: bool,
+        /// Count from the last sibling.
+        last: bool,
+    },
+    Not(Vec<Compound>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Compound(Vec<Simple>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    /// `a b`
+    Descendant,
+    /// `a > b`
+    Child,
+    /// `a + b`
+    NextSibling,
+    /// `a ~ b`
+    SubsequentSibling,
+}
+
+/// Compound selectors separated by combinators, `combinators[i]` is between
+/// `compounds[i]` and `compounds[i + 1]`.
+#[derive(Debug, Clone, PartialEq)]
+struct Complex {
+    compounds: Vec<Compound>,
+    combinators: Vec<Combinator>,
+}
+
+/// A parsed group of CSS selectors.
+///
+/// Type and attribute names are compared case-sensitively, as XML names are.
+/// Namespace prefixes are bound by [`namespace`](Self::namespace), the prefixes
+/// without binding are looked up in the scope of the root of the selection.
+#[derive(Debug, Clone)]
+pub struct Selector {
+    list: Vec<Complex>,
+    namespaces: BTreeMap<String, String>,
+}
+
+impl Selector {
+    /// Parse a comma separated list of selectors.
+    pub fn parse(selector: &str) -> Result<Self> {
+        let mut parser = Parser {
+            input: selector,
+            pos: 0,
+        };
+
+        parser.skip_whitespace();
+
+        let list = parser.list()?;
+
+        if parser.peek().is_some() {
+            return parser.unexpected();
+        }
+
+        Ok(Self {
+            list,
+            namespaces: BTreeMap::new(),
+        })
+    }
+
+    /// Bind `prefix` to `uri` as the `@namespace` rule does, an empty prefix
+    /// declares the default namespace of type selectors.
+    pub fn namespace<P, U>(mut self, prefix: P, uri: U) -> Self
+    where
+        P: Into<String>,
+        U: Into<String>,
+    {
+        self.namespaces.insert(prefix.into(), uri.into());
+        self
+    }
+
+    /// Returns true if `element` matches one of the selectors.
+    ///
+    /// The prefixes without binding are looked up in the scope of `element`.
+    pub fn matches(&self, document: &Document<'_>, element: &DOMObject) -> Result<bool> {
+        if element.node_type() != NodeType::Element {
+            return Ok(false);
+        }
+
+        let selector = self.resolve(document, element)?;
+
+        Ok(selector.matches_element(document, element))
+    }
+
+    /// Returns the elements of the subtree of `root` which match one of the selectors,
+    /// in document order, `root` itself excluded.
+    ///
+    /// Combinators may match ancestors and siblings outside of the subtree.
+    pub fn select<'d, 'a>(
+        &self,
+        document: &'d Document<'a>,
+        root: &DOMObject,
+    ) -> Result<Select<'d, 'a>> {
+        let selector = self.resolve(document, root)?;
+
+        let mut stack = document.children(Some(root)).copied().collect::<Vec<_>>();
+        stack.reverse();
+
+        Ok(Select {
+            document,
+            selector,
+            stack,
+        })
+    }
+
+    /// Returns a copy of this selector with the namespace prefixes replaced by URIs.
+    fn resolve(&self, document: &Document<'_>, scope: &DOMObject) -> Result<Self> {
+        let mut bindings = |prefix: &str| match self.namespaces.get(prefix) {
+            Some(uri) => Ok(uri.clone()),
+            // the default namespace of the document doesn't apply to selectors.
+            None if prefix.is_empty() => error("no default namespace".into()),
+            None => match document.lookup_namespace_uri(scope, Some(prefix)) {
+                Some(uri) => Ok(uri.to_owned()),
+                None => error(format!("undeclared prefix {}", prefix)),
+            },
+        };
+
+        let mut selector = self.clone();
+
+        for complex in &mut selector.list {
+            for compound in &mut complex.compounds {
+                compound.resolve(&mut bindings)?;
+            }
+        }
+
+        Ok(selector)
+    }
+
+    fn matches_element(&self, document: &Document<'_>, element: &DOMObject) -> bool {
+        self.list
+            .iter()
+            .any(|complex| complex.matches(document, complex.compounds.len() - 1, element))
+    }
+}
+
+impl Compound {
+    fn resolve(&mut self, bindings: &mut dyn FnMut(&str) -> Result<String>) -> Result<()> {
+        for simple in &mut self.0 {
+            match simple {
+                Simple::Type(name) => name.resolve(bindings, false)?,
+                Simple::Attribute { name, .. } => name.resolve(bindings, true)?,
+                Simple::Not(compounds) => {
+                    for compound in compounds {
+                        compound.resolve(bindings)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn matches(&self, document: &Document<'_>, element: &DOMObject) -> bool {
+        self.0
+            .iter()
+            .all(|simple| simple.matches(document, element))
+    }
+}
+
+impl Simple {
+    fn matches(&self, document: &Document<'_>, element: &DOMObject) -> bool {
+        match self {
+            Simple::Type(name) => document
+                .element(element)
+                .is_some_and(|el| name.matches(el.namespace_uri(), &el.tag().local_part)),
+            Simple::Attribute { name, op, value } => document
+                .element(element)
+                .into_iter()
+                .flat_map(|el| el.attributes())
+                .filter_map(|attr| document.attr(attr))
+                .filter(|attr| attr.namespace_uri() != Some(XMLNS_NAMESPACE))
+                .any(|attr| {
+                    name.matches(attr.namespace_uri(), &attr.name().local_part)
+                        && op.matches(attr.value(), value)
+                }),
+            Simple::Root => document
+                .parent_node(element)
+                .is_some_and(|parent| parent.node_type() == NodeType::Document),
+            Simple::Empty => document.children(Some(element)).all(|child| {
+                !matches!(
+                    child.node_type(),
+                    NodeType::Element
+                        | NodeType::Text
+                        | NodeType::CData
+                        | NodeType::EntityReference
+                )
+            }),
+            Simple::Nth {
+                a,
+                b,
+                of_type,
+                last,
+            } => {
+                let Some(parent) = document.parent_node(element) else {
+                    return false;
+                };
+
+                let expanded_name = |object: &DOMObject| {
+                    document
+                        .element(object)
+                        .map(|el| (el.namespace_uri(), el.tag().local_part.as_ref()))
+                };
+
+                let name = expanded_name(element);
+
+                let mut siblings = document
+                    .children(Some(&parent))
+                    .filter(|child| child.node_type() == NodeType::Element)
+                    .filter(|child| !of_type || expanded_name(child) == name);
+
+                let index = if *last {
+                    siblings.rev().position(|child| child == element)
+                } else {
+                    siblings.position(|child| child == element)
+                };
+
+                let Some(index) = index else {
+                    return false;
+                };
+
+                let position = index as i64 + 1;
+
+                // overflows are out of the range of positions.
+                match *a {
+                    0 => position == *b,
+                    a => position
+                        .checked_sub(*b)
+                        .is_some_and(|diff| diff.checked_rem(a) == Some(0) && diff / a >= 0),
+                }
+            }
+            Simple::Not(compounds) => !compounds
+                .iter()
+                .any(|compound| compound.matches(document, element)),
+        }
+    }
+}
+
+impl Complex {
+    /// Returns true if `element` matches the selector ending with `compounds[index]`.
+    fn matches(&self, document: &Document<'_>, index: usize, element: &DOMObject) -> bool {
+        if !self.compounds[index].matches(document, element) {
+            return false;
+        }
+
+        if index == 0 {
+            return true;
+        }
+
+        let index = index - 1;
+
+        match self.combinators[index] {
+            Combinator::Descendant => {
+                let mut ancestor = parent_element(document, element);
+
+                while let Some(current) = ancestor {
+                    if self.matches(document, index, &current) {
+                        return true;
+                    }
+
+                    ancestor = parent_element(document, &current);
+                }
+
+                false
+            }
+            Combinator::Child => parent_element(document, element)
+                .is_some_and(|parent| self.matches(document, index, &parent)),
+            Combinator::NextSibling => previous_element_siblings(document, element)
+                .next()
+                .is_some_and(|sibling| self.matches(document, index, &sibling)),
+            Combinator::SubsequentSibling => previous_element_siblings(document, element)
+                .any(|sibling| self.matches(document, index, &sibling)),
+        }
+    }
+}
+
+fn parent_element(document: &Document<'_>, object: &DOMObject) -> Option<DOMObject> {
+    document
+        .parent_node(object)
+        .filter(|parent| parent.node_type() == NodeType::Element)
+}
+
+fn previous_element_siblings<'d>(
+    document: &'d Document<'_>,
+    object: &DOMObject,
+) -> impl Iterator<Item = DOMObject> + 'd {
+    let mut sibling = Some(*object);
+
+    std::iter::from_fn(move || loop {
+        sibling = document.previous_sibling(&sibling?);
+
+        if sibling?.node_type() == NodeType::Element {
+            return sibling;
+        }
+    })
+}
+
+fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n' | '\x0C')
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || !c.is_ascii()
+}
+
+fn is_name_char(c: char) -> bool {
+    is_name_start(c) || c.is_ascii_digit() || c == '-'
+}
+
+struct Parser<'s> {
+    input: &'s str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn peek_second(&self) -> Option<char> {
+        self.input[self.pos..].chars().nth(1)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            self.unexpected()
+        }
+    }
+
+    /// Skip white spaces, returns true if any.
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.pos;
+
+        while self.peek().is_some_and(is_whitespace) {
+            self.pos += 1;
+        }
+
+        self.pos > start
+    }
+
+    fn unexpected<T>(&self) -> Result<T> {
+        match self.peek() {
+            Some(c) => error(format!("unexpected {:?} at {}", c, self.pos)),
+            None => error("unexpected end of selector".into()),
+        }
+    }
+
+    fn list(&mut self) -> Result<Vec<Complex>> {
+        let mut list = vec![self.complex()?];
+
+        while self.eat(',') {
+            self.skip_whitespace();
+            list.push(self.complex()?);
+        }
+
+        Ok(list)
+    }
+
+    fn complex(&mut self) -> Result<Complex> {
+        let mut compounds = vec![self.compound()?];
+        let mut combinators = vec![];
+
+        loop {
+            let whitespace = self.skip_whitespace();
+
+            let combinator = match self.peek() {
+                Some('>') => Combinator::Child,
+                Some('+') => Combinator::NextSibling,
+                Some('~') => Combinator::SubsequentSibling,
+                Some(',') | None => break,
+                _ if whitespace => {
+                    combinators.push(Combinator::Descendant);
+                    compounds.push(self.compound()?);
+                    continue;
+                }
+                _ => return self.unexpected(),
+            };
+
+            self.bump();
+            self.skip_whitespace();
+
+            combinators.push(combinator);
+            compounds.push(self.compound()?);
+        }
+
+        Ok(Complex {
+            compounds,
+            combinators,
+        })
+    }
+
+    fn compound(&mut self) -> Result<Compound> {
+        let mut simples = vec![];
+
+        if matches!(self.peek(), Some('*' | '|'))
+            || self
+                .peek()
+                .is_some_and(|c| is_name_start(c) || c == '-' || c == '\\')
+        {
+            simples.push(Simple::Type(self.name()?));
+        }
+
+        loop {
+            match self.peek() {
+                Some('[') => simples.push(self.attribute()?),
+                Some(':') => simples.push(self.pseudo_class()?),
+                _ => break,
+            }
+        }
+
+        if simples.is_empty() {
+            return self.unexpected();
+        }
+
+        Ok(Compound(simples))
+    }
+
+    /// Parse a qualified name with an optional namespace prefix, `*` is allowed
+    /// as prefix and local name.
+    fn name(&mut self) -> Result<NameTest> {
+        let first = if self.eat('*') {
+            None
+        } else if self.peek() == Some('|') {
+            Some(String::new())
+        } else {
+            Some(self.ident()?)
+        };
+
+        // `|=` is an attribute operator, not a namespace separator.
+        if self.peek() != Some('|') || self.peek_second() == Some('=') {
+            return Ok(NameTest {
+                namespace: NamespaceTest::Default,
+                local_name: first,
+            });
+        }
+
+        self.bump();
+
+        let namespace = match first {
+            None => NamespaceTest::Any,
+            Some(prefix) if prefix.is_empty() => NamespaceTest::None,
+            Some(prefix) => NamespaceTest::Prefix(prefix),
+        };
+
+        let local_name = if self.eat('*') {
+            None
+        } else {
+            Some(self.ident()?)
+        };
+
+        Ok(NameTest {
+            namespace,
+            local_name,
+        })
+    }
+
+    fn attribute(&mut self) -> Result<Simple> {
+        self.expect('[')?;
+        self.skip_whitespace();
+
+        let name = self.name()?;
+
+        if name.local_name.is_none() {
+            return error("attribute selectors require a name".into());
+        }
+
+        self.skip_whitespace();
+
+        let op = match self.peek() {
+            Some(']') => AttributeOp::Exists,
+            Some('=') => AttributeOp::Equals,
+            Some('~') => AttributeOp::Includes,
+            Some('|') => AttributeOp::DashMatch,
+            Some('^') => AttributeOp::Prefix,
+            Some('$') => AttributeOp::Suffix,
+            Some('*') => AttributeOp::Substring,
+            _ => return self.unexpected(),
+        };
+
+        let mut value = String::new();
+
+        if op != AttributeOp::Exists {
+            self.bump();
+
+            if op != AttributeOp::Equals {
+                self.expect('=')?;
+            }
+
+            self.skip_whitespace();
+
+            value = match self.peek() {
+                Some(quote @ ('"' | '\'')) => self.string(quote)?,
+                _ => self.ident()?,
+            };
+
+            self.skip_whitespace();
+        }
+
+        self.expect(']')?;
+
+        Ok(Simple::Attribute { name, op, value })
+    }
+
+    fn pseudo_class(&mut self) -> Result<Simple> {
+        self.expect(':')?;
+
+        let start = self.pos;
+        let name = self.ident()?.to_ascii_lowercase();
+
+        let nth = |a, b, of_type, last| Simple::Nth {
+            a,
+            b,
+            of_type,
+            last,
+        };
+
+        let simple = match name.as_str() {
+            "root" => Simple::Root,
+            "empty" => Simple::Empty,
+            "first-child" => nth(0, 1, false, false),
+            "last-child" => nth(0, 1, false, true),
+            "first-of-type" => nth(0, 1, true, false),
+            "last-of-type" => nth(0, 1, true, true),
+            "only-child" => Simple::Not(vec![
+                Compound(vec![nth(1, 2, false, false)]),
+                Compound(vec![nth(1, 2, false, true)]),
+            ]),
+            "only-of-type" => Simple::Not(vec![
+                Compound(vec![nth(1, 2, true, false)]),
+                Compound(vec![nth(1, 2, true, true)]),
+            ]),
+            "nth-child" | "nth-last-child" | "nth-of-type" | "nth-last-of-type" => {
+                self.expect('(')?;
+
+                let (a, b) = self.nth()?;
+
+                nth(
+                    a,
+                    b,
+                    name.ends_with("of-type"),
+                    name.starts_with("nth-last"),
+                )
+            }
+            "not" => {
+                self.expect('(')?;
+                self.skip_whitespace();
+
+                let mut compounds = vec![self.compound()?];
+
+                self.skip_whitespace();
+
+                while self.eat(',') {
+                    self.skip_whitespace();
+                    compounds.push(self.compound()?);
+                    self.skip_whitespace();
+                }
+
+                self.expect(')')?;
+
+                Simple::Not(compounds)
+            }
+            _ => return error(format!("unsupported pseudo-class :{} at {}", name, start)),
+        };
+
+        Ok(simple)
+    }
+
+    /// Parse the `an+b` argument of the `:nth-*` pseudo-classes and the closing parenthesis.
+    fn nth(&mut self) -> Result<(i64, i64)> {
+        let start = self.pos;
+
+        let Some(len) = self.input[start..].find(')') else {
+            return error("unexpected end of selector".into());
+        };
+
+        self.pos += len + 1;
+
+        let argument = self.input[start..start + len]
+            .trim_matches(is_whitespace)
+            .to_ascii_lowercase();
+
+        let invalid = || error(format!("invalid argument {:?} at {}", argument, start));
+
+        // an optional sign and digits, without white space.
+        let integer = |value: &str| {
+            let digits = value.strip_prefix(['+', '-']).unwrap_or(value);
+
+            if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+
+            value.strip_prefix('+').unwrap_or(value).parse::<i64>().ok()
+        };
+
+        let (a, b) = match argument.as_str() {
+            "odd" => (2, 1),
+            "even" => (2, 0),
+            argument => match argument.split_once('n') {
+                Some((a, b)) => {
+                    let a = match a {
+                        "" | "+" => Some(1),
+                        "-" => Some(-1),
+                        a => integer(a),
+                    };
+
+                    // white space is allowed around the binary `+` or `-` only.
+                    let b = b.trim_start_matches(is_whitespace);
+
+                    let b = match b.chars().next() {
+                        None => Some(0),
+                        Some(sign @ ('+' | '-')) => {
+                            let digits = b[1..].trim_start_matches(is_whitespace);
+
+                            digits
+                                .starts_with(|c: char| c.is_ascii_digit())
+                                .then(|| integer(&format!("{}{}", sign, digits)))
+                                .flatten()
+                        }
+                        Some(_) => None,
+                    };
+
+                    match (a, b) {
+                        (Some(a), Some(b)) => (a, b),
+                        _ => return invalid(),
+                    }
+                }
+                None => match integer(argument) {
+                    Some(b) => (0, b),
+                    None => return invalid(),
+                },
+            },
+        };
+
+        Ok((a, b))
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        let mut ident = String::new();
+
+        if self.eat('-') {
+            ident.push('-');
+        }
+
+        match self.peek() {
+            Some(c) if is_name_start(c) || c == '\\' => {}
+            _ => return self.unexpected(),
+        }
+
+        while let Some(c) = self.peek() {
+            if c == '\\' {
+                self.bump();
+                ident.push(self.escape()?);
+            } else if is_name_char(c) {
+                self.bump();
+                ident.push(c);
+            } else {
+                break;
+            }
+        }
+
+        Ok(ident)
+    }
+
+    fn string(&mut self, quote: char) -> Result<String> {
+        self.expect(quote)?;
+
+        let mut value = String::new();
+
+        loop {
+            match self.bump() {
+                Some('\\') => match self.peek() {
+                    // an escaped newline is removed.
+                    Some('\n') => {
+                        self.bump();
+                    }
+                    _ => value.push(self.escape()?),
+                },
+                Some(c) if c == quote => return Ok(value),
+                Some(c) => value.push(c),
+                None => return error("unterminated string".into()),
+            }
+        }
+    }
+
+    /// Parse the escape after a backslash.
+    fn escape(&mut self) -> Result<char> {
+        let start = self.pos;
+
+        while self.pos - start < 6 && self.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+            self.pos += 1;
+        }
+
+        if self.pos == start {
+            return match self.bump() {
+                Some(c) => Ok(c),
+                None => error("unexpected end of selector".into()),
+            };
+        }
+
+        let code = u32::from_str_radix(&self.input[start..self.pos], 16).expect("hex digits");
+
+        // one white space terminates the escape.
+        if self.peek().is_some_and(is_whitespace) {
+            self.bump();
+        }
+
+        Ok(char::from_u32(code)
+            .filter(|c| *c != '\0')
+            .unwrap_or(char::REPLACEMENT_CHARACTER))
+    }
+}
+
+/// An iterator over the elements matched by a [`Selector`] in document order.
+pub struct Select<'d, 'a> {
+    document: &'d Document<'a>,
+    selector: Selector,
+    /// The nodes to visit, the next one is the last.
+    stack: Vec<DOMObject>,
+}
+
+impl Iterator for Select<'_, '_> {
+    type Item = DOMObject;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            if !matches!(
+                node.node_type(),
+                NodeType::Element | NodeType::EntityReference
+            ) {
+                continue;
+            }
+
+            let children = self.document.children(Some(&node)).rev();
+            self.stack.extend(children.copied());
+
+            if node.node_type() == NodeType::Element
+                && self.selector.matches_element(self.document, &node)
+            {
+                return Some(node);
+            }
+        }
+
+        None
+    }
+}
+
+impl<'a> Document<'a> {
+    /// Returns the elements of the subtree of `root` which match the CSS `selector`,
+    /// see [`Selector::select`].
+    pub fn select<'d>(&'d self, root: &DOMObject, selector: &str) -> Result<Select<'d, 'a>> {
+        Selector::parse(selector)?.select(self, root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = r#"<list xmlns="urn:list" xmlns:ns="urn:ns">
+<ns:item type="a" lang="en-US" class="x y"><title>One</title><note/></ns:item>
+<ns:item type="b"><note>text</note><title>Two</title></ns:item>
+<item type="a"><title>Three</title></item>
+<ns:item type="a" data="prefix-suffix"><title>Four</title><title>Five</title></ns:item>
+</list>"#;
+
+    fn select(doc: &Document<'_>, selector: &str) -> Result<Vec<String>> {
+        let names = doc
+            .select(&DOMObject::default(), selector)?
+            .map(|el| {
+                let el = doc.element(&el).unwrap();
+
+                let text = doc
+                    .first_child(Some(el.as_ref()))
+                    .filter(|child| child.node_type() == NodeType::Text)
+                    .and_then(|child| doc.text(&child))
+                    .filter(|text| !text.data().trim().is_empty());
+
+                match text {
+                    Some(text) => format!("{}:{}", el.tag(), text.data()),
+                    None => el.tag().to_string(),
+                }
+            })
+            .collect();
+
+        Ok(names)
+    }
+
+    #[test]
+    fn test_select() {
+        let doc = Document::parse(DOCUMENT).unwrap();
+
+        assert_eq!(
+            select(&doc, "ns|item[type=a] > title:first-child").unwrap(),
+            ["title:One", "title:Four"]
+        );
+        assert_eq!(
+            select(&doc, "ns|item > title:last-of-type:not(:first-child)").unwrap(),
+            ["title:Two", "title:Five"]
+        );
+        assert_eq!(
+            select(&doc, "|item, *|item > *|title").unwrap(),
+            [
+                "title:One",
+                "title:Two",
+                "title:Three",
+                "title:Four",
+                "title:Five"
+            ]
+        );
+        assert_eq!(
+            select(&doc, "ns|*:nth-child(2n+1) title:only-of-type").unwrap(),
+            ["title:One"]
+        );
+        assert_eq!(
+            select(&doc, "item:nth-last-child(-n + 2) title").unwrap(),
+            ["title:Three", "title:Four", "title:Five"]
+        );
+        assert_eq!(
+            select(&doc, "ns|item:nth-of-type(even) note:empty").unwrap(),
+            Vec::<String>::new()
+        );
+        assert_eq!(select(&doc, "note:empty").unwrap(), ["note"]);
+        assert_eq!(select(&doc, "note:not(:empty)").unwrap(), ["note:text"]);
+        assert_eq!(select(&doc, ":root").unwrap(), ["list"]);
+        assert_eq!(
+            select(&doc, "[type=b] + item title ~ *").unwrap(),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            select(&doc, "[type=b] ~ * > title + title").unwrap(),
+            ["title:Five"]
+        );
+        assert_eq!(select(&doc, "[type=b] + * > *").unwrap(), ["title:Three"]);
+
+        // attribute operators.
+        assert_eq!(select(&doc, "[class~=y] title").unwrap(), ["title:One"]);
+        assert_eq!(select(&doc, "[lang|=en] title").unwrap(), ["title:One"]);
+        assert_eq!(
+            select(&doc, "[data^='prefix'] :first-child").unwrap(),
+            ["title:Four"]
+        );
+        assert_eq!(
+            select(&doc, "[data$=\"suffix\"] :last-child").unwrap(),
+            ["title:Five"]
+        );
+        assert_eq!(
+            select(&doc, "[data*=x-s] :nth-child(1)").unwrap(),
+            ["title:Four"]
+        );
+        assert_eq!(
+            select(&doc, "[data*=''], [xmlns], [ns|type]").unwrap(),
+            Vec::<String>::new()
+        );
+
+        assert!(doc
+            .select(&DOMObject::default(), "undeclared|item")
+            .is_err());
+        assert!(doc.select(&DOMObject::default(), "item >").is_err());
+        assert!(doc.select(&DOMObject::default(), ":hover").is_err());
+        assert!(doc.select(&DOMObject::default(), ":nth-child(n2)").is_err());
+    }
+
+    #[test]
+    fn test_namespaces() {
+        let doc = Document::parse(DOCUMENT).unwrap();
+        let list = doc.first_child(None).unwrap();
+
+        let selector = Selector::parse("title").unwrap().namespace("", "urn:other");
+        assert_eq!(selector.select(&doc, &list).unwrap().count(), 0);
+
+        let selector = Selector::parse("l|item > title")
+            .unwrap()
+            .namespace("l", "urn:list");
+        assert_eq!(selector.select(&doc, &list).unwrap().count(), 1);
+
+        let item = doc.select(&list, "item").unwrap().next().unwrap();
+        assert!(!selector.matches(&doc, &item).unwrap());
+        assert!(Selector::parse("list > :nth-child(1)")
+            .unwrap()
+            .matches(&doc, &item)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_nth() {
+        let doc =
+            Document::parse("<l><a>1</a><b>2</b><a>3</a><b>4</b><a>5</a><b>6</b><a>7</a></l>")
+                .unwrap();
+
+        let texts = |selector: &str| {
+            select(&doc, &format!("l > {}", selector))
+                .unwrap()
+                .into_iter()
+                .map(|name| name.split_once(':').unwrap().1.to_owned())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        assert_eq!(texts(":nth-child(3)"), "3");
+        assert_eq!(texts(":nth-child(2n)"), "2 4 6");
+        assert_eq!(texts(":nth-child(odd)"), "1 3 5 7");
+        assert_eq!(texts(":nth-child( -n + 3 )"), "1 2 3");
+        assert_eq!(texts(":nth-child(2n- 1)"), "1 3 5 7");
+        assert_eq!(texts(":nth-child(+2n +1)"), "1 3 5 7");
+        assert_eq!(texts(":nth-child(3n-1)"), "2 5");
+        assert_eq!(texts(":nth-child(-2n+5)"), "1 3 5");
+        assert_eq!(texts(":nth-child(N+6)"), "6 7");
+        assert_eq!(texts(":nth-last-child(2)"), "6");
+        assert_eq!(texts(":nth-last-child(3n+1)"), "1 4 7");
+        assert_eq!(texts("a:nth-of-type(2)"), "3");
+        assert_eq!(texts("b:nth-last-of-type(1)"), "6");
+        assert_eq!(texts("a:nth-last-of-type(-n+2)"), "5 7");
+        assert_eq!(texts(":nth-child(0n+0)"), "");
+        assert_eq!(texts(":nth-child(-n-1)"), "");
+
+        // the positions overflow.
+        assert_eq!(texts(":nth-child(2n-9223372036854775808)"), "");
+        assert_eq!(
+            texts(":nth-child(-9223372036854775808n-9223372036854775808)"),
+            ""
+        );
+        assert_eq!(
+            texts(":nth-child(-1n+9223372036854775807)"),
+            "1 2 3 4 5 6 7"
+        );
+        assert_eq!(texts(":nth-child(9223372036854775807n+7)"), "7");
+
+        for selector in [
+            ":nth-child(9223372036854775808)",
+            ":nth-child(2n+)",
+            ":nth-child(n-)",
+            ":nth-child(2n 1)",
+            ":nth-child(- n + 3)",
+            ":nth-child(+ 2n)",
+            ":nth-child(2 n)",
+            ":nth-child(2n + -1)",
+            ":nth-child(2n ++1)",
+            ":nth-child(++2n)",
+            ":nth-child(1 2)",
+            ":nth-child(x)",
+            ":nth-child(1",
+        ] {
+            assert!(doc.select(&DOMObject::default(), selector).is_err());
+        }
+    }
+}
@@ -0,0 +1,1056 @@
+//! Write a [`Document`] or a subtree as XML text.
+
+use std::{
+    borrow::Cow,
+    fmt::{self, Write as _},
+    io,
+};
+
+use rexml_encoding::Encoding;
+use rexml_stax::{parser::is_whitespace, writer::escape};
+
+use crate::{
+    Attr, DOMObject, Document, Error, ExceptionCode, NodeType, QName, Result, XMLNS_NAMESPACE,
+    XML_NAMESPACE,
+};
+
+/// Options of the serialization of a [`Document`].
+///
+/// Namespace declarations are added where the namespace URI of an element or attribute
+/// is not in scope, so any subtree is written as namespace well-formed XML.
+#[derive(Debug, Clone)]
+pub struct Serializer {
+    xml_declaration: bool,
+    encoding: Encoding,
+    standalone: Option<bool>,
+    self_closing: bool,
+    indent: Option<String>,
+    wrap_attributes: Option<usize>,
+    sort_attributes: bool,
+}
+
+impl Default for Serializer {
+    fn default() -> Self {
+        Self {
+            xml_declaration: true,
+            encoding: Encoding::Utf8,
+            standalone: None,
+            self_closing: true,
+            indent: None,
+            wrap_attributes: None,
+            sort_attributes: false,
+        }
+    }
+}
+
+impl Serializer {
+    /// Create a new `Serializer` with the default options.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Write the XML declaration before the document, default is true.
+    ///
+    /// The declaration is never written for subtrees.
+    pub fn xml_declaration(mut self, write: bool) -> Self {
+        self.xml_declaration = write;
+        self
+    }
+
+    /// The encoding of the output, default is UTF-8.
+    ///
+    /// Characters of text and attribute values which can't be encoded are written as character references.
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// The `standalone` pseudo-attribute of the XML declaration, omitted by default.
+    pub fn standalone(mut self, standalone: Option<bool>) -> Self {
+        self.standalone = standalone;
+        self
+    }
+
+    /// Write elements without content as empty-element tags `<a/>` instead of `<a></a>`, default is true.
+    pub fn self_closing(mut self, self_closing: bool) -> Self {
+        self.self_closing = self_closing;
+        self
+    }
+
+    /// Indent element content with `indent` once per level, not indented by default.
+    ///
+    /// Content is only indented where white spaces are not significant: elements with
+    /// text other than white spaces, CDATA sections or entity references are written as is,
+    /// and so is everything in the scope of `xml:space="preserve"`.
+    /// White space only text of indented content is replaced by the indentation.
+    pub fn indent<S>(mut self, indent: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.indent = Some(indent.into());
+        self
+    }
+
+    /// Indent element content with `width` spaces per level, see [`indent`](Self::indent).
+    pub fn indent_width(self, width: usize) -> Self {
+        self.indent(" ".repeat(width))
+    }
+
+    /// Write the attributes of a start tag one per line, aligned after the element name,
+    /// if the tag would end beyond `column`. Not wrapped by default.
+    pub fn wrap_attributes(mut self, column: usize) -> Self {
+        self.wrap_attributes = Some(column);
+        self
+    }
+
+    /// Write namespace declarations and then attributes sorted by qualified name
+    /// instead of in document order, default is false.
+    pub fn sort_attributes(mut self, sort: bool) -> Self {
+        self.sort_attributes = sort;
+        self
+    }
+
+    /// Write the subtree of `node` to `writer`, or the whole document if `node` is `None`.
+    pub fn write<W>(
+        &self,
+        document: &Document<'_>,
+        node: Option<&DOMObject>,
+        writer: &mut W,
+    ) -> Result<()>
+    where
+        W: fmt::Write + ?Sized,
+    {
+        let encoding = encoding_name(&self.encoding)?;
+
+        let mut output = Output {
+            writer,
+            ascii: matches!(self.encoding, Encoding::Ascii),
+            column: 0,
+        };
+
+        let mut state = State {
+            document,
+            output: &mut output,
+            options: self,
+            bindings: vec![],
+            scopes: vec![],
+            formatted: vec![],
+        };
+
+        match node {
+            Some(node) if node.node_type() != NodeType::Document => state.subtree(node),
+            _ => state.document(encoding),
+        }
+    }
+
+    /// Write the subtree of `node` to `writer` in the selected encoding,
+    /// or the whole document if `node` is `None`.
+    pub fn write_io<W>(
+        &self,
+        document: &Document<'_>,
+        node: Option<&DOMObject>,
+        writer: W,
+    ) -> Result<()>
+    where
+        W: io::Write,
+    {
+        let mut encoder = Encoder {
+            writer,
+            encoding: self.encoding.clone(),
+            error: None,
+        };
+
+        if matches!(self.encoding, Encoding::Utf16) && node.is_none() {
+            encoder.bytes(&[0xfe, 0xff]);
+        }
+
+        let result = self.write(document, node, &mut encoder);
+
+        match encoder.error {
+            Some(err) => Err(err.into()),
+            None => result,
+        }
+    }
+
+    /// Returns the subtree of `node` as a string, or the whole document if `node` is `None`.
+    pub fn to_string(&self, document: &Document<'_>, node: Option<&DOMObject>) -> Result<String> {
+        let mut output = String::new();
+
+        self.write(document, node, &mut output)?;
+
+        Ok(output)
+    }
+}
+
+impl<'a> fmt::Display for Document<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Serializer::new()
+            .write(self, None, f)
+            .map_err(|_| fmt::Error)
+    }
+}
+
+fn encoding_name(encoding: &Encoding) -> Result<&'static str> {
+    match encoding {
+        Encoding::Utf8 => Ok("UTF-8"),
+        Encoding::Utf16 => Ok("UTF-16"),
+        Encoding::Utf16LE => Ok("UTF-16LE"),
+        Encoding::Utf16BE => Ok("UTF-16BE"),
+        Encoding::Ascii => Ok("US-ASCII"),
+        Encoding::AsciiCompatible => Err(Error::DOMException(ExceptionCode::NOT_SUPPORTED_ERR)),
+    }
+}
+
+/// Encode the written text into an [`io::Write`].
+struct Encoder<W> {
+    writer: W,
+    encoding: Encoding,
+    /// The first error of the writer, reported instead of the [`fmt::Error`] it causes.
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> Encoder<W> {
+    fn bytes(&mut self, buf: &[u8]) {
+        if self.error.is_none() {
+            if let Err(err) = self.writer.write_all(buf) {
+                self.error = Some(err);
+            }
+        }
+    }
+}
+
+impl<W: io::Write> fmt::Write for Encoder<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match self.encoding {
+            Encoding::Utf16 | Encoding::Utf16BE => {
+                let buf = s
+                    .encode_utf16()
+                    .flat_map(u16::to_be_bytes)
+                    .collect::<Vec<_>>();
+                self.bytes(&buf);
+            }
+            Encoding::Utf16LE => {
+                let buf = s
+                    .encode_utf16()
+                    .flat_map(u16::to_le_bytes)
+                    .collect::<Vec<_>>();
+                self.bytes(&buf);
+            }
+            _ => self.bytes(s.as_bytes()),
+        }
+
+        match self.error {
+            Some(_) => Err(fmt::Error),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Escaping writer of one serialization.
+struct Output<'w, W: ?Sized> {
+    writer: &'w mut W,
+    /// Only ASCII characters can be encoded.
+    ascii: bool,
+    /// The number of characters written since the last line break.
+    column: usize,
+}
+
+impl<'w, W: fmt::Write + ?Sized> fmt::Write for Output<'w, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.writer.write_str(s)?;
+
+        match s.rfind('\n') {
+            Some(index) => self.column = s[index + 1..].chars().count(),
+            None => self.column += s.chars().count(),
+        }
+
+        Ok(())
+    }
+}
+
+impl<'w, W: fmt::Write + ?Sized> Output<'w, W> {
+    /// Write markup, which has no way to escape characters.
+    fn raw(&mut self, s: &str) -> Result<()> {
+        if self.ascii && !s.is_ascii() {
+            return Err(Error::DOMException(ExceptionCode::INVALID_CHARACTER_ERR));
+        }
+
+        Ok(self.write_str(s)?)
+    }
+
+    /// Write `s` with the characters for which `replace` returns a replacement escaped,
+    /// characters which can't be encoded are written as character references.
+    fn escaped<F>(&mut self, s: &str, replace: F) -> Result<()>
+    where
+        F: Fn(char) -> Option<&'static str>,
+    {
+        let ascii = self.ascii;
+
+        Ok(escape(self, s, ascii, replace)?)
+    }
+
+    fn qname(&mut self, name: &QName<'_>) -> Result<()> {
+        if let Some(prefix) = &name.prefix {
+            self.raw(prefix)?;
+            self.raw(":")?;
+        }
+
+        self.raw(&name.local_part)
+    }
+
+    fn text(&mut self, s: &str) -> Result<()> {
+        self.escaped(s, |c| match c {
+            '&' => Some("&amp;"),
+            '<' => Some("&lt;"),
+            '>' => Some("&gt;"),
+            '\r' => Some("&#xD;"),
+            _ => None,
+        })
+    }
+
+    /// Write an attribute value quoted by `"`, white spaces are escaped to survive normalization.
+    fn attr_value(&mut self, s: &str) -> Result<()> {
+        self.escaped(s, |c| match c {
+            '&' => Some("&amp;"),
+            '<' => Some("&lt;"),
+            '"' => Some("&quot;"),
+            '\t' => Some("&#x9;"),
+            '\n' => Some("&#xA;"),
+            '\r' => Some("&#xD;"),
+            _ => None,
+        })
+    }
+
+    /// Write a CDATA section, split where `]]>` or characters which can't be encoded appear.
+    fn cdata(&mut self, s: &str) -> Result<()> {
+        self.write_str("<![CDATA[")?;
+
+        let mut start = 0;
+
+        for (index, c) in s.char_indices() {
+            if self.ascii && !c.is_ascii() {
+                write!(self, "{}]]>&#x{:X};<![CDATA[", &s[start..index], c as u32)?;
+
+                start = index + c.len_utf8();
+            } else if c == '>' && s[start..index].ends_with("]]") {
+                write!(self, "{}]]><![CDATA[", &s[start..index])?;
+
+                start = index;
+            }
+        }
+
+        write!(self, "{}]]>", &s[start..])?;
+
+        Ok(())
+    }
+}
+
+/// Returns the declared prefix if `attr` is a namespace declaration,
+/// the default namespace has an empty prefix.
+fn declared_prefix<'d>(attr: &'d Attr<'_>) -> Option<&'d str> {
+    match (
+        attr.name().prefix.as_deref(),
+        attr.name().local_part.as_ref(),
+    ) {
+        (Some("xmlns"), prefix) => Some(prefix),
+        (None, "xmlns") => Some(""),
+        _ => None,
+    }
+}
+
+/// A step of the traversal of the tree.
+enum Item {
+    Node(DOMObject),
+    /// The end tag of one element.
+    End(DOMObject),
+}
+
+struct State<'d, 'a, 's, 'w, W: ?Sized> {
+    document: &'d Document<'a>,
+    output: &'s mut Output<'w, W>,
+    options: &'s Serializer,
+    /// In-scope `(prefix, namespace URI)` bindings, the default namespace has an empty prefix.
+    bindings: Vec<(Cow<'d, str>, &'d str)>,
+    /// The length of `bindings` before each open element.
+    scopes: Vec<usize>,
+    /// True for the open elements of which the content is indented.
+    formatted: Vec<bool>,
+}
+
+impl<'d, 'a, 's, 'w, W: fmt::Write + ?Sized> State<'d, 'a, 's, 'w, W> {
+    fn document(&mut self, encoding: &str) -> Result<()> {
+        if self.options.xml_declaration {
+            write!(
+                self.output,
+                "<?xml version=\"1.0\" encoding=\"{}\"",
+                encoding
+            )?;
+
+            if let Some(standalone) = self.options.standalone {
+                let standalone = if standalone { "yes" } else { "no" };
+                write!(self.output, " standalone=\"{}\"", standalone)?;
+            }
+
+            self.output.raw("?>\n")?;
+        }
+
+        for (index, child) in self.document.children(None).enumerate() {
+            if index > 0 {
+                self.output.raw("\n")?;
+            }
+
+            self.subtree(child)?;
+        }
+
+        Ok(())
+    }
+
+    fn subtree(&mut self, node: &DOMObject) -> Result<()> {
+        let mut stack = vec![];
+
+        self.node(node, &mut stack)?;
+
+        while let Some(item) = stack.pop() {
+            match item {
+                Item::Node(node) => {
+                    if self.formatted.last() == Some(&true) {
+                        self.newline(self.formatted.len())?;
+                    }
+
+                    self.node(&node, &mut stack)?
+                }
+                Item::End(element) => {
+                    if self.formatted.pop() == Some(true) {
+                        self.newline(self.formatted.len())?;
+                    }
+
+                    self.end_element(&element)?
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn node(&mut self, node: &DOMObject, stack: &mut Vec<Item>) -> Result<()> {
+        let document = self.document;
+        let not_found = || Error::DOMException(ExceptionCode::NOT_FOUND_ERR);
+
+        match node.node_type() {
+            NodeType::Element => self.start_element(node, stack)?,
+            NodeType::Text => {
+                let text = document.text(node).ok_or_else(not_found)?;
+                self.output.text(text.data())?;
+            }
+            NodeType::CData => {
+                let cdata = document.cdata(node).ok_or_else(not_found)?;
+                self.output.cdata(cdata.data())?;
+            }
+            NodeType::Comment => {
+                let data = document.comment(node).ok_or_else(not_found)?.data();
+
+                if data.contains("--") || data.ends_with('-') {
+                    return Err(Error::DOMException(ExceptionCode::INVALID_CHARACTER_ERR));
+                }
+
+                self.output.raw("<!--")?;
+                self.output.raw(data)?;
+                self.output.raw("-->")?;
+            }
+            NodeType::ProcessingInstruction => {
+                let pi = document.pi(node).ok_or_else(not_found)?;
+
+                if pi.data().contains("?>") {
+                    return Err(Error::DOMException(ExceptionCode::INVALID_CHARACTER_ERR));
+                }
+
+                self.output.raw("<?")?;
+                self.output.raw(pi.target())?;
+
+                if !pi.data().is_empty() {
+                    self.output.raw(" ")?;
+                    self.output.raw(pi.data())?;
+                }
+
+                self.output.raw("?>")?;
+            }
+            NodeType::EntityReference => {
+                let entity_ref = document.entity_reference(node).ok_or_else(not_found)?;
+
+                self.output.raw("&")?;
+                self.output.raw(entity_ref.name())?;
+                self.output.raw(";")?;
+            }
+            NodeType::DocumentType => self.doc_type(node)?,
+            // namespace nodes are written as declarations of their element.
+            NodeType::Namespace => {}
+            _ => return Err(Error::DOMException(ExceptionCode::NOT_SUPPORTED_ERR)),
+        }
+
+        Ok(())
+    }
+
+    fn doc_type(&mut self, node: &DOMObject) -> Result<()> {
+        let doc_type = self
+            .document
+            .doc_type(node)
+            .ok_or(Error::DOMException(ExceptionCode::NOT_FOUND_ERR))?;
+
+        self.output.raw("<!DOCTYPE ")?;
+        self.output.raw(doc_type.name())?;
+
+        if !doc_type.public_id().is_empty() {
+            self.output.raw(" PUBLIC ")?;
+            self.literal(doc_type.public_id())?;
+            self.output.raw(" ")?;
+            self.literal(doc_type.system_id())?;
+        } else if !doc_type.system_id().is_empty() {
+            self.output.raw(" SYSTEM ")?;
+            self.literal(doc_type.system_id())?;
+        }
+
+        if !doc_type.internal_subset().is_empty() {
+            self.output.raw(" [")?;
+            self.output.raw(doc_type.internal_subset())?;
+            self.output.raw("]")?;
+        }
+
+        self.output.raw(">")
+    }
+
+    /// Write a system or public literal, quoted by `'` if it contains `"`.
+    ///
+    /// Raises `INVALID_CHARACTER_ERR` if it contains both quotes.
+    fn literal(&mut self, value: &str) -> Result<()> {
+        let quote = match (value.contains('"'), value.contains('\'')) {
+            (false, _) => "\"",
+            (true, false) => "'",
+            (true, true) => return Err(Error::DOMException(ExceptionCode::INVALID_CHARACTER_ERR)),
+        };
+
+        self.output.raw(quote)?;
+        self.output.raw(value)?;
+        self.output.raw(quote)
+    }
+
+    /// Returns the namespace URI bound to `prefix`, empty if it is not bound.
+    fn resolve(&self, prefix: &str) -> &'d str {
+        if prefix == "xml" {
+            return XML_NAMESPACE;
+        }
+
+        self.bindings
+            .iter()
+            .rev()
+            .find(|(bound, _)| *bound == prefix)
+            .map_or("", |(_, uri)| uri)
+    }
+
+    fn start_element(&mut self, node: &DOMObject, stack: &mut Vec<Item>) -> Result<()> {
+        let document = self.document;
+
+        let el = document
+            .element(node)
+            .ok_or(Error::DOMException(ExceptionCode::NOT_FOUND_ERR))?;
+
+        self.scopes.push(self.bindings.len());
+
+        // the namespace of the element, the prefix of elements without namespace URI is kept as is.
+        let prefix = el.tag().prefix.as_deref().unwrap_or("");
+        let namespace_uri = (el.namespace_uri().is_some() || prefix.is_empty())
+            .then(|| el.namespace_uri().unwrap_or(""));
+
+        // an explicit declaration binding the prefix of the element to another URI is replaced.
+        let attrs = el
+            .attributes()
+            .into_iter()
+            .filter_map(|attr| document.attr(attr))
+            .filter(|attr| match (declared_prefix(attr), namespace_uri) {
+                (Some(declared), Some(uri)) => declared != prefix || attr.value() == uri,
+                _ => true,
+            })
+            .collect::<Vec<_>>();
+
+        // declarations present as attributes.
+        for attr in &attrs {
+            if let Some(declared) = declared_prefix(attr) {
+                self.bindings.push((declared.into(), attr.value()));
+            }
+        }
+
+        let declared = self.bindings.len();
+
+        for ns in document
+            .children(Some(node))
+            .filter(|obj| obj.node_type() == NodeType::Namespace)
+            .filter_map(|obj| document.ns(obj))
+        {
+            if !self.declares(ns.prefix()) {
+                self.bindings.push((ns.prefix().into(), ns.href()));
+            }
+        }
+
+        if let Some(uri) = namespace_uri {
+            if self.resolve(prefix) != uri {
+                self.bindings.push((prefix.into(), uri));
+            }
+        }
+
+        // the namespaces of attributes, a prefix is generated for the ones without.
+        let mut prefixes = Vec::with_capacity(attrs.len());
+
+        for attr in &attrs {
+            let prefix = attr.name().prefix.as_deref();
+
+            let prefix = match (attr.namespace_uri(), prefix) {
+                (Some(uri), prefix) if uri != XMLNS_NAMESPACE && prefix != Some("xml") => {
+                    match prefix {
+                        Some(prefix) if self.resolve(prefix) == uri => Some(prefix.into()),
+                        Some(prefix) if !self.declares(prefix) => {
+                            self.bindings.push((prefix.into(), uri));
+                            Some(prefix.into())
+                        }
+                        _ => Some(self.generate_prefix(uri)),
+                    }
+                }
+                (_, prefix) => prefix.map(Cow::Borrowed),
+            };
+
+            prefixes.push(prefix);
+        }
+
+        // `(prefix, local part, value)` of namespace declarations and attributes.
+        let mut items = self.bindings[declared..]
+            .iter()
+            .map(|(prefix, uri)| match prefix.as_ref() {
+                "" => (None, Cow::Borrowed("xmlns"), *uri),
+                _ => (Some(Cow::Borrowed("xmlns")), prefix.clone(), *uri),
+            })
+            .collect::<Vec<_>>();
+
+        let decls = items.len();
+
+        items.extend(attrs.iter().zip(prefixes).map(|(attr, prefix)| {
+            (
+                prefix,
+                Cow::Borrowed(attr.name().local_part.as_ref()),
+                attr.value(),
+            )
+        }));
+
+        if self.options.sort_attributes {
+            items[..decls].sort_by(|a, b| a.1.cmp(&b.1));
+            items[decls..].sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        }
+
+        self.output.raw("<")?;
+        self.output.qname(el.tag())?;
+
+        let wrap = self.options.wrap_attributes.is_some_and(|column| {
+            let len = items
+                .iter()
+                .map(|(prefix, local_part, value)| {
+                    prefix.as_ref().map_or(0, |prefix| prefix.len() + 1)
+                        + local_part.len()
+                        + value.len()
+                        + 4
+                })
+                .sum::<usize>();
+
+            items.len() > 1 && self.output.column + len + 1 > column
+        });
+
+        let align = self.output.column + 1;
+
+        for (index, (prefix, local_part, value)) in items.iter().enumerate() {
+            if wrap && index > 0 {
+                self.output.raw("\n")?;
+                write!(self.output, "{:align$}", "")?;
+            } else {
+                self.output.raw(" ")?;
+            }
+
+            if let Some(prefix) = prefix {
+                self.output.raw(prefix)?;
+                self.output.raw(":")?;
+            }
+
+            self.output.raw(local_part)?;
+            self.output.raw("=\"")?;
+            self.output.attr_value(value)?;
+            self.output.raw("\"")?;
+        }
+
+        // the content is indented if white spaces are not significant.
+        let formatted = self.options.indent.is_some()
+            && match document.get_attribute(node, "xml:space") {
+                Some("preserve") => false,
+                Some("default") => true,
+                _ => self.formatted.last().copied().unwrap_or(true),
+            }
+            && document
+                .children(Some(node))
+                .all(|obj| match obj.node_type() {
+                    NodeType::Text => document
+                        .text(obj)
+                        .is_some_and(|text| text.data().chars().all(is_whitespace)),
+                    NodeType::CData | NodeType::EntityReference => false,
+                    _ => true,
+                });
+
+        let children = document
+            .children(Some(node))
+            .filter(|obj| match obj.node_type() {
+                NodeType::Namespace => false,
+                NodeType::Text => !formatted,
+                _ => true,
+            });
+
+        if children.clone().next().is_none() {
+            if self.options.self_closing {
+                self.output.raw("/>")?;
+                self.pop_scope();
+
+                return Ok(());
+            }
+
+            self.output.raw(">")?;
+
+            return self.end_element(node);
+        }
+
+        self.output.raw(">")?;
+
+        self.formatted.push(formatted);
+
+        stack.push(Item::End(*node));
+        stack.extend(children.rev().map(|obj| Item::Node(*obj)));
+
+        Ok(())
+    }
+
+    /// Write a line break and the indentation of `depth` levels.
+    fn newline(&mut self, depth: usize) -> Result<()> {
+        let indent = self.options.indent.as_deref().unwrap_or_default();
+
+        self.output.raw("\n")?;
+
+        for _ in 0..depth {
+            self.output.raw(indent)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if the innermost scope declares `prefix`.
+    fn declares(&self, prefix: &str) -> bool {
+        let start = self.scopes.last().copied().unwrap_or(0);
+
+        self.bindings[start..]
+            .iter()
+            .any(|(bound, _)| bound == prefix)
+    }
+
+    /// Returns a prefix bound to `uri`, a new one is declared if there is none.
+    fn generate_prefix(&mut self, uri: &'d str) -> Cow<'d, str> {
+        if let Some((prefix, _)) = self
+            .bindings
+            .iter()
+            .rev()
+            .filter(|(prefix, bound)| *bound == uri && !prefix.is_empty())
+            .find(|(prefix, _)| self.resolve(prefix) == uri)
+        {
+            return prefix.clone();
+        }
+
+        let prefix: Cow<'d, str> = (0..)
+            .map(|index| format!("ns{}", index))
+            .find(|prefix| self.resolve(prefix).is_empty() && !self.declares(prefix))
+            .expect("namespace prefix")
+            .into();
+
+        self.bindings.push((prefix.clone(), uri));
+
+        prefix
+    }
+
+    fn end_element(&mut self, node: &DOMObject) -> Result<()> {
+        let el = self
+            .document
+            .element(node)
+            .ok_or(Error::DOMException(ExceptionCode::NOT_FOUND_ERR))?;
+
+        self.output.raw("</")?;
+        self.output.qname(el.tag())?;
+        self.output.raw(">")?;
+
+        self.pop_scope();
+
+        Ok(())
+    }
+
+    fn pop_scope(&mut self) {
+        if let Some(len) = self.scopes.pop() {
+            self.bindings.truncate(len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE doc SYSTEM "doc.dtd" [
+    <!ENTITY e "entity">
+]>
+<!-- prolog -->
+<doc xmlns="urn:doc" xmlns:x="urn:x" a="&lt;&amp;&quot;'&#9;&#10;&#13;">
+    <x:item x:id="1">text &amp; &lt;markup&gt; ]]&gt; &#13;</x:item>
+    <empty/>
+    <![CDATA[<raw> ]]]]><![CDATA[> 中]]>
+    <?pi data?>
+    <inner xmlns="">&e;</inner>
+</doc>
+<?epilog?>"#;
+
+    /// Assert the subtrees of `a` and `b` have the same nodes and content.
+    fn assert_same(doc_a: &Document<'_>, a: &DOMObject, doc_b: &Document<'_>, b: &DOMObject) {
+        assert_eq!(a.node_type(), b.node_type());
+
+        match a.node_type() {
+            NodeType::Element => {
+                let (el_a, el_b) = (doc_a.element(a).unwrap(), doc_b.element(b).unwrap());
+
+                assert_eq!(el_a.tag().to_string(), el_b.tag().to_string());
+                assert_eq!(el_a.namespace_uri(), el_b.namespace_uri());
+                assert_eq!(el_a.attributes().len(), el_b.attributes().len());
+
+                for (attr_a, attr_b) in el_a.attributes().into_iter().zip(el_b.attributes()) {
+                    let (attr_a, attr_b) =
+                        (doc_a.attr(attr_a).unwrap(), doc_b.attr(attr_b).unwrap());
+
+                    assert_eq!(attr_a.name().to_string(), attr_b.name().to_string());
+                    assert_eq!(attr_a.namespace_uri(), attr_b.namespace_uri());
+                    assert_eq!(attr_a.value(), attr_b.value());
+                }
+            }
+            NodeType::Text => {
+                assert_eq!(doc_a.text(a).unwrap().data(), doc_b.text(b).unwrap().data())
+            }
+            NodeType::CData => assert_eq!(
+                doc_a.cdata(a).unwrap().data(),
+                doc_b.cdata(b).unwrap().data()
+            ),
+            NodeType::Comment => assert_eq!(
+                doc_a.comment(a).unwrap().data(),
+                doc_b.comment(b).unwrap().data()
+            ),
+            NodeType::ProcessingInstruction => {
+                let (pi_a, pi_b) = (doc_a.pi(a).unwrap(), doc_b.pi(b).unwrap());
+
+                assert_eq!((pi_a.target(), pi_a.data()), (pi_b.target(), pi_b.data()));
+            }
+            NodeType::DocumentType => {
+                let (dt_a, dt_b) = (doc_a.doc_type(a).unwrap(), doc_b.doc_type(b).unwrap());
+
+                assert_eq!(dt_a.name(), dt_b.name());
+                assert_eq!(dt_a.system_id(), dt_b.system_id());
+                assert_eq!(dt_a.internal_subset(), dt_b.internal_subset());
+            }
+            _ => {}
+        }
+
+        let children_a = doc_a.children(Some(a)).collect::<Vec<_>>();
+        let children_b = doc_b.children(Some(b)).collect::<Vec<_>>();
+
+        assert_eq!(children_a.len(), children_b.len());
+
+        for (a, b) in children_a.into_iter().zip(children_b) {
+            assert_same(doc_a, a, doc_b, b);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let doc = Document::parse(DOCUMENT).unwrap();
+
+        let output = doc.to_string();
+
+        assert!(output.starts_with(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE doc SYSTEM \"doc.dtd\" ["
+        ));
+        assert!(output.contains("<empty/>"));
+        assert!(output.contains("]]]]><![CDATA[>"));
+        assert!(output.contains("a=\"&lt;&amp;&quot;'&#x9;&#xA;&#xD;\""));
+
+        let parsed = Document::parse(&output).unwrap();
+
+        assert_same(&doc, &DOMObject::default(), &parsed, &DOMObject::default());
+
+        // subtrees are written with the namespaces they use.
+        let epilog = doc.last_child(None).unwrap();
+        let root = doc.previous_sibling(&epilog).unwrap();
+        let item = doc.children(Some(&root)).nth(1).copied().unwrap();
+
+        assert_eq!(
+            Serializer::new().to_string(&doc, Some(&item)).unwrap(),
+            "<x:item xmlns:x=\"urn:x\" x:id=\"1\">text &amp; &lt;markup&gt; ]]&gt; &#xD;</x:item>"
+        );
+    }
+
+    #[test]
+    fn test_namespace_fixup() {
+        let mut doc = Document::default();
+
+        let root = doc.create_element_ns(Some("urn:a"), "root").unwrap();
+        let child = doc.create_element_ns(Some("urn:b"), "b:child").unwrap();
+        let plain = doc.create_element("plain").unwrap();
+
+        doc.set_attribute_ns(&child, Some("urn:c"), "id", "1")
+            .unwrap();
+        doc.set_attribute_ns(&child, Some("urn:b"), "b:id", "2")
+            .unwrap();
+
+        doc.append_child(Some(&child), plain).unwrap();
+        doc.append_child(Some(&root), child).unwrap();
+        doc.append_child(None, root).unwrap();
+
+        let output = Serializer::new()
+            .xml_declaration(false)
+            .self_closing(false)
+            .to_string(&doc, None)
+            .unwrap();
+
+        assert_eq!(
+            output,
+            "<root xmlns=\"urn:a\"><b:child xmlns:b=\"urn:b\" xmlns:ns0=\"urn:c\" ns0:id=\"1\" b:id=\"2\"><plain xmlns=\"\"></plain></b:child></root>"
+        );
+
+        let parsed = Document::parse(&output).unwrap();
+        let child = parsed
+            .first_child(Some(&parsed.first_child(None).unwrap()))
+            .unwrap();
+
+        assert_eq!(
+            parsed.get_attribute_ns(&child, Some("urn:c"), "id"),
+            Some("1")
+        );
+
+        // explicit declarations conflicting with the namespace of the element are replaced.
+        let mut doc = Document::default();
+
+        let root = doc.create_element("root").unwrap();
+        let child = doc.create_element_ns(Some("urn:p"), "p:child").unwrap();
+
+        doc.set_attribute_ns(&root, Some(XMLNS_NAMESPACE), "xmlns", "urn:x")
+            .unwrap();
+        doc.set_attribute_ns(&child, Some(XMLNS_NAMESPACE), "xmlns:p", "urn:y")
+            .unwrap();
+        doc.set_attribute_ns(&child, Some(XMLNS_NAMESPACE), "xmlns:q", "urn:q")
+            .unwrap();
+
+        doc.append_child(Some(&root), child).unwrap();
+        doc.append_child(None, root).unwrap();
+
+        let output = Serializer::new()
+            .xml_declaration(false)
+            .to_string(&doc, None)
+            .unwrap();
+
+        assert_eq!(
+            output,
+            "<root><p:child xmlns:p=\"urn:p\" xmlns:q=\"urn:q\"/></root>"
+        );
+    }
+
+    #[test]
+    fn test_pretty() {
+        let doc = Document::parse(
+            r#"<config b="2" a="1"><server port="8080" host="localhost" name="primary">
+<path>/var/www</path>  <empty></empty></server><p xml:space="preserve"><a/> <b/></p><mixed>text <b>bold</b></mixed></config>"#,
+        )
+        .unwrap();
+
+        let serializer = Serializer::new()
+            .xml_declaration(false)
+            .indent_width(2)
+            .sort_attributes(true)
+            .wrap_attributes(40);
+
+        let output = serializer.to_string(&doc, None).unwrap();
+
+        assert_eq!(
+            output,
+            r#"<config a="1" b="2">
+  <server host="localhost"
+          name="primary"
+          port="8080">
+    <path>/var/www</path>
+    <empty/>
+  </server>
+  <p xml:space="preserve"><a/> <b/></p>
+  <mixed>text <b>bold</b></mixed>
+</config>"#
+        );
+
+        // the output is stable.
+        let doc = Document::parse(&output).unwrap();
+
+        assert_eq!(serializer.to_string(&doc, None).unwrap(), output);
+    }
+
+    #[test]
+    fn test_encoding() {
+        let doc = Document::parse("<a b='中'>中<![CDATA[x中y]]></a>").unwrap();
+
+        let output = Serializer::new()
+            .encoding(Encoding::Ascii)
+            .to_string(&doc, None)
+            .unwrap();
+
+        assert_eq!(
+            output,
+            "<?xml version=\"1.0\" encoding=\"US-ASCII\"?>\n<a b=\"&#x4E2D;\">&#x4E2D;<![CDATA[x]]>&#x4E2D;<![CDATA[y]]></a>"
+        );
+
+        let doc = Document::parse("<中/>").unwrap();
+
+        assert!(Serializer::new()
+            .encoding(Encoding::Ascii)
+            .to_string(&doc, None)
+            .is_err());
+
+        let mut buf = vec![];
+
+        Serializer::new()
+            .encoding(Encoding::Utf16LE)
+            .xml_declaration(false)
+            .write_io(&doc, None, &mut buf)
+            .unwrap();
+
+        assert_eq!(buf, [b'<', 0, 0x2d, 0x4e, b'/', 0, b'>', 0]);
+    }
+
+    #[test]
+    fn test_doc_type() {
+        let serialize = |system_id: &str| {
+            let mut doc = Document::default();
+            let doc_type = doc
+                .create_document_type_named("a", "", system_id, "")
+                .unwrap();
+            doc.append_child(None, doc_type).unwrap();
+
+            Serializer::new()
+                .xml_declaration(false)
+                .to_string(&doc, None)
+        };
+
+        assert_eq!(serialize("a'b").unwrap(), r#"<!DOCTYPE a SYSTEM "a'b">"#);
+        assert_eq!(serialize("a\"b").unwrap(), r#"<!DOCTYPE a SYSTEM 'a"b'>"#);
+        assert!(matches!(
+            serialize("a'\"b"),
+            Err(Error::DOMException(ExceptionCode::INVALID_CHARACTER_ERR))
+        ));
+    }
+}
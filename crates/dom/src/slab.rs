@@ -0,0 +1,238 @@
+//! Storage of one node type with generational indices, and of the states of the
+//! handles kept outside the document.
+
+use std::sync::{Arc, Weak};
+
+use crate::{DOMObject, NodeType};
+
+/// One slot of [`Slab`].
+struct Entry<T> {
+    /// Increased every time the slot is freed, so stale handles never match a new node.
+    generation: u32,
+    value: Option<T>,
+}
+
+/// A vector of slots with a free list, freed slots are reused by later insertions.
+///
+/// Nodes never move, so a [`DOMObject`] stays valid until its node is freed,
+/// and a stale one is rejected by the generation check.
+pub(crate) struct Slab<T> {
+    /// The id of the document owning this slab, handles of other documents never match.
+    document: u32,
+    entries: Vec<Entry<T>>,
+    /// Indices of free slots.
+    free: Vec<u32>,
+}
+
+impl<T> Slab<T> {
+    pub(crate) fn new(document: u32) -> Self {
+        Self {
+            document,
+            entries: vec![],
+            free: vec![],
+        }
+    }
+
+    /// Returns the number of live values.
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len() - self.free.len()
+    }
+
+    /// Insert a new value created by `f` from its handle.
+    pub(crate) fn insert_with<F>(&mut self, node_type: NodeType, f: F) -> &mut T
+    where
+        F: FnOnce(DOMObject) -> T,
+    {
+        let id = match self.free.pop() {
+            Some(id) => id as usize,
+            None => {
+                self.entries.push(Entry {
+                    generation: 0,
+                    value: None,
+                });
+
+                self.entries.len() - 1
+            }
+        };
+
+        let entry = &mut self.entries[id];
+
+        let object = DOMObject::new(self.document, id, entry.generation, node_type);
+
+        entry.value.insert(f(object))
+    }
+
+    /// Returns the value referenced by `object`, or `None` if it has been freed.
+    pub(crate) fn get(&self, object: &DOMObject) -> Option<&T> {
+        if object.document() != self.document {
+            return None;
+        }
+
+        self.entries
+            .get(object.id())
+            .filter(|entry| entry.generation == object.generation())
+            .and_then(|entry| entry.value.as_ref())
+    }
+
+    /// Returns the mutable value referenced by `object`, or `None` if it has been freed.
+    pub(crate) fn get_mut(&mut self, object: &DOMObject) -> Option<&mut T> {
+        if object.document() != self.document {
+            return None;
+        }
+
+        self.entries
+            .get_mut(object.id())
+            .filter(|entry| entry.generation == object.generation())
+            .and_then(|entry| entry.value.as_mut())
+    }
+
+    /// Free the values for which `f` returns false.
+    pub(crate) fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        for (id, entry) in self.entries.iter_mut().enumerate() {
+            if let Some(value) = &mut entry.value {
+                if !f(value) {
+                    entry.value = None;
+                    entry.generation = entry.generation.wrapping_add(1);
+                    self.free.push(id as u32);
+                }
+            }
+        }
+    }
+}
+
+/// The states of the live handles of one document, such as node iterators and ranges.
+///
+/// A handle holds the `Arc` returned by [`insert`](Self::insert), its slot is freed on
+/// the next insertion or iteration after the handle is dropped.
+#[derive(Debug)]
+pub(crate) struct Handles<T> {
+    slots: Vec<Option<(Weak<()>, T)>>,
+}
+
+impl<T> Default for Handles<T> {
+    fn default() -> Self {
+        Self { slots: vec![] }
+    }
+}
+
+impl<T> Handles<T> {
+    /// Store `state` in a free slot, returns the slot and the `Arc` to keep in the handle.
+    pub(crate) fn insert(&mut self, state: T) -> (usize, Arc<()>) {
+        self.release();
+
+        let handle = Arc::new(());
+        let value = Some((Arc::downgrade(&handle), state));
+
+        let slot = match self.slots.iter().position(Option::is_none) {
+            Some(slot) => {
+                self.slots[slot] = value;
+                slot
+            }
+            None => {
+                self.slots.push(value);
+                self.slots.len() - 1
+            }
+        };
+
+        (slot, handle)
+    }
+
+    pub(crate) fn get(&self, slot: usize) -> Option<&T> {
+        self.slots.get(slot)?.as_ref().map(|(_, state)| state)
+    }
+
+    pub(crate) fn get_mut(&mut self, slot: usize) -> Option<&mut T> {
+        self.slots.get_mut(slot)?.as_mut().map(|(_, state)| state)
+    }
+
+    /// Free `slot`, call when its handle is released explicitly.
+    pub(crate) fn remove(&mut self, slot: usize) {
+        if let Some(value) = self.slots.get_mut(slot) {
+            *value = None;
+        }
+    }
+
+    /// Returns true if no handle is live.
+    pub(crate) fn is_empty(&mut self) -> bool {
+        self.release();
+        self.slots.iter().all(Option::is_none)
+    }
+
+    /// Returns the states of the live handles.
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.release();
+        self.slots.iter_mut().flatten().map(|(_, state)| state)
+    }
+
+    /// Free the slots of the dropped handles, and the free slots at the end.
+    fn release(&mut self) {
+        for value in &mut self.slots {
+            if value
+                .as_ref()
+                .is_some_and(|(handle, _)| handle.strong_count() == 0)
+            {
+                *value = None;
+            }
+        }
+
+        while let Some(None) = self.slots.last() {
+            self.slots.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Handles, Slab};
+    use crate::NodeType;
+
+    #[test]
+    fn test_reuse() {
+        let mut slab = Slab::new(1);
+
+        let a = *slab.insert_with(NodeType::Text, |object| object);
+        let b = *slab.insert_with(NodeType::Text, |object| object);
+
+        slab.retain(|object| *object != a);
+
+        assert_eq!(slab.len(), 1);
+        assert_eq!(slab.get(&a), None);
+        assert_eq!(slab.get(&b), Some(&b));
+
+        let c = *slab.insert_with(NodeType::Text, |object| object);
+
+        assert_ne!(a, c);
+        assert_eq!(slab.get(&a), None);
+        assert_eq!(slab.get(&c), Some(&c));
+
+        assert_eq!(Slab::<()>::new(2).get(&c), None);
+    }
+
+    #[test]
+    fn test_handles() {
+        let mut handles = Handles::default();
+
+        let (a, handle_a) = handles.insert('a');
+        let (b, handle_b) = handles.insert('b');
+
+        assert_eq!((a, b), (0, 1));
+        assert_eq!(handles.get(b), Some(&'b'));
+
+        // the slot of a dropped handle is reused.
+        drop(handle_a);
+
+        let (c, _handle_c) = handles.insert('c');
+
+        assert_eq!(c, a);
+        assert_eq!(handles.iter_mut().count(), 2);
+
+        handles.remove(c);
+        drop(handle_b);
+
+        assert!(handles.is_empty());
+        assert_eq!(handles.get(b), None);
+    }
+}
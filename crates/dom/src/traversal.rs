@@ -0,0 +1,859 @@
+//! DOM Level 2 Traversal: [`TreeWalker`] and [`DOMNodeIterator`].
+//!
+//! Both keep a position in the tree rather than a borrow of the document, so the tree
+//! can be mutated between two steps. The position of a `DOMNodeIterator` is stored
+//! by the document, which moves it when the reference node is removed.
+
+use std::{
+    ops::{BitOr, BitOrAssign},
+    sync::Arc,
+};
+
+use crate::{slab::Handles, DOMObject, Document, NodeType};
+
+/// A bitmask of the node types visible to a [`TreeWalker`] or a [`DOMNodeIterator`].
+///
+/// The nodes of other types are skipped, as if the filter returned [`FilterResult::Skip`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WhatToShow(u32);
+
+impl WhatToShow {
+    pub const SHOW_ALL: Self = Self(0xFFFF_FFFF);
+    pub const SHOW_ELEMENT: Self = Self(0x1);
+    pub const SHOW_ATTRIBUTE: Self = Self(0x2);
+    pub const SHOW_TEXT: Self = Self(0x4);
+    pub const SHOW_CDATA_SECTION: Self = Self(0x8);
+    pub const SHOW_ENTITY_REFERENCE: Self = Self(0x10);
+    pub const SHOW_ENTITY: Self = Self(0x20);
+    pub const SHOW_PROCESSING_INSTRUCTION: Self = Self(0x40);
+    pub const SHOW_COMMENT: Self = Self(0x80);
+    pub const SHOW_DOCUMENT: Self = Self(0x100);
+    pub const SHOW_DOCUMENT_TYPE: Self = Self(0x200);
+    pub const SHOW_DOCUMENT_FRAGMENT: Self = Self(0x400);
+    pub const SHOW_NOTATION: Self = Self(0x800);
+
+    /// Create a mask from the bits of the DOM `SHOW_*` constants.
+    pub fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    /// Returns the bits of this mask.
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Returns true if the nodes of `node_type` are visible.
+    ///
+    /// The bit of a node type is `1 << (node_type - 1)`, so namespace nodes are
+    /// only visible with [`SHOW_ALL`](Self::SHOW_ALL).
+    pub fn shows(&self, node_type: NodeType) -> bool {
+        match node_type {
+            NodeType::Namespace => *self == Self::SHOW_ALL,
+            node_type => self.0 & (1 << (node_type as u32 - 1)) != 0,
+        }
+    }
+}
+
+impl BitOr for WhatToShow {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for WhatToShow {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// The result of a [`NodeFilter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterResult {
+    /// The node is visible.
+    Accept,
+    /// The node and its descendants are skipped by a [`TreeWalker`],
+    /// a [`DOMNodeIterator`] skips the node only.
+    Reject,
+    /// The node is skipped, its descendants are still considered.
+    Skip,
+}
+
+/// A user filter of the nodes visible to a [`TreeWalker`] or a [`DOMNodeIterator`].
+pub trait NodeFilter {
+    /// Test whether `node` is visible, only called for the node types of [`WhatToShow`].
+    fn accept_node(&self, document: &Document<'_>, node: &DOMObject) -> FilterResult;
+}
+
+impl<F> NodeFilter for F
+where
+    F: Fn(&Document<'_>, &DOMObject) -> FilterResult,
+{
+    fn accept_node(&self, document: &Document<'_>, node: &DOMObject) -> FilterResult {
+        self(document, node)
+    }
+}
+
+/// The options shared by [`TreeWalker`] and [`DOMNodeIterator`].
+struct Visibility {
+    what_to_show: WhatToShow,
+    filter: Option<Box<dyn NodeFilter>>,
+    expand_entity_references: bool,
+}
+
+impl Visibility {
+    fn new(what_to_show: WhatToShow) -> Self {
+        Self {
+            what_to_show,
+            filter: None,
+            expand_entity_references: true,
+        }
+    }
+
+    fn accept(&self, document: &Document<'_>, node: &DOMObject) -> FilterResult {
+        if !self.what_to_show.shows(node.node_type()) {
+            return FilterResult::Skip;
+        }
+
+        match &self.filter {
+            Some(filter) => filter.accept_node(document, node),
+            None => FilterResult::Accept,
+        }
+    }
+
+    fn first_child(&self, document: &Document<'_>, node: &DOMObject) -> Option<DOMObject> {
+        if node.node_type() == NodeType::EntityReference && !self.expand_entity_references {
+            return None;
+        }
+
+        document.first_child(Some(node))
+    }
+
+    fn last_child(&self, document: &Document<'_>, node: &DOMObject) -> Option<DOMObject> {
+        if node.node_type() == NodeType::EntityReference && !self.expand_entity_references {
+            return None;
+        }
+
+        document.last_child(Some(node))
+    }
+}
+
+/// Navigate the subtree of a root node, in the view of the nodes made visible
+/// by [`WhatToShow`] and a [`NodeFilter`].
+///
+/// The navigation starts from [`current_node`](Self::current_node), which is not
+/// changed if the step finds no node. Mutations of the tree are seen by the next step.
+pub struct TreeWalker {
+    root: DOMObject,
+    current_node: DOMObject,
+    visibility: Visibility,
+}
+
+impl TreeWalker {
+    /// Create a new `TreeWalker` positioned at `root`, use [`DOMObject::default`]
+    /// to walk the whole document.
+    pub fn new(root: DOMObject, what_to_show: WhatToShow) -> Self {
+        Self {
+            root,
+            current_node: root,
+            visibility: Visibility::new(what_to_show),
+        }
+    }
+
+    /// Set the user filter of the visible nodes.
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: NodeFilter + 'static,
+    {
+        self.visibility.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Visit the content of `EntityReference` nodes, default is true.
+    pub fn expand_entity_references(mut self, expand: bool) -> Self {
+        self.visibility.expand_entity_references = expand;
+        self
+    }
+
+    /// Returns the root node.
+    pub fn root(&self) -> DOMObject {
+        self.root
+    }
+
+    /// Returns the mask of the visible node types.
+    pub fn what_to_show(&self) -> WhatToShow {
+        self.visibility.what_to_show
+    }
+
+    /// Returns the node at which this walker is positioned.
+    pub fn current_node(&self) -> DOMObject {
+        self.current_node
+    }
+
+    /// Move this walker to `node`, which may be any node.
+    pub fn set_current_node(&mut self, node: DOMObject) {
+        self.current_node = node;
+    }
+
+    /// Move to the closest visible ancestor of the current node, up to the root.
+    pub fn parent_node(&mut self, document: &Document<'_>) -> Option<DOMObject> {
+        let mut node = Some(self.current_node);
+
+        while let Some(current) = node.filter(|node| *node != self.root) {
+            node = document.parent_node(&current);
+
+            if let Some(parent) = node {
+                if self.visibility.accept(document, &parent) == FilterResult::Accept {
+                    self.current_node = parent;
+                    return Some(parent);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Move to the first visible child of the current node.
+    pub fn first_child(&mut self, document: &Document<'_>) -> Option<DOMObject> {
+        self.traverse_children(document, true)
+    }
+
+    /// Move to the last visible child of the current node.
+    pub fn last_child(&mut self, document: &Document<'_>) -> Option<DOMObject> {
+        self.traverse_children(document, false)
+    }
+
+    /// Move to the previous visible sibling of the current node.
+    pub fn previous_sibling(&mut self, document: &Document<'_>) -> Option<DOMObject> {
+        self.traverse_siblings(document, false)
+    }
+
+    /// Move to the next visible sibling of the current node.
+    pub fn next_sibling(&mut self, document: &Document<'_>) -> Option<DOMObject> {
+        self.traverse_siblings(document, true)
+    }
+
+    /// Move to the previous visible node in document order.
+    pub fn previous_node(&mut self, document: &Document<'_>) -> Option<DOMObject> {
+        let mut node = self.current_node;
+
+        while node != self.root {
+            let mut sibling = document.previous_sibling(&node);
+
+            while let Some(current) = sibling {
+                node = current;
+
+                let mut result = self.visibility.accept(document, &node);
+
+                while result != FilterResult::Reject {
+                    let Some(child) = self.visibility.last_child(document, &node) else {
+                        break;
+                    };
+
+                    node = child;
+                    result = self.visibility.accept(document, &node);
+                }
+
+                if result == FilterResult::Accept {
+                    self.current_node = node;
+                    return Some(node);
+                }
+
+                sibling = document.previous_sibling(&node);
+            }
+
+            if node == self.root {
+                return None;
+            }
+
+            node = document.parent_node(&node)?;
+
+            if self.visibility.accept(document, &node) == FilterResult::Accept {
+                self.current_node = node;
+                return Some(node);
+            }
+        }
+
+        None
+    }
+
+    /// Move to the next visible node in document order.
+    pub fn next_node(&mut self, document: &Document<'_>) -> Option<DOMObject> {
+        let mut node = self.current_node;
+        let mut result = FilterResult::Accept;
+
+        loop {
+            while result != FilterResult::Reject {
+                let Some(child) = self.visibility.first_child(document, &node) else {
+                    break;
+                };
+
+                node = child;
+                result = self.visibility.accept(document, &node);
+
+                if result == FilterResult::Accept {
+                    self.current_node = node;
+                    return Some(node);
+                }
+            }
+
+            node = following(document, &self.root, &node)?;
+            result = self.visibility.accept(document, &node);
+
+            if result == FilterResult::Accept {
+                self.current_node = node;
+                return Some(node);
+            }
+        }
+    }
+
+    fn traverse_children(&mut self, document: &Document<'_>, first: bool) -> Option<DOMObject> {
+        let visibility = &self.visibility;
+
+        let child = |node: &DOMObject| match first {
+            true => visibility.first_child(document, node),
+            false => visibility.last_child(document, node),
+        };
+
+        let sibling = |node: &DOMObject| match first {
+            true => document.next_sibling(node),
+            false => document.previous_sibling(node),
+        };
+
+        let mut node = child(&self.current_node);
+
+        'outer: while let Some(mut current) = node {
+            match visibility.accept(document, &current) {
+                FilterResult::Accept => {
+                    self.current_node = current;
+                    return Some(current);
+                }
+                FilterResult::Skip => {
+                    if let Some(child) = child(&current) {
+                        node = Some(child);
+                        continue;
+                    }
+                }
+                FilterResult::Reject => {}
+            }
+
+            loop {
+                if let Some(sibling) = sibling(&current) {
+                    node = Some(sibling);
+                    continue 'outer;
+                }
+
+                match document.parent_node(&current) {
+                    Some(parent) if parent != self.root && parent != self.current_node => {
+                        current = parent;
+                    }
+                    _ => return None,
+                }
+            }
+        }
+
+        None
+    }
+
+    fn traverse_siblings(&mut self, document: &Document<'_>, next: bool) -> Option<DOMObject> {
+        let visibility = &self.visibility;
+
+        let child = |node: &DOMObject| match next {
+            true => visibility.first_child(document, node),
+            false => visibility.last_child(document, node),
+        };
+
+        let sibling = |node: &DOMObject| match next {
+            true => document.next_sibling(node),
+            false => document.previous_sibling(node),
+        };
+
+        let mut node = self.current_node;
+
+        if node == self.root {
+            return None;
+        }
+
+        loop {
+            let mut next_node = sibling(&node);
+
+            while let Some(current) = next_node {
+                node = current;
+
+                let result = visibility.accept(document, &node);
+
+                if result == FilterResult::Accept {
+                    self.current_node = node;
+                    return Some(node);
+                }
+
+                next_node = match result {
+                    FilterResult::Reject => None,
+                    _ => child(&node),
+                };
+
+                if next_node.is_none() {
+                    next_node = sibling(&node);
+                }
+            }
+
+            node = document.parent_node(&node)?;
+
+            if node == self.root || visibility.accept(document, &node) == FilterResult::Accept {
+                return None;
+            }
+        }
+    }
+}
+
+/// Returns the node following the subtree of `node` in document order, inside `root`.
+fn following(document: &Document<'_>, root: &DOMObject, node: &DOMObject) -> Option<DOMObject> {
+    let mut node = *node;
+
+    loop {
+        if node == *root {
+            return None;
+        }
+
+        if let Some(sibling) = document.next_sibling(&node) {
+            return Some(sibling);
+        }
+
+        node = document.parent_node(&node)?;
+    }
+}
+
+/// Returns true if `ancestor` is `node` or one of its ancestors.
+pub(crate) fn is_inclusive_ancestor(
+    document: &Document<'_>,
+    ancestor: &DOMObject,
+    node: &DOMObject,
+) -> bool {
+    let mut node = Some(*node);
+
+    while let Some(current) = node {
+        if current == *ancestor {
+            return true;
+        }
+
+        node = document.parent_node(&current);
+    }
+
+    false
+}
+
+/// The position of one [`DOMNodeIterator`], stored by the document.
+#[derive(Debug, Clone, Copy)]
+struct IteratorState {
+    root: DOMObject,
+    reference: DOMObject,
+    pointer_before_reference: bool,
+}
+
+/// The positions of the live [`DOMNodeIterator`]s of one document.
+#[derive(Debug, Default)]
+pub(crate) struct NodeIterators {
+    slots: Handles<IteratorState>,
+}
+
+/// Iterate the nodes of the subtree of a root node in document order, in the view
+/// of the nodes made visible by [`WhatToShow`] and a [`NodeFilter`].
+///
+/// The position is kept by the document, and moved to a neighbouring node when the
+/// reference node or one of its ancestors is removed, so the iteration continues
+/// as expected after a mutation. The position is released by [`detach`](Self::detach)
+/// or when the iterator is dropped.
+///
+/// This is the DOM `NodeIterator`, named apart from [`NodeIterator`](crate::NodeIterator),
+/// the iterator over the children of a node.
+pub struct DOMNodeIterator {
+    document: u32,
+    slot: usize,
+    root: DOMObject,
+    visibility: Visibility,
+    /// Frees the slot of the position when dropped.
+    _handle: Arc<()>,
+}
+
+impl DOMNodeIterator {
+    /// Set the user filter of the visible nodes, [`FilterResult::Reject`] has the
+    /// same effect as [`FilterResult::Skip`].
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: NodeFilter + 'static,
+    {
+        self.visibility.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Visit the content of `EntityReference` nodes, default is true.
+    pub fn expand_entity_references(mut self, expand: bool) -> Self {
+        self.visibility.expand_entity_references = expand;
+        self
+    }
+
+    /// Returns the root node.
+    pub fn root(&self) -> DOMObject {
+        self.root
+    }
+
+    /// Returns the mask of the visible node types.
+    pub fn what_to_show(&self) -> WhatToShow {
+        self.visibility.what_to_show
+    }
+
+    /// Returns the node the iterator is positioned at, before or after it.
+    pub fn reference_node(&self, document: &Document<'_>) -> Option<DOMObject> {
+        self.state(document).map(|state| state.reference)
+    }
+
+    /// Returns true if the iterator is positioned before the reference node.
+    pub fn pointer_before_reference(&self, document: &Document<'_>) -> bool {
+        self.state(document)
+            .is_some_and(|state| state.pointer_before_reference)
+    }
+
+    /// Returns the next visible node and moves the position after it.
+    pub fn next_node(&self, document: &mut Document<'_>) -> Option<DOMObject> {
+        self.traverse(document, true)
+    }
+
+    /// Returns the previous visible node and moves the position before it.
+    pub fn previous_node(&self, document: &mut Document<'_>) -> Option<DOMObject> {
+        self.traverse(document, false)
+    }
+
+    /// Release the position kept by `document`.
+    pub fn detach(self, document: &mut Document<'_>) {
+        if self.document == document.id() {
+            document.node_iterators.slots.remove(self.slot);
+        }
+    }
+
+    fn state(&self, document: &Document<'_>) -> Option<IteratorState> {
+        if self.document != document.id() {
+            return None;
+        }
+
+        document.node_iterators.slots.get(self.slot).copied()
+    }
+
+    fn traverse(&self, document: &mut Document<'_>, next: bool) -> Option<DOMObject> {
+        let mut state = self.state(document)?;
+
+        let mut node = state.reference;
+        let mut before = state.pointer_before_reference;
+
+        loop {
+            if next {
+                if before {
+                    before = false;
+                } else {
+                    node = match self.visibility.first_child(document, &node) {
+                        Some(child) => child,
+                        None => following(document, &self.root, &node)?,
+                    };
+                }
+            } else if before {
+                node = self.preceding(document, &node)?;
+            } else {
+                before = true;
+            }
+
+            if self.visibility.accept(document, &node) == FilterResult::Accept {
+                break;
+            }
+        }
+
+        state.reference = node;
+        state.pointer_before_reference = before;
+
+        if let Some(slot) = document.node_iterators.slots.get_mut(self.slot) {
+            *slot = state;
+        }
+
+        Some(node)
+    }
+
+    /// Returns the node preceding `node` in document order, inside the root.
+    fn preceding(&self, document: &Document<'_>, node: &DOMObject) -> Option<DOMObject> {
+        if *node == self.root {
+            return None;
+        }
+
+        let Some(mut node) = document.previous_sibling(node) else {
+            return document.parent_node(node);
+        };
+
+        while let Some(child) = self.visibility.last_child(document, &node) {
+            node = child;
+        }
+
+        Some(node)
+    }
+}
+
+impl<'a> Document<'a> {
+    /// Create a [`DOMNodeIterator`] over the subtree of `root`, positioned before `root`.
+    ///
+    /// Use [`DOMObject::default`] as `root` to iterate the whole document.
+    pub fn create_node_iterator(
+        &mut self,
+        root: &DOMObject,
+        what_to_show: WhatToShow,
+    ) -> DOMNodeIterator {
+        let state = IteratorState {
+            root: *root,
+            reference: *root,
+            pointer_before_reference: true,
+        };
+
+        let (slot, handle) = self.node_iterators.slots.insert(state);
+
+        DOMNodeIterator {
+            document: self.id(),
+            slot,
+            root: *root,
+            visibility: Visibility::new(what_to_show),
+            _handle: handle,
+        }
+    }
+
+    /// Create a [`TreeWalker`] over the subtree of `root`, positioned at `root`.
+    pub fn create_tree_walker(&self, root: &DOMObject, what_to_show: WhatToShow) -> TreeWalker {
+        TreeWalker::new(*root, what_to_show)
+    }
+
+    /// Move the live node iterators whose reference node is in the subtree of `node`,
+    /// call before `node` is removed from its parent.
+    pub(crate) fn remove_from_iterators(&mut self, node: &DOMObject) {
+        if self.node_iterators.slots.is_empty() {
+            return;
+        }
+
+        let mut slots = std::mem::take(&mut self.node_iterators.slots);
+
+        for state in slots.iter_mut() {
+            if *node == state.root || !is_inclusive_ancestor(self, node, &state.reference) {
+                continue;
+            }
+
+            if state.pointer_before_reference {
+                if let Some(next) = following(self, &state.root, node)
+                    .filter(|next| is_inclusive_ancestor(self, &state.root, next))
+                {
+                    state.reference = next;
+                    continue;
+                }
+
+                state.pointer_before_reference = false;
+            }
+
+            state.reference = match self.previous_sibling(node) {
+                Some(mut previous) => {
+                    while let Some(child) = self.last_child(Some(&previous)) {
+                        previous = child;
+                    }
+
+                    previous
+                }
+                None => self.parent_node(node).unwrap_or(state.root),
+            };
+        }
+
+        self.node_iterators.slots = slots;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = "<a><b>1<c/>2</b><!--x--><d><e>3</e></d></a>";
+
+    fn names(document: &Document<'_>, nodes: &[DOMObject]) -> Vec<String> {
+        nodes
+            .iter()
+            .map(|node| match node.node_type() {
+                NodeType::Element => document.element(node).unwrap().tag().to_string(),
+                NodeType::Text => document.text(node).unwrap().data().to_owned(),
+                NodeType::Comment => "#comment".to_owned(),
+                node_type => format!("{:?}", node_type),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_what_to_show() {
+        let show = WhatToShow::SHOW_ELEMENT | WhatToShow::SHOW_COMMENT;
+
+        assert!(show.shows(NodeType::Element));
+        assert!(show.shows(NodeType::Comment));
+        assert!(!show.shows(NodeType::Text));
+        assert!(!show.shows(NodeType::Namespace));
+        assert!(WhatToShow::SHOW_ALL.shows(NodeType::Namespace));
+        assert_eq!(WhatToShow::from_bits(0x80), WhatToShow::SHOW_COMMENT);
+    }
+
+    #[test]
+    fn test_tree_walker() {
+        let doc = Document::parse(DOCUMENT).unwrap();
+        let a = doc.first_child(None).unwrap();
+
+        let mut walker =
+            doc.create_tree_walker(&a, WhatToShow::SHOW_ELEMENT | WhatToShow::SHOW_TEXT);
+
+        let mut forward = vec![];
+        while let Some(node) = walker.next_node(&doc) {
+            forward.push(node);
+        }
+
+        assert_eq!(names(&doc, &forward), ["b", "1", "c", "2", "d", "e", "3"]);
+
+        let mut backward = vec![];
+        while let Some(node) = walker.previous_node(&doc) {
+            backward.push(node);
+        }
+
+        assert_eq!(names(&doc, &backward), ["e", "d", "2", "c", "1", "b", "a"]);
+        assert_eq!(walker.current_node(), a);
+
+        // reject `b` and its descendants, skip `d` but not `e`.
+        let mut walker = TreeWalker::new(a, WhatToShow::SHOW_ELEMENT).filter(
+            |doc: &Document<'_>, node: &DOMObject| match doc
+                .element(node)
+                .unwrap()
+                .tag()
+                .to_string()
+                .as_str()
+            {
+                "b" => FilterResult::Reject,
+                "d" => FilterResult::Skip,
+                _ => FilterResult::Accept,
+            },
+        );
+
+        assert_eq!(names(&doc, &[walker.first_child(&doc).unwrap()]), ["e"]);
+        assert_eq!(walker.parent_node(&doc), Some(a));
+        assert_eq!(names(&doc, &[walker.last_child(&doc).unwrap()]), ["e"]);
+        assert_eq!(walker.previous_sibling(&doc), None);
+        assert_eq!(walker.next_sibling(&doc), None);
+        assert_eq!(walker.next_node(&doc), None);
+
+        let mut walker = doc.create_tree_walker(&a, WhatToShow::SHOW_ALL);
+        walker.first_child(&doc);
+
+        assert_eq!(
+            names(&doc, &[walker.next_sibling(&doc).unwrap()]),
+            ["#comment"]
+        );
+        assert_eq!(names(&doc, &[walker.next_sibling(&doc).unwrap()]), ["d"]);
+        assert_eq!(
+            names(&doc, &[walker.previous_sibling(&doc).unwrap()]),
+            ["#comment"]
+        );
+    }
+
+    #[test]
+    fn test_node_iterator() {
+        let mut doc = Document::parse(DOCUMENT).unwrap();
+        let a = doc.first_child(None).unwrap();
+
+        let iter = doc.create_node_iterator(&DOMObject::default(), WhatToShow::SHOW_ELEMENT);
+
+        let mut nodes = vec![];
+        while let Some(node) = iter.next_node(&mut doc) {
+            nodes.push(node);
+        }
+
+        assert_eq!(names(&doc, &nodes), ["a", "b", "c", "d", "e"]);
+        assert_eq!(iter.previous_node(&mut doc), nodes.last().copied());
+        assert_eq!(iter.previous_node(&mut doc), Some(nodes[3]));
+        assert!(iter.pointer_before_reference(&doc));
+
+        iter.detach(&mut doc);
+
+        // reject has the effect of skip.
+        let iter = doc.create_node_iterator(&a, WhatToShow::SHOW_ALL).filter(
+            |_: &Document<'_>, node: &DOMObject| match node.node_type() {
+                NodeType::Element => FilterResult::Reject,
+                _ => FilterResult::Accept,
+            },
+        );
+
+        let mut nodes = vec![];
+        while let Some(node) = iter.next_node(&mut doc) {
+            nodes.push(node);
+        }
+
+        assert_eq!(names(&doc, &nodes), ["1", "2", "#comment", "3"]);
+
+        iter.detach(&mut doc);
+    }
+
+    #[test]
+    fn test_node_iterator_mutation() {
+        let mut doc = Document::parse(DOCUMENT).unwrap();
+        let a = doc.first_child(None).unwrap();
+
+        let iter = doc.create_node_iterator(&a, WhatToShow::SHOW_ELEMENT);
+
+        let node = iter.next_node(&mut doc).unwrap();
+        assert_eq!(names(&doc, &[node]), ["a"]);
+
+        let b = iter.next_node(&mut doc).unwrap();
+        let c = iter.next_node(&mut doc).unwrap();
+
+        // remove the reference node while the pointer is after it.
+        doc.remove_child(Some(&b), &c).unwrap();
+
+        assert_eq!(
+            iter.reference_node(&doc).map(|node| names(&doc, &[node])),
+            Some(vec!["1".to_owned()])
+        );
+        let node = iter.next_node(&mut doc).unwrap();
+        assert_eq!(names(&doc, &[node]), ["d"]);
+
+        // remove the parent of the reference node while the pointer is before it.
+        let node = iter.previous_node(&mut doc).unwrap();
+        assert_eq!(names(&doc, &[node]), ["d"]);
+        let node = iter.next_node(&mut doc).unwrap();
+        assert_eq!(names(&doc, &[node]), ["d"]);
+        let e = iter.next_node(&mut doc).unwrap();
+        assert_eq!(iter.previous_node(&mut doc), Some(e));
+
+        let d = doc.parent_node(&e).unwrap();
+        doc.remove_child(Some(&a), &d).unwrap();
+
+        // no node follows, so the pointer moves after the comment.
+        assert!(!iter.pointer_before_reference(&doc));
+        assert_eq!(iter.next_node(&mut doc), None);
+        let node = iter.previous_node(&mut doc).unwrap();
+        assert_eq!(names(&doc, &[node]), ["b"]);
+
+        iter.detach(&mut doc);
+    }
+
+    #[test]
+    fn test_node_iterator_drop() {
+        let mut doc = Document::parse(DOCUMENT).unwrap();
+
+        let first = doc.create_node_iterator(&DOMObject::default(), WhatToShow::SHOW_ALL);
+        let second = doc.create_node_iterator(&DOMObject::default(), WhatToShow::SHOW_ALL);
+
+        assert_eq!(doc.node_iterators.slots.iter_mut().count(), 2);
+
+        // the slot of a dropped iterator is freed without detach.
+        drop(first);
+        assert_eq!(doc.node_iterators.slots.iter_mut().count(), 1);
+
+        let third = doc.create_node_iterator(&DOMObject::default(), WhatToShow::SHOW_ALL);
+        assert_eq!(third.next_node(&mut doc), Some(DOMObject::default()));
+        assert!(second.pointer_before_reference(&doc));
+
+        second.detach(&mut doc);
+        drop(third);
+        assert!(doc.node_iterators.slots.is_empty());
+    }
+}
@@ -0,0 +1,1120 @@
+//! Evaluation of parsed expressions and the core function library.
+
+use std::{cell::RefCell, collections::HashMap};
+
+use crate::{
+    arena::is_child_node, DOMObject, Document, Error, NodeType, Result, XMLNS_NAMESPACE,
+    XML_NAMESPACE,
+};
+
+use super::{
+    parser::{Axis, BinaryOp, Expr, NodeTest, Step},
+    Value, XPathContext, XPathNode,
+};
+
+fn error<T>(reason: &str) -> Result<T> {
+    Err(Error::XPath(reason.to_owned()))
+}
+
+/// The context node, position and size of the evaluation of one expression.
+struct Focus {
+    node: XPathNode,
+    position: usize,
+    size: usize,
+}
+
+pub(crate) struct Evaluator<'e, 'd, 'a> {
+    document: &'d Document<'a>,
+    context: &'e XPathContext,
+    /// The document order and the index in the children list of the parent of the nodes,
+    /// computed once per tree on first use.
+    order: RefCell<HashMap<DOMObject, (usize, usize)>>,
+}
+
+impl<'e, 'd, 'a> Evaluator<'e, 'd, 'a> {
+    pub(crate) fn new(document: &'d Document<'a>, context: &'e XPathContext) -> Self {
+        Self {
+            document,
+            context,
+            order: Default::default(),
+        }
+    }
+
+    pub(crate) fn evaluate(&self, expr: &Expr, node: XPathNode) -> Result<Value> {
+        self.eval(
+            expr,
+            &Focus {
+                node,
+                position: 1,
+                size: 1,
+            },
+        )
+    }
+
+    fn eval(&self, expr: &Expr, focus: &Focus) -> Result<Value> {
+        let document = self.document;
+
+        match expr {
+            Expr::Binary(BinaryOp::Or, left, right) => Ok(Value::Boolean(
+                to_boolean(&self.eval(left, focus)?) || to_boolean(&self.eval(right, focus)?),
+            )),
+            Expr::Binary(BinaryOp::And, left, right) => Ok(Value::Boolean(
+                to_boolean(&self.eval(left, focus)?) && to_boolean(&self.eval(right, focus)?),
+            )),
+            Expr::Binary(BinaryOp::Union, left, right) => {
+                let mut nodes = self.node_set(left, focus)?;
+                nodes.extend(self.node_set(right, focus)?);
+
+                Ok(Value::NodeSet(self.sort(nodes)))
+            }
+            Expr::Binary(
+                op @ (BinaryOp::Eq
+                | BinaryOp::Ne
+                | BinaryOp::Lt
+                | BinaryOp::Le
+                | BinaryOp::Gt
+                | BinaryOp::Ge),
+                left,
+                right,
+            ) => {
+                let left = self.eval(left, focus)?;
+                let right = self.eval(right, focus)?;
+
+                Ok(Value::Boolean(compare(document, *op, &left, &right)))
+            }
+            Expr::Binary(op, left, right) => {
+                let left = to_number(document, &self.eval(left, focus)?);
+                let right = to_number(document, &self.eval(right, focus)?);
+
+                let value = match op {
+                    BinaryOp::Add => left + right,
+                    BinaryOp::Sub => left - right,
+                    BinaryOp::Mul => left * right,
+                    BinaryOp::Div => left / right,
+                    _ => left % right,
+                };
+
+                Ok(Value::Number(value))
+            }
+            Expr::Negate(expr) => Ok(Value::Number(-to_number(
+                document,
+                &self.eval(expr, focus)?,
+            ))),
+            Expr::Literal(literal) => Ok(Value::String(literal.clone())),
+            Expr::Number(number) => Ok(Value::Number(*number)),
+            Expr::Variable(name) => match self.context.variables.get(name) {
+                Some(value) => Ok(value.clone()),
+                None => Err(Error::XPath(format!("undefined variable ${}", name))),
+            },
+            Expr::Function(name, args) => self.function(name, args, focus),
+            Expr::Filter(primary, predicates) => {
+                let mut nodes = self.node_set(primary, focus)?;
+
+                for predicate in predicates {
+                    nodes = self.filter(nodes, predicate)?;
+                }
+
+                Ok(Value::NodeSet(nodes))
+            }
+            Expr::Path(start, steps) => {
+                let mut nodes = self.node_set(start, focus)?;
+
+                for step in steps {
+                    let mut selected = vec![];
+
+                    for node in &nodes {
+                        selected.extend(self.step(node, step)?);
+                    }
+
+                    nodes = self.sort(selected);
+                }
+
+                Ok(Value::NodeSet(nodes))
+            }
+            Expr::Root => Ok(Value::NodeSet(vec![root(document, &focus.node)])),
+            Expr::ContextNode => Ok(Value::NodeSet(vec![focus.node.clone()])),
+        }
+    }
+
+    fn node_set(&self, expr: &Expr, focus: &Focus) -> Result<Vec<XPathNode>> {
+        match self.eval(expr, focus)? {
+            Value::NodeSet(nodes) => Ok(nodes),
+            _ => error("expected a node-set"),
+        }
+    }
+
+    /// Keep the nodes for which `predicate` is true, `nodes` are in the order of the axis.
+    fn filter(&self, nodes: Vec<XPathNode>, predicate: &Expr) -> Result<Vec<XPathNode>> {
+        let size = nodes.len();
+        let mut selected = vec![];
+
+        for (index, node) in nodes.into_iter().enumerate() {
+            let focus = Focus {
+                node,
+                position: index + 1,
+                size,
+            };
+
+            let keep = match self.eval(predicate, &focus)? {
+                Value::Number(number) => number == focus.position as f64,
+                value => to_boolean(&value),
+            };
+
+            if keep {
+                selected.push(focus.node);
+            }
+        }
+
+        Ok(selected)
+    }
+
+    /// Returns the nodes selected by `step` from `node`, in the order of the axis.
+    fn step(&self, node: &XPathNode, step: &Step) -> Result<Vec<XPathNode>> {
+        let mut nodes = vec![];
+
+        for candidate in self.axis(node, step.axis) {
+            if self.test(&candidate, step)? {
+                nodes.push(candidate);
+            }
+        }
+
+        for predicate in &step.predicates {
+            nodes = self.filter(nodes, predicate)?;
+        }
+
+        Ok(nodes)
+    }
+
+    fn test(&self, node: &XPathNode, step: &Step) -> Result<bool> {
+        let document = self.document;
+
+        let object = match node {
+            XPathNode::Node(object) => object,
+            XPathNode::Namespace { prefix, .. } => {
+                return match &step.test {
+                    NodeTest::Node => Ok(true),
+                    NodeTest::Name {
+                        prefix: None,
+                        local_name,
+                    } => Ok(step.axis == Axis::Namespace
+                        && local_name.as_ref().is_none_or(|name| name == prefix)),
+                    _ => Ok(false),
+                }
+            }
+        };
+
+        let node_type = object.node_type();
+
+        match &step.test {
+            NodeTest::Node => Ok(true),
+            NodeTest::Text => Ok(matches!(node_type, NodeType::Text | NodeType::CData)),
+            NodeTest::Comment => Ok(node_type == NodeType::Comment),
+            NodeTest::ProcessingInstruction(target) => Ok(node_type
+                == NodeType::ProcessingInstruction
+                && target.as_ref().is_none_or(|target| {
+                    document.pi(object).is_some_and(|pi| pi.target() == target)
+                })),
+            NodeTest::Name { prefix, local_name } => {
+                // the principal node type of the axis.
+                let principal = match step.axis {
+                    Axis::Attribute => NodeType::Attribute,
+                    Axis::Namespace => return Ok(false),
+                    _ => NodeType::Element,
+                };
+
+                if node_type != principal {
+                    return Ok(false);
+                }
+
+                let uri = match prefix {
+                    Some(prefix) => match self.context.namespaces.get(prefix) {
+                        Some(uri) => Some(uri.as_str()),
+                        None => return Err(Error::XPath(format!("undeclared prefix {}", prefix))),
+                    },
+                    None => None,
+                };
+
+                let (node_uri, node_local_name) = match node_type {
+                    NodeType::Element => {
+                        let el = document.element(object);
+                        (
+                            el.and_then(|el| el.namespace_uri()),
+                            el.map(|el| el.tag().local_part.as_ref()),
+                        )
+                    }
+                    _ => {
+                        let attr = document.attr(object);
+                        (
+                            attr.and_then(|attr| attr.namespace_uri()),
+                            attr.map(|attr| attr.name().local_part.as_ref()),
+                        )
+                    }
+                };
+
+                // `*` matches any name, `p:*` any name in the namespace of `p`.
+                let any = prefix.is_none() && local_name.is_none();
+
+                Ok((any || node_uri == uri)
+                    && local_name
+                        .as_deref()
+                        .is_none_or(|name| node_local_name == Some(name)))
+            }
+        }
+    }
+
+    /// Returns the nodes of `axis` from `node`, in the order of the axis.
+    fn axis(&self, node: &XPathNode, axis: Axis) -> Vec<XPathNode> {
+        let document = self.document;
+        let mut nodes = vec![];
+
+        match axis {
+            Axis::Child => {
+                if let XPathNode::Node(object) = node {
+                    nodes = children(document, object);
+                }
+            }
+            Axis::Descendant => descendants(document, node, &mut nodes),
+            Axis::DescendantOrSelf => {
+                nodes.push(node.clone());
+                descendants(document, node, &mut nodes);
+            }
+            Axis::Parent => nodes.extend(parent(document, node)),
+            Axis::Ancestor => {
+                nodes = ancestors_or_self(document, node);
+                nodes.remove(0);
+            }
+            Axis::AncestorOrSelf => nodes = ancestors_or_self(document, node),
+            Axis::SelfNode => nodes.push(node.clone()),
+            Axis::FollowingSibling => nodes = self.siblings(node).1,
+            Axis::PrecedingSibling => {
+                nodes = self.siblings(node).0;
+                nodes.reverse();
+            }
+            Axis::Following => {
+                let mut current = node.clone();
+
+                // the content of the owner element follows its attributes and namespaces.
+                if !matches!(&current, XPathNode::Node(object) if object.node_type() != NodeType::Attribute)
+                {
+                    let Some(owner) = parent(document, node) else {
+                        return nodes;
+                    };
+
+                    current = owner;
+                    descendants(document, &current, &mut nodes);
+                }
+
+                for ancestor in ancestors_or_self(document, &current) {
+                    for sibling in self.siblings(&ancestor).1 {
+                        nodes.push(sibling.clone());
+                        descendants(document, &sibling, &mut nodes);
+                    }
+                }
+            }
+            Axis::Preceding => {
+                let mut current = node.clone();
+
+                if !matches!(&current, XPathNode::Node(object) if object.node_type() != NodeType::Attribute)
+                {
+                    let Some(owner) = parent(document, node) else {
+                        return nodes;
+                    };
+
+                    current = owner;
+                }
+
+                for ancestor in ancestors_or_self(document, &current) {
+                    for sibling in self.siblings(&ancestor).0.into_iter().rev() {
+                        let mut subtree = vec![sibling.clone()];
+                        descendants(document, &sibling, &mut subtree);
+
+                        nodes.extend(subtree.into_iter().rev());
+                    }
+                }
+            }
+            Axis::Attribute => {
+                if let XPathNode::Node(object) = node {
+                    if let Some(el) = Some(object)
+                        .filter(|object| object.node_type() == NodeType::Element)
+                        .and_then(|object| document.element(object))
+                    {
+                        nodes.extend(
+                            el.attributes()
+                                .into_iter()
+                                .filter(|attr| {
+                                    document.attr(attr).is_some_and(|attr| {
+                                        attr.namespace_uri() != Some(XMLNS_NAMESPACE)
+                                    })
+                                })
+                                .map(|attr| XPathNode::Node(*attr)),
+                        );
+                    }
+                }
+            }
+            Axis::Namespace => {
+                if let XPathNode::Node(element) = node {
+                    if element.node_type() == NodeType::Element {
+                        nodes.extend(namespaces(document, element).into_iter().map(
+                            |(prefix, uri)| XPathNode::Namespace {
+                                element: *element,
+                                prefix,
+                                uri,
+                            },
+                        ));
+                    }
+                }
+            }
+        }
+
+        nodes
+    }
+
+    /// Returns the siblings of `node` before and after it.
+    ///
+    /// The siblings are read from the children list of the parent at the index of `node`,
+    /// then from the lists of the enclosing entity references.
+    fn siblings(&self, node: &XPathNode) -> (Vec<XPathNode>, Vec<XPathNode>) {
+        let document = self.document;
+
+        let (mut preceding, mut following) = (vec![], vec![]);
+
+        let mut current = match node {
+            XPathNode::Node(object) if object.node_type() != NodeType::Attribute => *object,
+            _ => return (preceding, following),
+        };
+
+        while let Some(parent) = document.parent_node(&current) {
+            let index = self.position(&current).1;
+            let list = Some(&parent).filter(|parent| parent.node_type() != NodeType::Document);
+
+            let mut before = vec![];
+
+            for child in document.children(list).take(index) {
+                push_child(document, child, &mut before);
+            }
+
+            before.append(&mut preceding);
+            preceding = before;
+
+            for child in document.children(list).skip(index + 1) {
+                push_child(document, child, &mut following);
+            }
+
+            if parent.node_type() != NodeType::EntityReference {
+                break;
+            }
+
+            current = parent;
+        }
+
+        (preceding, following)
+    }
+
+    /// Returns the document order and the index in the children list of the parent of `object`.
+    ///
+    /// The tree of `object` is indexed on first use, the trees are ordered by first use.
+    fn position(&self, object: &DOMObject) -> (usize, usize) {
+        if let Some(position) = self.order.borrow().get(object) {
+            return *position;
+        }
+
+        let document = self.document;
+        let mut root = *object;
+
+        while let Some(parent) = document
+            .parent_node(&root)
+            .or_else(|| document.owner_element(&root))
+        {
+            root = parent;
+        }
+
+        let mut order = self.order.borrow_mut();
+
+        if !order.contains_key(&root) {
+            index(document, &root, 0, &mut order);
+        }
+
+        let next = order.len();
+
+        *order.entry(*object).or_insert((next, 0))
+    }
+
+    /// Sort `nodes` in document order and remove duplicates.
+    ///
+    /// The namespaces and attributes of an element are ordered after it and before its children.
+    fn sort(&self, mut nodes: Vec<XPathNode>) -> Vec<XPathNode> {
+        if nodes.len() < 2 {
+            return nodes;
+        }
+
+        // namespace nodes are ordered by prefix, as returned by `namespaces`.
+        let key = |node: &XPathNode| match node {
+            XPathNode::Namespace {
+                element, prefix, ..
+            } => (self.position(element).0, Some(prefix.clone())),
+            XPathNode::Node(object) => (self.position(object).0, None),
+        };
+
+        nodes.sort_by_cached_key(key);
+        nodes.dedup();
+
+        nodes
+    }
+
+    fn function(&self, name: &str, args: &[Expr], focus: &Focus) -> Result<Value> {
+        let document = self.document;
+
+        let arity = |min: usize, max: usize| {
+            if args.len() < min || args.len() > max {
+                Err(Error::XPath(format!(
+                    "wrong number of arguments of {}()",
+                    name
+                )))
+            } else {
+                Ok(())
+            }
+        };
+
+        let string = |index: usize| -> Result<String> {
+            match args.get(index) {
+                Some(arg) => Ok(to_string(document, &self.eval(arg, focus)?)),
+                None => Ok(string_value(document, &focus.node)),
+            }
+        };
+
+        let number = |index: usize| -> Result<f64> {
+            Ok(to_number(document, &self.eval(&args[index], focus)?))
+        };
+
+        // the first node of the node-set argument, or the context node.
+        let node = || -> Result<Option<XPathNode>> {
+            match args.first() {
+                Some(arg) => Ok(self.node_set(arg, focus)?.into_iter().next()),
+                None => Ok(Some(focus.node.clone())),
+            }
+        };
+
+        let value = match name {
+            "last" => {
+                arity(0, 0)?;
+                Value::Number(focus.size as f64)
+            }
+            "position" => {
+                arity(0, 0)?;
+                Value::Number(focus.position as f64)
+            }
+            "count" => {
+                arity(1, 1)?;
+                Value::Number(self.node_set(&args[0], focus)?.len() as f64)
+            }
+            "id" => {
+                arity(1, 1)?;
+
+                let ids = match self.eval(&args[0], focus)? {
+                    Value::NodeSet(nodes) => nodes
+                        .iter()
+                        .map(|node| string_value(document, node))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    value => to_string(document, &value),
+                };
+
+                let nodes = ids
+                    .split_ascii_whitespace()
+                    .filter_map(|id| document.get_element_by_id(id))
+                    .map(XPathNode::Node)
+                    .collect();
+
+                Value::NodeSet(self.sort(nodes))
+            }
+            "local-name" | "namespace-uri" | "name" => {
+                arity(0, 1)?;
+
+                let value = node()?.map_or_else(String::new, |node| {
+                    let (prefix, local_name, uri) = node_name(document, &node);
+
+                    match name {
+                        "local-name" => local_name,
+                        "namespace-uri" => uri,
+                        _ => match prefix {
+                            Some(prefix) => format!("{}:{}", prefix, local_name),
+                            None => local_name,
+                        },
+                    }
+                });
+
+                Value::String(value)
+            }
+            "string" => {
+                arity(0, 1)?;
+                Value::String(string(0)?)
+            }
+            "concat" => {
+                if args.len() < 2 {
+                    return error("wrong number of arguments of concat()");
+                }
+
+                let mut value = String::new();
+
+                for index in 0..args.len() {
+                    value.push_str(&string(index)?);
+                }
+
+                Value::String(value)
+            }
+            "starts-with" => {
+                arity(2, 2)?;
+                Value::Boolean(string(0)?.starts_with(&string(1)?))
+            }
+            "contains" => {
+                arity(2, 2)?;
+                Value::Boolean(string(0)?.contains(&string(1)?))
+            }
+            "substring-before" => {
+                arity(2, 2)?;
+
+                let value = string(0)?;
+
+                Value::String(
+                    value
+                        .split_once(&string(1)?)
+                        .map_or("", |(before, _)| before)
+                        .to_owned(),
+                )
+            }
+            "substring-after" => {
+                arity(2, 2)?;
+
+                let value = string(0)?;
+
+                Value::String(
+                    value
+                        .split_once(&string(1)?)
+                        .map_or("", |(_, after)| after)
+                        .to_owned(),
+                )
+            }
+            "substring" => {
+                arity(2, 3)?;
+
+                let value = string(0)?;
+                let start = round(number(1)?);
+
+                let end = match args.len() {
+                    3 => start + round(number(2)?),
+                    _ => f64::INFINITY,
+                };
+
+                Value::String(
+                    value
+                        .chars()
+                        .enumerate()
+                        .filter(|(index, _)| {
+                            let position = (*index + 1) as f64;
+                            position >= start && position < end
+                        })
+                        .map(|(_, c)| c)
+                        .collect(),
+                )
+            }
+            "string-length" => {
+                arity(0, 1)?;
+                Value::Number(string(0)?.chars().count() as f64)
+            }
+            "normalize-space" => {
+                arity(0, 1)?;
+
+                Value::String(
+                    string(0)?
+                        .split([' ', '\t', '\r', '\n'])
+                        .filter(|s| !s.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                )
+            }
+            "translate" => {
+                arity(3, 3)?;
+
+                let from = string(1)?.chars().collect::<Vec<_>>();
+                let to = string(2)?.chars().collect::<Vec<_>>();
+
+                Value::String(
+                    string(0)?
+                        .chars()
+                        .filter_map(|c| match from.iter().position(|from| *from == c) {
+                            Some(index) => to.get(index).copied(),
+                            None => Some(c),
+                        })
+                        .collect(),
+                )
+            }
+            "boolean" => {
+                arity(1, 1)?;
+                Value::Boolean(to_boolean(&self.eval(&args[0], focus)?))
+            }
+            "not" => {
+                arity(1, 1)?;
+                Value::Boolean(!to_boolean(&self.eval(&args[0], focus)?))
+            }
+            "true" => {
+                arity(0, 0)?;
+                Value::Boolean(true)
+            }
+            "false" => {
+                arity(0, 0)?;
+                Value::Boolean(false)
+            }
+            "lang" => {
+                arity(1, 1)?;
+
+                let lang = string(0)?.to_ascii_lowercase();
+
+                let value = ancestors_or_self(document, &focus.node)
+                    .into_iter()
+                    .filter_map(|node| match node {
+                        XPathNode::Node(object) if object.node_type() == NodeType::Element => {
+                            document.get_attribute(&object, "xml:lang")
+                        }
+                        _ => None,
+                    })
+                    .next()
+                    .is_some_and(|value| {
+                        let value = value.to_ascii_lowercase();
+
+                        value == lang
+                            || value
+                                .strip_prefix(&lang)
+                                .is_some_and(|rest| rest.starts_with('-'))
+                    });
+
+                Value::Boolean(value)
+            }
+            "number" => {
+                arity(0, 1)?;
+
+                match args.first() {
+                    Some(arg) => Value::Number(to_number(document, &self.eval(arg, focus)?)),
+                    None => Value::Number(parse_number(&string_value(document, &focus.node))),
+                }
+            }
+            "sum" => {
+                arity(1, 1)?;
+
+                Value::Number(
+                    self.node_set(&args[0], focus)?
+                        .iter()
+                        .map(|node| parse_number(&string_value(document, node)))
+                        .sum(),
+                )
+            }
+            "floor" => {
+                arity(1, 1)?;
+                Value::Number(number(0)?.floor())
+            }
+            "ceiling" => {
+                arity(1, 1)?;
+                Value::Number(number(0)?.ceil())
+            }
+            "round" => {
+                arity(1, 1)?;
+                Value::Number(round(number(0)?))
+            }
+            _ => return Err(Error::XPath(format!("unknown function {}()", name))),
+        };
+
+        Ok(value)
+    }
+}
+
+/// Round to the closest integer, halves are rounded towards positive infinity.
+fn round(number: f64) -> f64 {
+    if number.is_nan() || number.is_infinite() {
+        number
+    } else {
+        (number + 0.5).floor()
+    }
+}
+
+/// Returns the children of `object` in the data model.
+fn children(document: &Document<'_>, object: &DOMObject) -> Vec<XPathNode> {
+    let parent = match object.node_type() {
+        NodeType::Document => None,
+        NodeType::Element | NodeType::EntityReference => Some(object),
+        _ => return vec![],
+    };
+
+    let mut nodes = vec![];
+
+    for child in document.children(parent) {
+        push_child(document, child, &mut nodes);
+    }
+
+    nodes
+}
+
+/// Append `child` to `nodes` in the data model, the content of entity references replaces them.
+fn push_child(document: &Document<'_>, child: &DOMObject, nodes: &mut Vec<XPathNode>) {
+    match child.node_type() {
+        NodeType::Element
+        | NodeType::Text
+        | NodeType::CData
+        | NodeType::Comment
+        | NodeType::ProcessingInstruction => nodes.push(XPathNode::Node(*child)),
+        NodeType::EntityReference => nodes.extend(children(document, child)),
+        _ => {}
+    }
+}
+
+/// Record the document order of the tree of `object`, its attributes follow each element.
+fn index(
+    document: &Document<'_>,
+    object: &DOMObject,
+    child_index: usize,
+    order: &mut HashMap<DOMObject, (usize, usize)>,
+) {
+    let next = order.len();
+    order.insert(*object, (next, child_index));
+
+    if let Some(el) = Some(object)
+        .filter(|object| object.node_type() == NodeType::Element)
+        .and_then(|object| document.element(object))
+    {
+        for attr in el.attributes().iter() {
+            let next = order.len();
+            order.insert(*attr, (next, 0));
+        }
+    }
+
+    let list = Some(object).filter(|object| object.node_type() != NodeType::Document);
+
+    for (child_index, child) in document.children(list).enumerate() {
+        if is_child_node(child) {
+            index(document, child, child_index, order);
+        }
+    }
+}
+
+/// Returns the parent of `node` in the data model.
+fn parent(document: &Document<'_>, node: &XPathNode) -> Option<XPathNode> {
+    let object = match node {
+        XPathNode::Namespace { element, .. } => return Some(XPathNode::Node(*element)),
+        XPathNode::Node(object) => object,
+    };
+
+    let mut parent = match object.node_type() {
+        NodeType::Attribute => document.owner_element(object),
+        _ => document.parent_node(object),
+    }?;
+
+    while parent.node_type() == NodeType::EntityReference {
+        parent = document.parent_node(&parent)?;
+    }
+
+    Some(XPathNode::Node(parent))
+}
+
+fn ancestors_or_self(document: &Document<'_>, node: &XPathNode) -> Vec<XPathNode> {
+    let mut nodes = vec![node.clone()];
+
+    while let Some(parent) = parent(document, nodes.last().expect("not empty")) {
+        nodes.push(parent);
+    }
+
+    nodes
+}
+
+/// Returns the root of the tree of `node`.
+fn root(document: &Document<'_>, node: &XPathNode) -> XPathNode {
+    ancestors_or_self(document, node).pop().expect("not empty")
+}
+
+/// Append the descendants of `node` to `nodes` in document order.
+fn descendants(document: &Document<'_>, node: &XPathNode, nodes: &mut Vec<XPathNode>) {
+    if let XPathNode::Node(object) = node {
+        for child in children(document, object) {
+            nodes.push(child.clone());
+            descendants(document, &child, nodes);
+        }
+    }
+}
+
+/// Returns the in-scope namespaces of `element` as `(prefix, URI)`, sorted by prefix.
+pub(crate) fn namespaces(document: &Document<'_>, element: &DOMObject) -> Vec<(String, String)> {
+    let mut bindings: Vec<(String, String)> = vec![];
+
+    let mut bind = |prefix: &str, uri: &str| {
+        if !bindings.iter().any(|(bound, _)| bound == prefix) {
+            bindings.push((prefix.to_owned(), uri.to_owned()));
+        }
+    };
+
+    bind("xml", XML_NAMESPACE);
+
+    let mut current = Some(*element);
+
+    while let Some(object) = current.filter(|obj| obj.node_type() == NodeType::Element) {
+        if let Some(el) = document.element(&object) {
+            if let Some(uri) = el.namespace_uri() {
+                bind(el.tag().prefix.as_deref().unwrap_or(""), uri);
+            }
+        }
+
+        for (prefix, uri) in document.namespace_declarations(&object) {
+            bind(prefix.unwrap_or(""), uri);
+        }
+
+        current = document.parent_node(&object);
+    }
+
+    bindings.retain(|(_, uri)| !uri.is_empty());
+    bindings.sort();
+
+    bindings
+}
+
+/// Returns `(prefix, local name, namespace URI)` of `node`.
+fn node_name(document: &Document<'_>, node: &XPathNode) -> (Option<String>, String, String) {
+    let object = match node {
+        XPathNode::Namespace { prefix, .. } => return (None, prefix.clone(), String::new()),
+        XPathNode::Node(object) => object,
+    };
+
+    match object.node_type() {
+        NodeType::Element => document
+            .element(object)
+            .map_or_else(Default::default, |el| {
+                (
+                    el.tag().prefix.as_deref().map(str::to_owned),
+                    el.tag().local_part.to_string(),
+                    el.namespace_uri().unwrap_or("").to_owned(),
+                )
+            }),
+        NodeType::Attribute => document.attr(object).map_or_else(Default::default, |attr| {
+            (
+                attr.name().prefix.as_deref().map(str::to_owned),
+                attr.name().local_part.to_string(),
+                attr.namespace_uri().unwrap_or("").to_owned(),
+            )
+        }),
+        NodeType::ProcessingInstruction => {
+            document.pi(object).map_or_else(Default::default, |pi| {
+                (None, pi.target().to_owned(), String::new())
+            })
+        }
+        _ => Default::default(),
+    }
+}
+
+/// Returns the string-value of `node`.
+pub(crate) fn string_value(document: &Document<'_>, node: &XPathNode) -> String {
+    let object = match node {
+        XPathNode::Namespace { uri, .. } => return uri.clone(),
+        XPathNode::Node(object) => object,
+    };
+
+    match object.node_type() {
+        NodeType::Document | NodeType::Element | NodeType::EntityReference => {
+            let mut nodes = vec![];
+            descendants(document, node, &mut nodes);
+
+            nodes
+                .iter()
+                .filter_map(|node| match node {
+                    XPathNode::Node(object) => match object.node_type() {
+                        NodeType::Text => document.text(object).map(|text| text.data()),
+                        NodeType::CData => document.cdata(object).map(|cdata| cdata.data()),
+                        _ => None,
+                    },
+                    _ => None,
+                })
+                .collect()
+        }
+        NodeType::Attribute => document
+            .attr(object)
+            .map_or_else(String::new, |attr| attr.value().to_owned()),
+        NodeType::Text => document
+            .text(object)
+            .map_or_else(String::new, |text| text.data().to_owned()),
+        NodeType::CData => document
+            .cdata(object)
+            .map_or_else(String::new, |cdata| cdata.data().to_owned()),
+        NodeType::Comment => document
+            .comment(object)
+            .map_or_else(String::new, |comment| comment.data().to_owned()),
+        NodeType::ProcessingInstruction => document
+            .pi(object)
+            .map_or_else(String::new, |pi| pi.data().to_owned()),
+        _ => String::new(),
+    }
+}
+
+pub(crate) fn to_boolean(value: &Value) -> bool {
+    match value {
+        Value::NodeSet(nodes) => !nodes.is_empty(),
+        Value::Boolean(value) => *value,
+        Value::Number(number) => *number != 0.0 && !number.is_nan(),
+        Value::String(value) => !value.is_empty(),
+    }
+}
+
+pub(crate) fn to_number(document: &Document<'_>, value: &Value) -> f64 {
+    match value {
+        Value::NodeSet(_) => parse_number(&to_string(document, value)),
+        value => atomic_number(value),
+    }
+}
+
+pub(crate) fn to_string(document: &Document<'_>, value: &Value) -> String {
+    match value {
+        Value::NodeSet(nodes) => nodes
+            .first()
+            .map_or_else(String::new, |node| string_value(document, node)),
+        value => atomic_string(value),
+    }
+}
+
+/// Convert a value other than a node-set to a number.
+fn atomic_number(value: &Value) -> f64 {
+    match value {
+        Value::Boolean(value) => *value as u8 as f64,
+        Value::Number(number) => *number,
+        Value::String(value) => parse_number(value),
+        Value::NodeSet(_) => f64::NAN,
+    }
+}
+
+/// Convert a value other than a node-set to a string.
+fn atomic_string(value: &Value) -> String {
+    match value {
+        Value::Boolean(value) => value.to_string(),
+        Value::Number(number) => format_number(*number),
+        Value::String(value) => value.clone(),
+        Value::NodeSet(_) => String::new(),
+    }
+}
+
+/// Parse the `Number` production with optional white spaces and minus sign, `NaN` otherwise.
+fn parse_number(value: &str) -> f64 {
+    let value = value.trim_matches([' ', '\t', '\r', '\n']);
+    let digits = value.strip_prefix('-').unwrap_or(value);
+
+    let valid = !digits.is_empty()
+        && digits != "."
+        && digits.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && digits.matches('.').count() <= 1;
+
+    if valid {
+        value.parse().unwrap_or(f64::NAN)
+    } else {
+        f64::NAN
+    }
+}
+
+fn format_number(number: f64) -> String {
+    if number.is_nan() {
+        "NaN".to_owned()
+    } else if number.is_infinite() {
+        if number > 0.0 {
+            "Infinity"
+        } else {
+            "-Infinity"
+        }
+        .to_owned()
+    } else if number == 0.0 {
+        "0".to_owned()
+    } else {
+        number.to_string()
+    }
+}
+
+/// Compare two values as by the operator `op`.
+fn compare(document: &Document<'_>, op: BinaryOp, left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::NodeSet(left), Value::NodeSet(right)) => {
+            let right = right
+                .iter()
+                .map(|node| Value::String(string_value(document, node)))
+                .collect::<Vec<_>>();
+
+            left.iter().any(|node| {
+                let left = Value::String(string_value(document, node));
+                right.iter().any(|right| compare_atomic(op, &left, right))
+            })
+        }
+        (Value::NodeSet(nodes), Value::Boolean(_)) => {
+            compare_atomic(op, &Value::Boolean(!nodes.is_empty()), right)
+        }
+        (Value::NodeSet(nodes), other) => nodes.iter().any(|node| {
+            let value = string_value(document, node);
+
+            let left = match other {
+                Value::Number(_) => Value::Number(parse_number(&value)),
+                _ => Value::String(value),
+            };
+
+            compare_atomic(op, &left, other)
+        }),
+        (_, Value::NodeSet(_)) => {
+            let op = match op {
+                BinaryOp::Lt => BinaryOp::Gt,
+                BinaryOp::Le => BinaryOp::Ge,
+                BinaryOp::Gt => BinaryOp::Lt,
+                BinaryOp::Ge => BinaryOp::Le,
+                op => op,
+            };
+
+            compare(document, op, right, left)
+        }
+        _ => compare_atomic(op, left, right),
+    }
+}
+
+fn compare_atomic(op: BinaryOp, left: &Value, right: &Value) -> bool {
+    match op {
+        BinaryOp::Eq | BinaryOp::Ne => {
+            let equal = match (left, right) {
+                (Value::Boolean(_), _) | (_, Value::Boolean(_)) => {
+                    to_boolean(left) == to_boolean(right)
+                }
+                (Value::Number(_), _) | (_, Value::Number(_)) => {
+                    atomic_number(left) == atomic_number(right)
+                }
+                _ => atomic_string(left) == atomic_string(right),
+            };
+
+            equal == (op == BinaryOp::Eq)
+        }
+        _ => {
+            let left = atomic_number(left);
+            let right = atomic_number(right);
+
+            match op {
+                BinaryOp::Lt => left < right,
+                BinaryOp::Le => left <= right,
+                BinaryOp::Gt => left > right,
+                _ => left >= right,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(parse_number(" -1.5 "), -1.5);
+        assert_eq!(parse_number(".5"), 0.5);
+        assert!(parse_number("1e3").is_nan());
+        assert!(parse_number("").is_nan());
+
+        assert_eq!(format_number(3.0), "3");
+        assert_eq!(format_number(-0.0), "0");
+        assert_eq!(format_number(0.1), "0.1");
+        assert_eq!(format_number(f64::NEG_INFINITY), "-Infinity");
+
+        assert_eq!(round(-0.5), 0.0);
+        assert_eq!(round(2.5), 3.0);
+    }
+}
@@ -0,0 +1,370 @@
+//! XPath 1.0 evaluation over a [`Document`].
+//!
+//! The data model is built on the fly from the tree: CDATA sections are text nodes,
+//! the content of entity references is part of their parent, the `xmlns` attributes
+//! are only visible as namespace nodes, and adjacent text nodes are not merged.
+
+mod eval;
+mod parser;
+mod stream;
+
+use std::collections::BTreeMap;
+
+use crate::{DOMObject, Document, Result};
+
+use eval::Evaluator;
+use parser::Expr;
+
+pub use stream::{StreamingXPath, XPathReader};
+
+/// A node of the XPath data model.
+#[derive(Debug, Clone, PartialEq)]
+pub enum XPathNode {
+    /// A node of the document, the root node is [`DOMObject::default`].
+    Node(DOMObject),
+    /// One namespace in scope of `element`, which has no `DOMObject`.
+    Namespace {
+        element: DOMObject,
+        prefix: String,
+        uri: String,
+    },
+}
+
+impl XPathNode {
+    /// Returns the `DOMObject` of this node, `None` for namespace nodes.
+    pub fn object(&self) -> Option<&DOMObject> {
+        match self {
+            XPathNode::Node(object) => Some(object),
+            XPathNode::Namespace { .. } => None,
+        }
+    }
+}
+
+/// The result of an XPath expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// Nodes in document order, without duplicates.
+    NodeSet(Vec<XPathNode>),
+    Boolean(bool),
+    Number(f64),
+    String(String),
+}
+
+impl Value {
+    /// Returns the nodes of a node-set.
+    pub fn into_nodes(self) -> Option<Vec<XPathNode>> {
+        match self {
+            Value::NodeSet(nodes) => Some(nodes),
+            _ => None,
+        }
+    }
+
+    /// Convert this value as by the `boolean()` function.
+    pub fn boolean(&self) -> bool {
+        eval::to_boolean(self)
+    }
+
+    /// Convert this value as by the `number()` function.
+    pub fn number(&self, document: &Document<'_>) -> f64 {
+        eval::to_number(document, self)
+    }
+
+    /// Convert this value as by the `string()` function.
+    pub fn string(&self, document: &Document<'_>) -> String {
+        eval::to_string(document, self)
+    }
+}
+
+/// The namespace and variable bindings of the evaluation of expressions.
+///
+/// Prefixes of names in expressions are resolved by these bindings, not by the
+/// namespace declarations of the document.
+#[derive(Debug, Clone, Default)]
+pub struct XPathContext {
+    namespaces: BTreeMap<String, String>,
+    variables: BTreeMap<String, Value>,
+}
+
+impl XPathContext {
+    /// Create a new context without bindings.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Bind `prefix` to `uri`.
+    pub fn namespace<P, U>(mut self, prefix: P, uri: U) -> Self
+    where
+        P: Into<String>,
+        U: Into<String>,
+    {
+        self.namespaces.insert(prefix.into(), uri.into());
+        self
+    }
+
+    /// Bind the variable `$name` to `value`, `name` is matched as written in expressions.
+    pub fn variable<N>(mut self, name: N, value: Value) -> Self
+    where
+        N: Into<String>,
+    {
+        self.variables.insert(name.into(), value);
+        self
+    }
+}
+
+/// A compiled XPath expression.
+#[derive(Debug, Clone)]
+pub struct XPath {
+    expr: Expr,
+}
+
+impl XPath {
+    /// Parse `expression`.
+    pub fn compile(expression: &str) -> Result<Self> {
+        Ok(Self {
+            expr: parser::parse(expression)?,
+        })
+    }
+
+    /// Evaluate this expression with `node` as context node.
+    pub fn evaluate(
+        &self,
+        document: &Document<'_>,
+        node: &DOMObject,
+        context: &XPathContext,
+    ) -> Result<Value> {
+        Evaluator::new(document, context).evaluate(&self.expr, XPathNode::Node(*node))
+    }
+}
+
+impl<'a> Document<'a> {
+    /// Evaluate the XPath `expression` with `node` as context node, without bindings.
+    pub fn evaluate(&self, node: &DOMObject, expression: &str) -> Result<Value> {
+        XPath::compile(expression)?.evaluate(self, node, &XPathContext::new())
+    }
+
+    /// Returns the nodes selected by the XPath `expression` with `node` as context node,
+    /// raises an error if the result is not a node-set.
+    pub fn select_nodes(&self, node: &DOMObject, expression: &str) -> Result<Vec<XPathNode>> {
+        self.evaluate(node, expression)?
+            .into_nodes()
+            .ok_or_else(|| crate::Error::XPath("not a node-set".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::XML_NAMESPACE;
+
+    const DOCUMENT: &str = r#"<?pi data?><library xmlns:b="urn:books" xml:lang="en-US">
+<b:book id="1" year="1990"><title>First</title><price>10</price></b:book>
+<b:book id="2" year="2005"><title>Second</title><price>25.5</price><!-- note --></b:book>
+<magazine id="3"><title><![CDATA[Third]]></title><price>4</price></magazine>
+</library>"#;
+
+    fn strings(doc: &Document<'_>, nodes: &[XPathNode]) -> Vec<String> {
+        nodes
+            .iter()
+            .map(|node| eval::string_value(doc, node))
+            .collect()
+    }
+
+    #[test]
+    fn test_paths() {
+        let doc = Document::parse(DOCUMENT).unwrap();
+        let root = DOMObject::default();
+
+        let select = |expr: &str| {
+            let context = XPathContext::new().namespace("bk", "urn:books");
+            let nodes = XPath::compile(expr)
+                .unwrap()
+                .evaluate(&doc, &root, &context)
+                .unwrap()
+                .into_nodes()
+                .unwrap();
+
+            strings(&doc, &nodes)
+        };
+
+        assert_eq!(select("//title"), ["First", "Second", "Third"]);
+        assert_eq!(select("/library/bk:book/@year"), ["1990", "2005"]);
+        assert_eq!(select("//*[price > 5]/title"), ["First", "Second"]);
+        assert_eq!(select("//bk:book[last()]/title"), ["Second"]);
+        assert_eq!(select("(//title)[position() >= 2]"), ["Second", "Third"]);
+        assert_eq!(select("//price[1]/ancestor::*[1]/@id"), ["1", "2", "3"]);
+        assert_eq!(
+            select("//title[. = 'Second']/../following::title"),
+            ["Third"]
+        );
+        assert_eq!(select("//magazine/preceding::title"), ["First", "Second"]);
+        assert_eq!(select("//bk:book[2]/preceding-sibling::*/@id"), ["1"]);
+        assert_eq!(select("//comment()"), [" note "]);
+        assert_eq!(select("/processing-instruction('pi')"), ["data"]);
+        assert_eq!(select("//@id | //@year"), ["1", "1990", "2", "2005", "3"]);
+        assert_eq!(
+            select("/library/namespace::*"),
+            ["urn:books", XML_NAMESPACE]
+        );
+
+        // undeclared prefix of the expression.
+        assert!(doc.evaluate(&root, "//bk:book").is_err());
+    }
+
+    #[test]
+    fn test_functions() {
+        let doc = Document::parse(DOCUMENT).unwrap();
+        let root = DOMObject::default();
+
+        let eval = |expr: &str| doc.evaluate(&root, expr).unwrap().string(&doc);
+
+        assert_eq!(eval("count(//price)"), "3");
+        assert_eq!(eval("sum(//price)"), "39.5");
+        assert_eq!(eval("name(//*[@id = 2])"), "b:book");
+        assert_eq!(eval("local-name(//*[@id = 2])"), "book");
+        assert_eq!(eval("namespace-uri(//*[@id = 2])"), "urn:books");
+        assert_eq!(
+            eval("concat(substring('12345', 1.5, 2.6), '-', 7 mod 3)"),
+            "234-1"
+        );
+        assert_eq!(
+            eval("translate(normalize-space('  a  b '), 'ab', 'B')"),
+            "B "
+        );
+        assert_eq!(eval("substring-after('key=value', '=')"), "value");
+        assert_eq!(eval("round(-2.5) + floor(1.9) + ceiling(0.1)"), "0");
+        assert_eq!(eval("1 div 0"), "Infinity");
+        assert_eq!(eval("string(number('x'))"), "NaN");
+        assert_eq!(eval("boolean(//magazine) and not(//newspaper)"), "true");
+        assert_eq!(eval("//title[lang('en')] = 'Third'"), "true");
+        assert_eq!(eval("string-length(//title)"), "5");
+        assert_eq!(eval("/library/*[position() = last() - 1]/@id"), "2");
+        assert_eq!(eval("string(//*[2]/@year)"), "2005");
+        assert_eq!(
+            eval("starts-with('prefix', 'pre') and contains('abc', 'b')"),
+            "true"
+        );
+        assert_eq!(eval("substring-before('key=value', '=')"), "key");
+        assert_eq!(eval("true() != false()"), "true");
+        assert_eq!(eval("number(' 12 ') + number(true())"), "13");
+        assert_eq!(eval("local-name(/library/@*) = 'lang'"), "true");
+        assert_eq!(eval("name(/processing-instruction())"), "pi");
+        assert_eq!(eval("name(/library/namespace::*[1])"), "b");
+        assert_eq!(eval("count(//newspaper) + sum(//none)"), "0");
+        assert_eq!(eval("boolean('') or boolean(0)"), "false");
+
+        let price = doc.select_nodes(&root, "//price").unwrap()[1].clone();
+
+        let context = XPathContext::new().variable("limit", Value::Number(20.0));
+        let value = XPath::compile(". > $limit and position() = 1")
+            .unwrap()
+            .evaluate(&doc, price.object().unwrap(), &context)
+            .unwrap();
+
+        assert_eq!(value, Value::Boolean(true));
+
+        assert!(doc.evaluate(&root, "unknown()").is_err());
+        assert!(doc.evaluate(&root, "$undefined").is_err());
+        assert!(doc.evaluate(&root, "count(1)").is_err());
+    }
+
+    #[test]
+    fn test_id() {
+        let doc = Document::parse(r#"<a><b xml:id="x">1</b><c xml:id="y">2</c></a>"#).unwrap();
+        let root = DOMObject::default();
+
+        let select = |expr: &str| strings(&doc, &doc.select_nodes(&root, expr).unwrap());
+
+        assert_eq!(select("id('y x z')"), ["1", "2"]);
+        assert_eq!(select("id(//@*)/self::c"), ["2"]);
+        assert!(select("id('')").is_empty());
+    }
+
+    #[test]
+    fn test_errors() {
+        let doc = Document::parse(DOCUMENT).unwrap();
+        let root = DOMObject::default();
+
+        let error = |expr: &str| doc.evaluate(&root, expr).is_err();
+
+        // syntax.
+        assert!(error(""));
+        assert!(error("//"));
+        assert!(error("1 +"));
+        assert!(error("//title["));
+        assert!(error("unknown::node()"));
+        assert!(error("'unterminated"));
+
+        // arity.
+        assert!(error("concat('a')"));
+        assert!(error("substring('a')"));
+        assert!(error("true(1)"));
+        assert!(error("last(1)"));
+
+        // node-sets.
+        assert!(error("(1)[1]"));
+        assert!(error("'a'/title"));
+        assert!(error("sum('a')"));
+        assert!(error("//title | 1"));
+        assert!(doc.select_nodes(&root, "count(//title)").is_err());
+    }
+
+    #[test]
+    fn test_axes() {
+        let doc = Document::parse(
+            r#"<!DOCTYPE doc [<!ENTITY e "<b id='2'>2</b><c>3</c>">]>
+<doc xmlns:x="urn:x"><a id="1" x:k="v">1</a>&e;<d>4</d></doc>"#,
+        )
+        .unwrap();
+        let root = DOMObject::default();
+
+        let select = |expr: &str| strings(&doc, &doc.select_nodes(&root, expr).unwrap());
+
+        assert_eq!(select("/doc/child::*"), ["1", "2", "3", "4"]);
+        assert_eq!(select("/doc/descendant::text()"), ["1", "2", "3", "4"]);
+        assert_eq!(select("//c/descendant-or-self::node()"), ["3", "3"]);
+        assert_eq!(select("//c/parent::*/d"), ["4"]);
+        assert_eq!(select("//c/text()/ancestor::*[1]"), ["3"]);
+        assert_eq!(select("//c/ancestor::node()[1]/d"), ["4"]);
+        assert_eq!(select("//c/ancestor-or-self::*[2]/d"), ["4"]);
+        assert_eq!(select("//c/self::c"), ["3"]);
+        assert_eq!(select("//c/self::d"), Vec::<String>::new());
+
+        // the content of the entity reference is part of the siblings of its parent.
+        assert_eq!(select("//a/following-sibling::*"), ["2", "3", "4"]);
+        assert_eq!(select("//a/following-sibling::*[1]"), ["2"]);
+        assert_eq!(select("//c/following-sibling::*"), ["4"]);
+        assert_eq!(select("//c/preceding-sibling::*"), ["1", "2"]);
+        assert_eq!(select("//d/preceding-sibling::*[1]"), ["3"]);
+        assert_eq!(select("//b/following::text()"), ["3", "4"]);
+        assert_eq!(select("//b/preceding::node()"), ["1", "1"]);
+        assert_eq!(select("//a/@id/following::*"), ["2", "3", "4"]);
+        assert_eq!(select("//a/@id/preceding::node()"), Vec::<String>::new());
+        assert_eq!(select("//a/attribute::*"), ["1", "v"]);
+        assert_eq!(select("//a/@*[1]/self::node()"), ["1"]);
+        assert_eq!(select("//c/namespace::x"), ["urn:x"]);
+        assert_eq!(
+            select("//b/@id | //b/namespace::x | //b | //a/@id | //b"),
+            ["1", "2", "urn:x", "2"]
+        );
+    }
+
+    #[test]
+    fn test_detached() {
+        let mut doc = Document::default();
+
+        let attr = doc.create_attr("id", "1").unwrap();
+        let element = doc.create_element("a").unwrap();
+        let child = doc.create_element("b").unwrap();
+        doc.append_child(Some(&element), child).unwrap();
+
+        let count = |node: &DOMObject, expr: &str| doc.evaluate(node, expr).unwrap().number(&doc);
+
+        assert_eq!(count(&attr, "count(following::node())"), 0.0);
+        assert_eq!(count(&attr, "count(preceding::node())"), 0.0);
+        assert_eq!(count(&attr, "count(ancestor-or-self::node())"), 1.0);
+        assert_eq!(count(&attr, "count(/ | ..)"), 1.0);
+
+        assert_eq!(count(&child, "count(/ | ../b | following::node())"), 2.0);
+        assert_eq!(count(&child, "count(preceding::node())"), 0.0);
+    }
+}
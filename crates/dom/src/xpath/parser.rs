@@ -0,0 +1,772 @@
+//! Tokenizer and parser of XPath 1.0 expressions.
+
+use rexml_stax::parser;
+
+use crate::{Error, Result};
+
+/// The axes of location steps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Axis {
+    Ancestor,
+    AncestorOrSelf,
+    Attribute,
+    Child,
+    Descendant,
+    DescendantOrSelf,
+    Following,
+    FollowingSibling,
+    Namespace,
+    Parent,
+    Preceding,
+    PrecedingSibling,
+    SelfNode,
+}
+
+impl Axis {
+    fn from_name(name: &str) -> Option<Self> {
+        let axis = match name {
+            "ancestor" => Self::Ancestor,
+            "ancestor-or-self" => Self::AncestorOrSelf,
+            "attribute" => Self::Attribute,
+            "child" => Self::Child,
+            "descendant" => Self::Descendant,
+            "descendant-or-self" => Self::DescendantOrSelf,
+            "following" => Self::Following,
+            "following-sibling" => Self::FollowingSibling,
+            "namespace" => Self::Namespace,
+            "parent" => Self::Parent,
+            "preceding" => Self::Preceding,
+            "preceding-sibling" => Self::PrecedingSibling,
+            "self" => Self::SelfNode,
+            _ => return None,
+        };
+
+        Some(axis)
+    }
+}
+
+/// The node tests of location steps.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum NodeTest {
+    /// A name test, `None` local name is `*`.
+    Name {
+        prefix: Option<String>,
+        local_name: Option<String>,
+    },
+    Node,
+    Text,
+    Comment,
+    ProcessingInstruction(Option<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Step {
+    pub(crate) axis: Axis,
+    pub(crate) test: NodeTest,
+    pub(crate) predicates: Vec<Expr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Union,
+}
+
+/// A parsed expression.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Expr {
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Negate(Box<Expr>),
+    Literal(String),
+    Number(f64),
+    Variable(String),
+    Function(String, Vec<Expr>),
+    /// A primary expression with predicates.
+    Filter(Box<Expr>, Vec<Expr>),
+    /// The location steps applied to the node-set of an expression.
+    Path(Box<Expr>, Vec<Step>),
+    /// The root node of the context node.
+    Root,
+    /// The context node.
+    ContextNode,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Slash,
+    DoubleSlash,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Dot,
+    DotDot,
+    At,
+    Comma,
+    ColonColon,
+    Pipe,
+    Plus,
+    Minus,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Multiply,
+    And,
+    Or,
+    Mod,
+    Div,
+    Literal(String),
+    Number(f64),
+    Variable(String),
+    NameTest(Option<String>, Option<String>),
+    NodeType(String),
+    FunctionName(String),
+    AxisName(String),
+}
+
+impl Token {
+    /// Returns true if a `*` or a name following this token is an operator.
+    fn precedes_operator(&self) -> bool {
+        !matches!(
+            self,
+            Token::At
+                | Token::ColonColon
+                | Token::LParen
+                | Token::LBracket
+                | Token::Comma
+                | Token::Slash
+                | Token::DoubleSlash
+                | Token::Pipe
+                | Token::Plus
+                | Token::Minus
+                | Token::Eq
+                | Token::Ne
+                | Token::Lt
+                | Token::Le
+                | Token::Gt
+                | Token::Ge
+                | Token::Multiply
+                | Token::And
+                | Token::Or
+                | Token::Mod
+                | Token::Div
+        )
+    }
+}
+
+fn syntax_error<T>(reason: &str) -> Result<T> {
+    Err(Error::XPath(reason.to_owned()))
+}
+
+/// Returns true if `c` can start an `NCName`.
+fn is_name_start_char(c: char) -> bool {
+    c != ':' && parser::is_name_start_char(c)
+}
+
+/// Returns true if `c` can continue an `NCName`.
+fn is_name_char(c: char) -> bool {
+    c != ':' && parser::is_name_char(c)
+}
+
+struct Lexer<'s> {
+    input: &'s str,
+    offset: usize,
+    tokens: Vec<Token>,
+}
+
+impl<'s> Lexer<'s> {
+    fn rest(&self) -> &'s str {
+        &self.input[self.offset..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.offset += rest.len() - rest.trim_start_matches([' ', '\t', '\r', '\n']).len();
+    }
+
+    fn ncname(&mut self) -> Option<&'s str> {
+        let rest = self.rest();
+
+        if !rest.starts_with(is_name_start_char) {
+            return None;
+        }
+
+        let len = rest.find(|c| !is_name_char(c)).unwrap_or(rest.len());
+        self.offset += len;
+
+        Some(&rest[..len])
+    }
+
+    fn tokenize(mut self) -> Result<Vec<Token>> {
+        loop {
+            self.skip_whitespace();
+
+            let rest = self.rest();
+
+            let Some(c) = rest.chars().next() else {
+                return Ok(self.tokens);
+            };
+
+            let operator = self.tokens.last().is_some_and(Token::precedes_operator);
+
+            let (token, len) = match c {
+                '/' if rest.starts_with("//") => (Token::DoubleSlash, 2),
+                '/' => (Token::Slash, 1),
+                '(' => (Token::LParen, 1),
+                ')' => (Token::RParen, 1),
+                '[' => (Token::LBracket, 1),
+                ']' => (Token::RBracket, 1),
+                '.' if rest.starts_with("..") => (Token::DotDot, 2),
+                '.' if !rest[1..].starts_with(|c: char| c.is_ascii_digit()) => (Token::Dot, 1),
+                '@' => (Token::At, 1),
+                ',' => (Token::Comma, 1),
+                ':' if rest.starts_with("::") => (Token::ColonColon, 2),
+                '|' => (Token::Pipe, 1),
+                '+' => (Token::Plus, 1),
+                '-' => (Token::Minus, 1),
+                '=' => (Token::Eq, 1),
+                '!' if rest.starts_with("!=") => (Token::Ne, 2),
+                '<' if rest.starts_with("<=") => (Token::Le, 2),
+                '<' => (Token::Lt, 1),
+                '>' if rest.starts_with(">=") => (Token::Ge, 2),
+                '>' => (Token::Gt, 1),
+                '*' if operator => (Token::Multiply, 1),
+                '*' => (Token::NameTest(None, None), 1),
+                '"' | '\'' => {
+                    let Some(end) = rest[1..].find(c) else {
+                        return syntax_error("unterminated literal");
+                    };
+
+                    (Token::Literal(rest[1..end + 1].to_owned()), end + 2)
+                }
+                '0'..='9' | '.' => {
+                    let len = rest
+                        .find(|c: char| !c.is_ascii_digit() && c != '.')
+                        .unwrap_or(rest.len());
+
+                    match rest[..len].parse::<f64>() {
+                        Ok(number) if rest[..len].matches('.').count() <= 1 => {
+                            (Token::Number(number), len)
+                        }
+                        _ => return syntax_error("invalid number"),
+                    }
+                }
+                '$' => {
+                    self.offset += 1;
+
+                    let Some(name) = self.qname() else {
+                        return syntax_error("expected a variable name");
+                    };
+
+                    self.tokens.push(Token::Variable(name));
+                    continue;
+                }
+                c if is_name_start_char(c) => {
+                    let token = self.name(operator)?;
+                    self.tokens.push(token);
+                    continue;
+                }
+                _ => return syntax_error("unexpected character"),
+            };
+
+            self.offset += len;
+            self.tokens.push(token);
+        }
+    }
+
+    /// Read a `QName`, the offset is unchanged if there is none.
+    fn qname(&mut self) -> Option<String> {
+        let start = self.offset;
+        let prefix = self.ncname()?;
+
+        if self.rest().starts_with(':') && !self.rest().starts_with("::") {
+            self.offset += 1;
+
+            match self.ncname() {
+                Some(local_name) => return Some(format!("{}:{}", prefix, local_name)),
+                None => {
+                    self.offset = start;
+                    return None;
+                }
+            }
+        }
+
+        Some(prefix.to_owned())
+    }
+
+    /// Read a name test, an operator name, a node type, a function name or an axis name.
+    fn name(&mut self, operator: bool) -> Result<Token> {
+        let start = self.offset;
+        let name = self.ncname().expect("name start char");
+
+        if operator {
+            return match name {
+                "and" => Ok(Token::And),
+                "or" => Ok(Token::Or),
+                "mod" => Ok(Token::Mod),
+                "div" => Ok(Token::Div),
+                _ => syntax_error("expected an operator"),
+            };
+        }
+
+        // `prefix:*`
+        if self.rest().starts_with(":*") {
+            self.offset += 2;
+            return Ok(Token::NameTest(Some(name.to_owned()), None));
+        }
+
+        self.offset = start;
+
+        let qname = self.qname().expect("name start char");
+        let end = self.offset;
+
+        self.skip_whitespace();
+
+        let token = if self.rest().starts_with('(') {
+            match qname.as_str() {
+                "comment" | "text" | "processing-instruction" | "node" => Token::NodeType(qname),
+                _ => Token::FunctionName(qname),
+            }
+        } else if self.rest().starts_with("::") {
+            Token::AxisName(qname)
+        } else {
+            match qname.split_once(':') {
+                Some((prefix, local_name)) => {
+                    Token::NameTest(Some(prefix.to_owned()), Some(local_name.to_owned()))
+                }
+                None => Token::NameTest(None, Some(qname)),
+            }
+        };
+
+        self.offset = end;
+
+        Ok(token)
+    }
+}
+
+/// Parse the XPath expression `input`.
+pub(crate) fn parse(input: &str) -> Result<Expr> {
+    let tokens = Lexer {
+        input,
+        offset: 0,
+        tokens: vec![],
+    }
+    .tokenize()?;
+
+    let mut parser = Parser { tokens, pos: 0 };
+
+    let expr = parser.expr()?;
+
+    if parser.pos < parser.tokens.len() {
+        return syntax_error("unexpected token");
+    }
+
+    Ok(expr)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Consume the next token if it is `token`.
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &Token, reason: &str) -> Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            syntax_error(reason)
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        self.or_expr()
+    }
+
+    /// Parse a left associative chain of `operand` separated by the operators of `ops`.
+    fn binary<F>(&mut self, ops: &[(Token, BinaryOp)], mut operand: F) -> Result<Expr>
+    where
+        F: FnMut(&mut Self) -> Result<Expr>,
+    {
+        let mut left = operand(self)?;
+
+        'chain: loop {
+            for (token, op) in ops {
+                if self.eat(token) {
+                    let right = operand(self)?;
+                    left = Expr::Binary(*op, Box::new(left), Box::new(right));
+                    continue 'chain;
+                }
+            }
+
+            return Ok(left);
+        }
+    }
+
+    fn or_expr(&mut self) -> Result<Expr> {
+        self.binary(&[(Token::Or, BinaryOp::Or)], Self::and_expr)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr> {
+        self.binary(&[(Token::And, BinaryOp::And)], Self::equality_expr)
+    }
+
+    fn equality_expr(&mut self) -> Result<Expr> {
+        self.binary(
+            &[(Token::Eq, BinaryOp::Eq), (Token::Ne, BinaryOp::Ne)],
+            Self::relational_expr,
+        )
+    }
+
+    fn relational_expr(&mut self) -> Result<Expr> {
+        self.binary(
+            &[
+                (Token::Lt, BinaryOp::Lt),
+                (Token::Le, BinaryOp::Le),
+                (Token::Gt, BinaryOp::Gt),
+                (Token::Ge, BinaryOp::Ge),
+            ],
+            Self::additive_expr,
+        )
+    }
+
+    fn additive_expr(&mut self) -> Result<Expr> {
+        self.binary(
+            &[(Token::Plus, BinaryOp::Add), (Token::Minus, BinaryOp::Sub)],
+            Self::multiplicative_expr,
+        )
+    }
+
+    fn multiplicative_expr(&mut self) -> Result<Expr> {
+        self.binary(
+            &[
+                (Token::Multiply, BinaryOp::Mul),
+                (Token::Div, BinaryOp::Div),
+                (Token::Mod, BinaryOp::Mod),
+            ],
+            Self::unary_expr,
+        )
+    }
+
+    fn unary_expr(&mut self) -> Result<Expr> {
+        if self.eat(&Token::Minus) {
+            return Ok(Expr::Negate(Box::new(self.unary_expr()?)));
+        }
+
+        self.binary(&[(Token::Pipe, BinaryOp::Union)], Self::path_expr)
+    }
+
+    fn path_expr(&mut self) -> Result<Expr> {
+        match self.peek() {
+            Some(Token::Slash) => {
+                self.pos += 1;
+
+                if self.peek().is_some_and(is_step_start) {
+                    Ok(Expr::Path(Box::new(Expr::Root), self.relative_path()?))
+                } else {
+                    Ok(Expr::Root)
+                }
+            }
+            Some(Token::DoubleSlash) => {
+                self.pos += 1;
+
+                let mut steps = vec![descendant_or_self()];
+                steps.extend(self.relative_path()?);
+
+                Ok(Expr::Path(Box::new(Expr::Root), steps))
+            }
+            Some(token) if is_step_start(token) => Ok(Expr::Path(
+                Box::new(Expr::ContextNode),
+                self.relative_path()?,
+            )),
+            _ => {
+                let filter = self.filter_expr()?;
+
+                let mut steps = vec![];
+
+                if self.eat(&Token::DoubleSlash) {
+                    steps.push(descendant_or_self());
+                } else if !self.eat(&Token::Slash) {
+                    return Ok(filter);
+                }
+
+                steps.extend(self.relative_path()?);
+
+                Ok(Expr::Path(Box::new(filter), steps))
+            }
+        }
+    }
+
+    fn filter_expr(&mut self) -> Result<Expr> {
+        let primary = match self.next() {
+            Some(Token::Variable(name)) => Expr::Variable(name),
+            Some(Token::LParen) => {
+                let expr = self.expr()?;
+                self.expect(&Token::RParen, "expected `)`")?;
+                expr
+            }
+            Some(Token::Literal(literal)) => Expr::Literal(literal),
+            Some(Token::Number(number)) => Expr::Number(number),
+            Some(Token::FunctionName(name)) => {
+                self.expect(&Token::LParen, "expected `(`")?;
+
+                let mut args = vec![];
+
+                if !self.eat(&Token::RParen) {
+                    loop {
+                        args.push(self.expr()?);
+
+                        if self.eat(&Token::RParen) {
+                            break;
+                        }
+
+                        self.expect(&Token::Comma, "expected `,` or `)`")?;
+                    }
+                }
+
+                Expr::Function(name, args)
+            }
+            _ => return syntax_error("expected an expression"),
+        };
+
+        let predicates = self.predicates()?;
+
+        if predicates.is_empty() {
+            Ok(primary)
+        } else {
+            Ok(Expr::Filter(Box::new(primary), predicates))
+        }
+    }
+
+    fn predicates(&mut self) -> Result<Vec<Expr>> {
+        let mut predicates = vec![];
+
+        while self.eat(&Token::LBracket) {
+            predicates.push(self.expr()?);
+            self.expect(&Token::RBracket, "expected `]`")?;
+        }
+
+        Ok(predicates)
+    }
+
+    fn relative_path(&mut self) -> Result<Vec<Step>> {
+        let mut steps = vec![self.step()?];
+
+        loop {
+            if self.eat(&Token::DoubleSlash) {
+                steps.push(descendant_or_self());
+            } else if !self.eat(&Token::Slash) {
+                return Ok(steps);
+            }
+
+            steps.push(self.step()?);
+        }
+    }
+
+    fn step(&mut self) -> Result<Step> {
+        if self.eat(&Token::Dot) {
+            return Ok(Step {
+                axis: Axis::SelfNode,
+                test: NodeTest::Node,
+                predicates: vec![],
+            });
+        }
+
+        if self.eat(&Token::DotDot) {
+            return Ok(Step {
+                axis: Axis::Parent,
+                test: NodeTest::Node,
+                predicates: vec![],
+            });
+        }
+
+        let axis = match self.peek() {
+            Some(Token::At) => {
+                self.pos += 1;
+                Axis::Attribute
+            }
+            Some(Token::AxisName(name)) => {
+                let Some(axis) = Axis::from_name(name) else {
+                    return syntax_error("unknown axis");
+                };
+
+                self.pos += 1;
+                self.expect(&Token::ColonColon, "expected `::`")?;
+
+                axis
+            }
+            _ => Axis::Child,
+        };
+
+        let test = match self.next() {
+            Some(Token::NameTest(prefix, local_name)) => NodeTest::Name { prefix, local_name },
+            Some(Token::NodeType(name)) => {
+                self.expect(&Token::LParen, "expected `(`")?;
+
+                let test = match name.as_str() {
+                    "node" => NodeTest::Node,
+                    "text" => NodeTest::Text,
+                    "comment" => NodeTest::Comment,
+                    _ => match self.peek() {
+                        Some(Token::Literal(target)) => {
+                            let target = target.clone();
+                            self.pos += 1;
+                            NodeTest::ProcessingInstruction(Some(target))
+                        }
+                        _ => NodeTest::ProcessingInstruction(None),
+                    },
+                };
+
+                self.expect(&Token::RParen, "expected `)`")?;
+
+                test
+            }
+            _ => return syntax_error("expected a node test"),
+        };
+
+        Ok(Step {
+            axis,
+            test,
+            predicates: self.predicates()?,
+        })
+    }
+}
+
+fn is_step_start(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Dot
+            | Token::DotDot
+            | Token::At
+            | Token::AxisName(_)
+            | Token::NameTest(..)
+            | Token::NodeType(_)
+    )
+}
+
+/// The step of the `//` abbreviation.
+fn descendant_or_self() -> Step {
+    Step {
+        axis: Axis::DescendantOrSelf,
+        test: NodeTest::Node,
+        predicates: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let name = |local_name: &str| NodeTest::Name {
+            prefix: None,
+            local_name: Some(local_name.to_owned()),
+        };
+
+        assert_eq!(
+            parse("//a/@b").unwrap(),
+            Expr::Path(
+                Box::new(Expr::Root),
+                vec![
+                    descendant_or_self(),
+                    Step {
+                        axis: Axis::Child,
+                        test: name("a"),
+                        predicates: vec![]
+                    },
+                    Step {
+                        axis: Axis::Attribute,
+                        test: name("b"),
+                        predicates: vec![]
+                    },
+                ]
+            )
+        );
+
+        // `*` and names as operators.
+        assert_eq!(
+            parse("div * div").unwrap(),
+            Expr::Binary(
+                BinaryOp::Mul,
+                Box::new(Expr::Path(
+                    Box::new(Expr::ContextNode),
+                    vec![Step {
+                        axis: Axis::Child,
+                        test: name("div"),
+                        predicates: vec![]
+                    }]
+                )),
+                Box::new(Expr::Path(
+                    Box::new(Expr::ContextNode),
+                    vec![Step {
+                        axis: Axis::Child,
+                        test: name("div"),
+                        predicates: vec![]
+                    }]
+                ))
+            )
+        );
+
+        assert_eq!(
+            parse("1 - -2 mod 3").unwrap(),
+            Expr::Binary(
+                BinaryOp::Sub,
+                Box::new(Expr::Number(1.0)),
+                Box::new(Expr::Binary(
+                    BinaryOp::Mod,
+                    Box::new(Expr::Negate(Box::new(Expr::Number(2.0)))),
+                    Box::new(Expr::Number(3.0))
+                ))
+            )
+        );
+
+        assert!(parse("ancestor::p:*[last()]/processing-instruction('x')").is_ok());
+        assert!(parse("$v[1]//text() | id('a')/..").is_ok());
+        assert!(parse("/").is_ok());
+
+        assert!(parse("a[").is_err());
+        assert!(parse("unknown::a").is_err());
+        assert!(parse("'open").is_err());
+        assert!(parse("f(1,)").is_err());
+
+        // the name characters of XML.
+        assert!(parse("//a\u{300}·b/x\u{203F}").is_ok());
+        assert!(parse("//\u{AA}").is_err());
+        assert!(parse("//a\u{D7}").is_err());
+    }
+}
@@ -0,0 +1,618 @@
+//! Forward-only evaluation of an XPath subset over the events of the stax [`Reader`].
+
+use std::collections::HashMap;
+
+use rexml_stax::parser::{Attribute, BorrowInput, Event, NamespaceStack, Reader};
+
+use crate::{Document, DocumentBuilder, Error, Result};
+
+use super::{
+    parser::{self, Axis, BinaryOp, Expr, NodeTest, Step},
+    XPathContext,
+};
+
+fn unsupported<T>(what: &str) -> Result<T> {
+    Err(Error::XPath(format!(
+        "{} is not supported by streaming evaluation",
+        what
+    )))
+}
+
+fn is_namespace_declaration(attr: &Attribute<'_>) -> bool {
+    attr.name == "xmlns" || attr.name.starts_with("xmlns:")
+}
+
+/// A name test with the prefix resolved, `None` local name is `*`.
+#[derive(Debug, Clone)]
+struct NameTest {
+    any_namespace: bool,
+    uri: Option<String>,
+    local_name: Option<String>,
+}
+
+impl NameTest {
+    fn new(test: &NodeTest, context: &XPathContext) -> Result<Self> {
+        let NodeTest::Name { prefix, local_name } = test else {
+            return unsupported("a node test other than a name test");
+        };
+
+        let uri = match prefix {
+            Some(prefix) => match context.namespaces.get(prefix) {
+                Some(uri) => Some(uri.clone()),
+                None => return Err(Error::XPath(format!("undeclared prefix {}", prefix))),
+            },
+            None => None,
+        };
+
+        Ok(Self {
+            any_namespace: prefix.is_none() && local_name.is_none(),
+            uri,
+            local_name: local_name.clone(),
+        })
+    }
+
+    fn matches(&self, uri: Option<&str>, local_name: &str) -> bool {
+        (self.any_namespace || self.uri.as_deref() == uri)
+            && self
+                .local_name
+                .as_deref()
+                .is_none_or(|name| name == local_name)
+    }
+}
+
+/// A predicate on the attributes of an element.
+#[derive(Debug, Clone)]
+enum Condition {
+    /// `[@name]`
+    Exists(NameTest),
+    /// `[@name = 'value']` or `[@name != 'value']`.
+    Compare {
+        name: NameTest,
+        value: String,
+        equal: bool,
+    },
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    fn new(expr: &Expr, context: &XPathContext) -> Result<Self> {
+        let condition = match expr {
+            Expr::Binary(BinaryOp::And, left, right) => Self::And(
+                Box::new(Self::new(left, context)?),
+                Box::new(Self::new(right, context)?),
+            ),
+            Expr::Binary(BinaryOp::Or, left, right) => Self::Or(
+                Box::new(Self::new(left, context)?),
+                Box::new(Self::new(right, context)?),
+            ),
+            Expr::Function(name, args) if name == "not" && args.len() == 1 => {
+                Self::Not(Box::new(Self::new(&args[0], context)?))
+            }
+            Expr::Binary(op @ (BinaryOp::Eq | BinaryOp::Ne), left, right) => {
+                let (attribute, value) = match (&**left, &**right) {
+                    (Expr::Literal(value), attribute) | (attribute, Expr::Literal(value)) => {
+                        (attribute, value)
+                    }
+                    _ => return unsupported("a comparison with something else than a string"),
+                };
+
+                let Some(name) = Self::attribute(attribute, context)? else {
+                    return unsupported("a comparison with something else than an attribute");
+                };
+
+                Self::Compare {
+                    name,
+                    value: value.clone(),
+                    equal: *op == BinaryOp::Eq,
+                }
+            }
+            expr => match Self::attribute(expr, context)? {
+                Some(name) => Self::Exists(name),
+                None => return unsupported("this predicate"),
+            },
+        };
+
+        Ok(condition)
+    }
+
+    /// Returns the name test of `expr` if it is `@name`.
+    fn attribute(expr: &Expr, context: &XPathContext) -> Result<Option<NameTest>> {
+        match expr {
+            Expr::Path(start, steps)
+                if **start == Expr::ContextNode
+                    && steps.len() == 1
+                    && steps[0].axis == Axis::Attribute
+                    && steps[0].predicates.is_empty() =>
+            {
+                NameTest::new(&steps[0].test, context).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Evaluate this condition on the `(namespace uri, local name, value)` of the attributes.
+    fn eval(&self, attributes: &[(Option<&str>, &str, &str)]) -> bool {
+        fn matching<'v>(
+            attributes: &'v [(Option<&str>, &str, &'v str)],
+            name: &'v NameTest,
+        ) -> impl Iterator<Item = &'v str> {
+            attributes
+                .iter()
+                .filter(move |(uri, local_name, _)| name.matches(*uri, local_name))
+                .map(|(_, _, value)| *value)
+        }
+
+        match self {
+            Self::Exists(name) => matching(attributes, name).next().is_some(),
+            Self::Compare { name, value, equal } => {
+                matching(attributes, name).any(|attr| (attr == value) == *equal)
+            }
+            Self::And(left, right) => left.eval(attributes) && right.eval(attributes),
+            Self::Or(left, right) => left.eval(attributes) || right.eval(attributes),
+            Self::Not(condition) => !condition.eval(attributes),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Predicate {
+    /// The position among the siblings which pass the previous predicates, 0 never matches.
+    Position(usize),
+    Condition(Condition),
+}
+
+impl Predicate {
+    fn new(expr: &Expr, context: &XPathContext) -> Result<Self> {
+        let position = |number: f64| {
+            if number >= 1.0 && number.fract() == 0.0 {
+                Self::Position(number as usize)
+            } else {
+                Self::Position(0)
+            }
+        };
+
+        match expr {
+            Expr::Number(number) => Ok(position(*number)),
+            Expr::Binary(BinaryOp::Eq, left, right) => match (&**left, &**right) {
+                (Expr::Function(name, args), Expr::Number(number))
+                | (Expr::Number(number), Expr::Function(name, args))
+                    if name == "position" && args.is_empty() =>
+                {
+                    Ok(position(*number))
+                }
+                _ => Condition::new(expr, context).map(Self::Condition),
+            },
+            expr => Condition::new(expr, context).map(Self::Condition),
+        }
+    }
+}
+
+/// One element step of the path.
+#[derive(Debug, Clone)]
+struct StreamStep {
+    /// Apply to all descendants of the context, not only to the children.
+    descendant: bool,
+    test: NameTest,
+    predicates: Vec<Predicate>,
+}
+
+/// An absolute location path evaluated on reader events, see [`XPathReader`].
+///
+/// Only the subset that can be decided at the start tag of an element is supported:
+/// the `child` and `descendant` axes (and `//`), element name tests, and predicates
+/// made of positions, `@name`, `@name = 'value'`, `@name != 'value'`, `and`, `or`
+/// and `not()`. Positions are counted among the siblings, so they are not allowed
+/// on the `descendant` axis.
+#[derive(Debug, Clone)]
+pub struct StreamingXPath {
+    steps: Vec<StreamStep>,
+}
+
+impl StreamingXPath {
+    /// Parse `expression`, the prefixes of names are resolved by the namespace
+    /// bindings of `context`.
+    pub fn compile(expression: &str, context: &XPathContext) -> Result<Self> {
+        let Expr::Path(start, path) = parser::parse(expression)? else {
+            return unsupported("an expression other than a location path");
+        };
+
+        if *start != Expr::Root {
+            return unsupported("a relative location path");
+        }
+
+        let mut steps = vec![];
+        let mut descendant = false;
+
+        for Step {
+            axis,
+            test,
+            predicates,
+        } in &path
+        {
+            match axis {
+                Axis::DescendantOrSelf if *test == NodeTest::Node && predicates.is_empty() => {
+                    descendant = true;
+                    continue;
+                }
+                Axis::Child => {}
+                Axis::Descendant => descendant = true,
+                axis => return unsupported(&format!("the {:?} axis", axis)),
+            }
+
+            let predicates = predicates
+                .iter()
+                .map(|predicate| Predicate::new(predicate, context))
+                .collect::<Result<Vec<_>>>()?;
+
+            if *axis == Axis::Descendant
+                && predicates
+                    .iter()
+                    .any(|predicate| matches!(predicate, Predicate::Position(_)))
+            {
+                return unsupported("a position on the descendant axis");
+            }
+
+            steps.push(StreamStep {
+                descendant,
+                test: NameTest::new(test, context)?,
+                predicates,
+            });
+
+            descendant = false;
+        }
+
+        if steps.is_empty() {
+            return unsupported("a path without element steps");
+        }
+
+        Ok(Self { steps })
+    }
+}
+
+/// The state of one open element, or of the document.
+#[derive(Debug, Default)]
+struct Frame<'a> {
+    /// The indexes of the steps applied to the children of this element.
+    contexts: Vec<usize>,
+    /// The number of children which reached the predicate `(step, predicate)`.
+    positions: HashMap<(usize, usize), usize>,
+    /// The namespace declarations of this element.
+    declarations: Vec<Attribute<'a>>,
+}
+
+/// The events of one matched element read so far.
+#[derive(Debug)]
+struct Capture<'a> {
+    /// The number of open frames inside the matched element.
+    depth: usize,
+    events: Vec<Event<'a>>,
+}
+
+/// Read the elements selected by a [`StreamingXPath`] from a [`Reader`].
+///
+/// The events of each selected element are returned as soon as its end tag is read,
+/// so memory use is bounded by the size of the selected elements, not of the document.
+/// Selected elements nested in another selected element are returned before it.
+///
+/// The start tag of a selected element also declares the namespaces in scope,
+/// so that the events can be read without the context of the ancestors.
+pub struct XPathReader<'r, 'a, I> {
+    reader: &'r mut Reader<I>,
+    path: &'r StreamingXPath,
+    namespaces: NamespaceStack<'a>,
+    /// The document followed by the open elements.
+    frames: Vec<Frame<'a>>,
+    captures: Vec<Capture<'a>>,
+}
+
+impl<'r, 'a, I> XPathReader<'r, 'a, I>
+where
+    I: BorrowInput<'a>,
+{
+    /// Evaluate `path` on the events of `reader`.
+    pub fn new(reader: &'r mut Reader<I>, path: &'r StreamingXPath) -> Self {
+        Self {
+            reader,
+            path,
+            namespaces: Default::default(),
+            frames: vec![Frame {
+                contexts: vec![0],
+                ..Default::default()
+            }],
+            captures: vec![],
+        }
+    }
+
+    /// Returns the events of the next selected element, `None` at the end of the document.
+    pub async fn next_events(&mut self) -> Result<Option<Vec<Event<'a>>>> {
+        while let Some(event) = self.reader.next().await? {
+            if let Some(events) = self.event(event)? {
+                return Ok(Some(events));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the next selected element as the document element of a new `Document`.
+    pub async fn next_document(&mut self) -> Result<Option<Document<'a>>> {
+        match self.next_events().await? {
+            Some(events) => DocumentBuilder::new().build_from_events(events).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Process one event, returns the events of the selected element it completes, if any.
+    fn event(&mut self, event: Event<'a>) -> Result<Option<Vec<Event<'a>>>> {
+        match event {
+            Event::StartElement { name, attributes } => {
+                self.namespaces.push_scope(&attributes)?;
+
+                let (matched, contexts) = self.select(&name, &attributes)?;
+
+                if matched {
+                    self.captures.push(Capture {
+                        depth: self.frames.len(),
+                        events: vec![],
+                    });
+                }
+
+                let declarations = attributes
+                    .iter()
+                    .filter(|attr| is_namespace_declaration(attr))
+                    .cloned()
+                    .collect::<Vec<_>>();
+
+                let (current, outer) = match self.captures.split_last_mut() {
+                    Some((last, outer)) if matched => (Some(last), outer),
+                    _ => (None, &mut self.captures[..]),
+                };
+
+                for capture in outer {
+                    capture.events.push(Event::StartElement {
+                        name: name.clone(),
+                        attributes: attributes.clone(),
+                    });
+                }
+
+                if let Some(capture) = current {
+                    let mut attributes = attributes;
+
+                    for frame in self.frames.iter().rev() {
+                        for declaration in &frame.declarations {
+                            if !attributes.iter().any(|attr| attr.name == declaration.name) {
+                                attributes.push(declaration.clone());
+                            }
+                        }
+                    }
+
+                    capture
+                        .events
+                        .push(Event::StartElement { name, attributes });
+                }
+
+                self.frames.push(Frame {
+                    contexts,
+                    positions: Default::default(),
+                    declarations,
+                });
+            }
+            Event::EndElement { name } => {
+                for capture in &mut self.captures {
+                    capture
+                        .events
+                        .push(Event::EndElement { name: name.clone() });
+                }
+
+                self.namespaces.pop_scope();
+
+                let depth = self.frames.len() - 1;
+                self.frames.pop();
+
+                if self
+                    .captures
+                    .last()
+                    .is_some_and(|capture| capture.depth == depth)
+                {
+                    return Ok(self.captures.pop().map(|capture| capture.events));
+                }
+            }
+            Event::StartDocument { .. } | Event::DocType(_) | Event::EndDocument => {}
+            event => {
+                for capture in &mut self.captures {
+                    capture.events.push(event.clone());
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Apply the steps of the parent contexts to a new element, returns whether the element
+    /// is selected and the contexts of its children.
+    fn select(&mut self, name: &str, attributes: &[Attribute<'a>]) -> Result<(bool, Vec<usize>)> {
+        let steps = &self.path.steps;
+        let namespaces = &self.namespaces;
+
+        let (uri, local_name) = namespaces.resolve_name(name, false)?;
+
+        let attributes = attributes
+            .iter()
+            .filter(|attr| !is_namespace_declaration(attr))
+            .map(|attr| {
+                namespaces
+                    .resolve_name(&attr.name, true)
+                    .map(|(uri, local_name)| (uri, local_name, attr.value.as_ref()))
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let parent = self.frames.last_mut().expect("document frame");
+
+        let mut matched = false;
+        let mut contexts = vec![];
+
+        for &index in &parent.contexts {
+            let step = &steps[index];
+
+            if step.descendant {
+                contexts.push(index);
+            }
+
+            if !step.test.matches(uri, local_name) {
+                continue;
+            }
+
+            let passed = step
+                .predicates
+                .iter()
+                .enumerate()
+                .all(|(i, predicate)| match predicate {
+                    Predicate::Position(position) => {
+                        let count = parent.positions.entry((index, i)).or_default();
+                        *count += 1;
+                        *count == *position
+                    }
+                    Predicate::Condition(condition) => condition.eval(&attributes),
+                });
+
+            if !passed {
+                continue;
+            }
+
+            if index + 1 == steps.len() {
+                matched = true;
+            } else {
+                contexts.push(index + 1);
+            }
+        }
+
+        contexts.sort_unstable();
+        contexts.dedup();
+
+        Ok((matched, contexts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::Serializer;
+
+    const FEED: &str = r#"<?xml version="1.0"?>
+<feed xmlns="urn:feed" xmlns:x="urn:x">
+  <title>News</title>
+  <entry type="a" x:rank="1"><title>First</title></entry>
+  <entry type="b"><title>Second</title><entry type="a"><title>Nested</title></entry></entry>
+  <entry type="a"><title>Third</title></entry>
+</feed>"#;
+
+    fn select(expression: &str) -> Result<Vec<String>> {
+        let context = XPathContext::new()
+            .namespace("f", "urn:feed")
+            .namespace("x", "urn:x");
+
+        let path = StreamingXPath::compile(expression, &context)?;
+
+        let mut reader = Reader::new(FEED);
+        let mut reader = XPathReader::new(&mut reader, &path);
+
+        let mut selected = vec![];
+
+        while let Some(doc) = block_on(reader.next_document())? {
+            selected.push(
+                Serializer::new()
+                    .xml_declaration(false)
+                    .to_string(&doc, None)?,
+            );
+        }
+
+        Ok(selected)
+    }
+
+    /// Returns the string value of the title of each selected document.
+    fn titles(selected: Vec<String>) -> Vec<String> {
+        selected
+            .iter()
+            .map(|doc| {
+                let doc = Document::parse(doc).unwrap();
+                doc.evaluate(&Default::default(), "string(//*[local-name() = 'title'])")
+                    .unwrap()
+                    .string(&doc)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_paths() {
+        assert_eq!(
+            titles(select("/f:feed/f:entry").unwrap()),
+            ["First", "Second", "Third"]
+        );
+        assert_eq!(titles(select("/f:feed/f:title").unwrap()), ["News"]);
+        assert_eq!(
+            titles(select("/*/*/f:title").unwrap()),
+            ["First", "Second", "Third"]
+        );
+        assert_eq!(select("/feed/entry").unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_predicates() {
+        assert_eq!(titles(select("/f:feed/f:entry[2]").unwrap()), ["Second"]);
+        assert_eq!(
+            titles(select("/f:feed/f:entry[@type = 'a'][2]").unwrap()),
+            ["Third"]
+        );
+        assert_eq!(
+            titles(select("/descendant::f:entry[not(@type = 'a')]").unwrap()),
+            ["Second"]
+        );
+        assert_eq!(
+            titles(select("/*/*[@x:rank or @type='b']").unwrap()),
+            ["First", "Second"]
+        );
+    }
+
+    #[test]
+    fn test_nested() {
+        // the nested entry is complete first.
+        assert_eq!(
+            titles(select("//f:entry").unwrap()),
+            ["First", "Nested", "Second", "Third"]
+        );
+        assert_eq!(
+            titles(select("//f:entry[@type = 'a']").unwrap()),
+            ["First", "Nested", "Third"]
+        );
+        assert_eq!(titles(select("//f:entry/f:entry").unwrap()), ["Nested"]);
+    }
+
+    #[test]
+    fn test_namespaces() {
+        // the declarations in scope are copied to the captured root.
+        assert_eq!(
+            select("/f:feed/f:entry[1]").unwrap(),
+            [
+                r#"<entry type="a" x:rank="1" xmlns="urn:feed" xmlns:x="urn:x"><title>First</title></entry>"#
+            ]
+        );
+        assert_eq!(
+            select("//f:entry/f:entry/f:title").unwrap(),
+            [r#"<title xmlns="urn:feed" xmlns:x="urn:x">Nested</title>"#]
+        );
+
+        assert!(select("//y:entry").is_err());
+    }
+
+    #[test]
+    fn test_unsupported() {
+        assert!(select("/f:feed/..").is_err());
+        assert!(select("//f:entry | //f:title").is_err());
+        assert!(select("//f:entry[last()]").is_err());
+        assert!(select("/descendant::f:entry[1]").is_err());
+    }
+}
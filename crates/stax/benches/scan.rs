@@ -0,0 +1,128 @@
+use std::{hint::black_box, sync::OnceLock};
+
+use divan::{counter::BytesCount, Bencher};
+
+fn main() {
+    divan::main();
+}
+
+/// A large document with long text nodes, attribute values and comments.
+fn document() -> &'static str {
+    static DOCUMENT: OnceLock<String> = OnceLock::new();
+
+    DOCUMENT.get_or_init(|| {
+        let text = "Lorem ipsum dolor sit amet, consectetur adipiscing elit. ".repeat(16);
+
+        let mut document = String::from("<?xml version=\"1.0\"?>\n<catalog>\n");
+
+        for index in 0..2000 {
+            document.push_str(&format!(
+                "  <item id=\"item-{}\" title=\"{}\">\n    <!-- {} -->\n    <p>{}&amp;{}</p>\n  </item>\n",
+                index,
+                &text[..200],
+                text,
+                text,
+                text
+            ));
+        }
+
+        document.push_str("</catalog>\n");
+
+        document
+    })
+}
+
+/// Count delimiters the way the reader scans character data.
+fn count_delimiters(find: fn(u8, u8, u8, &[u8]) -> Option<usize>, mut haystack: &[u8]) -> usize {
+    let mut count = 0;
+
+    while let Some(index) = find(b'<', b'&', b'\r', haystack) {
+        count += 1;
+        haystack = &haystack[index + 1..];
+    }
+
+    count
+}
+
+/// Count the end of comments the way the reader scans for a terminator.
+fn count_terminators(find: fn(&[u8], &[u8]) -> Option<usize>, mut haystack: &[u8]) -> usize {
+    let mut count = 0;
+
+    while let Some(index) = find(b"-->", haystack) {
+        count += 1;
+        haystack = &haystack[index + 3..];
+    }
+
+    count
+}
+
+#[divan::bench_group(sample_count = 20)]
+mod delimiters {
+    use super::*;
+
+    use rexml_stax::parser::scan;
+
+    #[divan::bench]
+    fn accelerated(bencher: Bencher) {
+        let document = document();
+
+        bencher
+            .counter(BytesCount::of_str(document))
+            .bench(|| count_delimiters(scan::find_byte3, black_box(document.as_bytes())));
+    }
+
+    #[divan::bench]
+    fn scalar(bencher: Bencher) {
+        let document = document();
+
+        bencher
+            .counter(BytesCount::of_str(document))
+            .bench(|| count_delimiters(scan::scalar::find_byte3, black_box(document.as_bytes())));
+    }
+}
+
+#[divan::bench_group(sample_count = 20)]
+mod terminators {
+    use super::*;
+
+    use rexml_stax::parser::scan;
+
+    #[divan::bench]
+    fn accelerated(bencher: Bencher) {
+        let document = document();
+
+        bencher
+            .counter(BytesCount::of_str(document))
+            .bench(|| count_terminators(scan::find_bytes, black_box(document.as_bytes())));
+    }
+
+    #[divan::bench]
+    fn scalar(bencher: Bencher) {
+        let document = document();
+
+        bencher
+            .counter(BytesCount::of_str(document))
+            .bench(|| count_terminators(scan::scalar::find_bytes, black_box(document.as_bytes())));
+    }
+}
+
+/// The whole reader, run with `--no-default-features --features std` for the scalar path.
+#[divan::bench(sample_count = 20)]
+fn reader(bencher: Bencher) {
+    use futures::executor::block_on;
+    use rexml_stax::parser::Reader;
+
+    let document = document();
+
+    bencher.counter(BytesCount::of_str(document)).bench(|| {
+        let mut reader = Reader::new(black_box(document));
+        let mut events = 0;
+
+        while let Some(event) = block_on(reader.next()).unwrap() {
+            black_box(event);
+            events += 1;
+        }
+
+        events
+    });
+}
@@ -3,4 +3,49 @@
 pub enum Error {
     #[error(transparent)]
     Io(#[from] std::io::Error),
+
+    #[error("Unexpected end of input")]
+    UnexpectedEof,
+
+    #[error("Input buffer overflow")]
+    Overflow,
+
+    #[error("Invalid UTF-8 sequence at {0}")]
+    Utf8(usize),
+
+    #[error("Syntax error at {offset}: {reason}")]
+    Syntax { offset: usize, reason: &'static str },
+
+    #[error("Mismatched end tag, expect `{expect}`, got `{found}`")]
+    MismatchedTag { expect: String, found: String },
+
+    #[error("Duplicate attribute: {0}")]
+    DuplicateAttribute(String),
+
+    #[error("Malformed reference: &{0}")]
+    MalformedReference(String),
+
+    #[error("Reference to undeclared entity: {0}")]
+    UndeclaredEntity(String),
+
+    #[error("Illegal reference to entity: {0}")]
+    IllegalEntityReference(String),
+
+    #[error("Undeclared namespace prefix: {0}")]
+    UndeclaredPrefix(String),
+
+    #[error("Illegal namespace declaration: {0}")]
+    ReservedNamespace(String),
+
+    #[error("Invalid name: {0}")]
+    InvalidName(String),
+
+    #[error("Invalid character: {0:?}")]
+    InvalidChar(char),
+
+    #[error("Out of order writer call: {0}")]
+    WriterState(&'static str),
 }
+
+/// Result type returns by this mod.
+pub type Result<T> = std::result::Result<T, Error>;
@@ -11,3 +11,7 @@ mod errors;
 pub use errors::*;
 
 pub mod parser;
+
+pub mod sax;
+
+pub mod writer;
@@ -0,0 +1,320 @@
+//! A restartable cursor over the buffered part of an input stream.
+
+use crate::Error;
+
+use super::{
+    is_name_char, is_name_start_char, is_whitespace,
+    scan::{find_byte, find_bytes},
+};
+
+/// Reason of a parsing step stopping early.
+pub(crate) enum Halt {
+    /// The buffered data ends in the middle of a construct.
+    Incomplete,
+    /// The document is not well-formed.
+    Error(Error),
+}
+
+impl From<Error> for Halt {
+    fn from(value: Error) -> Self {
+        Self::Error(value)
+    }
+}
+
+impl From<Halt> for Error {
+    fn from(value: Halt) -> Self {
+        match value {
+            Halt::Incomplete => Error::UnexpectedEof,
+            Halt::Error(err) => err,
+        }
+    }
+}
+
+/// Result type of partial parsing functions.
+pub(crate) type PResult<T> = std::result::Result<T, Halt>;
+
+/// Progress of the search which stopped the last step with [`Halt::Incomplete`].
+///
+/// The step is retried from the start of the construct once more data is buffered,
+/// the same search then continues from `end` instead of scanning the same bytes again.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Resume {
+    /// The position where the search started.
+    start: usize,
+    /// The searched terminator.
+    pat: &'static str,
+    /// The position up to which the terminator was not found.
+    end: usize,
+}
+
+/// A cursor over one buffered slice.
+///
+/// When `eof` is false, every function returns [`Halt::Incomplete`] instead of an error
+/// if the result might change once more data is buffered.
+pub(crate) struct Cursor<'s> {
+    input: &'s str,
+    pos: usize,
+    eof: bool,
+    /// Offset of `input` in the whole document, used by error reporting.
+    base: usize,
+    resume: Resume,
+}
+
+impl<'s> Cursor<'s> {
+    pub(crate) fn new(input: &'s str, eof: bool, base: usize) -> Self {
+        Self {
+            input,
+            pos: 0,
+            eof,
+            base,
+            resume: Resume::default(),
+        }
+    }
+
+    /// Continue the search saved by [`resume`](Self::resume) on a retry of the same input.
+    pub(crate) fn with_resume(mut self, resume: Resume) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    /// Returns the progress of the last search that halted with [`Halt::Incomplete`].
+    pub(crate) fn resume(&self) -> Resume {
+        self.resume
+    }
+
+    /// Search the ASCII terminator `pat` with `find` from the current position,
+    /// returns the index relative to the current position.
+    ///
+    /// Returns [`Halt::Incomplete`] if not found and more data may be buffered,
+    /// the progress is kept for a retry with the same `pat`.
+    pub(crate) fn search(
+        &mut self,
+        pat: &'static str,
+        find: impl FnOnce(&[u8]) -> Option<usize>,
+    ) -> PResult<Option<usize>> {
+        let bytes = self.input.as_bytes();
+
+        // a terminator may start in the last bytes of the previous search.
+        let from = match self.resume {
+            Resume { start, pat: p, end } if start == self.pos && p == pat => {
+                end.saturating_sub(pat.len() - 1).max(self.pos)
+            }
+            _ => self.pos,
+        };
+
+        match find(&bytes[from..]) {
+            Some(index) => Ok(Some(from + index - self.pos)),
+            None if self.eof => Ok(None),
+            None => {
+                self.resume = Resume {
+                    start: self.pos,
+                    pat,
+                    end: bytes.len(),
+                };
+
+                Err(Halt::Incomplete)
+            }
+        }
+    }
+
+    /// Returns the consumed length in bytes.
+    pub(crate) fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Returns the whole buffered slice.
+    pub(crate) fn input(&self) -> &'s str {
+        self.input
+    }
+
+    /// Returns the unconsumed part of the buffered slice.
+    pub(crate) fn rest(&self) -> &'s str {
+        &self.input[self.pos..]
+    }
+
+    pub(crate) fn is_eof(&self) -> bool {
+        self.eof
+    }
+
+    /// Returns true if the whole input has been consumed.
+    pub(crate) fn at_end(&self) -> PResult<bool> {
+        if self.pos < self.input.len() {
+            Ok(false)
+        } else if self.eof {
+            Ok(true)
+        } else {
+            Err(Halt::Incomplete)
+        }
+    }
+
+    pub(crate) fn advance(&mut self, steps: usize) {
+        self.pos += steps;
+        debug_assert!(self.input.is_char_boundary(self.pos));
+    }
+
+    /// Move back or forward to the absolute position `pos`.
+    pub(crate) fn reset(&mut self, pos: usize) {
+        self.pos = pos;
+        debug_assert!(self.input.is_char_boundary(self.pos));
+    }
+
+    /// Create a syntax error located at the current position.
+    pub(crate) fn error(&self, reason: &'static str) -> Halt {
+        Halt::Error(Error::Syntax {
+            offset: self.base + self.pos,
+            reason,
+        })
+    }
+
+    /// Returns the next char without consuming it.
+    pub(crate) fn peek(&self) -> PResult<Option<char>> {
+        match self.rest().chars().next() {
+            Some(c) => Ok(Some(c)),
+            None if self.eof => Ok(None),
+            None => Err(Halt::Incomplete),
+        }
+    }
+
+    /// Returns true if the unconsumed input starts with `pat`.
+    pub(crate) fn starts_with(&self, pat: &str) -> PResult<bool> {
+        let rest = self.rest();
+
+        if rest.len() >= pat.len() {
+            Ok(rest.as_bytes().starts_with(pat.as_bytes()))
+        } else if !self.eof && pat.as_bytes().starts_with(rest.as_bytes()) {
+            Err(Halt::Incomplete)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Consume `pat` if the unconsumed input starts with it.
+    pub(crate) fn eat(&mut self, pat: &str) -> PResult<bool> {
+        if self.starts_with(pat)? {
+            self.pos += pat.len();
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Consume `pat` or raise a syntax error.
+    pub(crate) fn expect(&mut self, pat: &str, reason: &'static str) -> PResult<()> {
+        if self.eat(pat)? {
+            Ok(())
+        } else {
+            Err(self.error(reason))
+        }
+    }
+
+    /// Skip white spaces, returns the number of skipped bytes.
+    pub(crate) fn skip_whitespace(&mut self) -> PResult<usize> {
+        let rest = self.rest();
+
+        let len = rest
+            .bytes()
+            .position(|c| !is_whitespace(c as char))
+            .unwrap_or(rest.len());
+
+        if len == rest.len() && !self.eof {
+            return Err(Halt::Incomplete);
+        }
+
+        self.pos += len;
+
+        Ok(len)
+    }
+
+    /// Skip at least one white space.
+    pub(crate) fn expect_whitespace(&mut self, reason: &'static str) -> PResult<()> {
+        if self.skip_whitespace()? == 0 {
+            Err(self.error(reason))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Consume a `Name` production.
+    pub(crate) fn name(&mut self) -> PResult<&'s str> {
+        let rest = self.rest();
+
+        let mut chars = rest.char_indices();
+
+        match chars.next() {
+            Some((_, c)) if is_name_start_char(c) => {}
+            Some(_) => return Err(self.error("expect name")),
+            None if self.eof => return Err(self.error("expect name")),
+            None => return Err(Halt::Incomplete),
+        }
+
+        let len = chars
+            .find(|(_, c)| !is_name_char(*c))
+            .map(|(index, _)| index)
+            .unwrap_or(rest.len());
+
+        if len == rest.len() && !self.eof {
+            return Err(Halt::Incomplete);
+        }
+
+        self.pos += len;
+
+        Ok(&rest[..len])
+    }
+
+    /// Consume a `Nmtoken` production.
+    pub(crate) fn nmtoken(&mut self) -> PResult<&'s str> {
+        let rest = self.rest();
+
+        let len = rest
+            .char_indices()
+            .find(|(_, c)| !is_name_char(*c))
+            .map(|(index, _)| index)
+            .unwrap_or(rest.len());
+
+        if len == rest.len() && !self.eof {
+            return Err(Halt::Incomplete);
+        }
+
+        if len == 0 {
+            return Err(self.error("expect nmtoken"));
+        }
+
+        self.pos += len;
+
+        Ok(&rest[..len])
+    }
+
+    /// Consume input up to and including `pat`, returns the content before `pat`.
+    pub(crate) fn take_until(&mut self, pat: &'static str) -> PResult<&'s str> {
+        let rest = self.rest();
+
+        match self.search(pat, |bytes| find_bytes(pat.as_bytes(), bytes))? {
+            Some(index) => {
+                self.pos += index + pat.len();
+                Ok(&rest[..index])
+            }
+            None => Err(Halt::Error(Error::UnexpectedEof)),
+        }
+    }
+
+    /// Consume a quoted literal, returns the content between the quotes.
+    pub(crate) fn quoted(&mut self, reason: &'static str) -> PResult<&'s str> {
+        let quote = match self.peek()? {
+            Some(c @ ('"' | '\'')) => c,
+            _ => return Err(self.error(reason)),
+        };
+
+        self.pos += 1;
+
+        let rest = self.rest();
+
+        match find_byte(quote as u8, rest.as_bytes()) {
+            Some(index) => {
+                self.pos += index + 1;
+                Ok(&rest[..index])
+            }
+            None if self.eof => Err(Halt::Error(Error::UnexpectedEof)),
+            None => Err(Halt::Incomplete),
+        }
+    }
+}
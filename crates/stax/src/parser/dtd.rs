@@ -0,0 +1,915 @@
+//! Document type definition declared by the internal subset.
+
+use std::{borrow::Cow, collections::BTreeMap};
+
+use crate::{Error, Result};
+
+use super::{
+    cursor::{Cursor, PResult},
+    is_whitespace, is_xml_char,
+};
+
+/// Maximum nesting depth of entity expansion.
+const MAX_ENTITY_DEPTH: usize = 16;
+
+/// The declared type of one attribute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttributeType {
+    /// `CDATA`
+    CData,
+    /// `ID`
+    Id,
+    /// `IDREF`
+    IdRef,
+    /// `IDREFS`
+    IdRefs,
+    /// `ENTITY`
+    Entity,
+    /// `ENTITIES`
+    Entities,
+    /// `NMTOKEN`
+    NmToken,
+    /// `NMTOKENS`
+    NmTokens,
+    /// `NOTATION (a|b)`
+    Notation(Vec<String>),
+    /// `(a|b)`
+    Enumeration(Vec<String>),
+}
+
+impl AttributeType {
+    /// Returns true if the value of this type is not collapsed by attribute normalization.
+    pub fn is_cdata(&self) -> bool {
+        matches!(self, AttributeType::CData)
+    }
+}
+
+/// The default declaration of one attribute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DefaultDecl {
+    /// `#REQUIRED`
+    Required,
+    /// `#IMPLIED`
+    Implied,
+    /// `#FIXED "value"`, the value is normalized.
+    Fixed(String),
+    /// `"value"`, the value is normalized.
+    Value(String),
+}
+
+impl DefaultDecl {
+    /// Returns the default value, if any.
+    pub fn value(&self) -> Option<&str> {
+        match self {
+            DefaultDecl::Fixed(value) | DefaultDecl::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// One attribute definition of an `<!ATTLIST` declaration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeDecl {
+    /// The qualified name of the attribute.
+    pub name: String,
+    /// The declared type.
+    pub attr_type: AttributeType,
+    /// The default declaration.
+    pub default: DefaultDecl,
+}
+
+/// The content specification of an `<!ELEMENT` declaration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentSpec {
+    /// `EMPTY`
+    Empty,
+    /// `ANY`
+    Any,
+    /// `(#PCDATA|a|b)*`, with the allowed child element names.
+    Mixed(Vec<String>),
+    /// Element content, with the model source text such as `(a,b?)*`.
+    Children(String),
+}
+
+/// An `<!ELEMENT` declaration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElementDecl {
+    /// The element type name.
+    pub name: String,
+    /// The content specification.
+    pub content: ContentSpec,
+}
+
+/// An `<!ENTITY` declaration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityDecl {
+    /// The entity name.
+    pub name: String,
+    /// The replacement text of internal entities.
+    pub value: Option<String>,
+    /// The public identifier of external entities.
+    pub public_id: Option<String>,
+    /// The system identifier of external entities.
+    pub system_id: Option<String>,
+    /// The notation name of unparsed entities.
+    pub notation_name: Option<String>,
+}
+
+impl EntityDecl {
+    /// Returns true if this is an internal entity.
+    pub fn is_internal(&self) -> bool {
+        self.value.is_some()
+    }
+
+    /// Returns true if this is an unparsed entity.
+    pub fn is_unparsed(&self) -> bool {
+        self.notation_name.is_some()
+    }
+}
+
+/// A `<!NOTATION` declaration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotationDecl {
+    /// The notation name.
+    pub name: String,
+    /// The public identifier.
+    pub public_id: Option<String>,
+    /// The system identifier.
+    pub system_id: Option<String>,
+}
+
+/// Declarations collected from the internal subset of one document.
+#[derive(Debug, Clone, Default)]
+pub struct Dtd {
+    elements: BTreeMap<String, ElementDecl>,
+    attlists: BTreeMap<String, Vec<AttributeDecl>>,
+    entities: BTreeMap<String, EntityDecl>,
+    parameter_entities: BTreeMap<String, EntityDecl>,
+    notations: BTreeMap<String, NotationDecl>,
+    /// Set after a reference to an unread parameter entity,
+    /// following declarations must not be processed (XML 1.0 §5.1).
+    skip_declarations: bool,
+}
+
+impl Dtd {
+    /// Parse the markup declarations of one internal subset,
+    /// such as the one of [`DocType`](super::DocType) events.
+    pub fn parse(internal_subset: &str) -> Result<Self> {
+        let mut dtd = Dtd::default();
+        let mut cursor = Cursor::new(internal_subset, true, 0);
+
+        parse_markup_decls(&mut cursor, &mut dtd)?;
+
+        if !cursor.at_end()? {
+            return Err(cursor.error("unexpected `]` in internal subset").into());
+        }
+
+        Ok(dtd)
+    }
+
+    /// Returns the element declaration by element type name.
+    pub fn element(&self, name: &str) -> Option<&ElementDecl> {
+        self.elements.get(name)
+    }
+
+    /// Returns the attribute declarations of one element type.
+    pub fn attributes(&self, element: &str) -> &[AttributeDecl] {
+        self.attlists
+            .get(element)
+            .map(|decls| decls.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Returns one attribute declaration of one element type.
+    pub fn attribute(&self, element: &str, name: &str) -> Option<&AttributeDecl> {
+        self.attributes(element)
+            .iter()
+            .find(|decl| decl.name == name)
+    }
+
+    /// Returns an iterator over the element types and their attribute declarations.
+    pub fn attlists(&self) -> impl Iterator<Item = (&str, &[AttributeDecl])> {
+        self.attlists
+            .iter()
+            .map(|(element, decls)| (element.as_str(), decls.as_slice()))
+    }
+
+    /// Returns the general entity declaration by name.
+    pub fn entity(&self, name: &str) -> Option<&EntityDecl> {
+        self.entities.get(name)
+    }
+
+    /// Returns an iterator over general entity declarations.
+    pub fn entities(&self) -> impl Iterator<Item = &EntityDecl> {
+        self.entities.values()
+    }
+
+    /// Returns the notation declaration by name.
+    pub fn notation(&self, name: &str) -> Option<&NotationDecl> {
+        self.notations.get(name)
+    }
+
+    /// Returns an iterator over notation declarations.
+    pub fn notations(&self) -> impl Iterator<Item = &NotationDecl> {
+        self.notations.values()
+    }
+
+    /// Normalize an attribute value, see [XML 1.0 §3.3.3](https://www.w3.org/TR/xml/#AVNormalize).
+    ///
+    /// References are replaced and white spaces are replaced by `#x20`,
+    /// if `attr_type` is not [`AttributeType::CData`], the value is trimmed
+    /// and sequences of spaces are collapsed. Undeclared attributes are treated as `CDATA`.
+    ///
+    /// `value` is returned as is if normalization does not change it.
+    pub fn normalize_attribute_value<'v>(
+        &self,
+        value: &'v str,
+        attr_type: Option<&AttributeType>,
+    ) -> Result<Cow<'v, str>> {
+        let collapse = attr_type.is_some_and(|attr_type| !attr_type.is_cdata());
+
+        if !value.contains(['&', '\r', '\n', '\t'])
+            && !(collapse
+                && (value.starts_with(' ') || value.ends_with(' ') || value.contains("  ")))
+        {
+            return Ok(Cow::Borrowed(value));
+        }
+
+        let mut normalized = String::with_capacity(value.len());
+
+        self.expand_attribute_value(value, &mut normalized, &mut vec![])?;
+
+        if collapse {
+            Ok(collapse_spaces(&normalized).into())
+        } else {
+            Ok(normalized.into())
+        }
+    }
+
+    fn expand_attribute_value<'a>(
+        &'a self,
+        mut value: &'a str,
+        normalized: &mut String,
+        expanding: &mut Vec<&'a str>,
+    ) -> Result<()> {
+        while let Some(index) = value.find(['&', '\r', '\n', '\t', '<']) {
+            normalized.push_str(&value[..index]);
+
+            let c = value.as_bytes()[index];
+
+            value = &value[index + 1..];
+
+            match c {
+                b'\r' => {
+                    normalized.push(' ');
+                    value = value.strip_prefix('\n').unwrap_or(value);
+                }
+                b'\n' | b'\t' => normalized.push(' '),
+                b'<' => {
+                    return Err(Error::IllegalEntityReference(
+                        expanding.last().copied().unwrap_or_default().to_owned(),
+                    ))
+                }
+                _ => {
+                    let end = value
+                        .find(';')
+                        .ok_or_else(|| Error::MalformedReference(value.to_owned()))?;
+
+                    let reference = &value[..end];
+
+                    value = &value[end + 1..];
+
+                    if let Some(c) = parse_reference(reference)? {
+                        normalized.push(c);
+                        continue;
+                    }
+
+                    let value = self.entity_replacement_text(reference, expanding)?;
+
+                    expanding.push(reference);
+                    self.expand_attribute_value(value, normalized, expanding)?;
+                    expanding.pop();
+                }
+            }
+        }
+
+        normalized.push_str(value);
+
+        Ok(())
+    }
+
+    /// Returns the replacement text of one internal general entity referenced in attribute values.
+    fn entity_replacement_text(&self, name: &str, expanding: &[&str]) -> Result<&str> {
+        let decl = self
+            .entities
+            .get(name)
+            .ok_or_else(|| Error::UndeclaredEntity(name.to_owned()))?;
+
+        if expanding.contains(&name) || expanding.len() >= MAX_ENTITY_DEPTH {
+            return Err(Error::IllegalEntityReference(name.to_owned()));
+        }
+
+        decl.value
+            .as_deref()
+            .ok_or_else(|| Error::IllegalEntityReference(name.to_owned()))
+    }
+}
+
+/// Trim spaces and collapse sequences of spaces into a single one.
+fn collapse_spaces(value: &str) -> String {
+    let mut collapsed = String::with_capacity(value.len());
+
+    for token in value.split(' ').filter(|token| !token.is_empty()) {
+        if !collapsed.is_empty() {
+            collapsed.push(' ');
+        }
+
+        collapsed.push_str(token);
+    }
+
+    collapsed
+}
+
+/// Replace `\r\n` and `\r` with `\n`, see [XML 1.0 §2.11](https://www.w3.org/TR/xml/#sec-line-ends).
+pub(crate) fn normalize_line_endings(value: &str) -> Cow<'_, str> {
+    if value.contains('\r') {
+        value.replace("\r\n", "\n").replace('\r', "\n").into()
+    } else {
+        value.into()
+    }
+}
+
+/// Parse the content of a character reference or a predefined entity reference.
+///
+/// Returns `None` for general entity references.
+pub(crate) fn parse_reference(reference: &str) -> Result<Option<char>> {
+    if let Some(code) = reference.strip_prefix('#') {
+        let code = if let Some(hex) = code.strip_prefix('x') {
+            u32::from_str_radix(hex, 16)
+        } else {
+            code.parse::<u32>()
+        };
+
+        return code
+            .ok()
+            .filter(|_| reference.bytes().skip(1).all(|c| c.is_ascii_alphanumeric()))
+            .and_then(char::from_u32)
+            .filter(|c| is_xml_char(*c))
+            .map(Some)
+            .ok_or_else(|| Error::MalformedReference(reference.to_owned()));
+    }
+
+    let c = match reference {
+        "lt" => '<',
+        "gt" => '>',
+        "amp" => '&',
+        "apos" => '\'',
+        "quot" => '"',
+        _ => {
+            let mut cursor = Cursor::new(reference, true, 0);
+
+            match cursor.name() {
+                Ok(name) if name.len() == reference.len() => return Ok(None),
+                _ => return Err(Error::MalformedReference(reference.to_owned())),
+            }
+        }
+    };
+
+    Ok(Some(c))
+}
+
+/// Parse markup declarations until `]` or the end of the input.
+pub(crate) fn parse_markup_decls(cursor: &mut Cursor<'_>, dtd: &mut Dtd) -> PResult<()> {
+    parse_markup_decls_with_depth(cursor, dtd, 0)
+}
+
+fn parse_markup_decls_with_depth(
+    cursor: &mut Cursor<'_>,
+    dtd: &mut Dtd,
+    depth: usize,
+) -> PResult<()> {
+    loop {
+        cursor.skip_whitespace()?;
+
+        if cursor.at_end()? || cursor.starts_with("]")? {
+            return Ok(());
+        }
+
+        if cursor.eat("<!--")? {
+            let comment = cursor.take_until("-->")?;
+
+            if comment.contains("--") || comment.ends_with('-') {
+                return Err(cursor.error("`--` is not allowed in comments"));
+            }
+        } else if cursor.eat("<?")? {
+            cursor.name()?;
+            cursor.take_until("?>")?;
+        } else if cursor.eat("<!ELEMENT")? {
+            parse_element_decl(cursor, dtd)?;
+        } else if cursor.eat("<!ATTLIST")? {
+            parse_attlist_decl(cursor, dtd)?;
+        } else if cursor.eat("<!ENTITY")? {
+            parse_entity_decl(cursor, dtd)?;
+        } else if cursor.eat("<!NOTATION")? {
+            parse_notation_decl(cursor, dtd)?;
+        } else if cursor.eat("%")? {
+            let name = cursor.name()?;
+            cursor.expect(";", "expect `;`")?;
+
+            let value = dtd
+                .parameter_entities
+                .get(name)
+                .and_then(|decl| decl.value.clone());
+
+            match value {
+                Some(value) => {
+                    if depth >= MAX_ENTITY_DEPTH {
+                        return Err(Error::IllegalEntityReference(name.to_owned()).into());
+                    }
+
+                    let mut nested = Cursor::new(&value, true, 0);
+
+                    parse_markup_decls_with_depth(&mut nested, dtd, depth + 1)?;
+
+                    if !nested.at_end()? {
+                        return Err(nested.error("unexpected `]` in parameter entity"));
+                    }
+                }
+                None => dtd.skip_declarations = true,
+            }
+        } else {
+            return Err(cursor.error("expect markup declaration"));
+        }
+    }
+}
+
+/// Parse the rest of `<!ELEMENT` declaration.
+fn parse_element_decl(cursor: &mut Cursor<'_>, dtd: &mut Dtd) -> PResult<()> {
+    cursor.expect_whitespace("expect white space")?;
+
+    let name = cursor.name()?;
+
+    cursor.expect_whitespace("expect white space")?;
+
+    let content = if cursor.eat("EMPTY")? {
+        ContentSpec::Empty
+    } else if cursor.eat("ANY")? {
+        ContentSpec::Any
+    } else if cursor.starts_with("(")? {
+        let model = cursor.take_until(">")?.trim_end();
+
+        let inner = model
+            .strip_prefix('(')
+            .ok_or_else(|| cursor.error("expect content model"))?
+            .trim_start();
+
+        if inner.starts_with("#PCDATA") {
+            let names = inner
+                .trim_end_matches('*')
+                .trim_end()
+                .strip_suffix(')')
+                .ok_or_else(|| cursor.error("expect `)`"))?["#PCDATA".len()..]
+                .split('|')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_owned)
+                .collect();
+
+            ContentSpec::Mixed(names)
+        } else {
+            ContentSpec::Children(model.to_owned())
+        }
+    } else {
+        return Err(cursor.error("expect content spec"));
+    };
+
+    if !matches!(content, ContentSpec::Mixed(_) | ContentSpec::Children(_)) {
+        cursor.skip_whitespace()?;
+        cursor.expect(">", "expect `>`")?;
+    }
+
+    if !dtd.elements.contains_key(name) {
+        dtd.elements.insert(
+            name.to_owned(),
+            ElementDecl {
+                name: name.to_owned(),
+                content,
+            },
+        );
+    }
+
+    Ok(())
+}
+
+/// Parse the rest of `<!ATTLIST` declaration.
+fn parse_attlist_decl(cursor: &mut Cursor<'_>, dtd: &mut Dtd) -> PResult<()> {
+    cursor.expect_whitespace("expect white space")?;
+
+    let element = cursor.name()?;
+
+    let mut decls = vec![];
+
+    loop {
+        let whitespace = cursor.skip_whitespace()?;
+
+        if cursor.eat(">")? {
+            break;
+        }
+
+        if whitespace == 0 {
+            return Err(cursor.error("expect white space"));
+        }
+
+        let name = cursor.name()?;
+
+        cursor.expect_whitespace("expect white space")?;
+
+        let attr_type = parse_attribute_type(cursor)?;
+
+        cursor.expect_whitespace("expect white space")?;
+
+        let default = if cursor.eat("#REQUIRED")? {
+            DefaultDecl::Required
+        } else if cursor.eat("#IMPLIED")? {
+            DefaultDecl::Implied
+        } else {
+            let fixed = if cursor.eat("#FIXED")? {
+                cursor.expect_whitespace("expect white space")?;
+                true
+            } else {
+                false
+            };
+
+            let value = cursor.quoted("expect default value")?;
+
+            if value.contains('<') {
+                return Err(cursor.error("`<` is not allowed in attribute values"));
+            }
+
+            let value = dtd
+                .normalize_attribute_value(value, Some(&attr_type))?
+                .into_owned();
+
+            if fixed {
+                DefaultDecl::Fixed(value)
+            } else {
+                DefaultDecl::Value(value)
+            }
+        };
+
+        decls.push(AttributeDecl {
+            name: name.to_owned(),
+            attr_type,
+            default,
+        });
+    }
+
+    if dtd.skip_declarations {
+        return Ok(());
+    }
+
+    let declared = dtd.attlists.entry(element.to_owned()).or_default();
+
+    // The first declaration is binding.
+    for decl in decls {
+        if !declared.iter().any(|declared| declared.name == decl.name) {
+            declared.push(decl);
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_attribute_type(cursor: &mut Cursor<'_>) -> PResult<AttributeType> {
+    const TYPES: [(&str, AttributeType); 8] = [
+        ("CDATA", AttributeType::CData),
+        ("IDREFS", AttributeType::IdRefs),
+        ("IDREF", AttributeType::IdRef),
+        ("ID", AttributeType::Id),
+        ("ENTITIES", AttributeType::Entities),
+        ("ENTITY", AttributeType::Entity),
+        ("NMTOKENS", AttributeType::NmTokens),
+        ("NMTOKEN", AttributeType::NmToken),
+    ];
+
+    if cursor.eat("NOTATION")? {
+        cursor.expect_whitespace("expect white space")?;
+        return Ok(AttributeType::Notation(parse_enumeration(cursor, true)?));
+    }
+
+    if cursor.starts_with("(")? {
+        return Ok(AttributeType::Enumeration(parse_enumeration(
+            cursor, false,
+        )?));
+    }
+
+    // Longer keywords are checked first, e.g. `IDREFS` before `IDREF`.
+    for (keyword, attr_type) in TYPES {
+        if cursor.eat(keyword)? {
+            return Ok(attr_type);
+        }
+    }
+
+    Err(cursor.error("expect attribute type"))
+}
+
+/// Parse `(a|b|c)`.
+fn parse_enumeration(cursor: &mut Cursor<'_>, names: bool) -> PResult<Vec<String>> {
+    cursor.expect("(", "expect `(`")?;
+
+    let mut values = vec![];
+
+    loop {
+        cursor.skip_whitespace()?;
+
+        let value = if names {
+            cursor.name()?
+        } else {
+            cursor.nmtoken()?
+        };
+
+        values.push(value.to_owned());
+
+        cursor.skip_whitespace()?;
+
+        if cursor.eat(")")? {
+            return Ok(values);
+        }
+
+        cursor.expect("|", "expect `|`")?;
+    }
+}
+
+/// Parse the rest of `<!ENTITY` declaration.
+fn parse_entity_decl(cursor: &mut Cursor<'_>, dtd: &mut Dtd) -> PResult<()> {
+    cursor.expect_whitespace("expect white space")?;
+
+    let parameter = if cursor.eat("%")? {
+        cursor.expect_whitespace("expect white space")?;
+        true
+    } else {
+        false
+    };
+
+    let name = cursor.name()?;
+
+    cursor.expect_whitespace("expect white space")?;
+
+    let mut decl = EntityDecl {
+        name: name.to_owned(),
+        value: None,
+        public_id: None,
+        system_id: None,
+        notation_name: None,
+    };
+
+    if matches!(cursor.peek()?, Some('"' | '\'')) {
+        let value = cursor.quoted("expect entity value")?;
+
+        decl.value = Some(parse_entity_value(cursor, value)?);
+    } else {
+        let (public_id, system_id) = parse_external_id(cursor, false)?;
+
+        decl.public_id = public_id;
+        decl.system_id = system_id;
+
+        let whitespace = cursor.skip_whitespace()?;
+
+        if !parameter && whitespace > 0 && cursor.eat("NDATA")? {
+            cursor.expect_whitespace("expect white space")?;
+            decl.notation_name = Some(cursor.name()?.to_owned());
+        }
+    }
+
+    cursor.skip_whitespace()?;
+    cursor.expect(">", "expect `>`")?;
+
+    if dtd.skip_declarations {
+        return Ok(());
+    }
+
+    let entities = if parameter {
+        &mut dtd.parameter_entities
+    } else {
+        &mut dtd.entities
+    };
+
+    // The first declaration is binding.
+    entities.entry(name.to_owned()).or_insert(decl);
+
+    Ok(())
+}
+
+/// Returns the replacement text of one `EntityValue` literal.
+///
+/// Character references are replaced and general entity references are bypassed.
+/// The line endings of the literal are normalized first, so `&#xD;` is kept.
+fn parse_entity_value(cursor: &Cursor<'_>, value: &str) -> PResult<String> {
+    let literal = normalize_line_endings(value);
+    let mut value = literal.as_ref();
+
+    let mut replacement = String::with_capacity(value.len());
+
+    while let Some(index) = value.find(['&', '%']) {
+        replacement.push_str(&value[..index]);
+
+        if value.as_bytes()[index] == b'%' {
+            return Err(cursor.error("parameter entity reference in markup declaration"));
+        }
+
+        let rest = &value[index + 1..];
+
+        let end = rest
+            .find(';')
+            .ok_or_else(|| Error::MalformedReference(rest.to_owned()))?;
+
+        let reference = &rest[..end];
+
+        if reference.starts_with('#') {
+            replacement.push(parse_reference(reference)?.unwrap());
+        } else {
+            // validate the syntax of general entity reference.
+            parse_reference(reference)?;
+            replacement.push_str(&value[index..index + end + 2]);
+        }
+
+        value = &rest[end + 1..];
+    }
+
+    replacement.push_str(value);
+
+    Ok(replacement)
+}
+
+/// Parse the rest of `<!NOTATION` declaration.
+fn parse_notation_decl(cursor: &mut Cursor<'_>, dtd: &mut Dtd) -> PResult<()> {
+    cursor.expect_whitespace("expect white space")?;
+
+    let name = cursor.name()?;
+
+    cursor.expect_whitespace("expect white space")?;
+
+    let (public_id, system_id) = parse_external_id(cursor, true)?;
+
+    cursor.skip_whitespace()?;
+    cursor.expect(">", "expect `>`")?;
+
+    dtd.notations
+        .entry(name.to_owned())
+        .or_insert(NotationDecl {
+            name: name.to_owned(),
+            public_id,
+            system_id,
+        });
+
+    Ok(())
+}
+
+/// Parse `ExternalID`, if `public_only` is true, the system literal of `PUBLIC` is optional.
+pub(crate) fn parse_external_id(
+    cursor: &mut Cursor<'_>,
+    public_only: bool,
+) -> PResult<(Option<String>, Option<String>)> {
+    if cursor.eat("SYSTEM")? {
+        cursor.expect_whitespace("expect white space")?;
+
+        let system_id = cursor.quoted("expect system literal")?;
+
+        return Ok((None, Some(system_id.to_owned())));
+    }
+
+    if cursor.eat("PUBLIC")? {
+        cursor.expect_whitespace("expect white space")?;
+
+        let public_id = cursor.quoted("expect public id literal")?;
+
+        if !public_id.chars().all(is_pubid_char) {
+            return Err(cursor.error("invalid public id literal"));
+        }
+
+        let whitespace = cursor.skip_whitespace()?;
+
+        if public_only && (whitespace == 0 || !matches!(cursor.peek()?, Some('"' | '\''))) {
+            return Ok((Some(public_id.to_owned()), None));
+        }
+
+        if whitespace == 0 {
+            return Err(cursor.error("expect white space"));
+        }
+
+        let system_id = cursor.quoted("expect system literal")?;
+
+        return Ok((Some(public_id.to_owned()), Some(system_id.to_owned())));
+    }
+
+    Err(cursor.error("expect external id"))
+}
+
+fn is_pubid_char(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+        || (is_whitespace(c) && c != '\t')
+        || "-'()+,./:=?;!*#@$_%".contains(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(subset: &str) -> Dtd {
+        let mut dtd = Dtd::default();
+        let mut cursor = Cursor::new(subset, true, 0);
+
+        parse_markup_decls(&mut cursor, &mut dtd)
+            .map_err(Error::from)
+            .unwrap();
+
+        dtd
+    }
+
+    #[test]
+    fn test_attlist() {
+        let dtd = parse(
+            r#"
+            <!ATTLIST a
+                id ID #IMPLIED
+                kind (x|y) "x"
+                ver CDATA #FIXED " 1.0 "
+                refs IDREFS "  a   b  "
+                n NOTATION (gif) #REQUIRED>
+            <!ATTLIST a kind CDATA "z">
+            "#,
+        );
+
+        let decls = dtd.attributes("a");
+
+        assert_eq!(decls.len(), 5);
+        assert_eq!(decls[0].attr_type, AttributeType::Id);
+        assert_eq!(decls[1].default, DefaultDecl::Value("x".into()));
+        assert_eq!(decls[2].default, DefaultDecl::Fixed(" 1.0 ".into()));
+        assert_eq!(decls[3].default, DefaultDecl::Value("a b".into()));
+        assert_eq!(
+            decls[4].attr_type,
+            AttributeType::Notation(vec!["gif".into()])
+        );
+    }
+
+    #[test]
+    fn test_entity() {
+        let dtd = parse(
+            r#"
+            <!ENTITY % p "<!ENTITY e2 'two'>">
+            %p;
+            <!ENTITY e1 "a&#x20;&e2;">
+            <!ENTITY pic SYSTEM "pic.gif" NDATA gif>
+            <!NOTATION gif PUBLIC "-//GIF//EN">
+            "#,
+        );
+
+        assert_eq!(dtd.entity("e1").unwrap().value.as_deref(), Some("a &e2;"));
+        assert_eq!(dtd.entity("e2").unwrap().value.as_deref(), Some("two"));
+        assert!(dtd.entity("pic").unwrap().is_unparsed());
+        assert_eq!(
+            dtd.notation("gif").unwrap().public_id.as_deref(),
+            Some("-//GIF//EN")
+        );
+
+        // line endings of the literal are normalized, not the character references.
+        let dtd = parse("<!ENTITY cr \"a&#xD;b\r\nc&#13;&#10;\">");
+
+        assert_eq!(
+            dtd.entity("cr").unwrap().value.as_deref(),
+            Some("a\rb\nc\r\n")
+        );
+    }
+
+    #[test]
+    fn test_normalize_attribute_value() {
+        let dtd = parse(r#"<!ENTITY e "x&#10;y"> <!ENTITY r "&r;"> <!ENTITY lt2 "<">"#);
+
+        assert_eq!(
+            dtd.normalize_attribute_value(" a\r\n\tb&#10;&e; ", None)
+                .unwrap(),
+            " a  b\nx y "
+        );
+
+        assert_eq!(
+            dtd.normalize_attribute_value(" a\r\n\tb&#32;&e; ", Some(&AttributeType::NmTokens))
+                .unwrap(),
+            "a b x y"
+        );
+
+        assert!(matches!(
+            dtd.normalize_attribute_value("a b", Some(&AttributeType::NmTokens)),
+            Ok(Cow::Borrowed("a b"))
+        ));
+
+        dtd.normalize_attribute_value("&r;", None)
+            .expect_err("recursive entity");
+
+        dtd.normalize_attribute_value("&lt2;", None)
+            .expect_err("`<` in replacement text");
+
+        dtd.normalize_attribute_value("&undeclared;", None)
+            .expect_err("undeclared entity");
+    }
+}
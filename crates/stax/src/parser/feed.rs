@@ -0,0 +1,164 @@
+//! Incremental parsing for push sources.
+
+use crate::{Error, Result};
+
+use super::{
+    cursor::Halt,
+    reader::{ReaderState, Step},
+    Dtd, Event,
+};
+
+/// A parser fed with byte chunks by the caller, for sources which are not [`InputStream`](super::InputStream).
+///
+/// Chunks can be split at any position, including the middle of a multi-byte character or a tag name,
+/// the incomplete tail is kept until the next [`feed`](FeedParser::feed).
+#[derive(Default)]
+pub struct FeedParser {
+    /// Bytes not consumed by the parser.
+    buf: Vec<u8>,
+    /// Length of the prefix of `buf` validated as UTF-8.
+    valid: usize,
+    state: ReaderState,
+}
+
+impl FeedParser {
+    /// Create a new `FeedParser` instance.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the declarations of the internal subset read so far.
+    pub fn dtd(&self) -> &Dtd {
+        self.state.dtd()
+    }
+
+    /// Enable entity boundary events, see [`Reader::set_entity_events`](super::Reader::set_entity_events).
+    pub fn set_entity_events(&mut self, enabled: bool) {
+        self.state.set_entity_events(enabled);
+    }
+
+    /// Append one chunk of the document, returns the events completed so far.
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Vec<Event<'static>>> {
+        self.buf.extend_from_slice(chunk);
+
+        self.parse(false)
+    }
+
+    /// Notify the end of the document, returns the remaining events.
+    ///
+    /// An error is returned if the document is incomplete.
+    pub fn finish(&mut self) -> Result<Vec<Event<'static>>> {
+        self.parse(true)
+    }
+
+    fn parse(&mut self, eof: bool) -> Result<Vec<Event<'static>>> {
+        // only the bytes appended since the last call are validated.
+        match std::str::from_utf8(&self.buf[self.valid..]) {
+            Ok(_) => self.valid = self.buf.len(),
+            Err(err) if err.error_len().is_none() && !eof => self.valid += err.valid_up_to(),
+            Err(err) => {
+                return Err(Error::Utf8(
+                    self.state.offset() + self.valid + err.valid_up_to(),
+                ))
+            }
+        }
+
+        // Safety: checked by `from_utf8`.
+        let input = unsafe { std::str::from_utf8_unchecked(&self.buf[..self.valid]) };
+
+        let mut events = vec![];
+        let mut consumed = 0;
+
+        loop {
+            match self.state.step(&input[consumed..], eof) {
+                Ok(Step::Event(len, event)) => {
+                    consumed += len;
+                    events.push(event.into_owned());
+                }
+                Ok(Step::Skip(len)) => consumed += len,
+                Ok(Step::Done) | Err(Halt::Incomplete) => break,
+                Err(Halt::Error(err)) => return Err(err),
+            }
+        }
+
+        self.buf.drain(..consumed);
+        self.valid -= consumed;
+
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::parser::Reader;
+
+    const DOCUMENT: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE 文档 [<!ENTITY e "&#x4e2d;<b/>">]>
+<文档 属性="值">&e;文本<![CDATA[数据]]><!--注释--></文档>"#;
+
+    fn read_all(input: &str) -> Vec<Event<'_>> {
+        let mut reader = Reader::new(input);
+        let mut events = vec![];
+
+        while let Some(event) = block_on(reader.next()).unwrap() {
+            events.push(event.into_owned());
+        }
+
+        events
+    }
+
+    #[test]
+    fn test_feed() {
+        let expected = read_all(DOCUMENT);
+
+        for size in 1..DOCUMENT.len() {
+            let mut parser = FeedParser::new();
+            let mut events = vec![];
+
+            for chunk in DOCUMENT.as_bytes().chunks(size) {
+                events.append(&mut parser.feed(chunk).unwrap());
+            }
+
+            events.append(&mut parser.finish().unwrap());
+
+            assert_eq!(events, expected, "chunk size {}", size);
+        }
+    }
+
+    #[test]
+    fn test_split() {
+        let expected = read_all(DOCUMENT);
+
+        for index in 0..=DOCUMENT.len() {
+            let mut parser = FeedParser::new();
+
+            let mut events = parser.feed(&DOCUMENT.as_bytes()[..index]).unwrap();
+            events.append(&mut parser.feed(&DOCUMENT.as_bytes()[index..]).unwrap());
+            events.append(&mut parser.finish().unwrap());
+
+            assert_eq!(events, expected, "split at {}", index);
+        }
+    }
+
+    #[test]
+    fn test_incomplete() {
+        let mut parser = FeedParser::new();
+
+        assert_eq!(parser.feed(b"<a><b").unwrap().len(), 2);
+
+        parser.finish().expect_err("incomplete document");
+
+        let mut parser = FeedParser::new();
+
+        parser.feed(&"<a>中</a>".as_bytes()[..4]).unwrap();
+
+        parser.finish().expect_err("incomplete character");
+
+        FeedParser::new()
+            .feed(b"<a>\xff</a>")
+            .expect_err("invalid utf-8");
+    }
+}
@@ -1,5 +1,7 @@
 use core::{future::Future, str};
 
+use std::borrow::Cow;
+
 use core::future::Ready;
 
 /// Result of [`lookahead`](InputStream::lookahead) function.
@@ -25,6 +27,11 @@ pub trait InputStream {
     /// Returns the lookahead buf length.
     fn len(&self) -> usize;
 
+    /// Returns true if the lookahead buf is empty.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     /// Returns the slice of lookahead buf.
     fn slice(&self) -> &[u8];
 
@@ -44,6 +51,15 @@ pub trait InputStream {
     fn position(&self) -> Self::Cursor;
 }
 
+/// An input stream which may lend its buffered data for the lifetime `'a`.
+///
+/// Streams that keep the whole document in memory return borrowed strings,
+/// streams that reuse their buffer return owned copies.
+pub trait BorrowInput<'a>: InputStream {
+    /// Returns `value`, a slice of [`as_str`](InputStream::as_str), as a string living for `'a`.
+    fn borrow_str(&self, value: &str) -> Cow<'a, str>;
+}
+
 /// An extension trait that convert self into [`InputStream`]
 pub trait IntoInputStream {
     /// Target `InputStream`.
@@ -73,11 +89,11 @@ impl InputStream for &str {
         Self: 'a;
 
     fn len(&self) -> usize {
-        str::len(&self)
+        str::len(self)
     }
 
     fn slice(&self) -> &[u8] {
-        str::as_bytes(&self)
+        str::as_bytes(self)
     }
 
     fn as_str(&self) -> &str {
@@ -96,7 +112,18 @@ impl InputStream for &str {
     }
 
     fn position(&self) -> Self::Cursor {
-        str::len(&self)
+        str::len(self)
+    }
+}
+
+impl<'a> BorrowInput<'a> for &'a str {
+    fn borrow_str(&self, value: &str) -> Cow<'a, str> {
+        let start = (value.as_ptr() as usize).checked_sub(self.as_ptr() as usize);
+
+        match start.and_then(|start| self.get(start..start + value.len())) {
+            Some(borrowed) => Cow::Borrowed(borrowed),
+            None => Cow::Owned(value.to_owned()),
+        }
     }
 }
 
@@ -144,3 +171,9 @@ impl InputStream for (String, usize) {
         self.1
     }
 }
+
+impl<'a> BorrowInput<'a> for (String, usize) {
+    fn borrow_str(&self, value: &str) -> Cow<'a, str> {
+        Cow::Owned(value.to_owned())
+    }
+}
@@ -104,7 +104,46 @@ where
         }
     }
 
-    pub fn next(&self) -> Option<Token> {
+    pub fn next(&self) -> Option<Token<'_>> {
         todo!()
     }
 }
+
+/// Returns true if `c` matches the `S` production.
+pub fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
+/// Returns true if `c` matches the `NameStartChar` production.
+pub fn is_name_start_char(c: char) -> bool {
+    c == ':'
+        || c == '_'
+        || matches!(
+            c, 'A'..='Z' | 'a'..='z' | '\u{c0}'..='\u{d6}'
+            | '\u{d8}'..='\u{f6}' | '\u{f8}'..='\u{2ff}'
+            | '\u{370}'..='\u{37d}' | '\u{37f}'..='\u{1fff}'
+            | '\u{200c}'..='\u{200d}' | '\u{2070}'..='\u{218f}'
+            | '\u{2c00}'..='\u{2fef}' | '\u{3001}'..='\u{d7ff}'
+            | '\u{f900}'..='\u{fdcf}' | '\u{fdf0}'..='\u{fffd}'
+            | '\u{10000}'..='\u{effff}'
+        )
+}
+
+/// Returns true if `c` matches the `NameChar` production.
+pub fn is_name_char(c: char) -> bool {
+    is_name_start_char(c)
+        || c == '-'
+        || c == '.'
+        || matches!(
+            c, '0'..='9' | '\u{b7}' | '\u{0300}'..='\u{036f}'|
+            '\u{203f}'..='\u{2040}'
+        )
+}
+
+/// Returns true if `c` matches the `Char` production.
+pub(crate) fn is_xml_char(c: char) -> bool {
+    matches!(
+        c,
+        '\t' | '\n' | '\r' | '\u{20}'..='\u{d7ff}' | '\u{e000}'..='\u{fffd}' | '\u{10000}'..='\u{10ffff}'
+    )
+}
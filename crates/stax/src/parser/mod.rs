@@ -4,3 +4,13 @@ mod input;
 pub use input::*;
 mod lexer;
 pub use lexer::*;
+mod cursor;
+mod dtd;
+pub mod scan;
+pub use dtd::*;
+mod reader;
+pub use reader::*;
+mod feed;
+pub use feed::*;
+mod namespace;
+pub use namespace::*;
@@ -0,0 +1,194 @@
+//! In-scope namespace bindings of the open elements.
+
+use std::borrow::Cow;
+
+use crate::{Error, Result};
+
+use super::Attribute;
+
+/// The namespace name bound to the `xml` prefix.
+pub const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// The namespace name bound to the `xmlns` prefix.
+pub const XMLNS_NAMESPACE: &str = "http://www.w3.org/2000/xmlns/";
+
+/// A stack of namespace declarations, one scope per open element.
+///
+/// The bindings borrow the declaring attributes of the lifetime `'a` if they are borrowed.
+#[derive(Debug, Default, Clone)]
+pub struct NamespaceStack<'a> {
+    /// `(prefix, namespace name)`, the default namespace has an empty prefix,
+    /// an empty namespace name undeclares the default namespace.
+    bindings: Vec<(Cow<'a, str>, Cow<'a, str>)>,
+    /// The length of `bindings` before each scope.
+    scopes: Vec<usize>,
+}
+
+impl<'a> NamespaceStack<'a> {
+    /// Open a new scope with the namespace declarations in `attributes`.
+    pub fn push_scope(&mut self, attributes: &[Attribute<'a>]) -> Result<()> {
+        self.scopes.push(self.bindings.len());
+
+        for attr in attributes {
+            let prefix = if attr.name == "xmlns" {
+                Cow::Borrowed("")
+            } else if let Some(prefix) = attr.name.strip_prefix("xmlns:") {
+                match &attr.name {
+                    Cow::Borrowed(name) => Cow::Borrowed(&name[6..]),
+                    Cow::Owned(_) => Cow::Owned(prefix.to_owned()),
+                }
+            } else {
+                continue;
+            };
+
+            match (prefix.as_ref(), attr.value.as_ref()) {
+                ("xml", XML_NAMESPACE) => continue,
+                ("xml", _) | ("xmlns", _) | (_, XML_NAMESPACE) | (_, XMLNS_NAMESPACE) => {
+                    return Err(Error::ReservedNamespace(attr.name.to_string()))
+                }
+                (prefix, "") if !prefix.is_empty() => {
+                    return Err(Error::ReservedNamespace(attr.name.to_string()))
+                }
+                _ => {}
+            }
+
+            self.bindings.push((prefix, attr.value.clone()));
+        }
+
+        Ok(())
+    }
+
+    /// Close the innermost scope.
+    pub fn pop_scope(&mut self) {
+        if let Some(len) = self.scopes.pop() {
+            self.bindings.truncate(len);
+        }
+    }
+
+    /// Returns the `(prefix, namespace name)` pairs declared by the innermost scope.
+    pub fn declared(&self) -> impl DoubleEndedIterator<Item = (&str, &str)> {
+        let start = self.scopes.last().copied().unwrap_or(self.bindings.len());
+
+        self.bindings[start..]
+            .iter()
+            .map(|(prefix, uri)| (prefix.as_ref(), uri.as_ref()))
+    }
+
+    /// Returns the namespace name bound to `prefix`, use an empty `prefix` for the default namespace.
+    pub fn resolve(&self, prefix: &str) -> Option<&str> {
+        match prefix {
+            "xml" => Some(XML_NAMESPACE),
+            "xmlns" => Some(XMLNS_NAMESPACE),
+            _ => self.binding(prefix).map(AsRef::as_ref),
+        }
+    }
+
+    /// Same as [`resolve`](Self::resolve), the namespace name is borrowed from
+    /// the declaring attribute for the lifetime `'a` if possible.
+    pub fn resolve_cow(&self, prefix: &str) -> Option<Cow<'a, str>> {
+        match prefix {
+            "xml" => Some(Cow::Borrowed(XML_NAMESPACE)),
+            "xmlns" => Some(Cow::Borrowed(XMLNS_NAMESPACE)),
+            _ => self.binding(prefix).cloned(),
+        }
+    }
+
+    /// Returns the innermost binding of `prefix` if it is not undeclared.
+    fn binding(&self, prefix: &str) -> Option<&Cow<'a, str>> {
+        self.bindings
+            .iter()
+            .rev()
+            .find(|(bound, _)| bound == prefix)
+            .map(|(_, uri)| uri)
+            .filter(|uri| !uri.is_empty())
+    }
+
+    /// Split a qualified name into the namespace name and the local part.
+    ///
+    /// The default namespace does not apply to attribute names.
+    pub fn resolve_name<'n>(
+        &self,
+        qname: &'n str,
+        attribute: bool,
+    ) -> Result<(Option<&str>, &'n str)> {
+        resolve_name_with(qname, attribute, |prefix| self.resolve(prefix))
+    }
+
+    /// Same as [`resolve_name`](Self::resolve_name), the namespace name is returned
+    /// as [`resolve_cow`](Self::resolve_cow) does.
+    pub fn resolve_name_cow<'n>(
+        &self,
+        qname: &'n str,
+        attribute: bool,
+    ) -> Result<(Option<Cow<'a, str>>, &'n str)> {
+        resolve_name_with(qname, attribute, |prefix| self.resolve_cow(prefix))
+    }
+}
+
+fn resolve_name_with<U>(
+    qname: &str,
+    attribute: bool,
+    resolve: impl Fn(&str) -> Option<U>,
+) -> Result<(Option<U>, &str)> {
+    match qname.split_once(':') {
+        Some((prefix, local_part)) => match resolve(prefix) {
+            Some(uri) => Ok((Some(uri), local_part)),
+            None => Err(Error::UndeclaredPrefix(prefix.to_owned())),
+        },
+        None if attribute => Ok((None, qname)),
+        None => Ok((resolve(""), qname)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attr<'a>(name: &'a str, value: &'a str) -> Attribute<'a> {
+        Attribute {
+            name: name.into(),
+            value: value.into(),
+            specified: true,
+        }
+    }
+
+    #[test]
+    fn test_namespace_stack() {
+        let mut stack = NamespaceStack::default();
+
+        stack
+            .push_scope(&[attr("xmlns", "urn:a"), attr("xmlns:b", "urn:b")])
+            .unwrap();
+
+        assert_eq!(
+            stack.resolve_name("x", false).unwrap(),
+            (Some("urn:a"), "x")
+        );
+        assert_eq!(stack.resolve_name("x", true).unwrap(), (None, "x"));
+        assert_eq!(
+            stack.resolve_name("b:x", true).unwrap(),
+            (Some("urn:b"), "x")
+        );
+
+        assert!(matches!(
+            stack.resolve_name_cow("b:x", false).unwrap(),
+            (Some(Cow::Borrowed("urn:b")), "x")
+        ));
+
+        stack.push_scope(&[attr("xmlns", "")]).unwrap();
+
+        assert_eq!(stack.resolve_name("x", false).unwrap(), (None, "x"));
+        stack
+            .resolve_name("c:x", false)
+            .expect_err("undeclared prefix");
+
+        stack.pop_scope();
+
+        assert_eq!(stack.resolve(""), Some("urn:a"));
+        assert_eq!(stack.declared().count(), 2);
+
+        stack
+            .push_scope(&[attr("xmlns:xmlns", "urn:x")])
+            .expect_err("reserved prefix");
+    }
+}
@@ -0,0 +1,1257 @@
+//! Reading XML documents in the pull approach.
+
+use std::borrow::Cow;
+
+use crate::{Error, Result};
+
+use super::{
+    cursor::{Cursor, Halt, PResult, Resume},
+    dtd::{normalize_line_endings, parse_external_id, parse_markup_decls, parse_reference},
+    is_whitespace, is_xml_char,
+    scan::{find_byte, find_byte3, find_bytes},
+    BorrowInput, Dtd, InputStream, IntoInputStream, Lookahead,
+};
+
+/// Maximum nesting depth of entity expansion in content.
+const MAX_ENTITY_DEPTH: usize = 16;
+
+/// The data length requested from the input stream when the buffered data is incomplete.
+const LOOKAHEAD_SIZE: usize = 4096;
+
+/// One attribute of a start tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribute<'a> {
+    /// The qualified name of this attribute.
+    pub name: Cow<'a, str>,
+    /// The normalized value of this attribute.
+    pub value: Cow<'a, str>,
+    /// False if this attribute is not present in the start tag and was defaulted from the DTD.
+    pub specified: bool,
+}
+
+/// The document type declaration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocType<'a> {
+    /// The name of the root element.
+    pub name: Cow<'a, str>,
+    /// The public identifier of the external subset.
+    pub public_id: Option<Cow<'a, str>>,
+    /// The system identifier of the external subset.
+    pub system_id: Option<Cow<'a, str>>,
+    /// The source text of the internal subset.
+    pub internal_subset: Option<Cow<'a, str>>,
+}
+
+/// An event returns by [`Reader`].
+///
+/// Strings borrow from the input if it is kept in memory, and are only allocated
+/// if references or normalization change the content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<'a> {
+    /// The start of the document, with the fields of the XML declaration if present.
+    StartDocument {
+        version: Option<Cow<'a, str>>,
+        encoding: Option<Cow<'a, str>>,
+        standalone: Option<bool>,
+    },
+    /// `<!DOCTYPE ...>`
+    DocType(DocType<'a>),
+    /// A start tag or an empty element tag.
+    StartElement {
+        name: Cow<'a, str>,
+        attributes: Vec<Attribute<'a>>,
+    },
+    /// An end tag, also generated after an empty element tag.
+    EndElement { name: Cow<'a, str> },
+    /// Character data with references replaced.
+    Characters(Cow<'a, str>),
+    /// `<![CDATA[...]]>`
+    CData(Cow<'a, str>),
+    /// `<!--...-->`
+    Comment(Cow<'a, str>),
+    /// `<?target data?>`
+    ProcessingInstruction {
+        target: Cow<'a, str>,
+        data: Option<Cow<'a, str>>,
+    },
+    /// The start of the replacement text of a general entity referenced in content,
+    /// only generated if enabled by [`Reader::set_entity_events`].
+    StartEntity(Cow<'a, str>),
+    /// The end of the replacement text of a general entity.
+    EndEntity(Cow<'a, str>),
+    /// The end of the document.
+    EndDocument,
+}
+
+impl<'a> Event<'a> {
+    /// Returns a new owning event from the given existing one.
+    pub fn into_owned(self) -> Event<'static> {
+        self.map(&mut |value| Cow::Owned(value.into_owned()))
+    }
+
+    /// Convert every string of this event with `f`.
+    fn map<'b, F>(self, f: &mut F) -> Event<'b>
+    where
+        F: FnMut(Cow<'a, str>) -> Cow<'b, str>,
+    {
+        match self {
+            Event::StartDocument {
+                version,
+                encoding,
+                standalone,
+            } => Event::StartDocument {
+                version: version.map(&mut *f),
+                encoding: encoding.map(&mut *f),
+                standalone,
+            },
+            Event::DocType(doc_type) => Event::DocType(DocType {
+                name: f(doc_type.name),
+                public_id: doc_type.public_id.map(&mut *f),
+                system_id: doc_type.system_id.map(&mut *f),
+                internal_subset: doc_type.internal_subset.map(&mut *f),
+            }),
+            Event::StartElement { name, attributes } => Event::StartElement {
+                name: f(name),
+                attributes: attributes
+                    .into_iter()
+                    .map(|attr| Attribute {
+                        name: f(attr.name),
+                        value: f(attr.value),
+                        specified: attr.specified,
+                    })
+                    .collect(),
+            },
+            Event::EndElement { name } => Event::EndElement { name: f(name) },
+            Event::Characters(text) => Event::Characters(f(text)),
+            Event::CData(text) => Event::CData(f(text)),
+            Event::Comment(text) => Event::Comment(f(text)),
+            Event::ProcessingInstruction { target, data } => Event::ProcessingInstruction {
+                target: f(target),
+                data: data.map(f),
+            },
+            Event::StartEntity(name) => Event::StartEntity(f(name)),
+            Event::EndEntity(name) => Event::EndEntity(f(name)),
+            Event::EndDocument => Event::EndDocument,
+        }
+    }
+}
+
+/// The position of the parser in the document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Before the XML declaration.
+    Start,
+    /// Before the root element.
+    Prolog,
+    /// Inside the root element.
+    Content,
+    /// After the root element.
+    Epilog,
+    /// After `EndDocument` event.
+    Done,
+}
+
+/// Replacement text of one general entity referenced in content.
+struct EntityFrame {
+    name: String,
+    text: String,
+    pos: usize,
+    /// The element depth at the reference.
+    depth: usize,
+}
+
+/// Result of one parsing step.
+pub(crate) enum Step<'s> {
+    /// An event and the number of consumed bytes.
+    Event(usize, Event<'s>),
+    /// Consumed bytes without generating any event.
+    Skip(usize),
+    /// No more events.
+    Done,
+}
+
+/// Result of parsing in the content of the root element.
+enum Content<'s> {
+    Event(Event<'s>),
+    /// A general entity reference was consumed.
+    Entity,
+    /// The input is exhausted.
+    End,
+}
+
+/// Character data parsed before the buffered data ended.
+struct PartialText {
+    /// The position of the text relative to the consumed bytes.
+    start: usize,
+    /// The position of the first segment not parsed yet.
+    end: usize,
+    /// The replaced text, if any reference or line ending was replaced.
+    owned: Option<String>,
+}
+
+/// The restartable state machine behind [`Reader`].
+///
+/// Each step parses one event from the start of the buffered data,
+/// if the data is incomplete, no state is changed and the step can be retried with more data.
+/// The progress of the incomplete construct is kept so a retry does not scan the same bytes again.
+#[derive(Default)]
+pub(crate) struct ReaderState {
+    state: Option<State>,
+    /// Names of open elements, concatenated to avoid one allocation per element.
+    names: String,
+    /// The end offsets of open element names in `names`.
+    name_ends: Vec<usize>,
+    /// The length of the last empty element tag, which is not consumed until its end event.
+    pending_end: Option<usize>,
+    /// General entities being expanded in content.
+    frames: Vec<EntityFrame>,
+    /// Generate events at the boundaries of expanded entities.
+    entity_events: bool,
+    doc_type: bool,
+    dtd: Dtd,
+    /// Number of consumed bytes.
+    offset: usize,
+    /// The search halted by incomplete data, relative to `offset`.
+    resume: Resume,
+    /// The character data halted by incomplete data.
+    partial_text: Option<PartialText>,
+}
+
+impl ReaderState {
+    pub(crate) fn dtd(&self) -> &Dtd {
+        &self.dtd
+    }
+
+    pub(crate) fn set_entity_events(&mut self, enabled: bool) {
+        self.entity_events = enabled;
+    }
+
+    /// Returns the number of consumed bytes.
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Parse the next event from the start of `input`.
+    pub(crate) fn step<'s>(&mut self, input: &'s str, eof: bool) -> PResult<Step<'s>> {
+        let mut consumed = 0;
+
+        loop {
+            match self.step_once(&input[consumed..], eof) {
+                Ok(Step::Event(len, event)) => return Ok(Step::Event(consumed + len, event)),
+                Ok(Step::Skip(len)) => consumed += len,
+                Ok(Step::Done) => return Ok(Step::Done),
+                Err(Halt::Incomplete) if consumed > 0 => return Ok(Step::Skip(consumed)),
+                Err(halt) => return Err(halt),
+            }
+        }
+    }
+
+    fn step_once<'s>(&mut self, input: &'s str, eof: bool) -> PResult<Step<'s>> {
+        if let Some(index) = self.frames.len().checked_sub(1) {
+            let text = std::mem::take(&mut self.frames[index].text);
+            let depth = self.frames[index].depth;
+
+            let mut cursor = Cursor::new(&text, true, 0);
+            cursor.advance(self.frames[index].pos);
+
+            // new frames are pushed on the top by entity references.
+            let content = match self.pending_end.take() {
+                Some(len) => self.empty_element_end(&mut cursor, len).map(Content::Event),
+                None => self.content(&mut cursor, Some(depth)),
+            }
+            .map(|content| match content {
+                Content::Event(event) => Content::Event(event.into_owned()),
+                Content::Entity => Content::Entity,
+                Content::End => Content::End,
+            });
+
+            let frame = &mut self.frames[index];
+            frame.pos = cursor.pos();
+            frame.text = text;
+
+            return match content? {
+                Content::Event(event) => Ok(Step::Event(0, event)),
+                Content::Entity => Ok(Step::Skip(0)),
+                Content::End => {
+                    let frame = self.frames.pop().expect("entity frame");
+
+                    if self.name_ends.len() != frame.depth {
+                        return Err(Error::IllegalEntityReference(frame.name).into());
+                    }
+
+                    if self.entity_events {
+                        return Ok(Step::Event(0, Event::EndEntity(frame.name.into())));
+                    }
+
+                    Ok(Step::Skip(0))
+                }
+            };
+        }
+
+        let mut cursor = Cursor::new(input, eof, self.offset).with_resume(self.resume);
+
+        let step = match self.step_input(&mut cursor) {
+            Err(Halt::Incomplete) => {
+                self.resume = cursor.resume();
+                return Err(Halt::Incomplete);
+            }
+            step => {
+                self.resume = Resume::default();
+                self.partial_text = None;
+                step?
+            }
+        };
+
+        if let Step::Done = step {
+            return Ok(Step::Done);
+        }
+
+        self.offset += cursor.pos();
+
+        match step {
+            Step::Event(_, event) => Ok(Step::Event(cursor.pos(), event)),
+            _ => Ok(Step::Skip(cursor.pos())),
+        }
+    }
+
+    fn step_input<'s>(&mut self, cursor: &mut Cursor<'s>) -> PResult<Step<'s>> {
+        if let Some(len) = self.pending_end.take() {
+            return Ok(Step::Event(0, self.empty_element_end(cursor, len)?));
+        }
+
+        Ok(match self.state.unwrap_or(State::Start) {
+            State::Start => Step::Event(0, self.start_document(cursor)?),
+            State::Prolog | State::Epilog => Step::Event(0, self.misc(cursor)?),
+            State::Content => match self.content(cursor, None)? {
+                Content::Event(event) => Step::Event(0, event),
+                Content::Entity => Step::Skip(0),
+                Content::End => return Err(Error::UnexpectedEof.into()),
+            },
+            State::Done => Step::Done,
+        })
+    }
+
+    fn start_document<'s>(&mut self, cursor: &mut Cursor<'s>) -> PResult<Event<'s>> {
+        cursor.eat("\u{feff}")?;
+
+        let mut version = None;
+        let mut encoding = None;
+        let mut standalone = None;
+
+        if cursor.starts_with("<?xml")? && self.is_xml_decl(cursor)? {
+            cursor.advance(5);
+
+            let mut pseudo_attrs = vec![];
+
+            loop {
+                let whitespace = cursor.skip_whitespace()?;
+
+                if cursor.eat("?>")? {
+                    break;
+                }
+
+                if whitespace == 0 {
+                    return Err(cursor.error("expect white space"));
+                }
+
+                let name = cursor.name()?;
+                cursor.skip_whitespace()?;
+                cursor.expect("=", "expect `=`")?;
+                cursor.skip_whitespace()?;
+                let value = cursor.quoted("expect quoted value")?;
+
+                pseudo_attrs.push((name, value));
+            }
+
+            let mut pseudo_attrs = pseudo_attrs.into_iter().peekable();
+
+            match pseudo_attrs.next() {
+                Some(("version", value)) if value.starts_with("1.") => version = Some(value.into()),
+                _ => return Err(cursor.error("invalid version info")),
+            }
+
+            if let Some((_, value)) = pseudo_attrs.next_if(|(name, _)| *name == "encoding") {
+                encoding = Some(value.into());
+            }
+
+            if let Some((_, value)) = pseudo_attrs.next_if(|(name, _)| *name == "standalone") {
+                standalone = match value {
+                    "yes" => Some(true),
+                    "no" => Some(false),
+                    _ => return Err(cursor.error("invalid standalone document declaration")),
+                };
+            }
+
+            if pseudo_attrs.next().is_some() {
+                return Err(cursor.error("invalid xml declaration"));
+            }
+        }
+
+        self.state = Some(State::Prolog);
+
+        Ok(Event::StartDocument {
+            version,
+            encoding,
+            standalone,
+        })
+    }
+
+    /// Returns true if `<?xml` is followed by a white space, otherwise it is a processing instruction.
+    fn is_xml_decl(&self, cursor: &Cursor<'_>) -> PResult<bool> {
+        match cursor.rest()[5..].chars().next() {
+            Some(c) => Ok(is_whitespace(c)),
+            None if cursor.is_eof() => Ok(false),
+            None => Err(Halt::Incomplete),
+        }
+    }
+
+    /// Parse `Misc` productions and the doctype before or after the root element.
+    fn misc<'s>(&mut self, cursor: &mut Cursor<'s>) -> PResult<Event<'s>> {
+        cursor.skip_whitespace()?;
+
+        if cursor.at_end()? {
+            if self.state == Some(State::Prolog) {
+                return Err(Error::UnexpectedEof.into());
+            }
+
+            self.state = Some(State::Done);
+
+            return Ok(Event::EndDocument);
+        }
+
+        if cursor.starts_with("<?")? {
+            return self.processing_instruction(cursor);
+        }
+
+        if cursor.starts_with("<!--")? {
+            return self.comment(cursor);
+        }
+
+        if self.state == Some(State::Prolog) {
+            if cursor.starts_with("<!DOCTYPE")? {
+                if self.doc_type {
+                    return Err(cursor.error("duplicate doctype"));
+                }
+
+                return self.doctype(cursor);
+            }
+
+            if cursor.starts_with("<")? {
+                let event = self.start_element(cursor)?;
+
+                self.state = Some(State::Content);
+
+                return Ok(event);
+            }
+        }
+
+        Err(cursor.error("unexpected content outside of the root element"))
+    }
+
+    /// Parse the next event in the content of the root element.
+    ///
+    /// `entity` is the element depth at the reference if parsing the replacement text of one entity.
+    fn content<'s>(
+        &mut self,
+        cursor: &mut Cursor<'s>,
+        entity: Option<usize>,
+    ) -> PResult<Content<'s>> {
+        if cursor.at_end()? {
+            return Ok(Content::End);
+        }
+
+        let event = if cursor.starts_with("</")? {
+            if entity == Some(self.name_ends.len()) {
+                return Err(cursor.error("end tag is not in the same entity as the start tag"));
+            }
+
+            self.end_tag(cursor)?
+        } else if cursor.starts_with("<?")? {
+            self.processing_instruction(cursor)?
+        } else if cursor.starts_with("<!--")? {
+            self.comment(cursor)?
+        } else if cursor.eat("<![CDATA[")? {
+            let data = check_chars(cursor.take_until("]]>")?)?;
+
+            // the line endings of replacement text were normalized in the entity value.
+            Event::CData(match entity {
+                Some(_) => data.into(),
+                None => normalize_line_endings(data),
+            })
+        } else if cursor.starts_with("<!")? {
+            return Err(cursor.error("unexpected markup declaration"));
+        } else if cursor.starts_with("<")? {
+            self.start_element(cursor)?
+        } else if let Some(text) = self.characters(cursor, entity.is_none())? {
+            Event::Characters(text)
+        } else {
+            let name = self.entity_reference(cursor)?;
+
+            if !self.entity_events {
+                return Ok(Content::Entity);
+            }
+
+            Event::StartEntity(name.into())
+        };
+
+        Ok(Content::Event(event))
+    }
+
+    /// Parse one general entity reference in content.
+    fn entity_reference<'s>(&mut self, cursor: &mut Cursor<'s>) -> PResult<&'s str> {
+        cursor.expect("&", "expect `&`")?;
+
+        let name = cursor.take_until(";")?;
+
+        let decl = self
+            .dtd
+            .entity(name)
+            .ok_or_else(|| Error::UndeclaredEntity(name.to_owned()))?;
+
+        if decl.is_unparsed()
+            || self.frames.iter().any(|frame| frame.name == name)
+            || self.frames.len() >= MAX_ENTITY_DEPTH
+        {
+            return Err(Error::IllegalEntityReference(name.to_owned()).into());
+        }
+
+        // the replacement text of external entities is not read by this parser,
+        // an empty frame still ends the entity for entity events.
+        if decl.value.is_some() || self.entity_events {
+            self.frames.push(EntityFrame {
+                name: name.to_owned(),
+                text: decl.value.clone().unwrap_or_default(),
+                pos: 0,
+                depth: self.name_ends.len(),
+            });
+        }
+
+        Ok(name)
+    }
+
+    /// Parse character data until markup or a general entity reference.
+    ///
+    /// The text is borrowed from the input until the first reference or line ending to be replaced.
+    /// The line endings are normalized if `normalize` is true, not in replacement text.
+    fn characters<'s>(
+        &mut self,
+        cursor: &mut Cursor<'s>,
+        normalize: bool,
+    ) -> PResult<Option<Cow<'s, str>>> {
+        let input = cursor.input();
+        let start = cursor.pos();
+
+        let mut owned: Option<String> = None;
+
+        // continue after the segments parsed before the data was incomplete.
+        if let Some(partial) = self.partial_text.take() {
+            if partial.start == start {
+                cursor.reset(partial.end);
+                owned = partial.owned;
+            }
+        }
+
+        loop {
+            let rest = cursor.rest();
+
+            let end = match cursor.search("<&\r", |bytes| find_byte3(b'<', b'&', b'\r', bytes)) {
+                Ok(end) => end,
+                Err(halt) => {
+                    self.partial_text = Some(PartialText {
+                        start,
+                        end: cursor.pos(),
+                        owned,
+                    });
+
+                    return Err(halt);
+                }
+            };
+
+            let segment = &rest[..end.unwrap_or(rest.len())];
+
+            // markup and references never appear in `]]>`, so it is inside one segment.
+            if let Some(index) = find_bytes(b"]]>", segment.as_bytes()) {
+                cursor.advance(index);
+                return Err(cursor.error("`]]>` is not allowed in content"));
+            }
+
+            check_chars(segment)?;
+
+            if let Some(text) = &mut owned {
+                text.push_str(segment);
+            }
+
+            cursor.advance(segment.len());
+
+            let Some(index) = end else {
+                break;
+            };
+
+            match rest.as_bytes()[index] {
+                b'<' => break,
+                b'\r' if !normalize => {
+                    if let Some(text) = &mut owned {
+                        text.push('\r');
+                    }
+
+                    cursor.advance(1);
+                }
+                b'\r' => {
+                    let text = owned.get_or_insert_with(|| input[start..cursor.pos()].to_owned());
+
+                    cursor.advance(1);
+                    cursor.eat("\n")?;
+                    text.push('\n');
+                }
+                _ => {
+                    let end = match cursor.search(";", |bytes| find_byte(b';', bytes)) {
+                        Ok(Some(end)) => end - 1,
+                        Ok(None) => {
+                            return Err(
+                                Error::MalformedReference(cursor.rest()[1..].to_owned()).into()
+                            )
+                        }
+                        Err(halt) => {
+                            self.partial_text = Some(PartialText {
+                                start,
+                                end: cursor.pos(),
+                                owned,
+                            });
+
+                            return Err(halt);
+                        }
+                    };
+
+                    let rest = &cursor.rest()[1..];
+
+                    match parse_reference(&rest[..end])? {
+                        Some(c) => {
+                            owned
+                                .get_or_insert_with(|| input[start..cursor.pos()].to_owned())
+                                .push(c);
+
+                            cursor.advance(end + 2);
+                        }
+                        None if cursor.pos() == start => return Ok(None),
+                        None => break,
+                    }
+                }
+            }
+        }
+
+        match owned {
+            Some(text) => Ok(Some(text.into())),
+            None => Ok(Some(input[start..cursor.pos()].into())),
+        }
+    }
+
+    fn start_element<'s>(&mut self, cursor: &mut Cursor<'s>) -> PResult<Event<'s>> {
+        let start = cursor.pos();
+
+        cursor.expect("<", "expect `<`")?;
+
+        let name = cursor.name()?;
+
+        let mut attributes: Vec<Attribute<'s>> = vec![];
+
+        let empty = loop {
+            let whitespace = cursor.skip_whitespace()?;
+
+            if cursor.eat("/>")? {
+                break true;
+            }
+
+            if cursor.eat(">")? {
+                break false;
+            }
+
+            if whitespace == 0 {
+                return Err(cursor.error("expect white space"));
+            }
+
+            let attr_name = cursor.name()?;
+            cursor.skip_whitespace()?;
+            cursor.expect("=", "expect `=`")?;
+            cursor.skip_whitespace()?;
+            let value = cursor.quoted("expect attribute value")?;
+
+            if find_byte(b'<', value.as_bytes()).is_some() {
+                return Err(cursor.error("`<` is not allowed in attribute values"));
+            }
+
+            if attributes.iter().any(|attr| attr.name == attr_name) {
+                return Err(Error::DuplicateAttribute(attr_name.to_owned()).into());
+            }
+
+            let attr_type = self
+                .dtd
+                .attribute(name, attr_name)
+                .map(|decl| &decl.attr_type);
+
+            attributes.push(Attribute {
+                name: attr_name.into(),
+                value: self.dtd.normalize_attribute_value(value, attr_type)?,
+                specified: true,
+            });
+        };
+
+        for decl in self.dtd.attributes(name) {
+            if let Some(value) = decl.default.value() {
+                if !attributes.iter().any(|attr| attr.name == decl.name) {
+                    attributes.push(Attribute {
+                        name: decl.name.clone().into(),
+                        value: value.to_owned().into(),
+                        specified: false,
+                    });
+                }
+            }
+        }
+
+        self.names.push_str(name);
+        self.name_ends.push(self.names.len());
+
+        if empty {
+            // the end event borrows the name from the same tag.
+            self.pending_end = Some(cursor.pos() - start);
+            cursor.reset(start);
+        }
+
+        Ok(Event::StartElement {
+            name: name.into(),
+            attributes,
+        })
+    }
+
+    /// Consume the empty element tag of `len` bytes, returns the end event.
+    fn empty_element_end<'s>(&mut self, cursor: &mut Cursor<'s>, len: usize) -> PResult<Event<'s>> {
+        let start = cursor.pos();
+
+        cursor.advance(1);
+
+        let name = cursor.name()?;
+
+        cursor.reset(start + len);
+
+        Ok(self.end_element(name))
+    }
+
+    fn end_tag<'s>(&mut self, cursor: &mut Cursor<'s>) -> PResult<Event<'s>> {
+        cursor.expect("</", "expect `</`")?;
+
+        let name = cursor.name()?;
+
+        cursor.skip_whitespace()?;
+        cursor.expect(">", "expect `>`")?;
+
+        match self.current_name() {
+            Some(expect) if expect == name => Ok(self.end_element(name)),
+            Some(expect) => Err(Error::MismatchedTag {
+                expect: expect.to_owned(),
+                found: name.to_owned(),
+            }
+            .into()),
+            None => Err(cursor.error("unexpected end tag")),
+        }
+    }
+
+    /// Returns the name of the innermost open element.
+    fn current_name(&self) -> Option<&str> {
+        let end = *self.name_ends.last()?;
+
+        let start = self
+            .name_ends
+            .len()
+            .checked_sub(2)
+            .map(|index| self.name_ends[index])
+            .unwrap_or_default();
+
+        Some(&self.names[start..end])
+    }
+
+    fn end_element<'s>(&mut self, name: &'s str) -> Event<'s> {
+        self.name_ends.pop();
+        self.names
+            .truncate(self.name_ends.last().copied().unwrap_or_default());
+
+        if self.name_ends.is_empty() {
+            self.state = Some(State::Epilog);
+        }
+
+        Event::EndElement { name: name.into() }
+    }
+
+    fn processing_instruction<'s>(&mut self, cursor: &mut Cursor<'s>) -> PResult<Event<'s>> {
+        cursor.expect("<?", "expect `<?`")?;
+
+        let target = cursor.name()?;
+
+        if target.eq_ignore_ascii_case("xml") {
+            return Err(cursor.error("reserved processing instruction target"));
+        }
+
+        let data = if cursor.eat("?>")? {
+            None
+        } else {
+            cursor.expect_whitespace("expect white space")?;
+
+            Some(normalize_line_endings(check_chars(
+                cursor.take_until("?>")?,
+            )?))
+        };
+
+        Ok(Event::ProcessingInstruction {
+            target: target.into(),
+            data,
+        })
+    }
+
+    fn comment<'s>(&mut self, cursor: &mut Cursor<'s>) -> PResult<Event<'s>> {
+        cursor.expect("<!--", "expect `<!--`")?;
+
+        let comment = cursor.take_until("-->")?;
+
+        if find_bytes(b"--", comment.as_bytes()).is_some() || comment.ends_with('-') {
+            return Err(cursor.error("`--` is not allowed in comments"));
+        }
+
+        Ok(Event::Comment(normalize_line_endings(check_chars(
+            comment,
+        )?)))
+    }
+
+    fn doctype<'s>(&mut self, cursor: &mut Cursor<'s>) -> PResult<Event<'s>> {
+        cursor.expect("<!DOCTYPE", "expect `<!DOCTYPE`")?;
+        cursor.expect_whitespace("expect white space")?;
+
+        let name = cursor.name()?;
+
+        let mut public_id = None;
+        let mut system_id = None;
+
+        if cursor.skip_whitespace()? > 0 && !cursor.starts_with("[")? && !cursor.starts_with(">")? {
+            (public_id, system_id) = parse_external_id(cursor, false)?;
+            cursor.skip_whitespace()?;
+        }
+
+        let mut dtd = Dtd::default();
+        let mut internal_subset = None;
+
+        if cursor.eat("[")? {
+            let start = cursor.pos();
+
+            parse_markup_decls(cursor, &mut dtd)?;
+
+            internal_subset = Some(cursor.input()[start..cursor.pos()].into());
+
+            cursor.expect("]", "expect `]`")?;
+            cursor.skip_whitespace()?;
+        }
+
+        cursor.expect(">", "expect `>`")?;
+
+        self.dtd = dtd;
+        self.doc_type = true;
+
+        Ok(Event::DocType(DocType {
+            name: name.into(),
+            public_id: public_id.map(Into::into),
+            system_id: system_id.map(Into::into),
+            internal_subset,
+        }))
+    }
+}
+
+/// A pull parser over one [`InputStream`].
+pub struct Reader<I> {
+    input: I,
+    state: ReaderState,
+    eof: bool,
+}
+
+impl<I> Reader<I>
+where
+    I: InputStream,
+{
+    /// Create a new `Reader` instance from an `IntoInputStream`.
+    pub fn new<II>(input: II) -> Self
+    where
+        II: IntoInputStream<InputStream = I>,
+    {
+        Self {
+            input: input.into_input_stream(),
+            state: Default::default(),
+            eof: false,
+        }
+    }
+
+    /// Returns the declarations of the internal subset read so far.
+    pub fn dtd(&self) -> &Dtd {
+        self.state.dtd()
+    }
+
+    /// Enable [`Event::StartEntity`] and [`Event::EndEntity`] around the events of
+    /// expanded general entities, disabled by default.
+    ///
+    /// Predefined entities and character references are always replaced silently.
+    pub fn set_entity_events(&mut self, enabled: bool) {
+        self.state.set_entity_events(enabled);
+    }
+
+    /// Returns the next event, or `None` after [`Event::EndDocument`].
+    ///
+    /// Events borrow from the input for the lifetime `'a` if the input stream supports it,
+    /// see [`BorrowInput`].
+    pub async fn next<'a>(&mut self) -> Result<Option<Event<'a>>>
+    where
+        I: BorrowInput<'a>,
+    {
+        loop {
+            let input = &self.input;
+
+            let (consumed, event) = match self.state.step(input.as_str(), self.eof) {
+                Ok(Step::Event(consumed, event)) => (
+                    consumed,
+                    Some(event.map(&mut |value| match value {
+                        Cow::Borrowed(value) => input.borrow_str(value),
+                        Cow::Owned(value) => Cow::Owned(value),
+                    })),
+                ),
+                Ok(Step::Skip(consumed)) => (consumed, None),
+                Ok(Step::Done) => return Ok(None),
+                Err(Halt::Error(err)) => return Err(err),
+                Err(Halt::Incomplete) => {
+                    match self.input.lookahead(LOOKAHEAD_SIZE).await {
+                        Lookahead::Buffered(_) => {}
+                        Lookahead::Overflow(_) => return Err(Error::Overflow),
+                        Lookahead::BrokenPipe => self.eof = true,
+                    }
+
+                    continue;
+                }
+            };
+
+            self.input.advance(consumed);
+
+            if event.is_some() {
+                return Ok(event);
+            }
+        }
+    }
+}
+
+/// Returns `text` if every char matches the `Char` production.
+fn check_chars(text: &str) -> Result<&str> {
+    match text.chars().find(|c| !is_xml_char(*c)) {
+        Some(c) => Err(Error::InvalidChar(c)),
+        None => Ok(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+
+    /// An input stream buffers `step` bytes per lookahead.
+    struct Chunked {
+        data: String,
+        start: usize,
+        end: usize,
+        step: usize,
+    }
+
+    impl InputStream for Chunked {
+        type Cursor = usize;
+
+        type Lookahead<'a> = std::future::Ready<Lookahead>;
+
+        fn len(&self) -> usize {
+            self.end - self.start
+        }
+
+        fn slice(&self) -> &[u8] {
+            &self.data.as_bytes()[self.start..self.end]
+        }
+
+        fn as_str(&self) -> &str {
+            &self.data[self.start..self.end]
+        }
+
+        fn lookahead(&mut self, _len: usize) -> Self::Lookahead<'_> {
+            if self.end == self.data.len() {
+                return std::future::ready(Lookahead::BrokenPipe);
+            }
+
+            let mut end = (self.end + self.step).min(self.data.len());
+
+            while !self.data.is_char_boundary(end) {
+                end += 1;
+            }
+
+            let buffered = end - self.end;
+
+            self.end = end;
+
+            std::future::ready(Lookahead::Buffered(buffered))
+        }
+
+        fn advance(&mut self, steps: usize) {
+            self.start += steps;
+        }
+
+        fn position(&self) -> Self::Cursor {
+            self.start
+        }
+    }
+
+    impl BorrowInput<'static> for Chunked {
+        fn borrow_str(&self, value: &str) -> Cow<'static, str> {
+            Cow::Owned(value.to_owned())
+        }
+    }
+
+    fn read_all<'a, I: BorrowInput<'a>>(mut reader: Reader<I>) -> Result<Vec<Event<'a>>> {
+        block_on(async {
+            let mut events = vec![];
+
+            while let Some(event) = reader.next().await? {
+                events.push(event);
+            }
+
+            Ok(events)
+        })
+    }
+
+    fn parse(input: &str) -> Result<Vec<Event<'_>>> {
+        read_all(Reader::new(input))
+    }
+
+    fn start<'a>(name: &'a str, attributes: &[(&'a str, &'a str, bool)]) -> Event<'a> {
+        Event::StartElement {
+            name: name.into(),
+            attributes: attributes
+                .iter()
+                .map(|(name, value, specified)| Attribute {
+                    name: (*name).into(),
+                    value: (*value).into(),
+                    specified: *specified,
+                })
+                .collect(),
+        }
+    }
+
+    fn end(name: &str) -> Event<'_> {
+        Event::EndElement { name: name.into() }
+    }
+
+    fn chars(text: &str) -> Event<'_> {
+        Event::Characters(text.into())
+    }
+
+    const DOCUMENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE doc [
+    <!ATTLIST item id ID #IMPLIED kind (a|b) "a" ver CDATA #FIXED "1">
+    <!ENTITY hello "Hello &amp; <b>world</b>">
+]>
+<!-- comment -->
+<doc>
+    <item id="  x1 " kind=' b'/>
+    <item>&hello;&#x21;</item>
+    <![CDATA[<raw>]]>
+    <?pi  data ?>
+</doc>
+"#;
+
+    #[test]
+    fn test_reader() {
+        let events = parse(DOCUMENT).unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::StartDocument {
+                    version: Some("1.0".into()),
+                    encoding: Some("UTF-8".into()),
+                    standalone: None
+                },
+                Event::DocType(DocType {
+                    name: "doc".into(),
+                    public_id: None,
+                    system_id: None,
+                    internal_subset: Some(
+                        r#"
+    <!ATTLIST item id ID #IMPLIED kind (a|b) "a" ver CDATA #FIXED "1">
+    <!ENTITY hello "Hello &amp; <b>world</b>">
+"#
+                        .into()
+                    )
+                }),
+                Event::Comment(" comment ".into()),
+                start("doc", &[]),
+                chars("\n    "),
+                start(
+                    "item",
+                    &[("id", "x1", true), ("kind", "b", true), ("ver", "1", false)]
+                ),
+                end("item"),
+                chars("\n    "),
+                start("item", &[("kind", "a", false), ("ver", "1", false)]),
+                chars("Hello & "),
+                start("b", &[]),
+                chars("world"),
+                end("b"),
+                chars("!"),
+                end("item"),
+                chars("\n    "),
+                Event::CData("<raw>".into()),
+                chars("\n    "),
+                Event::ProcessingInstruction {
+                    target: "pi".into(),
+                    data: Some("data ".into())
+                },
+                chars("\n"),
+                end("doc"),
+                Event::EndDocument,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chunked() {
+        let data = DOCUMENT.replace("<doc>", "<doc>\u{4e2d}");
+
+        let expected = parse(&data).unwrap();
+
+        for step in 1..16 {
+            let reader = Reader::new(Chunked {
+                data: data.clone(),
+                start: 0,
+                end: 0,
+                step,
+            });
+
+            assert_eq!(read_all(reader).unwrap(), expected, "step {}", step);
+        }
+    }
+
+    #[test]
+    fn test_borrowed() {
+        let events = parse("<a b='1' c='&amp;'>x<d/>y&#x21;</a>").unwrap();
+
+        let Event::StartElement { name, attributes } = &events[1] else {
+            panic!("expect start element");
+        };
+
+        assert!(matches!(name, Cow::Borrowed("a")));
+        assert!(matches!(attributes[0].value, Cow::Borrowed("1")));
+        assert!(matches!(attributes[1].value, Cow::Owned(_)));
+        assert!(matches!(events[2], Event::Characters(Cow::Borrowed("x"))));
+        assert!(matches!(
+            events[4],
+            Event::EndElement {
+                name: Cow::Borrowed("d")
+            }
+        ));
+        assert!(matches!(events[5], Event::Characters(Cow::Owned(_))));
+
+        let events = read_all(Reader::new(String::from("<a/>"))).unwrap();
+
+        assert!(matches!(
+            events[1],
+            Event::StartElement {
+                name: Cow::Owned(_),
+                ..
+            }
+        ));
+        assert_eq!(events[1].clone().into_owned(), start("a", &[]));
+    }
+
+    #[test]
+    fn test_line_endings() {
+        assert_eq!(
+            parse("<a b='1\r\n2'>x\r\ny\rz</a>").unwrap()[1..4],
+            [
+                start("a", &[("b", "1 2", true)]),
+                chars("x\ny\nz"),
+                end("a")
+            ]
+        );
+
+        // the character references in entity values are not normalized.
+        let events =
+            parse("<!DOCTYPE a [<!ENTITY e 'x&#xD;y\r\nz'>]><a>&e;<![CDATA[\r]]></a>").unwrap();
+
+        assert!(events.contains(&chars("x\ry\nz")));
+        assert!(events.contains(&Event::CData("\n".into())));
+    }
+
+    #[test]
+    fn test_entity_events() {
+        let mut reader = Reader::new(DOCUMENT);
+        reader.set_entity_events(true);
+
+        let events = read_all(reader).unwrap();
+
+        let index = events
+            .iter()
+            .position(|event| *event == Event::StartEntity("hello".into()))
+            .expect("start entity");
+
+        assert_eq!(
+            events[index + 1..index + 7],
+            [
+                chars("Hello & "),
+                start("b", &[]),
+                chars("world"),
+                end("b"),
+                Event::EndEntity("hello".into()),
+                chars("!"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_well_formedness() {
+        parse("<a></b>").expect_err("mismatched tag");
+        parse("<a x='1' x='2'/>").expect_err("duplicate attribute");
+        parse("<a>&undeclared;</a>").expect_err("undeclared entity");
+        parse("<a>]]></a>").expect_err("`]]>` in content");
+        parse("<a><!-- -- --></a>").expect_err("`--` in comment");
+        parse("<a x='<'/>").expect_err("`<` in attribute value");
+        parse("<a/><b/>").expect_err("two root elements");
+        parse("<a>").expect_err("unclosed element");
+        parse("text<a/>").expect_err("text in prolog");
+        parse("<!DOCTYPE a [<!ENTITY e '<b>'>]><a>&e;</a>").expect_err("unbalanced entity");
+        parse("<!DOCTYPE a [<!ENTITY e '&e;'>]><a>&e;</a>").expect_err("recursive entity");
+        parse("<a>\u{1}</a>").expect_err("invalid char in content");
+        parse("<a>x&amp;\u{fffe}</a>").expect_err("invalid char after reference");
+        parse("<a><!--\u{1}--></a>").expect_err("invalid char in comment");
+        parse("<a><![CDATA[\u{1}]]></a>").expect_err("invalid char in CDATA section");
+        parse("<a><?pi \u{1}?></a>").expect_err("invalid char in processing instruction");
+    }
+
+    #[test]
+    fn test_resume() {
+        let text = "line\r\n&amp;&#x4e2d;]]".repeat(64);
+        let data = format!(
+            "<a>{text}<!--{comment}--><![CDATA[{text}]]>{text}</a>",
+            comment = "-c".repeat(256)
+        );
+
+        let expected = parse(&data).unwrap();
+
+        assert_eq!(expected.len(), 8);
+
+        for step in 1..8 {
+            let reader = Reader::new(Chunked {
+                data: data.clone(),
+                start: 0,
+                end: 0,
+                step,
+            });
+
+            assert_eq!(read_all(reader).unwrap(), expected, "step {}", step);
+        }
+    }
+}
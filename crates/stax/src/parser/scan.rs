@@ -0,0 +1,115 @@
+//! Searching bytes in the buffered input.
+//!
+//! With the `memchr` feature the search is vectorized, otherwise the portable
+//! [`scalar`] implementation is used. The needles searched by the parser are all ASCII,
+//! so the returned offsets are always on a char boundary.
+
+#[cfg(feature = "memchr")]
+mod imp {
+    /// Returns the index of the first `n1` in `haystack`.
+    #[inline]
+    pub fn find_byte(n1: u8, haystack: &[u8]) -> Option<usize> {
+        memchr::memchr(n1, haystack)
+    }
+
+    /// Returns the index of the first `n1` or `n2` in `haystack`.
+    #[inline]
+    pub fn find_byte2(n1: u8, n2: u8, haystack: &[u8]) -> Option<usize> {
+        memchr::memchr2(n1, n2, haystack)
+    }
+
+    /// Returns the index of the first `n1`, `n2` or `n3` in `haystack`.
+    #[inline]
+    pub fn find_byte3(n1: u8, n2: u8, n3: u8, haystack: &[u8]) -> Option<usize> {
+        memchr::memchr3(n1, n2, n3, haystack)
+    }
+
+    /// Returns the index of the first occurrence of `needle` in `haystack`.
+    #[inline]
+    pub fn find_bytes(needle: &[u8], haystack: &[u8]) -> Option<usize> {
+        memchr::memmem::find(haystack, needle)
+    }
+}
+
+#[cfg(not(feature = "memchr"))]
+use scalar as imp;
+
+pub use imp::*;
+
+/// Portable byte search, one byte per iteration.
+pub mod scalar {
+    /// Returns the index of the first `n1` in `haystack`.
+    #[inline]
+    pub fn find_byte(n1: u8, haystack: &[u8]) -> Option<usize> {
+        haystack.iter().position(|c| *c == n1)
+    }
+
+    /// Returns the index of the first `n1` or `n2` in `haystack`.
+    #[inline]
+    pub fn find_byte2(n1: u8, n2: u8, haystack: &[u8]) -> Option<usize> {
+        haystack.iter().position(|c| *c == n1 || *c == n2)
+    }
+
+    /// Returns the index of the first `n1`, `n2` or `n3` in `haystack`.
+    #[inline]
+    pub fn find_byte3(n1: u8, n2: u8, n3: u8, haystack: &[u8]) -> Option<usize> {
+        haystack
+            .iter()
+            .position(|c| *c == n1 || *c == n2 || *c == n3)
+    }
+
+    /// Returns the index of the first occurrence of `needle` in `haystack`.
+    pub fn find_bytes(needle: &[u8], haystack: &[u8]) -> Option<usize> {
+        let (&first, rest) = match needle.split_first() {
+            Some(split) => split,
+            None => return Some(0),
+        };
+
+        let mut offset = 0;
+
+        while let Some(index) = find_byte(first, &haystack[offset..]) {
+            let start = offset + index;
+
+            if haystack[start + 1..].starts_with(rest) {
+                return Some(start);
+            }
+
+            offset = start + 1;
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scalar_matches() {
+        let haystack = "text ]] more]]> <a b='&amp;'>中\r\n</a>".as_bytes();
+
+        for start in 0..haystack.len() {
+            let haystack = &haystack[start..];
+
+            assert_eq!(find_byte(b'<', haystack), scalar::find_byte(b'<', haystack));
+            assert_eq!(
+                find_byte2(b'<', b'&', haystack),
+                scalar::find_byte2(b'<', b'&', haystack)
+            );
+            assert_eq!(
+                find_byte3(b'<', b'&', b'\r', haystack),
+                scalar::find_byte3(b'<', b'&', b'\r', haystack)
+            );
+
+            for needle in ["]]>", "-->", "?>", "\r\n", "x"] {
+                assert_eq!(
+                    find_bytes(needle.as_bytes(), haystack),
+                    scalar::find_bytes(needle.as_bytes(), haystack),
+                    "{}",
+                    needle
+                );
+            }
+        }
+    }
+}
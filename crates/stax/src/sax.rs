@@ -0,0 +1,360 @@
+//! Push-style (SAX) API driven by the pull [`Reader`].
+
+use futures::executor::block_on;
+
+use crate::{
+    parser::{BorrowInput, ContentSpec, Dtd, Event, NamespaceStack, Reader, XMLNS_NAMESPACE},
+    Error,
+};
+
+/// One attribute of a start tag with its expanded name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SaxAttribute<'a> {
+    /// The namespace URI, `None` if the attribute name has no prefix.
+    ///
+    /// The namespace declarations `xmlns` and `xmlns:*` have the URI [`XMLNS_NAMESPACE`].
+    pub uri: Option<&'a str>,
+    /// The local part of the attribute name.
+    pub local_name: &'a str,
+    /// The qualified name as written in the start tag.
+    pub qname: &'a str,
+    /// The normalized value.
+    pub value: &'a str,
+    /// False if this attribute was defaulted from the DTD.
+    pub specified: bool,
+}
+
+/// Receive notification of the logical content of a document.
+///
+/// All methods have an empty default implementation,
+/// returning an error from any method aborts the parsing.
+#[allow(unused_variables)]
+pub trait ContentHandler {
+    /// Error type returns by this handler, parsing errors are converted into it.
+    type Error: From<Error>;
+
+    /// Receive notification of the beginning of a document.
+    fn start_document(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Receive notification of the end of a document.
+    fn end_document(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Begin the scope of a prefix-URI namespace mapping,
+    /// the default namespace has an empty `prefix`.
+    fn start_prefix_mapping(&mut self, prefix: &str, uri: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// End the scope of a prefix-URI mapping.
+    fn end_prefix_mapping(&mut self, prefix: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Receive notification of the beginning of an element.
+    fn start_element(
+        &mut self,
+        uri: Option<&str>,
+        local_name: &str,
+        qname: &str,
+        attributes: &[SaxAttribute<'_>],
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Receive notification of the end of an element.
+    fn end_element(
+        &mut self,
+        uri: Option<&str>,
+        local_name: &str,
+        qname: &str,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Receive notification of character data, including the content of CDATA sections.
+    fn characters(&mut self, text: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Receive notification of white spaces in element content declared by the DTD.
+    fn ignorable_whitespace(&mut self, text: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Receive notification of a processing instruction.
+    fn processing_instruction(&mut self, target: &str, data: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Receive notification of a comment.
+    fn comment(&mut self, text: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Convert reader events into [`ContentHandler`] callbacks.
+#[derive(Default)]
+struct Dispatcher<'a> {
+    namespaces: NamespaceStack<'a>,
+    /// Names of open elements.
+    elements: Vec<String>,
+}
+
+impl<'a> Dispatcher<'a> {
+    fn dispatch<H>(&mut self, dtd: &Dtd, event: Event<'a>, handler: &mut H) -> Result<(), H::Error>
+    where
+        H: ContentHandler,
+    {
+        match event {
+            Event::StartDocument { .. } => handler.start_document(),
+            Event::DocType(_) => Ok(()),
+            Event::StartElement { name, attributes } => {
+                self.namespaces.push_scope(&attributes)?;
+
+                for (prefix, uri) in self.namespaces.declared() {
+                    handler.start_prefix_mapping(prefix, uri)?;
+                }
+
+                let (uri, local_name) = self.namespaces.resolve_name(&name, false)?;
+
+                let attributes = attributes
+                    .iter()
+                    .map(|attr| {
+                        let (uri, local_name) = match attr.name.as_ref() {
+                            "xmlns" => (Some(XMLNS_NAMESPACE), "xmlns"),
+                            name => self.namespaces.resolve_name(name, true)?,
+                        };
+
+                        Ok(SaxAttribute {
+                            uri,
+                            local_name,
+                            qname: &attr.name,
+                            value: &attr.value,
+                            specified: attr.specified,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+
+                handler.start_element(uri, local_name, &name, &attributes)?;
+
+                self.elements.push(name.into_owned());
+
+                Ok(())
+            }
+            Event::EndElement { name } => {
+                let (uri, local_name) = self.namespaces.resolve_name(&name, false)?;
+
+                handler.end_element(uri, local_name, &name)?;
+
+                for (prefix, _) in self.namespaces.declared().rev() {
+                    handler.end_prefix_mapping(prefix)?;
+                }
+
+                self.namespaces.pop_scope();
+                self.elements.pop();
+
+                Ok(())
+            }
+            Event::Characters(text) => {
+                if self.is_element_content(dtd) && text.chars().all(|c| c.is_ascii_whitespace()) {
+                    handler.ignorable_whitespace(&text)
+                } else {
+                    handler.characters(&text)
+                }
+            }
+            Event::CData(text) => handler.characters(&text),
+            Event::Comment(text) => handler.comment(&text),
+            Event::ProcessingInstruction { target, data } => {
+                handler.processing_instruction(&target, data.as_deref().unwrap_or_default())
+            }
+            Event::StartEntity(_) | Event::EndEntity(_) => Ok(()),
+            Event::EndDocument => handler.end_document(),
+        }
+    }
+
+    /// Returns true if the current element is declared with element content.
+    fn is_element_content(&self, dtd: &Dtd) -> bool {
+        self.elements
+            .last()
+            .and_then(|name| dtd.element(name))
+            .map(|decl| matches!(decl.content, ContentSpec::Children(_)))
+            .unwrap_or_default()
+    }
+}
+
+/// Parse the document from `reader` and report its content to `handler`.
+pub async fn parse<'a, I, H>(mut reader: Reader<I>, handler: &mut H) -> Result<(), H::Error>
+where
+    I: BorrowInput<'a>,
+    H: ContentHandler,
+{
+    let mut dispatcher = Dispatcher::default();
+
+    while let Some(event) = reader.next().await? {
+        dispatcher.dispatch(reader.dtd(), event, handler)?;
+    }
+
+    Ok(())
+}
+
+/// Blocking version of [`parse`], the current thread is blocked while the input stream is pending.
+pub fn parse_sync<'a, I, H>(reader: Reader<I>, handler: &mut H) -> Result<(), H::Error>
+where
+    I: BorrowInput<'a>,
+    H: ContentHandler,
+{
+    block_on(parse(reader, handler))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder {
+        calls: Vec<String>,
+        abort_on: Option<&'static str>,
+    }
+
+    impl Recorder {
+        fn record(&mut self, call: String) -> Result<(), Error> {
+            if self
+                .abort_on
+                .is_some_and(|abort_on| call.starts_with(abort_on))
+            {
+                return Err(Error::Syntax {
+                    offset: 0,
+                    reason: "aborted",
+                });
+            }
+
+            self.calls.push(call);
+
+            Ok(())
+        }
+    }
+
+    impl ContentHandler for Recorder {
+        type Error = Error;
+
+        fn start_document(&mut self) -> Result<(), Self::Error> {
+            self.record("start_document".into())
+        }
+
+        fn end_document(&mut self) -> Result<(), Self::Error> {
+            self.record("end_document".into())
+        }
+
+        fn start_prefix_mapping(&mut self, prefix: &str, uri: &str) -> Result<(), Self::Error> {
+            self.record(format!("start_prefix_mapping {} {}", prefix, uri))
+        }
+
+        fn end_prefix_mapping(&mut self, prefix: &str) -> Result<(), Self::Error> {
+            self.record(format!("end_prefix_mapping {}", prefix))
+        }
+
+        fn start_element(
+            &mut self,
+            uri: Option<&str>,
+            local_name: &str,
+            qname: &str,
+            attributes: &[SaxAttribute<'_>],
+        ) -> Result<(), Self::Error> {
+            let attributes = attributes
+                .iter()
+                .map(|attr| {
+                    format!(
+                        " {:?} {} {}={}",
+                        attr.uri, attr.local_name, attr.qname, attr.value
+                    )
+                })
+                .collect::<String>();
+
+            self.record(format!(
+                "start_element {:?} {} {}{}",
+                uri, local_name, qname, attributes
+            ))
+        }
+
+        fn end_element(
+            &mut self,
+            uri: Option<&str>,
+            local_name: &str,
+            qname: &str,
+        ) -> Result<(), Self::Error> {
+            self.record(format!("end_element {:?} {} {}", uri, local_name, qname))
+        }
+
+        fn characters(&mut self, text: &str) -> Result<(), Self::Error> {
+            self.record(format!("characters {:?}", text))
+        }
+
+        fn ignorable_whitespace(&mut self, text: &str) -> Result<(), Self::Error> {
+            self.record(format!("ignorable_whitespace {:?}", text))
+        }
+
+        fn processing_instruction(&mut self, target: &str, data: &str) -> Result<(), Self::Error> {
+            self.record(format!("processing_instruction {} {}", target, data))
+        }
+
+        fn comment(&mut self, text: &str) -> Result<(), Self::Error> {
+            self.record(format!("comment {}", text))
+        }
+    }
+
+    const DOCUMENT: &str = r#"<!DOCTYPE a:root [<!ELEMENT a:root (b)*>]>
+<a:root xmlns:a="urn:a" xmlns="urn:b">
+    <b x="1" a:y="2"><![CDATA[text]]> <!--c--><?pi data?></b>
+</a:root>"#;
+
+    #[test]
+    fn test_handler() {
+        let mut handler = Recorder::default();
+
+        parse_sync(Reader::new(DOCUMENT), &mut handler).unwrap();
+
+        assert_eq!(
+            handler.calls,
+            [
+                "start_document",
+                "start_prefix_mapping a urn:a",
+                "start_prefix_mapping  urn:b",
+                r#"start_element Some("urn:a") root a:root Some("http://www.w3.org/2000/xmlns/") a xmlns:a=urn:a Some("http://www.w3.org/2000/xmlns/") xmlns xmlns=urn:b"#,
+                r#"ignorable_whitespace "\n    ""#,
+                r#"start_element Some("urn:b") b b None x x=1 Some("urn:a") y a:y=2"#,
+                r#"characters "text""#,
+                r#"characters " ""#,
+                "comment c",
+                "processing_instruction pi data",
+                r#"end_element Some("urn:b") b b"#,
+                r#"ignorable_whitespace "\n""#,
+                r#"end_element Some("urn:a") root a:root"#,
+                "end_prefix_mapping ",
+                "end_prefix_mapping a",
+                "end_document"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_abort() {
+        let mut handler = Recorder {
+            abort_on: Some("comment"),
+            ..Default::default()
+        };
+
+        futures::executor::block_on(parse(Reader::new(DOCUMENT), &mut handler))
+            .expect_err("aborted");
+
+        assert_eq!(handler.calls.last().unwrap(), r#"characters " ""#);
+    }
+
+    #[test]
+    fn test_undeclared_prefix() {
+        parse_sync(Reader::new("<a:b/>"), &mut Recorder::default()).expect_err("undeclared prefix");
+    }
+}
@@ -0,0 +1,763 @@
+//! Writing XML documents in the push approach, the counterpart of the pull [`Reader`](crate::parser::Reader).
+
+use std::{fmt, io};
+
+use futures::{AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    parser::{
+        is_name_char, is_name_start_char, is_whitespace, is_xml_char, XMLNS_NAMESPACE,
+        XML_NAMESPACE,
+    },
+    Error, Result,
+};
+
+/// The state shared by [`XmlWriter`] and [`AsyncXmlWriter`].
+///
+/// Each call checks the order of the calls and appends the markup to `buf`,
+/// the start tag is left open until the next call to allow attributes and namespace declarations,
+/// its prefixes are checked once it is closed.
+#[derive(Default)]
+struct WriterState {
+    /// Markup not yet written to the sink.
+    buf: String,
+    /// Names of open elements, concatenated to avoid one allocation per element.
+    names: String,
+    /// The end offsets of open element names in `names`.
+    name_ends: Vec<usize>,
+    /// The start tag of the innermost element is not closed, attributes can be written.
+    start_tag: bool,
+    /// Qualified names of the attributes of the open start tag.
+    attributes: Vec<String>,
+    /// In-scope `(prefix, namespace URI)` declarations, the default namespace has an empty prefix.
+    bindings: Vec<(String, String)>,
+    /// The length of `bindings` before each open element.
+    scopes: Vec<usize>,
+    /// Any markup has been written.
+    started: bool,
+    /// The root element has been started.
+    root: bool,
+}
+
+impl WriterState {
+    fn start_document(&mut self) -> Result<()> {
+        if self.started {
+            return Err(Error::WriterState("XML declaration after markup"));
+        }
+
+        self.started = true;
+        self.buf
+            .push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+
+        Ok(())
+    }
+
+    fn start_element(&mut self, name: &str) -> Result<()> {
+        check_name(name)?;
+
+        if self.name_ends.is_empty() && self.root {
+            return Err(Error::WriterState("more than one root element"));
+        }
+
+        self.close_start_tag()?;
+
+        self.started = true;
+        self.root = true;
+        self.start_tag = true;
+        self.attributes.clear();
+
+        self.buf.push('<');
+        self.buf.push_str(name);
+
+        self.names.push_str(name);
+        self.name_ends.push(self.names.len());
+        self.scopes.push(self.bindings.len());
+
+        Ok(())
+    }
+
+    fn attribute(&mut self, name: &str, value: &str) -> Result<()> {
+        if name == "xmlns" {
+            return self.namespace("", value);
+        }
+
+        if let Some(prefix) = name.strip_prefix("xmlns:") {
+            check_ncname(prefix)?;
+
+            return self.namespace(prefix, value);
+        }
+
+        self.write_attribute(name, value)
+    }
+
+    fn write_attribute(&mut self, name: &str, value: &str) -> Result<()> {
+        if !self.start_tag {
+            return Err(Error::WriterState("attribute outside of a start tag"));
+        }
+
+        check_name(name)?;
+
+        if self.attributes.iter().any(|attr| attr == name) {
+            return Err(Error::DuplicateAttribute(name.to_owned()));
+        }
+
+        check_chars(value)?;
+
+        self.attributes.push(name.to_owned());
+
+        self.buf.push(' ');
+        self.buf.push_str(name);
+        self.buf.push_str("=\"");
+        escape(&mut self.buf, value, false, |c| match c {
+            '&' => Some("&amp;"),
+            '<' => Some("&lt;"),
+            '"' => Some("&quot;"),
+            '\t' => Some("&#x9;"),
+            '\n' => Some("&#xA;"),
+            '\r' => Some("&#xD;"),
+            _ => None,
+        })
+        .expect("write into a string");
+        self.buf.push('"');
+
+        Ok(())
+    }
+
+    fn namespace(&mut self, prefix: &str, uri: &str) -> Result<()> {
+        let name = if prefix.is_empty() {
+            "xmlns".to_owned()
+        } else {
+            format!("xmlns:{}", prefix)
+        };
+
+        match (prefix, uri) {
+            ("xml", XML_NAMESPACE) => {}
+            ("xml", _) | ("xmlns", _) | (_, XML_NAMESPACE) | (_, XMLNS_NAMESPACE) => {
+                return Err(Error::ReservedNamespace(name))
+            }
+            (prefix, "") if !prefix.is_empty() => return Err(Error::ReservedNamespace(name)),
+            _ => {}
+        }
+
+        if !prefix.is_empty() {
+            check_ncname(prefix)?;
+        }
+
+        self.write_attribute(&name, uri)?;
+        self.bindings.push((prefix.to_owned(), uri.to_owned()));
+
+        Ok(())
+    }
+
+    fn characters(&mut self, text: &str) -> Result<()> {
+        if self.name_ends.is_empty() && !text.chars().all(is_whitespace) {
+            return Err(Error::WriterState(
+                "character data outside of the root element",
+            ));
+        }
+
+        check_chars(text)?;
+
+        self.close_start_tag()?;
+
+        escape(&mut self.buf, text, false, |c| match c {
+            '&' => Some("&amp;"),
+            '<' => Some("&lt;"),
+            '>' => Some("&gt;"),
+            '\r' => Some("&#xD;"),
+            _ => None,
+        })
+        .expect("write into a string");
+
+        Ok(())
+    }
+
+    fn cdata(&mut self, text: &str) -> Result<()> {
+        if self.name_ends.is_empty() {
+            return Err(Error::WriterState(
+                "CDATA section outside of the root element",
+            ));
+        }
+
+        check_chars(text)?;
+
+        self.close_start_tag()?;
+
+        self.buf.push_str("<![CDATA[");
+        self.buf.push_str(&text.replace("]]>", "]]]]><![CDATA[>"));
+        self.buf.push_str("]]>");
+
+        Ok(())
+    }
+
+    fn comment(&mut self, text: &str) -> Result<()> {
+        if text.contains("--") || text.ends_with('-') {
+            return Err(Error::WriterState("`--` in comment"));
+        }
+
+        check_chars(text)?;
+
+        self.close_start_tag()?;
+
+        self.started = true;
+        self.buf.push_str("<!--");
+        self.buf.push_str(text);
+        self.buf.push_str("-->");
+
+        Ok(())
+    }
+
+    fn processing_instruction(&mut self, target: &str, data: Option<&str>) -> Result<()> {
+        check_name(target)?;
+
+        if target.eq_ignore_ascii_case("xml") {
+            return Err(Error::WriterState("reserved processing instruction target"));
+        }
+
+        if let Some(data) = data {
+            if data.contains("?>") {
+                return Err(Error::WriterState("`?>` in processing instruction"));
+            }
+
+            check_chars(data)?;
+        }
+
+        self.close_start_tag()?;
+
+        self.started = true;
+        self.buf.push_str("<?");
+        self.buf.push_str(target);
+
+        if let Some(data) = data.filter(|data| !data.is_empty()) {
+            self.buf.push(' ');
+            self.buf.push_str(data);
+        }
+
+        self.buf.push_str("?>");
+
+        Ok(())
+    }
+
+    fn end_element(&mut self) -> Result<()> {
+        if self.start_tag {
+            self.check_prefixes()?;
+        }
+
+        let Some(end) = self.name_ends.pop() else {
+            return Err(Error::WriterState("no open element"));
+        };
+
+        let start = self.name_ends.last().copied().unwrap_or(0);
+
+        if let Some(len) = self.scopes.pop() {
+            self.bindings.truncate(len);
+        }
+
+        if self.start_tag {
+            self.start_tag = false;
+            self.buf.push_str("/>");
+        } else {
+            self.buf.push_str("</");
+            self.buf.push_str(&self.names[start..end]);
+            self.buf.push('>');
+        }
+
+        self.names.truncate(start);
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        while !self.name_ends.is_empty() {
+            self.end_element()?;
+        }
+
+        if !self.root {
+            return Err(Error::WriterState("no root element"));
+        }
+
+        Ok(())
+    }
+
+    fn close_start_tag(&mut self) -> Result<()> {
+        if self.start_tag {
+            self.check_prefixes()?;
+
+            self.start_tag = false;
+            self.buf.push('>');
+        }
+
+        Ok(())
+    }
+
+    /// Check that the prefixes of the open start tag are declared.
+    fn check_prefixes(&self) -> Result<()> {
+        let end = self.name_ends.last().copied().unwrap_or(0);
+        let start = self
+            .name_ends
+            .len()
+            .checked_sub(2)
+            .map(|index| self.name_ends[index])
+            .unwrap_or(0);
+
+        let names = std::iter::once(&self.names[start..end])
+            .chain(self.attributes.iter().map(String::as_str))
+            .filter(|name| *name != "xmlns" && !name.starts_with("xmlns:"));
+
+        for name in names {
+            let Some((prefix, _)) = name.split_once(':') else {
+                continue;
+            };
+
+            let declared = prefix == "xml"
+                || self
+                    .bindings
+                    .iter()
+                    .rev()
+                    .find(|(bound, _)| bound == prefix)
+                    .is_some_and(|(_, uri)| !uri.is_empty());
+
+            if !declared {
+                return Err(Error::UndeclaredPrefix(prefix.to_owned()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Check that `name` matches the `Name` production.
+fn check_name(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(c) if is_name_start_char(c) && chars.all(is_name_char) => Ok(()),
+        _ => Err(Error::InvalidName(name.to_owned())),
+    }
+}
+
+/// Check that `name` matches the `NCName` production, a name without colons.
+fn check_ncname(name: &str) -> Result<()> {
+    check_name(name)?;
+
+    if name.contains(':') {
+        return Err(Error::InvalidName(name.to_owned()));
+    }
+
+    Ok(())
+}
+
+/// Check that `text` only has characters allowed in XML documents.
+fn check_chars(text: &str) -> Result<()> {
+    match text.chars().find(|c| !is_xml_char(*c)) {
+        Some(c) => Err(Error::InvalidChar(c)),
+        None => Ok(()),
+    }
+}
+
+/// Write `text` into `writer`, with the characters for which `f` returns a replacement escaped.
+///
+/// If `ascii` is true, the other characters out of ASCII are written as character references.
+pub fn escape<W, F>(writer: &mut W, text: &str, ascii: bool, f: F) -> fmt::Result
+where
+    W: fmt::Write + ?Sized,
+    F: Fn(char) -> Option<&'static str>,
+{
+    let mut start = 0;
+
+    for (index, c) in text.char_indices() {
+        let replacement = f(c);
+
+        if replacement.is_none() && (!ascii || c.is_ascii()) {
+            continue;
+        }
+
+        writer.write_str(&text[start..index])?;
+
+        match replacement {
+            Some(replacement) => writer.write_str(replacement)?,
+            None => write!(writer, "&#x{:X};", c as u32)?,
+        }
+
+        start = index + c.len_utf8();
+    }
+
+    writer.write_str(&text[start..])
+}
+
+/// A writer of XML documents into an [`io::Write`].
+///
+/// Calls out of order, such as an attribute after character data,
+/// return [`Error::WriterState`] and write nothing.
+/// The markup is written as soon as possible, a start tag once it is closed,
+/// wrap `W` in a buffer for small writes. The prefixes of a start tag must be declared
+/// on the tag or an ancestor, otherwise the call closing it returns [`Error::UndeclaredPrefix`].
+pub struct XmlWriter<W> {
+    sink: W,
+    state: WriterState,
+}
+
+impl<W> XmlWriter<W>
+where
+    W: io::Write,
+{
+    /// Create a new `XmlWriter` instance writing to `sink`.
+    pub fn new(sink: W) -> Self {
+        Self {
+            sink,
+            state: Default::default(),
+        }
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.sink
+    }
+
+    /// Write the XML declaration, only allowed before any other markup.
+    pub fn write_start_document(&mut self) -> Result<()> {
+        self.state.start_document()?;
+        self.write_buf()
+    }
+
+    /// Write the start tag of a new element, the tag is left open for attributes.
+    pub fn write_start_element(&mut self, name: &str) -> Result<()> {
+        self.state.start_element(name)?;
+        self.write_buf()
+    }
+
+    /// Write an attribute of the element just started.
+    pub fn write_attribute(&mut self, name: &str, value: &str) -> Result<()> {
+        self.state.attribute(name, value)?;
+        self.write_buf()
+    }
+
+    /// Write a namespace declaration of the element just started,
+    /// use an empty `prefix` for the default namespace.
+    pub fn write_namespace(&mut self, prefix: &str, uri: &str) -> Result<()> {
+        self.state.namespace(prefix, uri)?;
+        self.write_buf()
+    }
+
+    /// Write character data, only white spaces are allowed outside of the root element.
+    pub fn write_characters(&mut self, text: &str) -> Result<()> {
+        self.state.characters(text)?;
+        self.write_buf()
+    }
+
+    /// Write a CDATA section, split where `text` contains `]]>`.
+    pub fn write_cdata(&mut self, text: &str) -> Result<()> {
+        self.state.cdata(text)?;
+        self.write_buf()
+    }
+
+    /// Write a comment.
+    pub fn write_comment(&mut self, text: &str) -> Result<()> {
+        self.state.comment(text)?;
+        self.write_buf()
+    }
+
+    /// Write a processing instruction.
+    pub fn write_pi(&mut self, target: &str, data: Option<&str>) -> Result<()> {
+        self.state.processing_instruction(target, data)?;
+        self.write_buf()
+    }
+
+    /// Write the end of the innermost open element, as an empty-element tag if it has no content.
+    pub fn write_end_element(&mut self) -> Result<()> {
+        self.state.end_element()?;
+        self.write_buf()
+    }
+
+    /// Close all open elements and flush the underlying writer.
+    ///
+    /// An error is returned if no root element has been written.
+    pub fn finish(&mut self) -> Result<()> {
+        self.state.finish()?;
+        self.write_buf()?;
+
+        Ok(self.sink.flush()?)
+    }
+
+    /// Write the markup of the closed tags, the markup is dropped if the sink fails.
+    fn write_buf(&mut self) -> Result<()> {
+        if self.state.start_tag {
+            return Ok(());
+        }
+
+        let result = self.sink.write_all(self.state.buf.as_bytes());
+        self.state.buf.clear();
+
+        Ok(result?)
+    }
+}
+
+/// A writer of XML documents into an [`AsyncWrite`], see [`XmlWriter`].
+pub struct AsyncXmlWriter<W> {
+    sink: W,
+    state: WriterState,
+}
+
+impl<W> AsyncXmlWriter<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    /// Create a new `AsyncXmlWriter` instance writing to `sink`.
+    pub fn new(sink: W) -> Self {
+        Self {
+            sink,
+            state: Default::default(),
+        }
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.sink
+    }
+
+    /// See [`XmlWriter::write_start_document`].
+    pub async fn write_start_document(&mut self) -> Result<()> {
+        self.state.start_document()?;
+        self.write_buf().await
+    }
+
+    /// See [`XmlWriter::write_start_element`].
+    pub async fn write_start_element(&mut self, name: &str) -> Result<()> {
+        self.state.start_element(name)?;
+        self.write_buf().await
+    }
+
+    /// See [`XmlWriter::write_attribute`].
+    pub async fn write_attribute(&mut self, name: &str, value: &str) -> Result<()> {
+        self.state.attribute(name, value)?;
+        self.write_buf().await
+    }
+
+    /// See [`XmlWriter::write_namespace`].
+    pub async fn write_namespace(&mut self, prefix: &str, uri: &str) -> Result<()> {
+        self.state.namespace(prefix, uri)?;
+        self.write_buf().await
+    }
+
+    /// See [`XmlWriter::write_characters`].
+    pub async fn write_characters(&mut self, text: &str) -> Result<()> {
+        self.state.characters(text)?;
+        self.write_buf().await
+    }
+
+    /// See [`XmlWriter::write_cdata`].
+    pub async fn write_cdata(&mut self, text: &str) -> Result<()> {
+        self.state.cdata(text)?;
+        self.write_buf().await
+    }
+
+    /// See [`XmlWriter::write_comment`].
+    pub async fn write_comment(&mut self, text: &str) -> Result<()> {
+        self.state.comment(text)?;
+        self.write_buf().await
+    }
+
+    /// See [`XmlWriter::write_pi`].
+    pub async fn write_pi(&mut self, target: &str, data: Option<&str>) -> Result<()> {
+        self.state.processing_instruction(target, data)?;
+        self.write_buf().await
+    }
+
+    /// See [`XmlWriter::write_end_element`].
+    pub async fn write_end_element(&mut self) -> Result<()> {
+        self.state.end_element()?;
+        self.write_buf().await
+    }
+
+    /// See [`XmlWriter::finish`].
+    pub async fn finish(&mut self) -> Result<()> {
+        self.state.finish()?;
+        self.write_buf().await?;
+
+        Ok(self.sink.flush().await?)
+    }
+
+    async fn write_buf(&mut self) -> Result<()> {
+        if self.state.start_tag {
+            return Ok(());
+        }
+
+        let result = self.sink.write_all(self.state.buf.as_bytes()).await;
+        self.state.buf.clear();
+
+        Ok(result?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::parser::{Event, Reader};
+
+    #[test]
+    fn test_writer() {
+        let mut writer = XmlWriter::new(vec![]);
+
+        writer.write_start_document().unwrap();
+        writer.write_comment(" generated ").unwrap();
+        writer.write_start_element("doc").unwrap();
+        writer.write_namespace("", "urn:doc").unwrap();
+        writer.write_attribute("a", "<\"&\t>").unwrap();
+        writer.write_characters("x & y > z").unwrap();
+        writer.write_start_element("empty").unwrap();
+        writer.write_end_element().unwrap();
+        writer.write_cdata("a]]>b").unwrap();
+        writer.write_pi("pi", Some("data")).unwrap();
+        writer.write_start_element("open").unwrap();
+        writer.finish().unwrap();
+
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+
+        assert_eq!(
+            output,
+            r#"<?xml version="1.0" encoding="UTF-8"?><!-- generated --><doc xmlns="urn:doc" a="&lt;&quot;&amp;&#x9;>">x &amp; y &gt; z<empty/><![CDATA[a]]]]><![CDATA[>b]]><?pi data?><open/></doc>"#
+        );
+
+        let mut reader = Reader::new(output.as_str());
+        let mut events = vec![];
+
+        while let Some(event) = block_on(reader.next()).unwrap() {
+            events.push(event);
+        }
+
+        assert!(events.contains(&Event::Characters("x & y > z".into())));
+        assert!(events.contains(&Event::CData("a]]".into())));
+    }
+
+    #[test]
+    fn test_order() {
+        let mut writer = XmlWriter::new(vec![]);
+
+        writer
+            .write_attribute("a", "1")
+            .expect_err("attribute before element");
+        writer
+            .write_characters("text")
+            .expect_err("text outside of root");
+        writer.write_end_element().expect_err("no open element");
+
+        writer.write_start_element("a").unwrap();
+        writer
+            .write_start_document()
+            .expect_err("declaration after markup");
+        writer.write_attribute("b", "1").unwrap();
+        writer
+            .write_attribute("b", "2")
+            .expect_err("duplicate attribute");
+        writer.write_attribute("1b", "2").expect_err("invalid name");
+        writer
+            .write_namespace("xmlns", "urn:x")
+            .expect_err("reserved prefix");
+        writer.write_characters("text").unwrap();
+        writer
+            .write_attribute("c", "1")
+            .expect_err("attribute after content");
+        writer.write_comment("a--b").expect_err("`--` in comment");
+        writer.write_pi("xml", None).expect_err("reserved target");
+        writer
+            .write_characters("\u{0}")
+            .expect_err("invalid character");
+        writer.write_end_element().unwrap();
+
+        writer
+            .write_start_element("b")
+            .expect_err("two root elements");
+
+        writer.finish().unwrap();
+
+        assert_eq!(writer.into_inner(), b"<a b=\"1\">text</a>");
+
+        XmlWriter::new(vec![])
+            .finish()
+            .expect_err("no root element");
+    }
+
+    #[test]
+    fn test_namespaces() {
+        let mut writer = XmlWriter::new(vec![]);
+
+        writer.write_start_element("p:a").unwrap();
+        writer.write_attribute("q:b", "1").unwrap();
+        writer.write_namespace("p", "urn:p").unwrap();
+        writer
+            .write_characters("text")
+            .expect_err("undeclared attribute prefix");
+        writer
+            .write_namespace("q:r", "urn:q")
+            .expect_err("prefix is not an NCName");
+        writer
+            .write_attribute("xmlns:", "urn:q")
+            .expect_err("empty prefix");
+        writer.write_attribute("xmlns:q", "urn:q").unwrap();
+        writer.write_attribute("xml:lang", "en").unwrap();
+        writer.write_start_element("p:c").unwrap();
+        writer.write_end_element().unwrap();
+        writer.write_end_element().unwrap();
+
+        assert_eq!(
+            writer.into_inner(),
+            br#"<p:a q:b="1" xmlns:p="urn:p" xmlns:q="urn:q" xml:lang="en"><p:c/></p:a>"#
+        );
+
+        let mut writer = XmlWriter::new(vec![]);
+
+        writer.write_start_element("a").unwrap();
+        writer.write_start_element("b").unwrap();
+        writer.write_namespace("p", "urn:p").unwrap();
+        writer.write_end_element().unwrap();
+        writer.write_start_element("p:c").unwrap();
+        writer.write_end_element().expect_err("prefix out of scope");
+    }
+
+    #[test]
+    fn test_io_error() {
+        /// A sink failing the first write.
+        struct Failing(bool);
+
+        impl io::Write for Failing {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                if std::mem::replace(&mut self.0, false) {
+                    return Err(io::ErrorKind::Other.into());
+                }
+
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut writer = XmlWriter::new(Failing(true));
+
+        writer.write_comment("lost").expect_err("io error");
+
+        assert!(writer.state.buf.is_empty());
+
+        writer.write_start_element("a").unwrap();
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_async_writer() {
+        let mut writer = AsyncXmlWriter::new(vec![]);
+
+        block_on(async {
+            writer.write_start_element("a").await?;
+            writer.write_attribute("b", "1").await?;
+            writer.write_start_element("c").await?;
+            writer.write_characters("text").await?;
+            writer.finish().await
+        })
+        .unwrap();
+
+        assert_eq!(writer.into_inner(), b"<a b=\"1\"><c>text</c></a>");
+    }
+}